@@ -0,0 +1,215 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::parser::{CharacterClass, Element, Quantifier, Sign, Term, Token};
+
+// The literals a pattern consists of, if it is nothing but a single one,
+// like `needle`, or an alternation of them, like `if|else|while`.
+pub fn literal_alternatives(terms: &[Term]) -> Option<Vec<String>> {
+    terms
+        .iter()
+        .map(|term| {
+            if term.left_anchored || term.right_anchored || term.elements.is_empty() {
+                return None;
+            }
+            term.elements
+                .iter()
+                .map(|element| match element {
+                    Element::Class(CharacterClass {
+                        sign: Sign::Inclusive,
+                        items,
+                        quantifier: Quantifier::Once,
+                    }) => match items.as_slice() {
+                        &[Token::Literal(c)] => Some(c),
+                        _ => None,
+                    },
+                    _ => None,
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// An Aho-Corasick automaton, which finds any of a set of literals in a
+// single pass over the haystack, however many there are. It is a trie of
+// the literals' bytes, in which every node also links to the node for the
+// longest proper suffix of its path, to carry on from when the next byte
+// doesn't continue the path. A single literal is left to substring search,
+// which is faster.
+pub struct AhoCorasick {
+    nodes: Vec<TrieNode>,
+    literals: Vec<String>,
+    // Whether the longest literal starting at the leftmost position wins,
+    // rather than the one that comes first.
+    longest: bool,
+    // The length of the longest literal in bytes.
+    max_len: usize,
+}
+
+struct TrieNode {
+    // The transitions, sorted by byte.
+    next: Vec<(u8, usize)>,
+    fail: usize,
+    // The literals ending here, including those ending in a suffix.
+    outputs: Vec<usize>,
+}
+
+impl AhoCorasick {
+    pub fn new(literals: Vec<String>, longest: bool) -> Self {
+        let root = TrieNode {
+            next: Vec::new(),
+            fail: 0,
+            outputs: Vec::new(),
+        };
+        let mut nodes = vec![root];
+        for (idx, literal) in literals.iter().enumerate() {
+            let mut node = 0;
+            for &byte in literal.as_bytes() {
+                node = match nodes[node].next.binary_search_by_key(&byte, |t| t.0) {
+                    Ok(found) => nodes[node].next[found].1,
+                    Err(at) => {
+                        nodes.push(TrieNode {
+                            next: Vec::new(),
+                            fail: 0,
+                            outputs: Vec::new(),
+                        });
+                        let child = nodes.len() - 1;
+                        nodes[node].next.insert(at, (byte, child));
+                        child
+                    }
+                };
+            }
+            nodes[node].outputs.push(idx);
+        }
+        // Breadth first, so that the suffix links of shorter paths are
+        // known before they are needed.
+        let mut queue: VecDeque<usize> = nodes[0].next.iter().map(|t| t.1).collect();
+        while let Some(node) = queue.pop_front() {
+            for (byte, child) in nodes[node].next.clone() {
+                let mut fail = nodes[node].fail;
+                let target = loop {
+                    if let Some(target) = Self::child(&nodes, fail, byte) {
+                        break target;
+                    }
+                    if fail == 0 {
+                        break 0;
+                    }
+                    fail = nodes[fail].fail;
+                };
+                nodes[child].fail = target;
+                let inherited = nodes[target].outputs.clone();
+                nodes[child].outputs.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+        let max_len = literals.iter().map(String::len).max().unwrap_or(0);
+        Self {
+            nodes,
+            literals,
+            longest,
+            max_len,
+        }
+    }
+    fn child(nodes: &[TrieNode], node: usize, byte: u8) -> Option<usize> {
+        let next = &nodes[node].next;
+        next.binary_search_by_key(&byte, |t| t.0)
+            .ok()
+            .map(|found| next[found].1)
+    }
+    // The leftmost match at or after byte offset `start`, or right at it if
+    // `anchored` is set.
+    pub fn find_at(&self, haystack: &str, start: usize, anchored: bool) -> Option<(usize, usize)> {
+        if let [literal] = self.literals.as_slice() {
+            let rest = &haystack[start..];
+            let from = match anchored {
+                true => rest.starts_with(literal.as_str()).then_some(start)?,
+                false => start + rest.find(literal.as_str())?,
+            };
+            return Some((from, from + literal.len()));
+        }
+        if anchored {
+            let rest = &haystack[start..];
+            let idx = (0..self.literals.len())
+                .filter(|&idx| rest.starts_with(self.literals[idx].as_str()))
+                .reduce(|best, idx| if self.prefers(idx, best) { idx } else { best })?;
+            return Some((start, start + self.literals[idx].len()));
+        }
+        let bytes = haystack.as_bytes();
+        let mut node = 0;
+        // The start of the best match so far, and its literal.
+        let mut best: Option<(usize, usize)> = None;
+        for (position, &byte) in bytes.iter().enumerate().skip(start) {
+            node = loop {
+                if let Some(next) = Self::child(&self.nodes, node, byte) {
+                    break next;
+                }
+                if node == 0 {
+                    break 0;
+                }
+                node = self.nodes[node].fail;
+            };
+            for &idx in &self.nodes[node].outputs {
+                let from = position + 1 - self.literals[idx].len();
+                best = match best {
+                    Some((best_from, best_idx))
+                        if best_from < from
+                            || best_from == from && !self.prefers(idx, best_idx) =>
+                    {
+                        Some((best_from, best_idx))
+                    }
+                    _ => Some((from, idx)),
+                };
+            }
+            // Later matches can't start at or before the best one.
+            if best.is_some_and(|(from, _)| position + 1 >= from + self.max_len) {
+                break;
+            }
+        }
+        best.map(|(from, idx)| (from, from + self.literals[idx].len()))
+    }
+    // Whether literal `idx` wins over literal `other` starting at the same
+    // position.
+    fn prefers(&self, idx: usize, other: usize) -> bool {
+        if self.longest && self.literals[idx].len() != self.literals[other].len() {
+            return self.literals[idx].len() > self.literals[other].len();
+        }
+        idx < other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_regex;
+
+    #[test]
+    fn finds_leftmost_literals() {
+        let literals = |pattern| literal_alternatives(&parse_regex(pattern).unwrap());
+        assert_eq!(
+            literals("he|she|his|hers"),
+            Some(vec!["he".into(), "she".into(), "his".into(), "hers".into()])
+        );
+        assert_eq!(literals("needle"), Some(vec!["needle".into()]));
+        for pattern in ["", "a|b*", "^a|b", "a|", "(a)|b", "a|[bc]", "(?i)a|b"] {
+            assert_eq!(literals(pattern), None, "{pattern}");
+        }
+        let words = literals("he|she|his|hers").unwrap();
+        let first = AhoCorasick::new(words.clone(), false);
+        assert_eq!(first.find_at("ushers", 0, false), Some((1, 4)));
+        assert_eq!(first.find_at("ushers", 2, false), Some((2, 4)));
+        assert_eq!(first.find_at("ushers", 2, true), Some((2, 4)));
+        assert_eq!(first.find_at("ushers", 3, true), None);
+        assert_eq!(
+            AhoCorasick::new(words, true).find_at("ushers", 2, false),
+            Some((2, 6))
+        );
+        let first = AhoCorasick::new(vec!["abcd".into(), "bc".into(), "ab".into()], false);
+        assert_eq!(first.find_at("xabcd", 0, false), Some((1, 5)));
+        assert_eq!(first.find_at("xabce", 0, false), Some((1, 3)));
+        let single = AhoCorasick::new(vec!["ab".into()], false);
+        assert_eq!(single.find_at("xabab", 2, false), Some((3, 5)));
+        assert_eq!(single.find_at("xabab", 2, true), None);
+    }
+}
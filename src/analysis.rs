@@ -0,0 +1,712 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Display, Formatter};
+use core::slice;
+
+use crate::ast::{
+    to_pattern, CharacterClass, Element, Quantifier, Sign, SpecialSequence, Term, Token,
+};
+use crate::charset::CharSet;
+use crate::fsm::Look;
+use crate::hir::{Flags, Hir};
+use crate::{Error, RegexBuilder};
+
+// How many states a DFA may need for a pattern before that is reported.
+const BLOWUP: usize = 1 << 12;
+// How many alternatives may be compared with each other, which takes time
+// quadratic in their number, when looking for unreachable ones.
+const SHADOWING: usize = 1 << 10;
+
+/// Analyzes `pattern` with the default settings of a [`RegexBuilder`],
+/// without compiling it. Use [`RegexBuilder::analyze`] for other settings.
+///
+/// ```
+/// use regexrs::{analyze, Risk};
+///
+/// assert!(analyze("[a-z]+@[a-z]+").unwrap().risks().is_empty());
+/// assert_eq!(analyze("(a+)+$").unwrap().risks(), [Risk::NestedQuantifiers]);
+/// ```
+pub fn analyze(pattern: &str) -> Result<Report, Error> {
+    RegexBuilder::new(pattern).analyze()
+}
+
+/// What [`analyze`] found out about a pattern, so that patterns taken from
+/// users can be turned down before they are compiled.
+///
+/// Searches with a [`Regex`](crate::Regex) take time linear in the length
+/// of the haystack, unless the pattern has to be matched by backtracking
+/// (see [`backtracks`](Self::backtracks)), which has a
+/// [limit](RegexBuilder::backtrack_limit). The risks are reported anyway,
+/// as they matter to other engines the pattern might be used with, and
+/// slow down building DFA states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    risks: Vec<Risk>,
+    states: usize,
+    backtracks: bool,
+    warnings: CompileWarnings,
+}
+
+/// Something about a pattern that can make matching it slow or compiling it
+/// large, see [`Report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Risk {
+    /// An unbounded repetition of something that has one of its own, like
+    /// `(a+)*`, which a backtracking engine can split the same text up in
+    /// exponentially many ways.
+    NestedQuantifiers,
+    /// An unbounded repetition of alternatives that can start with the same
+    /// character, like `(a|ab)*`, which a backtracking engine can also match
+    /// in many ways.
+    OverlappingAlternatives,
+    /// A counted repetition that can start with the character before it,
+    /// after something that can repeat that character, like `[ab]*a[ab]{20}`,
+    /// for which a DFA needs a number of states exponential in the count.
+    /// Searches behave like the pattern starts with `.*?`, unless it is
+    /// anchored, so `a[ab]{20}` needs as many. Holds the estimated number
+    /// of states.
+    StateBlowup(usize),
+}
+
+impl Report {
+    pub(crate) fn new(hir: &Hir, warnings: CompileWarnings) -> Self {
+        let mut analysis = Analysis { risks: Vec::new() };
+        // The search tries every position of the haystack, as though the
+        // pattern started with `(?s:.)*?`.
+        let before = if hir.is_anchored() {
+            CharSet::default()
+        } else {
+            CharSet::full()
+        };
+        analysis.visit(hir, false, &before);
+        Self {
+            risks: analysis.risks,
+            states: states(hir),
+            backtracks: backtracks(hir),
+            warnings,
+        }
+    }
+    /// The risks found, in the order in which they come up in the pattern.
+    pub fn risks(&self) -> &[Risk] {
+        &self.risks
+    }
+    /// The estimated number of states of the automaton the pattern compiles
+    /// to, which counted repetitions multiply, as in `(a{100}){100}`.
+    pub fn states(&self) -> usize {
+        self.states
+    }
+    /// Whether searches match the pattern by backtracking, because it has
+    /// backreferences, atomic groups, possessive quantifiers or
+    /// conditionals.
+    pub fn backtracks(&self) -> bool {
+        self.backtracks
+    }
+    /// The warnings [`Regex::warnings`](crate::Regex::warnings) would
+    /// return for the pattern.
+    pub fn warnings(&self) -> &CompileWarnings {
+        &self.warnings
+    }
+}
+
+/// Things about a pattern that are probably mistakes, but don't keep it
+/// from compiling, see [`Regex::warnings`](crate::Regex::warnings). It
+/// displays as one warning per line.
+///
+/// ```
+/// use regexrs::{Regex, Warning};
+///
+/// let regex = Regex::new("a|ab|b").unwrap();
+/// assert_eq!(regex.warnings().len(), 1);
+/// assert!(matches!(
+///     regex.warnings().iter().next(),
+///     Some(Warning::UnreachableAlternative { alternative, .. }) if alternative == "ab"
+/// ));
+/// assert_eq!(regex.warnings().to_string(), "alternative `ab` never matches, as `a` matches first");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompileWarnings(Vec<Warning>);
+
+impl CompileWarnings {
+    // Looks for alternatives shadowed by earlier ones, which only leftmost-
+    // first matching prefers regardless of what they match.
+    pub(crate) fn new(ast: &[Term], flags: Flags, first_match: bool) -> Self {
+        let mut warnings = Vec::new();
+        lint(ast, flags, (false, false), &mut warnings);
+        if first_match {
+            unreachable(ast, flags, &mut warnings);
+        }
+        Self(warnings)
+    }
+    /// Returns an iterator over the warnings.
+    pub fn iter(&self) -> slice::Iter<'_, Warning> {
+        self.0.iter()
+    }
+    /// The number of warnings.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    /// Returns true if there are no warnings.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> IntoIterator for &'a CompileWarnings {
+    type Item = &'a Warning;
+    type IntoIter = slice::Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Display for CompileWarnings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, warning) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{warning}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A warning about a pattern, see [`CompileWarnings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// An alternative that never matches, because an alternative before
+    /// it, `shadowed_by`, matches wherever it does, and is preferred, like
+    /// `ab` in `a|ab`. Both are written as patterns.
+    ///
+    /// Only alternatives at the end of the pattern are looked at, as in
+    /// `(?:a|ab)c` the first one may fail to match where the second
+    /// doesn't. For the same reason, the alternative may still match with
+    /// [`Regex::is_full_match`](crate::Regex::is_full_match). Alternations
+    /// of more than 1024 alternatives are left out as well.
+    UnreachableAlternative {
+        alternative: String,
+        shadowed_by: String,
+    },
+    /// Something that looks like an octal escape, like `\012`, but is a
+    /// null character or a backreference followed by digits, as octal
+    /// escapes aren't supported. It holds the escape and the digits.
+    OctalLookingEscape(String),
+    /// An anchor that never matches, like `\A` in `a\Ab`, because every
+    /// match of the pattern has something before it, or after it for an
+    /// anchor at the end. Anchors for lines are left out.
+    UnmatchableAnchor(String),
+    /// A class that matches no character at all, like `[^\s\S]`.
+    EmptyClass(String),
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnreachableAlternative {
+                alternative,
+                shadowed_by,
+            } => write!(
+                f,
+                "alternative `{alternative}` never matches, as `{shadowed_by}` matches first"
+            ),
+            Warning::OctalLookingEscape(escape) => {
+                let (escape, digits) = escape.split_at(2);
+                write!(
+                    f,
+                    "`{escape}{digits}` is `{escape}` followed by `{digits}`, not an octal escape"
+                )
+            }
+            Warning::UnmatchableAnchor(anchor) => {
+                write!(f, "anchor `{anchor}` never matches where it is")
+            }
+            Warning::EmptyClass(class) => write!(f, "class `{class}` matches no character"),
+        }
+    }
+}
+
+struct Analysis {
+    risks: Vec<Risk>,
+}
+
+impl Analysis {
+    // `repeated` tells whether `hir` is part of an unbounded repetition, and
+    // `before` holds the characters repeated without bound before it.
+    fn visit(&mut self, hir: &Hir, repeated: bool, before: &CharSet) {
+        match hir {
+            Hir::Empty | Hir::Class(_) | Hir::Look(_) | Hir::Backreference(_) => {}
+            Hir::Concat(parts) => {
+                let mut before = before.clone();
+                for (i, part) in parts.iter().enumerate() {
+                    let previous = i
+                        .checked_sub(1)
+                        .filter(|&i| !matches!(parts[i], Hir::Repeat { max: None, .. }));
+                    if let (
+                        Some(previous),
+                        Hir::Repeat {
+                            sub,
+                            max: Some(max),
+                            ..
+                        },
+                    ) = (previous, part)
+                    {
+                        let (start, _) = first(sub);
+                        let (last, _) = first(&parts[previous]);
+                        if *max > 1 && start.intersects(&last) && last.intersects(&before) {
+                            let states = 1usize.checked_shl(*max as u32).unwrap_or(usize::MAX);
+                            if states > BLOWUP {
+                                self.risks.push(Risk::StateBlowup(states));
+                            }
+                        }
+                    }
+                    self.visit(part, repeated, &before);
+                    if let Hir::Repeat { sub, max: None, .. } = part {
+                        before = before.union(&first(sub).0);
+                    }
+                }
+            }
+            Hir::Alternation(branches) => {
+                if repeated && overlap(branches) {
+                    self.risks.push(Risk::OverlappingAlternatives);
+                }
+                for branch in branches {
+                    self.visit(branch, repeated, before);
+                }
+            }
+            Hir::Repeat { sub, max, .. } => {
+                if repeated && max.is_none() {
+                    self.risks.push(Risk::NestedQuantifiers);
+                }
+                self.visit(sub, repeated || max.is_none(), before);
+            }
+            Hir::Capture(_, sub) | Hir::Lookahead(_, sub) => self.visit(sub, repeated, before),
+            // Atomic groups don't backtrack into what they have matched.
+            Hir::Atomic(sub) => self.visit(sub, false, before),
+            Hir::Conditional(_, yes, no) => {
+                self.visit(yes, repeated, before);
+                self.visit(no, repeated, before);
+            }
+        }
+    }
+}
+
+// Whether two of the alternatives can start with the same character, or
+// both match the empty string.
+fn overlap(branches: &[Hir]) -> bool {
+    let firsts: Vec<(CharSet, bool)> = branches.iter().map(first).collect();
+    firsts.iter().enumerate().any(|(i, (set, empty))| {
+        firsts[i + 1..]
+            .iter()
+            .any(|(other, other_empty)| set.intersects(other) || *empty && *other_empty)
+    })
+}
+
+// The characters a match of `hir` can start with, and whether it can be
+// empty. Assertions are taken to hold, and backreferences to match
+// anything.
+fn first(hir: &Hir) -> (CharSet, bool) {
+    match hir {
+        Hir::Empty | Hir::Look(_) | Hir::Lookahead(..) => (CharSet::default(), true),
+        Hir::Class(set) => (set.clone(), false),
+        Hir::Backreference(_) => (CharSet::full(), true),
+        Hir::Concat(parts) => {
+            let mut set = CharSet::default();
+            for part in parts {
+                let (start, empty) = first(part);
+                set = set.union(&start);
+                if !empty {
+                    return (set, false);
+                }
+            }
+            (set, true)
+        }
+        Hir::Alternation(branches) => branches.iter().map(first).fold(
+            (CharSet::default(), false),
+            |(set, empty), (start, can_be_empty)| (set.union(&start), empty || can_be_empty),
+        ),
+        Hir::Repeat { sub, min, .. } => {
+            let (set, empty) = first(sub);
+            (set, empty || *min == 0)
+        }
+        Hir::Capture(_, sub) | Hir::Atomic(sub) => first(sub),
+        Hir::Conditional(_, yes, no) => {
+            let ((yes, yes_empty), (no, no_empty)) = (first(yes), first(no));
+            (yes.union(&no), yes_empty || no_empty)
+        }
+    }
+}
+
+// Looks for escapes, anchors and classes that are probably mistakes in
+// `terms`. `context` tells whether something is always matched before and
+// after them.
+fn lint(terms: &[Term], flags: Flags, context: (bool, bool), warnings: &mut Vec<Warning>) {
+    let mut flags = flags;
+    for term in terms {
+        let (before, after) = context;
+        // Whether each element always matches something, which is the same
+        // regardless of the flags.
+        let consumes: Vec<bool> = term.elements.iter().map(consumes).collect();
+        let unmatchable = |anchor: &str| Warning::UnmatchableAnchor(anchor.into());
+        if term.left_anchored && before && flags.at_start(term).start_anchor() == Look::Start {
+            warnings.push(unmatchable("^"));
+        }
+        let mut current = flags;
+        for (i, element) in term.elements.iter().enumerate() {
+            let before = before || consumes[..i].contains(&true);
+            let after = after || consumes[i + 1..].contains(&true);
+            match element {
+                Element::Flags(changes) => current = current.apply(changes),
+                Element::Class(class) => {
+                    let once = CharacterClass {
+                        quantifier: Quantifier::Once,
+                        ..class.clone()
+                    };
+                    let once = single(Element::Class(once));
+                    let hir = Hir::translate(slice::from_ref(&once), current, usize::MAX);
+                    if matches!(hir, Ok(Hir::Class(set)) if set.ranges().is_empty()) {
+                        warnings.push(Warning::EmptyClass(to_pattern(&once)));
+                    }
+                }
+                Element::Sequence(SpecialSequence::Start, _) if before => {
+                    warnings.push(unmatchable("\\A"))
+                }
+                Element::Sequence(SpecialSequence::SearchStart, _) if before => {
+                    warnings.push(unmatchable("\\G"))
+                }
+                Element::Sequence(SpecialSequence::AbsoluteEnd, _) if after => {
+                    warnings.push(unmatchable("\\z"))
+                }
+                Element::Sequence(SpecialSequence::LineStart, _)
+                    if before && current.start_anchor() == Look::Start =>
+                {
+                    warnings.push(unmatchable("^"))
+                }
+                Element::Sequence(SpecialSequence::LineEnd, _)
+                    if after && current.end_anchor() == Look::End =>
+                {
+                    warnings.push(unmatchable("$"))
+                }
+                Element::Group(changes, terms, _) => {
+                    lint(terms, current.apply(changes), (before, after), warnings)
+                }
+                // What comes after a lookahead isn't matched after what it
+                // looks for.
+                Element::Lookahead(_, terms) => lint(terms, current, (before, false), warnings),
+                element => {
+                    if let Some(terms) = element.terms() {
+                        lint(terms, current, (before, after), warnings);
+                    }
+                }
+            }
+            if let Some(escape) = octal_looking(&term.elements[i..]) {
+                warnings.push(Warning::OctalLookingEscape(escape));
+            }
+        }
+        if term.right_anchored && after && current.end_anchor() == Look::End {
+            warnings.push(unmatchable("$"));
+        }
+        flags = flags.after(term);
+    }
+}
+
+// Whether every match of `element` is at least a character long.
+fn consumes(element: &Element) -> bool {
+    let term = single(element.clone());
+    Hir::translate(slice::from_ref(&term), Flags::default(), usize::MAX)
+        .is_ok_and(|hir| !first(&hir).1)
+}
+
+fn single(element: Element) -> Term {
+    Term {
+        left_anchored: false,
+        right_anchored: false,
+        elements: vec![element],
+    }
+}
+
+// The null character or the backreference `elements` start with, and the
+// digits right after it, if there are any.
+fn octal_looking(elements: &[Element]) -> Option<String> {
+    let literal = |element: &Element| match element {
+        Element::Class(CharacterClass {
+            sign: Sign::Inclusive,
+            items,
+            quantifier: Quantifier::Once,
+        }) => match items.as_slice() {
+            [Token::Literal(c)] => Some(*c),
+            _ => None,
+        },
+        _ => None,
+    };
+    let (mut escape, octal) = match elements.first()? {
+        Element::Backreference(group, Quantifier::Once) => (format!("\\{group}"), false),
+        element if literal(element) == Some('\0') => (String::from("\\0"), true),
+        _ => return None,
+    };
+    let digits: String = elements[1..]
+        .iter()
+        .map_while(literal)
+        .take_while(|c| {
+            if octal {
+                ('0'..='7').contains(c)
+            } else {
+                c.is_ascii_digit()
+            }
+        })
+        .collect();
+    if digits.is_empty() {
+        return None;
+    }
+    escape.push_str(&digits);
+    Some(escape)
+}
+
+// Looks for alternatives that never match among `terms`, which nothing comes
+// after, and in the groups at the end of them. Where one matches, the search
+// ends, so an alternative is shadowed by an earlier one that matches a
+// prefix of everything it matches.
+fn unreachable(terms: &[Term], flags: Flags, warnings: &mut Vec<Warning>) {
+    // What the alternatives so far need to match, unless they are shadowed
+    // themselves or have assertions.
+    let mut earlier: Vec<Option<Vec<CharSet>>> = Vec::with_capacity(terms.len());
+    let mut flags = flags;
+    for term in terms {
+        let hir = Hir::translate(slice::from_ref(term), flags, usize::MAX).ok();
+        let known = hir.as_ref().map(prefix).unwrap_or_default();
+        // Empty classes are warned about on their own.
+        let matches = !known.iter().any(|set| set.ranges().is_empty());
+        let shadowed_by = hir
+            .as_ref()
+            .filter(|_| matches && terms.len() <= SHADOWING)
+            .and_then(|_| {
+                earlier.iter().position(|needed| {
+                    needed.as_ref().is_some_and(|needed| {
+                        needed.len() <= known.len()
+                            && needed
+                                .iter()
+                                .zip(&known)
+                                .all(|(needed, known)| known.is_subset(needed))
+                    })
+                })
+            });
+        match shadowed_by {
+            Some(shadowing) => warnings.push(Warning::UnreachableAlternative {
+                alternative: to_pattern(term),
+                shadowed_by: to_pattern(&terms[shadowing]),
+            }),
+            None if !term.right_anchored => match term.elements.last() {
+                Some(Element::Group(changes, terms, Quantifier::Once)) => {
+                    unreachable(terms, flags.after(term).apply(changes), warnings)
+                }
+                Some(Element::CaptureGroup(_, terms, Quantifier::Once)) => {
+                    unreachable(terms, flags.after(term), warnings)
+                }
+                _ => {}
+            },
+            None => {}
+        }
+        earlier.push(
+            hir.as_ref()
+                .filter(|_| shadowed_by.is_none())
+                .and_then(needed),
+        );
+        flags = flags.after(term);
+    }
+}
+
+// The characters a match of `hir` needs, one set per character, if it has
+// nothing else that could keep it from matching.
+fn needed(hir: &Hir) -> Option<Vec<CharSet>> {
+    match hir {
+        Hir::Empty => Some(Vec::new()),
+        Hir::Class(set) => Some(vec![set.clone()]),
+        Hir::Concat(parts) => parts.iter().try_fold(Vec::new(), |mut chars, part| {
+            chars.extend(needed(part)?);
+            Some(chars)
+        }),
+        Hir::Repeat { sub, min, .. } => {
+            let once = needed(sub)?;
+            Some(
+                once.iter()
+                    .cycle()
+                    .take(once.len() * min)
+                    .cloned()
+                    .collect(),
+            )
+        }
+        Hir::Capture(_, sub) => needed(sub),
+        _ => None,
+    }
+}
+
+// The characters every match of `hir` starts with, one set per character,
+// as far as they are known. Assertions only keep it from matching.
+fn prefix(hir: &Hir) -> Vec<CharSet> {
+    // Adds to `known`, returning whether that is all `hir` matches.
+    fn extend(hir: &Hir, known: &mut Vec<CharSet>) -> bool {
+        match hir {
+            Hir::Empty | Hir::Look(_) | Hir::Lookahead(..) => true,
+            Hir::Class(set) => {
+                known.push(set.clone());
+                true
+            }
+            Hir::Concat(parts) => parts.iter().all(|part| extend(part, known)),
+            Hir::Repeat { sub, min, max, .. } => {
+                (0..*min).all(|_| extend(sub, known)) && *max == Some(*min)
+            }
+            Hir::Capture(_, sub) | Hir::Atomic(sub) => extend(sub, known),
+            Hir::Alternation(_) | Hir::Backreference(_) | Hir::Conditional(..) => false,
+        }
+    }
+    let mut known = Vec::new();
+    extend(hir, &mut known);
+    known
+}
+
+// Roughly the number of nodes `NFA::compile` makes of `hir`.
+fn states(hir: &Hir) -> usize {
+    match hir {
+        Hir::Empty => 0,
+        Hir::Class(_) | Hir::Look(_) | Hir::Backreference(_) => 1,
+        Hir::Concat(parts) => parts.iter().map(states).fold(0, usize::saturating_add),
+        Hir::Alternation(branches) => branches.iter().map(states).fold(1, usize::saturating_add),
+        Hir::Repeat { sub, min, max, .. } => {
+            let copies = max.unwrap_or(*min).max(1);
+            let skips = max.map_or(1, |max| max - min);
+            states(sub)
+                .saturating_add(1)
+                .saturating_mul(copies)
+                .saturating_add(skips)
+        }
+        Hir::Capture(_, sub) => states(sub).saturating_add(2),
+        Hir::Lookahead(_, sub) | Hir::Atomic(sub) => states(sub).saturating_add(1),
+        Hir::Conditional(_, yes, no) => states(yes).saturating_add(states(no)).saturating_add(1),
+    }
+}
+
+fn backtracks(hir: &Hir) -> bool {
+    match hir {
+        Hir::Empty | Hir::Class(_) | Hir::Look(_) => false,
+        Hir::Backreference(_) | Hir::Atomic(_) | Hir::Conditional(..) => true,
+        Hir::Concat(parts) | Hir::Alternation(parts) => parts.iter().any(backtracks),
+        Hir::Repeat { sub, .. } | Hir::Capture(_, sub) | Hir::Lookahead(_, sub) => backtracks(sub),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MatchKind, Regex};
+    use alloc::string::ToString;
+
+    fn risks(pattern: &str) -> Vec<Risk> {
+        analyze(pattern).unwrap().risks().to_vec()
+    }
+
+    #[test]
+    fn finds_risks() {
+        assert_eq!(risks(r"(\w+\s?)*$"), [Risk::NestedQuantifiers]);
+        assert_eq!(risks("(?:a*)*"), [Risk::NestedQuantifiers]);
+        assert_eq!(risks("(?>a+)*b"), []);
+        assert_eq!(risks("(a|ab)*c"), [Risk::OverlappingAlternatives]);
+        assert_eq!(risks("(?i:a|A)+"), [Risk::OverlappingAlternatives]);
+        assert_eq!(risks("(a|b)+c|(|x?)*"), [Risk::OverlappingAlternatives]);
+        assert_eq!(risks("[ab]*a[ab]{20}"), [Risk::StateBlowup(1 << 20)]);
+        assert_eq!(risks("a[ab]{20}"), [Risk::StateBlowup(1 << 20)]);
+        assert_eq!(risks("^a[ab]{20}"), []);
+        assert_eq!(risks(r"x\d{20}|[ab]*[ab]{20}|\d{3}-\d{4}"), []);
+    }
+
+    #[test]
+    fn estimates_states() {
+        let report = analyze("(a{100}){100}").unwrap();
+        assert!(report.states() > 10_000);
+        assert!(!report.backtracks());
+        assert!(analyze("a{3}").unwrap().states() < 10);
+        assert!(analyze(r"(a)\1").unwrap().backtracks());
+        assert!(analyze("a++").unwrap().backtracks());
+        assert!(analyze("(").is_err());
+    }
+
+    #[test]
+    fn finds_unreachable_alternatives() {
+        let unreachable = |pattern: &str| -> Vec<(String, String)> {
+            let regex = Regex::new(pattern).unwrap();
+            regex
+                .warnings()
+                .iter()
+                .filter_map(|warning| match warning {
+                    Warning::UnreachableAlternative {
+                        alternative,
+                        shadowed_by,
+                    } => Some((alternative.clone(), shadowed_by.clone())),
+                    _ => None,
+                })
+                .collect()
+        };
+        let pair = |alternative: &str, shadowed_by: &str| {
+            (alternative.to_string(), shadowed_by.to_string())
+        };
+        assert_eq!(unreachable("a|ab|[ab]c|b"), [pair("ab", "a")]);
+        assert_eq!(
+            unreachable("x(?:a+|aa|(a)b)"),
+            [pair("aa", "a+"), pair("(a)b", "a+")]
+        );
+        assert_eq!(
+            unreachable("(?i:a)|A|b?|c"),
+            [pair("A", "(?i:a)"), pair("c", "b?")]
+        );
+        assert_eq!(unreachable("(?i)a|A"), [pair("A", "(?i)a")]);
+        assert_eq!(unreachable("(a)|(a)\\b"), [pair("(a)\\b", "(a)")]);
+        assert!(unreachable("(?:a|ab)c|^a|a$|ab|\\ba|ba").is_empty());
+        let longest = RegexBuilder::new("a|ab")
+            .match_kind(MatchKind::LeftmostLongest)
+            .build()
+            .unwrap();
+        assert!(longest.warnings().is_empty());
+        let report = analyze("a|a").unwrap();
+        assert_eq!(report.warnings().len(), 1);
+    }
+
+    #[test]
+    fn warns_about_likely_mistakes() {
+        let warnings = |pattern: &str| {
+            Regex::new(pattern)
+                .unwrap()
+                .warnings()
+                .iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let octal = |escape: &str| Warning::OctalLookingEscape(escape.to_string());
+        let anchor = |anchor: &str| Warning::UnmatchableAnchor(anchor.to_string());
+        assert_eq!(warnings(r"\012|(a)\10"), [octal(r"\012"), octal(r"\10")]);
+        assert!(warnings(r"\0|a\08").is_empty());
+        assert_eq!(
+            warnings(r"a\Ab|x(?:^b)|a\zb|(?:a$)b|a\G"),
+            [
+                anchor(r"\A"),
+                anchor("^"),
+                anchor(r"\z"),
+                anchor("$"),
+                anchor(r"\G")
+            ]
+        );
+        assert!(warnings(r"\Aa|a?\Ab|(?:a|^b)|(?m)x(?:^b)|(?=a$)b|a\Z\n").is_empty());
+        assert_eq!(warnings("a^b|a$b"), [anchor("^"), anchor("$")]);
+        assert!(warnings(r"(?m)a$\n^b|a?^b").is_empty());
+        let class = |class: &str| Warning::EmptyClass(class.to_string());
+        assert_eq!(
+            warnings(r"[^\s\S]|(?i)[^\w\W]+|[^a]"),
+            [class(r"[^\s\S]"), class(r"[^\w\W]")]
+        );
+        assert_eq!(
+            octal(r"\012").to_string(),
+            r"`\012` is `\0` followed by `12`, not an octal escape"
+        );
+    }
+}
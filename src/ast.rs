@@ -0,0 +1,575 @@
+//! The syntax tree of a pattern, for tools that inspect patterns rather
+//! than match them, like linters.
+//!
+//! A pattern is a list of [`Term`]s, its alternatives separated by `|`,
+//! each a sequence of [`Element`]s. Groups hold alternatives of their own.
+//!
+//! ```
+//! use regexrs::ast::{self, Element, Quantifier};
+//!
+//! let terms = ast::parse(r"(?P<year>\d{4})-\d\d|today").unwrap();
+//! assert_eq!(terms.len(), 2);
+//! match &terms[0].elements[0] {
+//!     Element::CaptureGroup(name, _, quantifier) => {
+//!         assert_eq!(name.as_deref(), Some("year"));
+//!         assert_eq!(*quantifier, Quantifier::Once);
+//!     }
+//!     _ => unreachable!(),
+//! }
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+use core::ops::{Bound, RangeBounds, RangeInclusive};
+
+use crate::parser::Written;
+use crate::{Error, Regex, RegexBuilder};
+
+pub use crate::parser::{
+    CharacterClass, Element, Flag, Quantifier, Sign, SpecialSequence, Term, Token,
+};
+pub use crate::simplify::simplify;
+
+/// Parses `pattern` with the default settings of a [`RegexBuilder`]. Use
+/// [`RegexBuilder::parse`] for other settings.
+pub fn parse(pattern: &str) -> Result<Vec<Term>, Error> {
+    RegexBuilder::new(pattern).parse()
+}
+
+/// Writes a syntax tree back as a pattern, with `|` between the terms,
+/// which [`parse`] turns into the same tree again. Accepts a single
+/// [`Term`] as well as all the terms of a pattern.
+///
+/// Patterns that parse to the same tree are written the same way, so the
+/// result can serve as a normalized form of a pattern, like a cache key.
+/// Inline flags and escapes are kept, as they are part of the tree, but
+/// whitespace and comments in verbose mode aren't.
+///
+/// ```
+/// use regexrs::ast;
+///
+/// let terms = ast::parse(r"[a]{,3}(?x) \# \d").unwrap();
+/// assert_eq!(ast::to_pattern(&terms), r"a{0,3}(?x)\#\d");
+/// assert_eq!(ast::to_pattern(&terms[0]), ast::to_pattern(&terms));
+/// ```
+pub fn to_pattern<T: AsRef<[Term]> + ?Sized>(terms: &T) -> String {
+    Written(terms.as_ref()).to_string()
+}
+
+impl AsRef<[Term]> for Term {
+    fn as_ref(&self) -> &[Term] {
+        core::slice::from_ref(self)
+    }
+}
+
+impl Element {
+    /// The alternatives of a group, lookahead, atomic group or conditional,
+    /// or `None` for the other elements.
+    pub fn terms(&self) -> Option<&[Term]> {
+        match self {
+            Element::CaptureGroup(_, terms, _)
+            | Element::Group(_, terms, _)
+            | Element::Lookahead(_, terms)
+            | Element::Atomic(terms, _)
+            | Element::Conditional(_, terms, _) => Some(terms),
+            Element::Class(_)
+            | Element::Sequence(..)
+            | Element::Flags(_)
+            | Element::Backreference(..) => None,
+        }
+    }
+    pub(crate) fn terms_mut(&mut self) -> Option<&mut Vec<Term>> {
+        match self {
+            Element::CaptureGroup(_, terms, _)
+            | Element::Group(_, terms, _)
+            | Element::Lookahead(_, terms)
+            | Element::Atomic(terms, _)
+            | Element::Conditional(_, terms, _) => Some(terms),
+            Element::Class(_)
+            | Element::Sequence(..)
+            | Element::Flags(_)
+            | Element::Backreference(..) => None,
+        }
+    }
+}
+
+/// Hooks called by [`walk`] on the way into and out of every term and
+/// element of a syntax tree. All of them do nothing by default.
+///
+/// ```
+/// use regexrs::ast::{self, Element, Visitor};
+///
+/// // How deeply groups are nested.
+/// #[derive(Default)]
+/// struct Depth {
+///     current: usize,
+///     max: usize,
+/// }
+///
+/// impl Visitor for Depth {
+///     fn enter_element(&mut self, element: &Element) {
+///         if element.terms().is_some() {
+///             self.current += 1;
+///             self.max = self.max.max(self.current);
+///         }
+///     }
+///     fn exit_element(&mut self, element: &Element) {
+///         if element.terms().is_some() {
+///             self.current -= 1;
+///         }
+///     }
+/// }
+///
+/// let mut depth = Depth::default();
+/// ast::walk(&ast::parse("a(b(?:c|(?=d)))|e").unwrap(), &mut depth);
+/// assert_eq!(depth.max, 3);
+/// ```
+pub trait Visitor {
+    /// Called before the elements of `term`.
+    fn enter_term(&mut self, _term: &Term) {}
+    /// Called after the elements of `term`.
+    fn exit_term(&mut self, _term: &Term) {}
+    /// Called before the terms inside `element`, if any.
+    fn enter_element(&mut self, _element: &Element) {}
+    /// Called after the terms inside `element`, if any.
+    fn exit_element(&mut self, _element: &Element) {}
+}
+
+/// Goes through `terms` depth first, in the order they appear in the
+/// pattern, calling the hooks of `visitor`.
+pub fn walk<V: Visitor + ?Sized>(terms: &[Term], visitor: &mut V) {
+    for term in terms {
+        visitor.enter_term(term);
+        for element in &term.elements {
+            visitor.enter_element(element);
+            if let Some(terms) = element.terms() {
+                walk(terms, visitor);
+            }
+            visitor.exit_element(element);
+        }
+        visitor.exit_term(term);
+    }
+}
+
+/// Rewrites a syntax tree bottom up: every element is passed to `f` after
+/// the terms inside it have been rewritten, and replaced by what `f`
+/// returns.
+///
+/// ```
+/// use regexrs::ast::{self, Element, Quantifier};
+///
+/// // Makes lazy quantifiers greedy.
+/// let terms = ast::fold(ast::parse("(a+?)*?").unwrap(), |mut element| {
+///     if let Element::Class(class) = &mut element {
+///         if class.quantifier == Quantifier::LazyOneOrMore {
+///             class.quantifier = Quantifier::OneOrMore;
+///         }
+///     }
+///     if let Element::CaptureGroup(_, _, quantifier) = &mut element {
+///         if *quantifier == Quantifier::LazyZeroOrMore {
+///             *quantifier = Quantifier::ZeroOrMore;
+///         }
+///     }
+///     element
+/// });
+/// assert_eq!(terms, ast::parse("(a+)*").unwrap());
+/// ```
+pub fn fold<F: FnMut(Element) -> Element>(terms: Vec<Term>, mut f: F) -> Vec<Term> {
+    fold_terms(terms, &mut f)
+}
+
+fn fold_terms(terms: Vec<Term>, f: &mut dyn FnMut(Element) -> Element) -> Vec<Term> {
+    terms
+        .into_iter()
+        .map(|term| Term {
+            elements: term
+                .elements
+                .into_iter()
+                .map(|mut element| {
+                    if let Some(terms) = element.terms_mut() {
+                        *terms = fold_terms(core::mem::take(terms), f);
+                    }
+                    f(element)
+                })
+                .collect(),
+            ..term
+        })
+        .collect()
+}
+
+/// Builds the syntax tree of a pattern in code, without writing the pattern
+/// and escaping what goes into it.
+///
+/// A pattern starts out as a literal, a class or a special sequence, and is
+/// then repeated, grouped, and combined with others. It displays as what
+/// [`to_pattern`] writes for it.
+///
+/// ```
+/// use regexrs::ast::{class, Pattern};
+///
+/// let regex = Pattern::literal("a.b")
+///     .then(class(['a'..='z', 'A'..='Z']).repeated(1..))
+///     .or(Pattern::literal("?"))
+///     .build()
+///     .unwrap();
+/// assert_eq!(regex.as_str(), r"a\.b[a-zA-Z]+|\?");
+/// assert!(regex.is_match("a.bcd"));
+/// assert!(!regex.is_match("axbcd"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    terms: Vec<Term>,
+}
+
+impl Pattern {
+    /// Matches `text` as it is.
+    pub fn literal(text: &str) -> Self {
+        let elements = text
+            .chars()
+            .map(|c| class_element(Sign::Inclusive, vec![Token::Literal(c)]))
+            .collect();
+        Self::element_sequence(elements)
+    }
+    /// Matches any of `items`, which are characters, like `'_'`, or
+    /// ranges of them, like `'a'..='z'`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `items` is empty, as no pattern stands for an empty class.
+    pub fn class<T: Into<Token>, I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::class_with(Sign::Inclusive, items)
+    }
+    /// Matches any character but `items`, like [`class`](Self::class).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `items` is empty.
+    pub fn not_class<T: Into<Token>, I: IntoIterator<Item = T>>(items: I) -> Self {
+        Self::class_with(Sign::Exclusive, items)
+    }
+    /// Matches a special sequence, like `\d` or `.`.
+    pub fn special(sequence: SpecialSequence) -> Self {
+        Self::element_sequence(vec![Element::Sequence(sequence, Quantifier::Once)])
+    }
+    /// Matches this pattern followed by `next`.
+    pub fn then(self, next: Pattern) -> Self {
+        let mut term = self.into_term();
+        term.elements.extend(next.into_term().elements);
+        Self { terms: vec![term] }
+    }
+    /// Matches this pattern or, if it doesn't match, `other`.
+    pub fn or(mut self, other: Pattern) -> Self {
+        self.terms.extend(other.terms);
+        self
+    }
+    /// Matches this pattern as often as `range` allows, as many times as
+    /// possible. `1..` stands for `+`, `..=1` for `?`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    pub fn repeated<R: RangeBounds<usize>>(self, range: R) -> Self {
+        let min = match range.start_bound() {
+            Bound::Included(&min) => min,
+            Bound::Excluded(&min) => min + 1,
+            Bound::Unbounded => 0,
+        };
+        let max = match range.end_bound() {
+            Bound::Included(&max) => Some(max),
+            Bound::Excluded(&max) => Some(max.checked_sub(1).expect("empty repetition range")),
+            Bound::Unbounded => None,
+        };
+        let quantifier = match (min, max) {
+            (min, Some(max)) if min > max => panic!("empty repetition range"),
+            (0, None) => Quantifier::ZeroOrMore,
+            (1, None) => Quantifier::OneOrMore,
+            (min, None) => Quantifier::AtLeast(min),
+            (0, Some(1)) => Quantifier::Maybe,
+            (1, Some(1)) => return self,
+            (min, Some(max)) if min == max => Quantifier::Exactly(min),
+            (min, Some(max)) => Quantifier::Between(min, max),
+        };
+        let mut term = self.into_term();
+        if let [element] = term.elements.as_mut_slice() {
+            if let Some(once @ Quantifier::Once) = quantifier_mut(element) {
+                *once = quantifier;
+                return Self { terms: vec![term] };
+            }
+        }
+        Self::element_sequence(vec![Element::Group(vec![], vec![term], quantifier)])
+    }
+    /// Captures what this pattern matches in a group.
+    pub fn captured(self) -> Self {
+        Self::element_sequence(vec![Element::CaptureGroup(
+            None,
+            self.terms,
+            Quantifier::Once,
+        )])
+    }
+    /// Captures what this pattern matches in a group called `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `name` is made of letters, digits and underscores, and
+    /// doesn't start with a digit.
+    pub fn named(self, name: &str) -> Self {
+        let valid = name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        assert!(
+            valid && !name.starts_with(|c: char| c.is_numeric()),
+            "invalid group name {name:?}"
+        );
+        Self::element_sequence(vec![Element::CaptureGroup(
+            Some(name.to_string()),
+            self.terms,
+            Quantifier::Once,
+        )])
+    }
+    /// The syntax tree, one term per alternative.
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+    /// Returns the syntax tree.
+    pub fn into_terms(self) -> Vec<Term> {
+        self.terms
+    }
+    /// Starts configuring a regex for the pattern, with
+    /// [`RegexBuilder::from_ast`].
+    pub fn builder(&self) -> RegexBuilder {
+        RegexBuilder::from_ast(self.terms.clone())
+    }
+    /// Compiles the pattern with the default settings.
+    pub fn build(&self) -> Result<Regex, Error> {
+        self.builder().build()
+    }
+    fn class_with<T: Into<Token>, I: IntoIterator<Item = T>>(sign: Sign, items: I) -> Self {
+        let items: Vec<Token> = items.into_iter().map(Into::into).collect();
+        assert!(!items.is_empty(), "empty character class");
+        Self::element_sequence(vec![class_element(sign, items)])
+    }
+    fn element_sequence(elements: Vec<Element>) -> Self {
+        Self {
+            terms: vec![Term {
+                left_anchored: false,
+                right_anchored: false,
+                elements,
+            }],
+        }
+    }
+    // The pattern as a single term, grouping its alternatives if needed.
+    fn into_term(mut self) -> Term {
+        match self.terms.len() {
+            1 => self.terms.remove(0),
+            _ => Term {
+                left_anchored: false,
+                right_anchored: false,
+                elements: vec![Element::Group(vec![], self.terms, Quantifier::Once)],
+            },
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Written(&self.terms).fmt(f)
+    }
+}
+
+/// A shorthand for [`Pattern::class`].
+pub fn class<T: Into<Token>, I: IntoIterator<Item = T>>(items: I) -> Pattern {
+    Pattern::class(items)
+}
+
+impl From<char> for Token {
+    fn from(c: char) -> Self {
+        Token::Literal(c)
+    }
+}
+
+impl From<RangeInclusive<char>> for Token {
+    fn from(range: RangeInclusive<char>) -> Self {
+        Token::Range(*range.start(), *range.end())
+    }
+}
+
+fn class_element(sign: Sign, items: Vec<Token>) -> Element {
+    Element::Class(CharacterClass {
+        sign,
+        items,
+        quantifier: Quantifier::Once,
+    })
+}
+
+// The quantifier of an element that can be repeated.
+pub(crate) fn quantifier_mut(element: &mut Element) -> Option<&mut Quantifier> {
+    match element {
+        Element::Class(class) => Some(&mut class.quantifier),
+        Element::Sequence(_, quantifier)
+        | Element::CaptureGroup(_, _, quantifier)
+        | Element::Group(_, _, quantifier)
+        | Element::Backreference(_, quantifier)
+        | Element::Atomic(_, quantifier)
+        | Element::Conditional(_, _, quantifier) => Some(quantifier),
+        Element::Flags(_) | Element::Lookahead(..) => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Visitor for Events {
+        fn enter_term(&mut self, term: &Term) {
+            self.0.push(format!("term {}", term.elements.len()));
+        }
+        fn exit_term(&mut self, _term: &Term) {
+            self.0.push("/term".to_string());
+        }
+        fn enter_element(&mut self, element: &Element) {
+            self.0.push(format!(
+                "element {}",
+                element.terms().map_or(0, <[Term]>::len)
+            ));
+        }
+        fn exit_element(&mut self, _element: &Element) {
+            self.0.push("/element".to_string());
+        }
+    }
+
+    #[test]
+    fn walks_depth_first() {
+        let mut events = Events::default();
+        walk(&parse("(?>x|y)z").unwrap(), &mut events);
+        let expected = [
+            "term 2",
+            "element 2",
+            "term 1",
+            "element 0",
+            "/element",
+            "/term",
+            "term 1",
+            "element 0",
+            "/element",
+            "/term",
+            "/element",
+            "element 0",
+            "/element",
+            "/term",
+        ];
+        assert_eq!(events.0, expected);
+    }
+
+    #[test]
+    fn folds_bottom_up() {
+        let mut order = Vec::new();
+        let terms = fold(parse("(a(?=b))c").unwrap(), |element| {
+            order.push(element.terms().is_some());
+            match element {
+                Element::Lookahead(..) => {
+                    Element::Sequence(SpecialSequence::Digit, Quantifier::Once)
+                }
+                element => element,
+            }
+        });
+        assert_eq!(order, [false, false, true, true, false]);
+        assert_eq!(terms, parse(r"(a\d)c").unwrap());
+    }
+
+    #[test]
+    fn writes_patterns_back() {
+        let patterns = [
+            r"^a|b$|^$|",
+            r"(?m)^(?i)x|(?s-i:.)+?",
+            r"[]a-c\d[^x-z]\]\-\[^]*+",
+            r"[^a]\^\.\*\$\|",
+            "\t\n\\u{1}\u{85}\u{2028} #[ #\t]",
+            r"(?P<n>a)(?<m>b)\2{2}(?(1)c|d){3,}e{,4}",
+            r"(?=a|b)(?!c)(?>d|e)?+\A\b\B\G\R\X\Z\z\s\S\w\W",
+            "(?x) a # comment\n b",
+            r"(\N{CJK UNIFIED IDEOGRAPH-4E00})\x41\0\10",
+        ];
+        for pattern in patterns {
+            let terms = parse(pattern).unwrap();
+            let written = to_pattern(&terms);
+            assert_eq!(parse(&written).unwrap(), terms, "{pattern} => {written}");
+        }
+        assert_eq!(to_pattern(&parse(" (?x)  a b").unwrap()), r"\ (?x)ab");
+    }
+
+    #[test]
+    fn builds_patterns() {
+        let pattern = Pattern::literal("ab")
+            .then(class(['a'..='z']).repeated(1..))
+            .named("word");
+        assert_eq!(pattern.terms(), parse("(?P<word>ab[a-z]+)").unwrap());
+        let regex = pattern
+            .clone()
+            .or(Pattern::special(SpecialSequence::Digit).repeated(2..=3))
+            .build()
+            .unwrap();
+        assert_eq!(regex.as_str(), r"(?P<word>ab[a-z]+)|\d{2,3}");
+        assert_eq!(
+            regex
+                .captures("xabc")
+                .unwrap()
+                .name("word")
+                .unwrap()
+                .as_str(),
+            "abc"
+        );
+        let repeated = Pattern::literal("ab")
+            .or(Pattern::not_class(['c']))
+            .repeated(..3)
+            .then(Pattern::literal("d"));
+        assert_eq!(repeated.to_string(), "(?:ab|[^c]){0,2}d");
+        assert_eq!(
+            Pattern::literal("a")
+                .repeated(0..=1)
+                .repeated(1..)
+                .to_string(),
+            "(?:a?)+"
+        );
+        let duplicate = Pattern::literal("a")
+            .named("x")
+            .then(Pattern::literal("b").named("x"));
+        assert_eq!(
+            duplicate.build().unwrap_err().kind(),
+            &ErrorKind::DuplicateGroupName("x".to_string())
+        );
+        assert!(Pattern::literal("").build().unwrap().is_match(""));
+    }
+
+    #[test]
+    #[should_panic(expected = "empty repetition range")]
+    fn rejects_empty_ranges() {
+        let _ = Pattern::literal("a").repeated(2..2);
+    }
+
+    #[test]
+    fn parses_patterns() {
+        let terms = parse("^[^a-c]+?$").unwrap();
+        assert!(terms[0].left_anchored && terms[0].right_anchored);
+        assert_eq!(
+            terms[0].elements,
+            [Element::Class(CharacterClass {
+                sign: Sign::Exclusive,
+                items: vec![Token::Range('a', 'c')],
+                quantifier: Quantifier::LazyOneOrMore,
+            })]
+        );
+        let verbose = RegexBuilder::new("a b # c")
+            .ignore_whitespace(true)
+            .parse()
+            .unwrap();
+        assert_eq!(verbose[0].elements.len(), 2);
+        assert_eq!(
+            parse(r"(a)\2").unwrap_err().kind(),
+            &ErrorKind::InvalidBackreference(2)
+        );
+    }
+}
@@ -0,0 +1,592 @@
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::aho_corasick::{literal_alternatives, AhoCorasick};
+use crate::analysis::{CompileWarnings, Report};
+use crate::ast::{simplify, to_pattern};
+use crate::cache::Pool;
+use crate::dfa::{LazyDFA, Search, DFA};
+use crate::fixed::FixedRegex;
+use crate::fsm::{Budget, NFA};
+use crate::hir::{Flags, Hir};
+use crate::onepass::OnePass;
+use crate::parser::{
+    check_nesting, element_offsets, group_names, parse_regex, resolve_classes, Element, Flag,
+    Stripped, Term, Token,
+};
+use crate::trace::{Tracer, Tracing};
+use crate::utf8::ByteFSM;
+use crate::{Error, ErrorKind, Inner, Regex};
+
+// How many ways of writing a prefix that matches in any case searches look
+// for at most, like the 64 of `(?i)needle`.
+const PREFIX_VARIANTS: usize = 64;
+
+/// Configures and compiles a [`Regex`].
+///
+/// The flags set here are in effect from the start of the pattern, as if it
+/// began with the corresponding inline flags, and can still be changed by
+/// the pattern itself.
+///
+/// ```
+/// use regexrs::RegexBuilder;
+///
+/// let regex = RegexBuilder::new("hello").case_insensitive(true).build().unwrap();
+/// assert!(regex.is_match("HeLLo"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RegexBuilder {
+    match_kind: MatchKind,
+    pattern: String,
+    case_insensitive: bool,
+    multi_line: bool,
+    dot_matches_new_line: bool,
+    ignore_whitespace: bool,
+    unicode: bool,
+    crlf: bool,
+    prefer_dfa: bool,
+    simplify: bool,
+    size_limit: usize,
+    dfa_size_limit: usize,
+    nest_limit: usize,
+    repetition_limit: usize,
+    backtrack_limit: usize,
+    invalid_bytes: InvalidBytes,
+    quit_bytes: u128,
+    tracer: Tracing,
+    // The classes the pattern can refer to by name.
+    classes: BTreeMap<String, Vec<Token>>,
+    // The syntax tree to compile instead of parsing the pattern, which was
+    // written from it.
+    ast: Option<Vec<Term>>,
+}
+
+impl RegexBuilder {
+    /// Starts configuring a regex for `pattern`, with all flags but Unicode
+    /// mode disabled and the default limits.
+    pub fn new<S: AsRef<str>>(pattern: S) -> Self {
+        Self {
+            pattern: pattern.as_ref().to_owned(),
+            match_kind: MatchKind::LeftmostFirst,
+            case_insensitive: false,
+            multi_line: false,
+            dot_matches_new_line: false,
+            ignore_whitespace: false,
+            unicode: true,
+            crlf: false,
+            prefer_dfa: false,
+            simplify: false,
+            size_limit: 10 * (1 << 20),
+            dfa_size_limit: 2 * (1 << 20),
+            nest_limit: 250,
+            repetition_limit: 1000,
+            backtrack_limit: Budget::DEFAULT_STEPS,
+            invalid_bytes: InvalidBytes::Error,
+            quit_bytes: 0,
+            tracer: Tracing::default(),
+            classes: BTreeMap::new(),
+            ast: None,
+        }
+    }
+    /// Starts configuring a regex for a syntax tree, like one returned by
+    /// [`parse`](Self::parse) and then changed, which is compiled as it
+    /// is. The pattern of the regex is written from it with
+    /// [`ast::to_pattern`](crate::ast::to_pattern).
+    ///
+    /// Whitespace in the tree is matched literally, even with
+    /// [`ignore_whitespace`](Self::ignore_whitespace).
+    pub fn from_ast(terms: Vec<Term>) -> Self {
+        Self {
+            ast: Some(terms.clone()),
+            ..Self::new(to_pattern(&terms))
+        }
+    }
+    /// Compiles the pattern with the current configuration.
+    pub fn build(&self) -> Result<Regex, Error> {
+        let Compiled {
+            ast,
+            nfa,
+            names,
+            literals,
+            inner_literal,
+        } = self.compile()?;
+        let bytes = ByteFSM::new(&nfa, self.size_limit);
+        let dfa = bytes
+            .as_ref()
+            .filter(|_| self.prefer_dfa)
+            .and_then(|bytes| DFA::new(bytes, self.dfa_size_limit));
+        let lazy = bytes
+            .as_ref()
+            .filter(|_| dfa.is_none())
+            .and_then(|bytes| LazyDFA::new(bytes, self.dfa_size_limit));
+        let search = bytes
+            .as_ref()
+            .filter(|_| !nfa.longest())
+            .and_then(|bytes| Search::new(bytes, self.size_limit, self.dfa_size_limit));
+        let onepass = OnePass::new(&nfa, self.size_limit);
+        let prefix = nfa.literal_prefix();
+        let prefixes = match prefix {
+            Some(_) => None,
+            None => nfa
+                .prefix_variants(PREFIX_VARIANTS)
+                .map(|variants| AhoCorasick::new(variants, false)),
+        };
+        let suffix = nfa.literal_suffix();
+        let reverse_suffix = nfa
+            .reverse_suffix()
+            .filter(|_| search.is_some() && prefix.is_none());
+        // Without any of those to look for, searches can look for a literal
+        // in the middle of the pattern.
+        let search = match (search, inner_literal) {
+            (Some(search), Some((before, literal)))
+                if prefix.is_none() && prefixes.is_none() && reverse_suffix.is_none() =>
+            {
+                let before = NFA::compile(&before, self.size_limit)
+                    .ok()
+                    .and_then(|before| ByteFSM::new(&before, self.size_limit));
+                Some(match before {
+                    Some(before) => search.with_inner_literal(literal, &before, self.size_limit),
+                    None => search,
+                })
+            }
+            (search, _) => search,
+        };
+        let literals = literals.map(|literals| AhoCorasick::new(literals, nfa.longest()));
+        let warnings = CompileWarnings::new(&ast, self.flags(), !nfa.longest());
+        let inner = Inner {
+            pattern: self.pattern.clone(),
+            ast,
+            warnings,
+            nfa,
+            prefix,
+            prefixes,
+            suffix,
+            reverse_suffix,
+            literals,
+            bytes,
+            dfa,
+            lazy,
+            search,
+            onepass,
+            names: Arc::from(names),
+            backtrack_limit: self.backtrack_limit,
+            invalid_bytes: self.invalid_bytes,
+            quit_bytes: self.quit_bytes,
+            dfa_size_limit: self.dfa_size_limit,
+            tracer: self.tracer.0.clone(),
+            pool: Pool::new(),
+        };
+        Ok(Regex {
+            inner: Arc::new(inner),
+        })
+    }
+    /// Compiles the pattern into a [`FixedRegex`] with room for `STATES`
+    /// states and `SLOTS` capture slots, failing with
+    /// [`ErrorKind::StateCapacityExceeded`](crate::ErrorKind::StateCapacityExceeded)
+    /// or [`ErrorKind::SlotCapacityExceeded`](crate::ErrorKind::SlotCapacityExceeded)
+    /// if it needs more.
+    pub fn build_fixed<const STATES: usize, const SLOTS: usize>(
+        &self,
+    ) -> Result<FixedRegex<STATES, SLOTS>, Error> {
+        let Compiled {
+            ast, nfa, names, ..
+        } = self.compile()?;
+        FixedRegex::new(&nfa, 2 * names.len()).map_err(|kind| {
+            // Points at the first element that can't be matched, or at the
+            // first group without slots.
+            let element = match &kind {
+                ErrorKind::Unsupported(_) => find_element(&ast, &mut |element| {
+                    matches!(
+                        element,
+                        Element::Lookahead(..)
+                            | Element::Backreference(..)
+                            | Element::Atomic(..)
+                            | Element::Conditional(..)
+                    )
+                }),
+                ErrorKind::SlotCapacityExceeded(slots) => {
+                    let mut groups = 0;
+                    find_element(&ast, &mut |element| {
+                        groups += matches!(element, Element::CaptureGroup(..)) as usize;
+                        groups > 0 && groups == slots / 2
+                    })
+                }
+                _ => None,
+            };
+            self.error(&ast, kind, element)
+        })
+    }
+    /// Parses the pattern into its syntax tree, one [`Term`] per top-level
+    /// alternative, without compiling it. The pattern is checked as
+    /// [`build`](Self::build) would, except for the size limits.
+    ///
+    /// With [`ignore_whitespace`](Self::ignore_whitespace), whitespace and
+    /// comments are left out of the tree.
+    pub fn parse(&self) -> Result<Vec<Term>, Error> {
+        self.parse_with_names().map(|(ast, _)| ast)
+    }
+    // Parses the pattern, and collects the names of its groups.
+    fn parse_with_names(&self) -> Result<(Vec<Term>, Vec<Option<String>>), Error> {
+        let pattern = self.pattern.as_str();
+        if let Some(ast) = &self.ast {
+            if let Some(tracer) = &self.tracer.0 {
+                tracer.parsed(ast);
+            }
+            check_nesting(pattern, ast, self.nest_limit)?;
+            let names = group_names(pattern, ast)?;
+            return Ok((resolve_classes(pattern, ast.clone(), &self.classes)?, names));
+        }
+        let stripped = Stripped::new(pattern, self.ignore_whitespace);
+        let locate = |error| stripped.locate(pattern, error);
+        let ast = parse_regex(&stripped.text).map_err(locate)?;
+        if let Some(tracer) = &self.tracer.0 {
+            tracer.parsed(&ast);
+        }
+        check_nesting(&stripped.text, &ast, self.nest_limit).map_err(locate)?;
+        let names = group_names(&stripped.text, &ast).map_err(locate)?;
+        let ast = resolve_classes(&stripped.text, ast, &self.classes).map_err(locate)?;
+        Ok((ast, names))
+    }
+    /// Analyzes the pattern for what could make compiling or matching it
+    /// expensive, see [`Report`]. The pattern is checked as
+    /// [`parse`](Self::parse) checks it, and for the repetition limit.
+    pub fn analyze(&self) -> Result<Report, Error> {
+        let ast = self.parse()?;
+        let ast = if self.simplify { simplify(ast) } else { ast };
+        let hir = Hir::translate(&ast, self.flags(), self.repetition_limit)
+            .map_err(|(kind, element)| self.error(&ast, kind, Some(element)))?;
+        let warnings = CompileWarnings::new(
+            &ast,
+            self.flags(),
+            self.match_kind == MatchKind::LeftmostFirst,
+        );
+        Ok(Report::new(&hir, warnings))
+    }
+    // The error `kind`, pointing at the start of the element of `ast` with
+    // the number `element`, counted in the order `ast::walk` enters them,
+    // or at the start of the pattern if there is none. A simplified tree
+    // doesn't come from the pattern, so its elements can't be pointed at.
+    fn error(&self, ast: &[Term], kind: ErrorKind, element: Option<usize>) -> Error {
+        let pattern = self.pattern.as_str();
+        let Some(element) = element.filter(|_| !self.simplify) else {
+            return Error::new(kind, pattern, 0);
+        };
+        if self.ast.is_some() {
+            let position = element_offsets(pattern, ast)
+                .get(element)
+                .copied()
+                .unwrap_or(0);
+            return Error::new(kind, pattern, position);
+        }
+        let stripped = Stripped::new(pattern, self.ignore_whitespace);
+        let position = element_offsets(&stripped.text, ast)
+            .get(element)
+            .copied()
+            .unwrap_or(0);
+        stripped.locate(pattern, Error::new(kind, &stripped.text, position))
+    }
+    // The flags patterns start out with.
+    fn flags(&self) -> Flags {
+        Flags::new(&[
+            (Flag::CaseInsensitive, self.case_insensitive),
+            (Flag::MultiLine, self.multi_line),
+            (Flag::DotAll, self.dot_matches_new_line),
+            (Flag::Verbose, self.ignore_whitespace),
+            (Flag::Unicode, self.unicode),
+            (Flag::Crlf, self.crlf),
+        ])
+    }
+    // Parses and compiles the pattern.
+    pub(crate) fn compile(&self) -> Result<Compiled, Error> {
+        let (ast, names) = self.parse_with_names()?;
+        let ast = if self.simplify { simplify(ast) } else { ast };
+        let hir = Hir::translate(&ast, self.flags(), self.repetition_limit)
+            .map_err(|(kind, element)| self.error(&ast, kind, Some(element)))?;
+        let mut nfa = NFA::compile(&hir, self.size_limit)
+            .map_err(|(kind, element)| self.error(&ast, kind, element))?;
+        nfa.set_longest(self.match_kind == MatchKind::LeftmostLongest);
+        let literals = Some(&ast)
+            .filter(|_| !self.case_insensitive)
+            .and_then(|ast| literal_alternatives(ast));
+        Ok(Compiled {
+            ast,
+            nfa,
+            names,
+            literals,
+            inner_literal: hir.inner_literal(),
+        })
+    }
+    /// Defines a class of characters that the pattern can refer to by
+    /// `name`, as `[[:name:]]` or `\p{name}`, or to everything but them, as
+    /// `[[:^name:]]` or `\P{name}`. The items are characters, ranges or
+    /// anything else a class can contain, and are matched with the flags in
+    /// effect where the name is used. Defining a name again replaces it.
+    ///
+    /// Using a name that isn't defined fails with
+    /// [`ErrorKind::UnknownClassName`](crate::ErrorKind::UnknownClassName),
+    /// as does using one in the items of another. The syntax tree of the
+    /// regex contains the items in place of the name.
+    ///
+    /// ```
+    /// use regexrs::RegexBuilder;
+    ///
+    /// let regex = RegexBuilder::new(r"0x[[:hexdigit:]]+|\p{sign}")
+    ///     .define_class("hexdigit", ['0'..='9', 'a'..='f', 'A'..='F'])
+    ///     .define_class("sign", ['+', '-'])
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(regex.find("= 0xFFg").unwrap().as_str(), "0xFF");
+    /// assert!(regex.is_match("-"));
+    /// ```
+    pub fn define_class<T: Into<Token>, I: IntoIterator<Item = T>>(
+        &mut self,
+        name: &str,
+        items: I,
+    ) -> &mut Self {
+        self.classes
+            .insert(name.to_owned(), items.into_iter().map(Into::into).collect());
+        self
+    }
+    /// Chooses which of the matches starting at the leftmost position is
+    /// reported.
+    ///
+    /// Defaults to [`MatchKind::LeftmostFirst`].
+    pub fn match_kind(&mut self, kind: MatchKind) -> &mut Self {
+        self.match_kind = kind;
+        self
+    }
+    /// Matches letters regardless of case, like the `i` flag.
+    pub fn case_insensitive(&mut self, yes: bool) -> &mut Self {
+        self.case_insensitive = yes;
+        self
+    }
+    /// Lets `^` and `$` match at line boundaries, like the `m` flag.
+    pub fn multi_line(&mut self, yes: bool) -> &mut Self {
+        self.multi_line = yes;
+        self
+    }
+    /// Lets `.` match `\n` as well, like the `s` flag.
+    pub fn dot_matches_new_line(&mut self, yes: bool) -> &mut Self {
+        self.dot_matches_new_line = yes;
+        self
+    }
+    /// Ignores whitespace and `#` comments in the pattern, like the `x`
+    /// flag.
+    pub fn ignore_whitespace(&mut self, yes: bool) -> &mut Self {
+        self.ignore_whitespace = yes;
+        self
+    }
+    /// Uses the Unicode definitions of `\d`, `\s`, `\w` and case folding,
+    /// like the `u` flag. Turning this off restricts them to ASCII, which is
+    /// cheaper to match.
+    ///
+    /// Enabled by default.
+    pub fn unicode(&mut self, yes: bool) -> &mut Self {
+        self.unicode = yes;
+        self
+    }
+    /// Treats `\r\n` as a single line terminator, like the `R` flag: in
+    /// multi-line mode, `^` and `$` also match after and before a lone `\r`,
+    /// but never between `\r` and `\n`, and `.` doesn't match `\r` either.
+    pub fn crlf(&mut self, yes: bool) -> &mut Self {
+        self.crlf = yes;
+        self
+    }
+    /// Builds a DFA for [`Regex::is_match`] up front, which then takes a
+    /// single table lookup per byte of the haystack.
+    ///
+    /// Building it may take time and memory exponential in the size of the
+    /// pattern. If it would exceed the DFA size limit, or the pattern uses
+    /// lookaheads or backreferences, the regex quietly goes without. It
+    /// then falls back to a DFA that builds only the states a search needs,
+    /// unless the pattern has `\Z`, line anchors in CRLF mode, or word
+    /// boundaries that aren't restricted to ASCII with `(?-u)`.
+    ///
+    /// Disabled by default.
+    pub fn prefer_dfa(&mut self, yes: bool) -> &mut Self {
+        self.prefer_dfa = yes;
+        self
+    }
+    /// Simplifies the syntax tree with [`ast::simplify`](crate::ast::simplify)
+    /// before compiling it, which makes patterns generated by code, like
+    /// long lists of alternatives, smaller and faster to match.
+    /// [`Regex::ast`] then returns the simplified tree.
+    ///
+    /// Disabled by default, as patterns written by hand rarely gain
+    /// anything, and alternatives of plain strings are already matched
+    /// together without it.
+    pub fn simplify(&mut self, yes: bool) -> &mut Self {
+        self.simplify = yes;
+        self
+    }
+    /// Sets the approximate number of bytes the compiled pattern may take
+    /// up. Compiling a larger one fails with
+    /// [`ErrorKind::CompiledTooBig`](crate::ErrorKind::CompiledTooBig),
+    /// before it takes up much more than that, so that patterns like
+    /// `((a|aa){1,1000}){1,1000}` can't exhaust memory. Engines derived
+    /// from the compiled pattern, like the one-pass matcher, are left out if
+    /// they would exceed the limit, and searches use the others.
+    ///
+    /// Defaults to 10 MiB.
+    pub fn size_limit(&mut self, bytes: usize) -> &mut Self {
+        self.size_limit = bytes;
+        self
+    }
+    /// Sets the approximate number of bytes a DFA may take up: the one built
+    /// up front if [`RegexBuilder::prefer_dfa`] is set, or the cache of
+    /// states built during searches otherwise. The DFA is left out if it
+    /// would be larger, and when the cache fills up, searches fall back to
+    /// slower engines.
+    ///
+    /// Defaults to 2 MiB.
+    pub fn dfa_size_limit(&mut self, bytes: usize) -> &mut Self {
+        self.dfa_size_limit = bytes;
+        self
+    }
+    /// Sets how deeply groups may be nested. Compiling a pattern that nests
+    /// them deeper fails with
+    /// [`ErrorKind::NestLimitExceeded`](crate::ErrorKind::NestLimitExceeded).
+    ///
+    /// Defaults to 250.
+    pub fn nest_limit(&mut self, limit: usize) -> &mut Self {
+        self.nest_limit = limit;
+        self
+    }
+    /// Sets how often a counted repetition like `a{3,5}` may repeat its
+    /// element. Counted repetitions are compiled by copying the element, so
+    /// compiling a pattern with a larger bound fails with
+    /// [`ErrorKind::RepetitionLimitExceeded`](crate::ErrorKind::RepetitionLimitExceeded).
+    ///
+    /// Defaults to 1000.
+    pub fn repetition_limit(&mut self, limit: usize) -> &mut Self {
+        self.repetition_limit = limit;
+        self
+    }
+    /// Sets how many steps a single search may take when the pattern has to
+    /// be matched by backtracking, which is the case for backreferences,
+    /// atomic groups and conditionals, also within lookaheads. A search
+    /// that takes more gives up: [`Regex::try_find`] and the other `try_`
+    /// methods report
+    /// [`MatchError::BacktrackLimitExceeded`](crate::MatchError::BacktrackLimitExceeded),
+    /// while the others find no match.
+    ///
+    /// Backtracking skips the states it has been in before, but those also
+    /// depend on what the groups have captured, so their number can grow
+    /// with a high power of the length of the haystack. The limit keeps
+    /// patterns like `(a*)*\1b` from taking practically forever.
+    ///
+    /// Defaults to 1,000,000.
+    pub fn backtrack_limit(&mut self, steps: usize) -> &mut Self {
+        self.backtrack_limit = steps;
+        self
+    }
+    /// Chooses what [`Regex::find_bytes`] does at bytes of the haystack that
+    /// aren't part of valid UTF-8, and at the
+    /// [quit bytes](Self::quit_bytes).
+    ///
+    /// Defaults to [`InvalidBytes::Error`].
+    pub fn invalid_bytes(&mut self, handling: InvalidBytes) -> &mut Self {
+        self.invalid_bytes = handling;
+        self
+    }
+    /// Makes [`Regex::find_bytes`] handle `bytes` like bytes that aren't
+    /// part of valid UTF-8, see [`invalid_bytes`](Self::invalid_bytes),
+    /// e.g. to stop at NUL bytes in data that is mostly text. Replaces the
+    /// quit bytes set before.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the bytes isn't ASCII, since the others are part of
+    /// characters.
+    pub fn quit_bytes(&mut self, bytes: &[u8]) -> &mut Self {
+        assert!(bytes.is_ascii(), "quit bytes must be ASCII");
+        self.quit_bytes = bytes.iter().fold(0, |quit, &byte| quit | 1 << byte);
+        self
+    }
+    /// Reports the parsed pattern and the steps of searches to `tracer`,
+    /// see [`Tracer`].
+    pub fn tracer(&mut self, tracer: Arc<dyn Tracer>) -> &mut Self {
+        self.tracer = Tracing(Some(tracer));
+        self
+    }
+}
+
+// The number of the first element of `terms` for which `found` holds,
+// counted in the order `ast::walk` enters them.
+fn find_element(terms: &[Term], found: &mut dyn FnMut(&Element) -> bool) -> Option<usize> {
+    fn find(
+        terms: &[Term],
+        found: &mut dyn FnMut(&Element) -> bool,
+        count: &mut usize,
+    ) -> Option<usize> {
+        for element in terms.iter().flat_map(|term| &term.elements) {
+            if found(element) {
+                return Some(*count);
+            }
+            *count += 1;
+            if let Some(element) = element.terms().and_then(|terms| find(terms, found, count)) {
+                return Some(element);
+            }
+        }
+        None
+    }
+    find(terms, found, &mut 0)
+}
+
+// What the parts of a `Regex` are built from.
+pub(crate) struct Compiled {
+    pub(crate) ast: Vec<Term>,
+    pub(crate) nfa: NFA,
+    // The names of the groups, starting with group 0.
+    pub(crate) names: Vec<Option<String>>,
+    // The literals the pattern consists of, if it is just a literal or an
+    // alternation of them.
+    pub(crate) literals: Option<Vec<String>>,
+    // The literal in the middle of the pattern, and the part before it.
+    pub(crate) inner_literal: Option<(Hir, String)>,
+}
+
+/// What [`Regex::find_bytes`] does at bytes of the haystack that aren't part
+/// of valid UTF-8, and at the quit bytes, see
+/// [`RegexBuilder::invalid_bytes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidBytes {
+    /// Fails with [`MatchError::InvalidByte`](crate::MatchError::InvalidByte)
+    /// at the first of them, if the haystack has any.
+    Error,
+    /// Searches the text between them, each run on its own, so that no
+    /// match includes one, and they are the start and end of the text to
+    /// assertions like `^` and `\b`.
+    Boundary,
+    /// Matches them as part of the text: a run of bytes that aren't valid
+    /// UTF-8 like U+FFFD, the replacement character, which
+    /// `String::from_utf8_lossy` would put in their place, and quit bytes
+    /// as they are.
+    Raw,
+}
+
+/// How a [`Regex`] chooses between several matches that start at the same,
+/// leftmost position in the haystack.
+///
+/// ```
+/// use regexrs::{MatchKind, RegexBuilder};
+///
+/// let regex = RegexBuilder::new("foo|foobar")
+///     .match_kind(MatchKind::LeftmostLongest)
+///     .build()
+///     .unwrap();
+/// assert_eq!(regex.find("foobar").unwrap().as_str(), "foobar");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// Prefers the match that the pattern tries first, as in Perl: earlier
+    /// alternatives win over later ones, and greedy quantifiers repeat as
+    /// often as they can. `foo|foobar` matches `foo` in `foobar`.
+    LeftmostFirst,
+    /// Prefers the longest match, as POSIX does. `foo|foobar` matches all
+    /// of `foobar`.
+    LeftmostLongest,
+}
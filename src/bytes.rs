@@ -0,0 +1,213 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use crate::{InvalidBytes, MatchError, Regex};
+
+// A run of a byte haystack: text, or bytes that searches stop at, which
+// aren't part of valid UTF-8 or are quit bytes. Both start at the offset.
+enum Span<'h> {
+    Text(usize, &'h str),
+    Stop(usize, &'h [u8]),
+}
+
+// Splits `haystack` into runs of text and of the bytes between them, with
+// the quit bytes in `quit`, one bit for each ASCII byte, on their own. Each
+// run of bytes that aren't valid UTF-8 is as long as `from_utf8_lossy` would
+// replace with a single character.
+fn spans(haystack: &[u8], quit: u128) -> Vec<Span<'_>> {
+    let mut spans = Vec::new();
+    let mut offset = 0;
+    for chunk in haystack.utf8_chunks() {
+        let mut text = chunk.valid();
+        while let Some(at) = text.bytes().position(|byte| is_quit(quit, byte)) {
+            spans.push(Span::Text(offset, &text[..at]));
+            spans.push(Span::Stop(offset + at, &text.as_bytes()[at..=at]));
+            offset += at + 1;
+            text = &text[at + 1..];
+        }
+        spans.push(Span::Text(offset, text));
+        offset += text.len();
+        spans.push(Span::Stop(offset, chunk.invalid()));
+        offset += chunk.invalid().len();
+    }
+    spans.retain(|span| match span {
+        Span::Text(_, text) => !text.is_empty(),
+        Span::Stop(_, bytes) => !bytes.is_empty(),
+    });
+    spans
+}
+
+fn is_quit(quit: u128, byte: u8) -> bool {
+    byte < 128 && quit >> byte & 1 == 1
+}
+
+impl Regex {
+    /// Returns the span of the leftmost match in `haystack`, which may not
+    /// be valid UTF-8. What the search does at bytes that aren't part of a
+    /// character, and at quit bytes, is set with
+    /// [`RegexBuilder::invalid_bytes`](crate::RegexBuilder::invalid_bytes).
+    /// Offsets are into `haystack`.
+    ///
+    /// Fails like [`Regex::try_find`], and with
+    /// [`MatchError::InvalidByte`] if the regex is set to.
+    ///
+    /// ```
+    /// use regexrs::{InvalidBytes, MatchError, RegexBuilder};
+    ///
+    /// let haystack = b"id=\xFF\xFE7 id=42";
+    /// let mut builder = RegexBuilder::new(r"id=\D*\d+");
+    /// let regex = builder.build().unwrap();
+    /// assert_eq!(regex.find_bytes(haystack), Err(MatchError::InvalidByte(3)));
+    /// let regex = builder.invalid_bytes(InvalidBytes::Boundary).build().unwrap();
+    /// assert_eq!(regex.find_bytes(haystack), Ok(Some(7..12)));
+    /// let regex = builder.invalid_bytes(InvalidBytes::Raw).build().unwrap();
+    /// assert_eq!(regex.find_bytes(haystack), Ok(Some(0..6)));
+    /// ```
+    pub fn find_bytes(&self, haystack: &[u8]) -> Result<Option<Range<usize>>, MatchError> {
+        let quit = self.inner.quit_bytes;
+        if let Ok(text) = core::str::from_utf8(haystack) {
+            if !text.bytes().any(|byte| is_quit(quit, byte)) {
+                return Ok(self.try_find(text)?.map(|m| m.range()));
+            }
+        }
+        match self.inner.invalid_bytes {
+            InvalidBytes::Error => {
+                let at = spans(haystack, quit).iter().find_map(|span| match span {
+                    Span::Stop(at, _) => Some(*at),
+                    Span::Text(..) => None,
+                });
+                Err(MatchError::InvalidByte(at.unwrap_or(0)))
+            }
+            InvalidBytes::Boundary => {
+                for span in spans(haystack, quit) {
+                    let Span::Text(offset, text) = span else {
+                        continue;
+                    };
+                    if let Some(m) = self.try_find(text)? {
+                        return Ok(Some(offset + m.start()..offset + m.end()));
+                    }
+                }
+                Ok(None)
+            }
+            InvalidBytes::Raw => {
+                // Where each span starts in the text searched, and in the
+                // haystack.
+                let mut text = String::with_capacity(haystack.len());
+                let mut starts = Vec::new();
+                for span in spans(haystack, 0) {
+                    match span {
+                        Span::Text(offset, piece) => {
+                            starts.push((text.len(), offset));
+                            text.push_str(piece);
+                        }
+                        Span::Stop(offset, _) => {
+                            starts.push((text.len(), offset));
+                            text.push(char::REPLACEMENT_CHARACTER);
+                        }
+                    }
+                }
+                starts.push((text.len(), haystack.len()));
+                // Matches start and end at characters, so never inside a
+                // replaced run.
+                let position = |at: usize| {
+                    let (start, offset) =
+                        starts[starts.partition_point(|&(start, _)| start <= at) - 1];
+                    offset + (at - start)
+                };
+                Ok(self
+                    .try_find(&text)?
+                    .map(|m| position(m.start())..position(m.end())))
+            }
+        }
+    }
+    /// Returns true if the pattern matches somewhere in `haystack`, which
+    /// may not be valid UTF-8, as described in [`Regex::find_bytes`].
+    pub fn is_match_bytes(&self, haystack: &[u8]) -> Result<bool, MatchError> {
+        Ok(self.find_bytes(haystack)?.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{InvalidBytes, MatchError, RegexBuilder};
+
+    #[test]
+    fn handles_invalid_and_quit_bytes() {
+        let find = |pattern: &str, handling, quit: &[u8], haystack: &[u8]| {
+            let regex = RegexBuilder::new(pattern)
+                .invalid_bytes(handling)
+                .quit_bytes(quit)
+                .build()
+                .unwrap();
+            regex.find_bytes(haystack)
+        };
+        for handling in [
+            InvalidBytes::Error,
+            InvalidBytes::Boundary,
+            InvalidBytes::Raw,
+        ] {
+            assert_eq!(
+                find(r"\w+", handling, b"", "  héllo".as_bytes()),
+                Ok(Some(2..8))
+            );
+            assert_eq!(find("a", handling, b"", b""), Ok(None));
+        }
+        // The first of several stops is reported.
+        let haystack = b"ab\0c\xC3d\xFF";
+        assert_eq!(
+            find("d", InvalidBytes::Error, b"\0", haystack),
+            Err(MatchError::InvalidByte(2))
+        );
+        assert_eq!(
+            find("d", InvalidBytes::Error, b"", haystack),
+            Err(MatchError::InvalidByte(4))
+        );
+        // No match reaches across a stop, and assertions take it for the end
+        // of the text.
+        assert_eq!(
+            find("b.c", InvalidBytes::Boundary, b"\0", haystack),
+            Ok(None)
+        );
+        assert_eq!(
+            find("b.c", InvalidBytes::Boundary, b"", haystack),
+            Ok(Some(1..4))
+        );
+        assert_eq!(
+            find("^cd?$", InvalidBytes::Boundary, b"\0", haystack),
+            Ok(Some(3..4))
+        );
+        assert_eq!(
+            find(r"d\z", InvalidBytes::Boundary, b"", haystack),
+            Ok(Some(5..6))
+        );
+        // A run of invalid bytes matches like a replaced character, while
+        // quit bytes match as they are.
+        assert_eq!(
+            find("c.d.$", InvalidBytes::Raw, b"\0", haystack),
+            Ok(Some(3..7))
+        );
+        assert_eq!(
+            find("b\0", InvalidBytes::Raw, b"\0", haystack),
+            Ok(Some(1..3))
+        );
+        assert_eq!(
+            find(
+                "\u{FFFD}+",
+                InvalidBytes::Raw,
+                b"",
+                b"a\xF0\x9F\x98b\xFF\xFE"
+            ),
+            Ok(Some(1..4))
+        );
+        assert_eq!(
+            find(
+                "b\u{FFFD}{2}",
+                InvalidBytes::Raw,
+                b"",
+                b"a\xF0\x9F\x98b\xFF\xFE"
+            ),
+            Ok(Some(4..7))
+        );
+    }
+}
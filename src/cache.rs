@@ -0,0 +1,122 @@
+#[cfg(not(feature = "std"))]
+use core::marker::PhantomData;
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::sync::Mutex;
+
+use crate::dfa::{self, SearchCache};
+
+/// Scratch space for the searches of a [`Regex`](crate::Regex), which holds
+/// the states its lazy DFAs have built so far.
+///
+/// Searches usually take one from a pool inside the regex, so that threads
+/// searching at the same time don't have to wait for each other. Without
+/// the `std` feature, they create a new one every time instead. Passing
+/// one to methods like [`Regex::find_with`](crate::Regex::find_with) saves
+/// that step, which pays off in tight loops.
+///
+/// ```
+/// use regexrs::Regex;
+///
+/// let regex = Regex::new(r"\d+").unwrap();
+/// let mut cache = regex.create_cache();
+/// let total: usize = ["a1", "22b", "c"]
+///     .into_iter()
+///     .filter_map(|line| regex.find_with(&mut cache, line))
+///     .map(|m| m.as_str().len())
+///     .sum();
+/// assert_eq!(total, 3);
+/// ```
+pub struct Cache {
+    // The address of the compiled pattern the cache was created for.
+    pub(crate) owner: usize,
+    pub(crate) lazy: Option<dfa::Cache>,
+    pub(crate) search: Option<SearchCache>,
+}
+
+impl core::fmt::Debug for Cache {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Cache").finish_non_exhaustive()
+    }
+}
+
+// Hands out caches to searches without making them wait for one another.
+// Every thread has a slot it tries first, so that it usually gets back the
+// cache it used last, along with the states built in it. If that slot is
+// taken, the search tries the others, and as a last resort uses a new cache
+// that is dropped afterwards.
+//
+// Without the standard library, there are no locks to guard the slots, so
+// every search creates a new value.
+pub struct Pool<T> {
+    #[cfg(feature = "std")]
+    slots: Box<[Mutex<Option<T>>]>,
+    #[cfg(not(feature = "std"))]
+    slots: PhantomData<T>,
+}
+
+#[cfg(feature = "std")]
+static THREADS: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(feature = "std")]
+thread_local! {
+    // The slot the current thread tries first.
+    static THREAD: usize = THREADS.fetch_add(1, Ordering::Relaxed);
+}
+
+#[cfg(feature = "std")]
+impl<T> Pool<T> {
+    pub fn new() -> Self {
+        let slots = std::thread::available_parallelism().map_or(4, |threads| threads.get());
+        Self {
+            slots: (0..slots).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+    // Runs `search` with a value from the pool, which `create` makes if
+    // the slot is still empty.
+    pub fn with<C, F, R>(&self, create: C, search: F) -> R
+    where
+        C: FnOnce() -> T,
+        F: FnOnce(&mut T) -> R,
+    {
+        let first = THREAD.with(|thread| *thread);
+        for offset in 0..self.slots.len() {
+            let slot = &self.slots[(first + offset) % self.slots.len()];
+            if let Ok(mut value) = slot.try_lock() {
+                return search(value.get_or_insert_with(create));
+            }
+        }
+        search(&mut create())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Pool<T> {
+    pub fn new() -> Self {
+        Self { slots: PhantomData }
+    }
+    pub fn with<C, F, R>(&self, create: C, search: F) -> R
+    where
+        C: FnOnce() -> T,
+        F: FnOnce(&mut T) -> R,
+    {
+        search(&mut create())
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hands_out_values_without_waiting() {
+        let pool = Pool::new();
+        assert_eq!(pool.with(|| 1, |value| *value), 1);
+        // The thread gets its value back.
+        assert_eq!(pool.with(|| 2, |value| *value), 1);
+        // While it is taken, there are others.
+        let nested = pool.with(|| 1, |_| pool.with(|| 2, |value| *value));
+        assert_ne!(nested, 1);
+    }
+}
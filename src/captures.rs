@@ -0,0 +1,193 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::ops::Index;
+
+use crate::fsm::Slots;
+use crate::Match;
+
+/// The spans of all capture groups in a single match.
+///
+/// Group 0 is the whole match; the remaining groups are numbered by the
+/// position of their opening parenthesis in the pattern. Named groups can
+/// also be looked up by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    slots: Slots,
+    names: Arc<[Option<String>]>,
+}
+
+impl<'h> Captures<'h> {
+    pub(crate) fn new(haystack: &'h str, slots: Slots, names: Arc<[Option<String>]>) -> Self {
+        Self {
+            haystack,
+            slots,
+            names,
+        }
+    }
+    /// Returns the match of group `idx`, or `None` if the group did not
+    /// take part in the match or doesn't exist.
+    pub fn get(&self, idx: usize) -> Option<Match<'h>> {
+        match (self.slots.get(2 * idx)?, self.slots.get(2 * idx + 1)?) {
+            (&Some(start), &Some(end)) => Some(Match {
+                haystack: self.haystack,
+                start,
+                end,
+            }),
+            _ => None,
+        }
+    }
+    /// Returns the match of the group called `name`, or `None` if the group
+    /// did not take part in the match or doesn't exist.
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        let idx = self.names.iter().position(|n| n.as_deref() == Some(name))?;
+        self.get(idx)
+    }
+    /// The number of groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+    /// Always false, since group 0 is part of every match.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+    /// Appends `replacement` to `dst`, substituting group references.
+    ///
+    /// `$1` or `${1}` refer to a group by number, `$name` or `${name}` by
+    /// name, and `$$` stands for a literal `$`. A reference is as long as
+    /// possible, so `$1a` refers to a group named `1a`; use `${1}a` to
+    /// follow group 1 with an `a`. References to groups that don't exist or
+    /// didn't take part in the match expand to nothing, and a `$` that
+    /// doesn't start a valid reference is kept as is.
+    pub fn expand(&self, replacement: &str, dst: &mut String) {
+        let mut rest = replacement;
+        while let Some(idx) = rest.find('$') {
+            dst.push_str(&rest[..idx]);
+            rest = &rest[idx..];
+            if let Some(after) = rest.strip_prefix("$$") {
+                dst.push('$');
+                rest = after;
+                continue;
+            }
+            match group_reference(&rest[1..]) {
+                Some((name, after)) => {
+                    if let Some(m) = self.reference(name) {
+                        dst.push_str(m.as_str());
+                    }
+                    rest = after;
+                }
+                None => {
+                    dst.push('$');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        dst.push_str(rest);
+    }
+    fn reference(&self, name: &str) -> Option<Match<'h>> {
+        match name.parse() {
+            Ok(idx) => self.get(idx),
+            Err(_) => self.name(name),
+        }
+    }
+    /// Returns an iterator over all groups in order, yielding `None` for
+    /// groups that did not take part in the match.
+    pub fn iter<'c>(&'c self) -> impl Iterator<Item = Option<Match<'h>>> + 'c {
+        (0..self.len()).map(|idx| self.get(idx))
+    }
+}
+
+/// The byte offsets of the capture groups in a match, in a buffer that can
+/// be reused from one search to the next.
+///
+/// Created by [`Regex::capture_locations`](crate::Regex::capture_locations)
+/// and filled in by [`Regex::captures_read`](crate::Regex::captures_read).
+/// Unlike [`Captures`], it doesn't hold on to the haystack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureLocations(pub(crate) Slots);
+
+impl CaptureLocations {
+    /// Returns the start and end offsets of group `idx`, or `None` if the
+    /// group did not take part in the last match or doesn't exist.
+    pub fn get(&self, idx: usize) -> Option<(usize, usize)> {
+        match (self.0.get(2 * idx)?, self.0.get(2 * idx + 1)?) {
+            (&Some(start), &Some(end)) => Some((start, end)),
+            _ => None,
+        }
+    }
+    /// The number of groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.0.len() / 2
+    }
+    /// Always false, since there is room for group 0.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    // Stores the slots of a match, or clears all groups if there is none,
+    // and returns the whole match.
+    pub(crate) fn read<'h>(
+        &mut self,
+        haystack: &'h str,
+        captured: Option<Slots>,
+    ) -> Option<Match<'h>> {
+        let Some(captured) = captured else {
+            self.0.fill(None);
+            return None;
+        };
+        self.0.clear();
+        self.0.extend(captured);
+        Some(Match {
+            haystack,
+            start: self.0[0]?,
+            end: self.0[1]?,
+        })
+    }
+}
+
+// Splits the group name off a reference following a `$`.
+fn group_reference(input: &str) -> Option<(&str, &str)> {
+    if let Some(braced) = input.strip_prefix('{') {
+        let end = braced.find('}')?;
+        return match &braced[..end] {
+            "" => None,
+            name => Some((name, &braced[end + 1..])),
+        };
+    }
+    let end = input
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .unwrap_or(input.len());
+    match end {
+        0 => None,
+        _ => Some(input.split_at(end)),
+    }
+}
+
+impl<'h> Index<usize> for Captures<'h> {
+    type Output = str;
+
+    /// Returns the text of group `idx`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group did not take part in the match or doesn't exist.
+    fn index(&self, idx: usize) -> &str {
+        self.get(idx)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group at index {idx}"))
+    }
+}
+
+impl<'h> Index<&str> for Captures<'h> {
+    type Output = str;
+
+    /// Returns the text of the group called `name`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group did not take part in the match or doesn't exist.
+    fn index(&self, name: &str) -> &str {
+        self.name(name)
+            .map(|m| m.as_str())
+            .unwrap_or_else(|| panic!("no group named '{name}'"))
+    }
+}
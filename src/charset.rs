@@ -0,0 +1,169 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+// A set of characters, stored as sorted ranges that neither overlap nor
+// touch, so that membership is a binary search.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct CharSet(Vec<(char, char)>);
+
+impl CharSet {
+    pub fn new<I: IntoIterator<Item = (char, char)>>(ranges: I) -> Self {
+        let mut ranges: Vec<(char, char)> = ranges.into_iter().collect();
+        ranges.sort_unstable();
+        let mut merged: Vec<(char, char)> = Vec::with_capacity(ranges.len());
+        for (from, to) in ranges {
+            match merged.last_mut() {
+                Some(last) if successor(last.1).is_none_or(|next| from <= next) => {
+                    last.1 = last.1.max(to);
+                }
+                _ => merged.push((from, to)),
+            }
+        }
+        Self(merged)
+    }
+    pub fn full() -> Self {
+        Self(vec![('\0', char::MAX)])
+    }
+    // The characters that satisfy `predicate`, found by trying every one.
+    pub fn from_predicate<F: Fn(char) -> bool>(predicate: F) -> Self {
+        let mut ranges: Vec<(char, char)> = Vec::new();
+        for c in ('\0'..=char::MAX).filter(|&c| predicate(c)) {
+            match ranges.last_mut() {
+                Some(last) if successor(last.1) == Some(c) => last.1 = c,
+                _ => ranges.push((c, c)),
+            }
+        }
+        Self(ranges)
+    }
+    pub fn contains(&self, c: char) -> bool {
+        self.0
+            .binary_search_by(|&(from, to)| {
+                if to < c {
+                    core::cmp::Ordering::Less
+                } else if from > c {
+                    core::cmp::Ordering::Greater
+                } else {
+                    core::cmp::Ordering::Equal
+                }
+            })
+            .is_ok()
+    }
+    pub fn complement(&self) -> Self {
+        let mut ranges = Vec::with_capacity(self.0.len() + 1);
+        let mut next = Some('\0');
+        for &(from, to) in &self.0 {
+            if let Some(start) = next.filter(|&start| start < from) {
+                ranges.push((start, predecessor(from).unwrap()));
+            }
+            next = successor(to);
+        }
+        if let Some(start) = next {
+            ranges.push((start, char::MAX));
+        }
+        Self(ranges)
+    }
+    // Adds every character that `variants` maps a member of the set to.
+    pub fn close_over<F, I>(&self, variants: F) -> Self
+    where
+        F: Fn(char) -> I,
+        I: IntoIterator<Item = char>,
+    {
+        let added = self
+            .0
+            .iter()
+            .flat_map(|&(from, to)| from..=to)
+            .flat_map(variants)
+            .map(|c| (c, c));
+        Self::new(self.0.iter().copied().chain(added))
+    }
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(self.0.iter().chain(&other.0).copied())
+    }
+    pub fn intersects(&self, other: &Self) -> bool {
+        let (mut ours, mut theirs) = (self.0.iter().peekable(), other.0.iter().peekable());
+        while let (Some(&&(from, to)), Some(&&(other_from, other_to))) =
+            (ours.peek(), theirs.peek())
+        {
+            if from <= other_to && other_from <= to {
+                return true;
+            }
+            if to < other_to {
+                ours.next();
+            } else {
+                theirs.next();
+            }
+        }
+        false
+    }
+    pub fn is_subset(&self, other: &Self) -> bool {
+        let mut theirs = other.0.iter().peekable();
+        self.0.iter().all(|&(from, to)| {
+            while theirs.next_if(|&&(_, other_to)| other_to < from).is_some() {}
+            theirs
+                .peek()
+                .is_some_and(|&&(other_from, other_to)| other_from <= from && to <= other_to)
+        })
+    }
+    pub fn ranges(&self) -> &[(char, char)] {
+        &self.0
+    }
+}
+
+impl Display for CharSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for &(from, to) in &self.0 {
+            if from == to {
+                write!(f, "{}", from.escape_debug())?;
+            } else {
+                write!(f, "{}-{}", from.escape_debug(), to.escape_debug())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The characters right after and before `c`, skipping the surrogate gap.
+fn successor(c: char) -> Option<char> {
+    match c {
+        '\u{D7FF}' => Some('\u{E000}'),
+        _ => char::from_u32(c as u32 + 1),
+    }
+}
+
+fn predecessor(c: char) -> Option<char> {
+    match c {
+        '\u{E000}' => Some('\u{D7FF}'),
+        _ => char::from_u32((c as u32).checked_sub(1)?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn complements_around_surrogates() {
+        let set = CharSet::new([
+            ('b', 'c'),
+            ('a', 'a'),
+            ('\u{E000}', '\u{E001}'),
+            ('\u{D7FF}', '\u{D7FF}'),
+        ]);
+        assert_eq!(set.ranges(), &[('a', 'c'), ('\u{D7FF}', '\u{E001}')]);
+        let complement = set.complement();
+        assert!(complement.contains('\0'));
+        assert!(!complement.contains('b'));
+        assert!(complement.contains('d'));
+        assert!(!complement.contains('\u{E000}'));
+        assert!(complement.contains('\u{E002}'));
+        assert_eq!(complement.complement(), set);
+        assert_eq!(CharSet::full().complement(), CharSet::default());
+        assert!(set.intersects(&CharSet::new([('c', 'd')])));
+        assert!(!set.intersects(&complement));
+        assert_eq!(set.union(&complement), CharSet::full());
+        assert!(CharSet::new([('b', 'b'), ('\u{D7FF}', '\u{D7FF}')]).is_subset(&set));
+        assert!(!CharSet::new([('c', 'd')]).is_subset(&set));
+        assert!(CharSet::default().is_subset(&set));
+    }
+}
@@ -0,0 +1,6 @@
+// Hash maps and sets need the standard library for their random seeds, so
+// without it, ordered ones stand in for them.
+#[cfg(not(feature = "std"))]
+pub use alloc::collections::{BTreeMap as Map, BTreeSet as Set};
+#[cfg(feature = "std")]
+pub use std::collections::{HashMap as Map, HashSet as Set};
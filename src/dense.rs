@@ -0,0 +1,683 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::fmt::Display;
+
+use crate::collections::Map;
+use crate::error::DeserializeError;
+
+// A serialized DFA starts with `MAGIC` and the version of the format,
+// followed by these little-endian `u32`s:
+//
+// - the length of a row of transitions, which is also the identifier of the
+//   state that has matched, the one after the state that can't match any
+//   more,
+// - the number of states,
+// - the start state.
+//
+// Then come the byte classes, one byte each, whether every state matches at
+// the end of the haystack, one byte each and padded to a multiple of four,
+// and the rows of transitions as `u32`s. States are identified by the
+// offset of their row.
+pub(crate) const MAGIC: &[u8; 8] = b"regexrsD";
+pub(crate) const VERSION: u32 = 1;
+const HEADER: usize = MAGIC.len() + 4 * 4 + 256;
+// The state that can't match any more.
+const DEAD: usize = 0;
+
+/// A DFA that answers [`Regex::is_match`](crate::Regex::is_match) straight
+/// from its serialized form, with a single table lookup per byte.
+///
+/// Building a DFA can take a while for large patterns. This lets it be
+/// built ahead of time with [`Regex::to_dfa`](crate::Regex::to_dfa), saved
+/// with [`Dfa::to_bytes`], and later loaded with [`Dfa::from_bytes`], which
+/// only checks the bytes rather than copying them. They can be borrowed,
+/// say from `include_bytes!` or a memory-mapped file, at any alignment.
+///
+/// ```
+/// use regexrs::{Dfa, RegexBuilder};
+///
+/// let regex = RegexBuilder::new(r"\d{3}-\d{4}").prefer_dfa(true).build().unwrap();
+/// let bytes = regex.to_dfa().unwrap().to_bytes();
+///
+/// let dfa = Dfa::from_bytes(bytes.as_slice()).unwrap();
+/// assert!(dfa.is_match(b"call 555-1234"));
+/// assert!(!dfa.is_match(b"call 555-12"));
+/// ```
+#[derive(Clone)]
+pub struct Dfa<T = Vec<u8>> {
+    bytes: T,
+    stride: usize,
+    states: usize,
+    start: usize,
+    // Where the flags of whether states match at the end, and the
+    // transitions, begin.
+    accepts: usize,
+    table: usize,
+}
+
+impl<T: AsRef<[u8]>> Dfa<T> {
+    /// Loads a DFA serialized by [`Dfa::to_bytes`], failing unless `bytes`
+    /// hold one in the format of this version of the crate.
+    pub fn from_bytes(bytes: T) -> Result<Self, DeserializeError> {
+        let data = bytes.as_ref();
+        if !data.starts_with(MAGIC) {
+            return Err(DeserializeError::NotADfa);
+        }
+        let word = |idx: usize| read(data, MAGIC.len() + 4 * idx);
+        let version = word(0).ok_or(DeserializeError::Corrupt)?;
+        if version != VERSION as usize {
+            return Err(DeserializeError::UnsupportedVersion(version as u32));
+        }
+        let header = || {
+            Some((
+                word(1)?,
+                word(2)?,
+                word(3)?,
+                data.get(HEADER - 256..HEADER)?,
+            ))
+        };
+        let (stride, states, start, classes) = header().ok_or(DeserializeError::Corrupt)?;
+        // Classes are numbered in the order of their bytes, from zero.
+        let numbered = classes[0] == 0
+            && classes
+                .windows(2)
+                .all(|pair| pair[1].wrapping_sub(pair[0]) <= 1)
+            && classes[255] as usize + 1 == stride;
+        let accepts = HEADER;
+        let table = accepts + states.next_multiple_of(4);
+        let end = states
+            .checked_mul(stride)
+            .and_then(|len| len.checked_mul(4))
+            .and_then(|len| len.checked_add(table));
+        if !numbered || states < 2 || end != Some(data.len()) {
+            return Err(DeserializeError::Corrupt);
+        }
+        let dfa = Self {
+            bytes,
+            stride,
+            states,
+            start,
+            accepts,
+            table,
+        };
+        // Every transition has to lead to a state, so that searches never
+        // run off the table.
+        let valid = |state: usize| state.is_multiple_of(stride) && state / stride < states;
+        if !valid(start) || !(0..states * stride).all(|idx| valid(dfa.transition(idx))) {
+            return Err(DeserializeError::Corrupt);
+        }
+        Ok(dfa)
+    }
+    /// Returns true if the pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        let data = self.bytes.as_ref();
+        let mut state = self.start;
+        for &byte in haystack {
+            if state <= self.stride {
+                return state == self.stride;
+            }
+            let class = data[HEADER - 256 + byte as usize] as usize;
+            state = self.transition(state + class);
+        }
+        data[self.accepts + state / self.stride] == 1
+    }
+    /// Returns the serialized DFA, to be loaded with [`Dfa::from_bytes`].
+    pub fn as_bytes(&self) -> &[u8] {
+        self.bytes.as_ref()
+    }
+    /// Like [`Dfa::as_bytes`], but copies the bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+    /// Renders the DFA as the source of a Rust function called `name`,
+    /// which takes a `&[u8]` haystack and returns whether the pattern
+    /// matches anywhere in it. The function doesn't depend on this crate, so
+    /// it can be generated at build time for targets where building or
+    /// loading a DFA at startup is too costly.
+    ///
+    /// ```
+    /// use regexrs::RegexBuilder;
+    ///
+    /// let regex = RegexBuilder::new("ab+").prefer_dfa(true).build().unwrap();
+    /// let source = regex.to_dfa().unwrap().to_rust("is_abb");
+    /// assert!(source.starts_with("pub fn is_abb(haystack: &[u8]) -> bool {"));
+    /// ```
+    pub fn to_rust(&self, name: &str) -> String {
+        // Without a state to end in that accepts, nothing matches.
+        let mut accepting = self.reachable();
+        accepting.retain(|&state| self.accepts_at_end(state));
+        accepting.sort_unstable();
+        let accepting: Vec<String> = accepting.iter().map(|state| state.to_string()).collect();
+        if self.start <= self.stride || accepting.is_empty() {
+            let matched = self.start == self.stride;
+            return format!("pub fn {name}(_haystack: &[u8]) -> bool {{\n    {matched}\n}}\n");
+        }
+        let mut source = format!("pub fn {name}(haystack: &[u8]) -> bool {{\n");
+        source += &format!("    let mut state: u32 = {};\n", self.start / self.stride);
+        source += "    for &byte in haystack {\n";
+        source += "        state = match state {\n";
+        source += "            0 => return false,\n";
+        source += "            1 => return true,\n";
+        for state in 2..self.states {
+            source += &format!("            {state} => match byte {{\n");
+            let (ranges, fallback) = self.row(state);
+            for (low, high, target) in ranges {
+                let range = match low == high {
+                    true => literal(low),
+                    false => format!("{}..={}", literal(low), literal(high)),
+                };
+                source += &format!("                {range} => {target},\n");
+            }
+            source += &format!("                _ => {fallback},\n");
+            source += "            },\n";
+        }
+        source += "            _ => unreachable!(),\n";
+        source += "        };\n";
+        source += "    }\n";
+        source += &format!("    matches!(state, {})\n", accepting.join(" | "));
+        source += "}\n";
+        source
+    }
+    /// Returns a DFA that matches the haystacks both this one and `other`
+    /// match, somewhere each.
+    ///
+    /// ```
+    /// use regexrs::RegexBuilder;
+    ///
+    /// let dfa = |pattern| RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap().to_dfa().unwrap();
+    /// // Mentions a number, but not a negative one.
+    /// let positive = dfa(r"[0-9]").and(&dfa(r"-[0-9]").not());
+    /// assert!(positive.is_match(b"up 5%"));
+    /// assert!(!positive.is_match(b"down -5%"));
+    /// ```
+    pub fn and<U: AsRef<[u8]>>(&self, other: &Dfa<U>) -> Dfa {
+        self.product(other, |matched, other| matched && other)
+    }
+    /// Returns a DFA that matches the haystacks either this one or `other`
+    /// matches.
+    pub fn or<U: AsRef<[u8]>>(&self, other: &Dfa<U>) -> Dfa {
+        self.product(other, |matched, other| matched || other)
+    }
+    /// Returns a DFA that matches the haystacks this one doesn't.
+    pub fn not(&self) -> Dfa {
+        self.product(self, |matched, _| !matched)
+    }
+    /// Returns true if the DFA matches no haystack at all, like the ones for
+    /// `[^\d\D]` or `(?-u)a\bb`, or `dfa.and(&dfa.not())`.
+    pub fn matches_nothing(&self) -> bool {
+        !self
+            .reachable()
+            .into_iter()
+            .any(|state| self.accepts_at_end(state))
+    }
+    /// Returns true if the DFA matches every haystack, the empty one
+    /// included, like the ones for `x*` or `^|a`, or `dfa.or(&dfa.not())`.
+    ///
+    /// ```
+    /// use regexrs::RegexBuilder;
+    ///
+    /// let dfa = |pattern| RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap().to_dfa().unwrap();
+    /// // Every haystack has an end.
+    /// assert!(dfa("a|$").matches_everything());
+    /// assert!(!dfa("a|^$").matches_everything());
+    /// ```
+    pub fn matches_everything(&self) -> bool {
+        self.reachable()
+            .into_iter()
+            .all(|state| self.accepts_at_end(state))
+    }
+    // The indices of the states some haystack leads to.
+    fn reachable(&self) -> Vec<usize> {
+        let mut seen = vec![false; self.states];
+        let mut stack = vec![self.start / self.stride];
+        seen[self.start / self.stride] = true;
+        let mut reachable = Vec::new();
+        while let Some(state) = stack.pop() {
+            reachable.push(state);
+            for class in 0..self.stride {
+                let target = self.transition(state * self.stride + class) / self.stride;
+                if !seen[target] {
+                    seen[target] = true;
+                    stack.push(target);
+                }
+            }
+        }
+        reachable
+    }
+    // The DFA that runs both DFAs at once, and matches as `combine` says
+    // given whether they do. Its states are the pairs of their states that
+    // can be reached, and its byte classes tell apart the bytes that either
+    // of them does.
+    fn product<U: AsRef<[u8]>>(&self, other: &Dfa<U>, combine: fn(bool, bool) -> bool) -> Dfa {
+        let mut classes = [0u8; 256];
+        for byte in 1..256 {
+            let split = self.class(byte as u8) != self.class(byte as u8 - 1)
+                || other.class(byte as u8) != other.class(byte as u8 - 1);
+            classes[byte] = classes[byte - 1] + split as u8;
+        }
+        let representatives: Vec<u8> = (0..=255u8)
+            .filter(|&byte| byte == 0 || classes[byte as usize] != classes[byte as usize - 1])
+            .collect();
+        // The pairs found so far, by their state in the product. The first
+        // two stand for the states that can't match any more and that have
+        // matched, which pairs whose outcome is settled lead to.
+        let mut states: Map<(usize, usize), usize> = Map::new();
+        let mut pairs = vec![(DEAD, DEAD), (1, 1)];
+        let mut state = |pair: (usize, usize), pairs: &mut Vec<(usize, usize)>| {
+            let outcomes = |settled: Option<bool>| match settled {
+                Some(matched) => vec![matched],
+                None => vec![false, true],
+            };
+            let (left, right) = (
+                outcomes(self.settled(pair.0)),
+                outcomes(other.settled(pair.1)),
+            );
+            let combined: Vec<bool> = left
+                .iter()
+                .flat_map(|&left| right.iter().map(move |&right| combine(left, right)))
+                .collect();
+            if combined.iter().all(|&matched| matched == combined[0]) {
+                return combined[0] as usize;
+            }
+            *states.entry(pair).or_insert_with(|| {
+                pairs.push(pair);
+                pairs.len() - 1
+            })
+        };
+        let start = state(
+            (self.start / self.stride, other.start / other.stride),
+            &mut pairs,
+        );
+        let mut table = Vec::new();
+        let mut next = 2;
+        while next < pairs.len() {
+            let (left, right) = pairs[next];
+            for &byte in &representatives {
+                let left = self.transition(left * self.stride + self.class(byte)) / self.stride;
+                let right =
+                    other.transition(right * other.stride + other.class(byte)) / other.stride;
+                table.push(state((left, right), &mut pairs));
+            }
+            next += 1;
+        }
+        let accepts: Vec<bool> = pairs
+            .iter()
+            .enumerate()
+            .map(|(idx, &(left, right))| match idx {
+                DEAD => false,
+                1 => true,
+                _ => combine(self.accepts_at_end(left), other.accepts_at_end(right)),
+            })
+            .collect();
+        // The rows of the settled states lead back to themselves.
+        let stride = representatives.len();
+        let mut rows = vec![DEAD; stride];
+        rows.resize(2 * stride, 1);
+        rows.extend(table);
+        minimized(classes, start, &accepts, &rows)
+    }
+    fn class(&self, byte: u8) -> usize {
+        self.bytes.as_ref()[HEADER - 256 + byte as usize] as usize
+    }
+    // Whether the state with index `state` has matched or can't match any
+    // more, if either.
+    fn settled(&self, state: usize) -> Option<bool> {
+        (state <= 1).then_some(state == 1)
+    }
+    fn accepts_at_end(&self, state: usize) -> bool {
+        self.bytes.as_ref()[self.accepts + state] == 1
+    }
+    // The transitions out of the state with index `state`, as ranges of
+    // bytes with the same target, and the most common target, which the
+    // ranges leave out. Targets are indices as well.
+    fn row(&self, state: usize) -> (Vec<(u8, u8, usize)>, usize) {
+        let data = self.bytes.as_ref();
+        let targets: Vec<usize> = (0..=255u8)
+            .map(|byte| {
+                let class = data[HEADER - 256 + byte as usize] as usize;
+                self.transition(state * self.stride + class) / self.stride
+            })
+            .collect();
+        let fallback = (0..self.states)
+            .max_by_key(|&target| {
+                (
+                    targets.iter().filter(|&&t| t == target).count(),
+                    Reverse(target),
+                )
+            })
+            .unwrap_or(DEAD);
+        let mut ranges = Vec::new();
+        let mut low = 0;
+        while low < 256 {
+            let mut high = low;
+            while high < 255 && targets[high + 1] == targets[low] {
+                high += 1;
+            }
+            if targets[low] != fallback {
+                ranges.push((low as u8, high as u8, targets[low]));
+            }
+            low = high + 1;
+        }
+        (ranges, fallback)
+    }
+    // The transition at `idx` in the table.
+    fn transition(&self, idx: usize) -> usize {
+        read(self.bytes.as_ref(), self.table + 4 * idx).expect("checked when loaded")
+    }
+}
+
+// Serializes the DFA with the given byte classes, start state, accepts and
+// rows of targets, where states are indices and the first two are the dead
+// state and the one that has matched, after merging the states that match
+// the same haystacks from there on.
+fn minimized(classes: [u8; 256], start: usize, accepts: &[bool], rows: &[usize]) -> Dfa {
+    let stride = classes[255] as usize + 1;
+    // Split the states by where their transitions lead until that doesn't
+    // tell any more apart.
+    let mut partition: Vec<usize> = accepts.iter().map(|&accepts| accepts as usize).collect();
+    let mut blocks = 2;
+    loop {
+        let mut split: Map<(usize, &[usize]), usize> = Map::new();
+        let targets: Vec<Vec<usize>> = rows
+            .chunks(stride)
+            .map(|row| row.iter().map(|&target| partition[target]).collect())
+            .collect();
+        let refined: Vec<usize> = (0..accepts.len())
+            .map(|state| {
+                let next = split.len();
+                *split
+                    .entry((partition[state], &targets[state]))
+                    .or_insert(next)
+            })
+            .collect();
+        let done = split.len() == blocks;
+        blocks = split.len();
+        partition = refined;
+        if done {
+            break;
+        }
+    }
+    // Number the blocks so that the dead state and the one that has matched
+    // keep their indices.
+    let mut numbers: Map<usize, usize> = Map::new();
+    let mut representatives = Vec::new();
+    for (state, &block) in partition.iter().enumerate() {
+        numbers.entry(block).or_insert_with(|| {
+            representatives.push(state);
+            representatives.len() - 1
+        });
+    }
+    let word = |value: usize| {
+        u32::try_from(value)
+            .expect("the DFA fits the format")
+            .to_le_bytes()
+    };
+    let mut bytes = MAGIC.to_vec();
+    for value in [
+        VERSION as usize,
+        stride,
+        representatives.len(),
+        numbers[&partition[start]] * stride,
+    ] {
+        bytes.extend(word(value));
+    }
+    bytes.extend(classes);
+    bytes.extend(representatives.iter().map(|&state| accepts[state] as u8));
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+    for &state in &representatives {
+        for &target in &rows[state * stride..(state + 1) * stride] {
+            bytes.extend(word(numbers[&partition[target]] * stride));
+        }
+    }
+    Dfa::from_bytes(bytes).expect("the DFA is valid")
+}
+
+/// Shows the DFA as a table with a row per state. Bytes with the same
+/// target are grouped into ranges, and the most common target is shown as
+/// `else`. State 0 can't match any more, state 1 has matched, and states
+/// marked with `$` match if the haystack ends in them.
+///
+/// ```
+/// use regexrs::RegexBuilder;
+///
+/// let regex = RegexBuilder::new("^[a-c]x").prefer_dfa(true).build().unwrap();
+/// let table = regex.to_dfa().unwrap().to_string();
+/// assert!(table.contains("a-c → 3, else → 0"));
+/// ```
+impl<T: AsRef<[u8]>> Display for Dfa<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let data = self.bytes.as_ref();
+        write!(f, "start {}", self.start / self.stride)?;
+        for state in 0..self.states {
+            let accepts = if data[self.accepts + state] == 1 {
+                "$"
+            } else {
+                " "
+            };
+            write!(f, "\n{accepts}{state:>4}  ")?;
+            match state {
+                DEAD => write!(f, "dead")?,
+                1 => write!(f, "matched")?,
+                _ => {
+                    let (ranges, fallback) = self.row(state);
+                    for (low, high, target) in ranges {
+                        match low == high {
+                            true => write!(f, "{} → {target}, ", label(low))?,
+                            false => write!(f, "{}-{} → {target}, ", label(low), label(high))?,
+                        }
+                    }
+                    write!(f, "else → {fallback}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T> core::fmt::Debug for Dfa<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Dfa")
+            .field("stride", &self.stride)
+            .finish_non_exhaustive()
+    }
+}
+
+// A byte as a pattern of generated code.
+fn literal(byte: u8) -> String {
+    match byte {
+        b'\'' | b'\\' => format!("b'\\{}'", byte as char),
+        _ if byte.is_ascii_graphic() || byte == b' ' => format!("b'{}'", byte as char),
+        _ => format!("0x{byte:02X}"),
+    }
+}
+
+// A byte as shown in tables, escaped unless it is printable and can't be
+// mistaken for part of a range or a list.
+fn label(byte: u8) -> String {
+    match byte {
+        b' ' | b',' | b'-' => format!("\\x{byte:02x}"),
+        _ => byte.escape_ascii().to_string(),
+    }
+}
+
+// The little-endian `u32` at byte offset `at`.
+fn read(data: &[u8], at: usize) -> Option<usize> {
+    let word = data.get(at..at.checked_add(4)?)?;
+    Some(u32::from_le_bytes(word.try_into().ok()?) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RegexBuilder;
+
+    #[test]
+    fn loads_what_it_saved() {
+        for pattern in [r"\d{3}-\d{4}", r"(?m)^ab$", r"(?-u)\bcat\b", "x*", "[^a]"] {
+            let regex = RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap();
+            let bytes = regex.to_dfa().unwrap().to_bytes();
+            let dfa = Dfa::from_bytes(bytes.as_slice()).unwrap();
+            // Loading doesn't depend on the alignment of the bytes.
+            let shifted = [&[0][..], &bytes].concat();
+            let unaligned = Dfa::from_bytes(&shifted[1..]).unwrap();
+            for haystack in [
+                "", "555-1234", "ab\n", "x\nab", "a cat!", "a cats", "a", "b",
+            ] {
+                let expected = regex.is_match(haystack);
+                assert_eq!(
+                    dfa.is_match(haystack.as_bytes()),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    unaligned.is_match(haystack.as_bytes()),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+            }
+        }
+        assert!(RegexBuilder::new("a").build().unwrap().to_dfa().is_none());
+    }
+
+    #[test]
+    fn renders_rust_source() {
+        let dfa = |pattern| {
+            RegexBuilder::new(pattern)
+                .prefer_dfa(true)
+                .build()
+                .unwrap()
+                .to_dfa()
+                .unwrap()
+        };
+        let source = dfa("a[bc]").to_rust("is_match");
+        assert!(source.starts_with("pub fn is_match(haystack: &[u8]) -> bool {\n"));
+        assert!(source.contains("            5 => match byte {\n                0x0A => 4,\n                b'a' => 5,\n                b'b'..=b'c' => 1,\n                _ => 3,\n            },\n"));
+        assert!(source.ends_with("    matches!(state, 1)\n}\n"));
+        assert!(dfa("a'\\\\").to_rust("f").contains("b'\\'' => 6,\n"));
+        assert_eq!(
+            dfa("x*").to_rust("f"),
+            "pub fn f(_haystack: &[u8]) -> bool {\n    true\n}\n"
+        );
+        assert_eq!(
+            dfa(r"[^\s\S]").to_rust("f"),
+            "pub fn f(_haystack: &[u8]) -> bool {\n    false\n}\n"
+        );
+    }
+
+    #[test]
+    fn displays_a_row_per_state() {
+        let regex = RegexBuilder::new("^[a-c]x")
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        let table = regex.to_dfa().unwrap().to_string();
+        assert_eq!(table, "start 2\n    0  dead\n$   1  matched\n    2  a-c → 3, else → 0\n    3  x → 1, else → 0");
+        let regex = RegexBuilder::new("a, b").prefer_dfa(true).build().unwrap();
+        let table = regex.to_dfa().unwrap().to_string();
+        assert!(table.contains("    6  \\n → 4, \\x20 → 7, a → 5, else → 3\n"));
+    }
+
+    #[test]
+    fn combines_dfas() {
+        let regex = |pattern| RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap();
+        let patterns = [r"\d+", "^ab", "b$", "x*", "[^a]"];
+        let haystacks = ["", "12", "ab", "ab1", "cab", "a", "xb", "aaa", "b\n"];
+        for first in patterns {
+            for second in patterns {
+                let (a, b) = (regex(first), regex(second));
+                let (and, or) = (
+                    a.to_dfa().unwrap().and(&b.to_dfa().unwrap()),
+                    a.to_dfa().unwrap().or(&b.to_dfa().unwrap()),
+                );
+                let not = a.to_dfa().unwrap().not();
+                for haystack in haystacks {
+                    let (matches, other) = (a.is_match(haystack), b.is_match(haystack));
+                    assert_eq!(
+                        and.is_match(haystack.as_bytes()),
+                        matches && other,
+                        "{first} and {second} on {haystack:?}"
+                    );
+                    assert_eq!(
+                        or.is_match(haystack.as_bytes()),
+                        matches || other,
+                        "{first} or {second} on {haystack:?}"
+                    );
+                    assert_eq!(
+                        not.is_match(haystack.as_bytes()),
+                        !matches,
+                        "not {first} on {haystack:?}"
+                    );
+                }
+            }
+        }
+        // Combinations that match everywhere or nowhere settle at the start.
+        let digits = regex(r"\d").to_dfa().unwrap();
+        assert_eq!(
+            digits.or(&digits.not()).to_string(),
+            "start 1\n    0  dead\n$   1  matched"
+        );
+        assert_eq!(
+            digits.and(&digits.not()).to_string(),
+            "start 0\n    0  dead\n$   1  matched"
+        );
+    }
+
+    #[test]
+    fn decides_emptiness_and_universality() {
+        let dfa = |pattern| {
+            RegexBuilder::new(pattern)
+                .prefer_dfa(true)
+                .build()
+                .unwrap()
+                .to_dfa()
+                .unwrap()
+        };
+        for pattern in [r"(?-u)a\bb", "[^\\d\\D]", r"(?-u)[ab]\b[ab]"] {
+            assert!(dfa(pattern).matches_nothing(), "{pattern}");
+        }
+        for pattern in ["", "x*", "^|a", "(?s).|$"] {
+            assert!(dfa(pattern).matches_everything(), "{pattern}");
+        }
+        for pattern in ["a", "^$", r"\d+", "(?s)."] {
+            let dfa = dfa(pattern);
+            assert!(
+                !dfa.matches_nothing() && !dfa.matches_everything(),
+                "{pattern}"
+            );
+            assert!(
+                dfa.and(&dfa.not()).matches_nothing() && dfa.or(&dfa.not()).matches_everything(),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_bytes() {
+        let regex = RegexBuilder::new("a+b").prefer_dfa(true).build().unwrap();
+        let bytes = regex.to_dfa().unwrap().to_bytes();
+        assert_eq!(
+            Dfa::from_bytes(&b"PK\x03\x04"[..]).unwrap_err(),
+            DeserializeError::NotADfa
+        );
+        let mut newer = bytes.clone();
+        newer[MAGIC.len()] = 2;
+        assert_eq!(
+            Dfa::from_bytes(newer).unwrap_err(),
+            DeserializeError::UnsupportedVersion(2)
+        );
+        assert_eq!(
+            Dfa::from_bytes(&bytes[..bytes.len() - 1]).unwrap_err(),
+            DeserializeError::Corrupt
+        );
+        let mut astray = bytes.clone();
+        let last = astray.len() - 4;
+        astray[last..].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(
+            Dfa::from_bytes(astray).unwrap_err(),
+            DeserializeError::Corrupt
+        );
+    }
+}
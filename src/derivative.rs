@@ -0,0 +1,273 @@
+// A matcher working on Brzozowski derivatives, which is slow but simple
+// enough to trust, so that tests can check the other engines against it on
+// random patterns. The derivative of a regular expression by a character
+// matches the rest of every string it matches that starts with the
+// character, so a string matches in full if deriving by each of its
+// characters in turn leaves an expression that matches the empty string.
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Re {
+    // Matches nothing at all.
+    Nothing,
+    // Matches the empty string.
+    Empty,
+    // Matches one character from the ranges, written as in the pattern.
+    Class(&'static str, Vec<(char, char)>),
+    Concat(Box<Re>, Box<Re>),
+    Alternate(Box<Re>, Box<Re>),
+    // Matches between `min` and `max` repetitions, if there is a maximum.
+    Repeat(Box<Re>, usize, Option<usize>),
+}
+
+impl Re {
+    fn concat(first: Re, second: Re) -> Re {
+        match (first, second) {
+            (Re::Nothing, _) | (_, Re::Nothing) => Re::Nothing,
+            (Re::Empty, re) | (re, Re::Empty) => re,
+            (first, second) => Re::Concat(Box::new(first), Box::new(second)),
+        }
+    }
+    fn alternate(first: Re, second: Re) -> Re {
+        match (first, second) {
+            (Re::Nothing, re) | (re, Re::Nothing) => re,
+            (first, second) if first == second => first,
+            (first, second) => Re::Alternate(Box::new(first), Box::new(second)),
+        }
+    }
+    // Whether the expression matches the empty string.
+    pub fn nullable(&self) -> bool {
+        match self {
+            Re::Nothing | Re::Class(..) => false,
+            Re::Empty => true,
+            Re::Concat(first, second) => first.nullable() && second.nullable(),
+            Re::Alternate(first, second) => first.nullable() || second.nullable(),
+            Re::Repeat(re, min, _) => *min == 0 || re.nullable(),
+        }
+    }
+    pub fn derive(&self, c: char) -> Re {
+        match self {
+            Re::Nothing | Re::Empty => Re::Nothing,
+            Re::Class(_, ranges) => match ranges.iter().any(|&(from, to)| from <= c && c <= to) {
+                true => Re::Empty,
+                false => Re::Nothing,
+            },
+            Re::Concat(first, second) => {
+                let derived = Re::concat(first.derive(c), (**second).clone());
+                match first.nullable() {
+                    true => Re::alternate(derived, second.derive(c)),
+                    false => derived,
+                }
+            }
+            Re::Alternate(first, second) => Re::alternate(first.derive(c), second.derive(c)),
+            // The first repetition consumes the character, and if it may
+            // match the empty string, so may the other required ones.
+            Re::Repeat(_, _, Some(0)) => Re::Nothing,
+            Re::Repeat(re, min, max) => {
+                let min = if re.nullable() {
+                    0
+                } else {
+                    min.saturating_sub(1)
+                };
+                let rest = Re::Repeat(re.clone(), min, max.map(|max| max - 1));
+                Re::concat(re.derive(c), rest)
+            }
+        }
+    }
+    // Whether the expression matches all of `haystack`.
+    pub fn is_full_match(&self, haystack: &str) -> bool {
+        haystack
+            .chars()
+            .fold(self.clone(), |re, c| re.derive(c))
+            .nullable()
+    }
+    // The leftmost match that is longest among those starting there.
+    pub fn find_longest(&self, haystack: &str) -> Option<(usize, usize)> {
+        let starts = haystack
+            .char_indices()
+            .map(|(idx, _)| idx)
+            .chain([haystack.len()]);
+        starts.into_iter().find_map(|start| {
+            let mut re = self.clone();
+            let mut end = re.nullable().then_some(start);
+            for (idx, c) in haystack[start..].char_indices() {
+                re = re.derive(c);
+                if re == Re::Nothing {
+                    break;
+                }
+                if re.nullable() {
+                    end = Some(start + idx + c.len_utf8());
+                }
+            }
+            end.map(|end| (start, end))
+        })
+    }
+    // The expression as a pattern, with groups around alternations and
+    // repetitions where they need them.
+    pub fn to_pattern(&self) -> String {
+        match self {
+            Re::Nothing => String::from("[^\\s\\S]"),
+            Re::Empty => String::new(),
+            Re::Class(pattern, _) => String::from(*pattern),
+            Re::Concat(first, second) => format!("{}{}", first.atom(true), second.atom(true)),
+            Re::Alternate(first, second) => {
+                format!("{}|{}", first.to_pattern(), second.to_pattern())
+            }
+            Re::Repeat(re, min, max) => {
+                let quantifier = match (min, max) {
+                    (0, None) => String::from("*"),
+                    (1, None) => String::from("+"),
+                    (0, Some(1)) => String::from("?"),
+                    (min, None) => format!("{{{min},}}"),
+                    (min, Some(max)) if min == max => format!("{{{min}}}"),
+                    (min, Some(max)) => format!("{{{min},{max}}}"),
+                };
+                format!("{}{quantifier}", re.atom(false))
+            }
+        }
+    }
+    // The pattern of the expression as part of a concatenation, or as what
+    // is repeated.
+    fn atom(&self, concatenated: bool) -> String {
+        match self {
+            Re::Class(..) => self.to_pattern(),
+            Re::Concat(..) | Re::Repeat(..) if concatenated => self.to_pattern(),
+            _ => format!("(?:{})", self.to_pattern()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MatchKind, Regex, RegexBuilder};
+
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    fn class(rng: &mut Rng) -> Re {
+        let not_newline = vec![('\0', '\t'), ('\u{b}', char::MAX)];
+        match rng.below(6) {
+            0 => Re::Class("a", vec![('a', 'a')]),
+            1 => Re::Class("b", vec![('b', 'b')]),
+            2 => Re::Class("é", vec![('é', 'é')]),
+            3 => Re::Class("[ab]", vec![('a', 'b')]),
+            4 => Re::Class("[^a]", vec![('\0', '`'), ('b', char::MAX)]),
+            _ => Re::Class(".", not_newline),
+        }
+    }
+
+    fn random(rng: &mut Rng, depth: usize) -> Re {
+        if depth == 0 {
+            return class(rng);
+        }
+        match rng.below(8) {
+            0 | 1 => class(rng),
+            2 | 3 => Re::Concat(
+                Box::new(random(rng, depth - 1)),
+                Box::new(random(rng, depth - 1)),
+            ),
+            4 => Re::Alternate(
+                Box::new(random(rng, depth - 1)),
+                Box::new(random(rng, depth - 1)),
+            ),
+            5 => Re::Empty,
+            _ => {
+                let bounds = [
+                    (0, None),
+                    (1, None),
+                    (0, Some(1)),
+                    (2, Some(2)),
+                    (1, Some(3)),
+                    (2, None),
+                ];
+                let (min, max) = bounds[rng.below(bounds.len())];
+                Re::Repeat(Box::new(random(rng, depth - 1)), min, max)
+            }
+        }
+    }
+
+    fn haystack(rng: &mut Rng) -> String {
+        (0..rng.below(7))
+            .map(|_| ['a', 'b', 'é', '\n'][rng.below(4)])
+            .collect()
+    }
+
+    #[test]
+    fn derives_expressions() {
+        let re = Re::Repeat(Box::new(Re::Class("[ab]", vec![('a', 'b')])), 2, Some(3));
+        assert_eq!(re.to_pattern(), "[ab]{2,3}");
+        assert!(!re.is_full_match("a") && re.is_full_match("ab") && re.is_full_match("bab"));
+        assert!(!re.is_full_match("abab") && !re.is_full_match("ac"));
+        assert_eq!(re.find_longest("cabbba"), Some((1, 4)));
+        let empty = Re::Repeat(Box::new(Re::Empty), 3, None);
+        assert!(empty.is_full_match("") && !empty.is_full_match("a"));
+        assert_eq!(empty.find_longest("a"), Some((0, 0)));
+    }
+
+    #[test]
+    fn engines_agree_with_derivatives() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for _ in 0..300 {
+            let re = random(&mut rng, 4);
+            let pattern = re.to_pattern();
+            let regex = Regex::new(&pattern).unwrap();
+            let dfa = RegexBuilder::new(&pattern)
+                .prefer_dfa(true)
+                .build()
+                .unwrap();
+            let longest = RegexBuilder::new(&pattern)
+                .match_kind(MatchKind::LeftmostLongest)
+                .build()
+                .unwrap();
+            for _ in 0..20 {
+                let haystack = haystack(&mut rng);
+                let expected = re.find_longest(&haystack);
+                assert_eq!(
+                    regex.is_full_match(&haystack),
+                    re.is_full_match(&haystack),
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    regex.is_match(&haystack),
+                    expected.is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    dfa.is_match(&haystack),
+                    expected.is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+                let found = longest.find(&haystack).map(|m| (m.start(), m.end()));
+                assert_eq!(found, expected, "{pattern} on {haystack:?}");
+                // Leftmost-first matches start at the same place, but may end
+                // anywhere the expression matches.
+                let found = regex.find(&haystack).map(|m| (m.start(), m.end()));
+                let start = found.map(|(start, _)| start);
+                assert_eq!(
+                    start,
+                    expected.map(|(start, _)| start),
+                    "{pattern} on {haystack:?}"
+                );
+                if let Some((start, end)) = found {
+                    assert!(
+                        re.is_full_match(&haystack[start..end]),
+                        "{pattern} on {haystack:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1193 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::collections::{Map, Set};
+use crate::dense::{MAGIC, VERSION};
+use crate::fsm::{Look, ReverseSuffix};
+use crate::utf8::{ByteFSM, ByteNode};
+
+// The state without a way to match any more, and the one that has matched.
+const DEAD: usize = 0;
+const MATCH: usize = 1;
+// A transition of the lazy DFA that hasn't been worked out yet.
+const UNKNOWN: usize = usize::MAX;
+// How often a lazy DFA's cache may fill up before it gives up.
+const MAX_RESETS: usize = 3;
+
+// What comes before the position of a state, which is all that the
+// assertions looking backwards need to know. Whether the last byte is an
+// ASCII word character is only told apart if the pattern asks for it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Context {
+    Start,
+    LineStart,
+    Word,
+    Other,
+}
+
+// What comes after the position of a state, once it is known.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Ahead {
+    Byte(u8),
+    End,
+}
+
+// What a DFA does about matches. With `Earliest`, it only finds out whether
+// there is one, and stops at the first. The others find out where matches
+// end, with every transition telling whether one ends right before the byte
+// it consumes: `LeftmostFirst` drops what comes after a match in the order
+// of priority, like the simulation of the `NFA` does, while `All` keeps
+// everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Semantics {
+    Earliest,
+    LeftmostFirst,
+    All,
+}
+
+// A deterministic automaton built from a `ByteFSM` by subset construction.
+// Every state stands for the set of byte automaton nodes that the
+// simulation could be in, so that matching takes one table lookup per byte.
+//
+// Assertions looking ahead can't be decided before the next byte is known,
+// so states keep them around and settle them on the next transition. Word
+// boundaries are only supported for ASCII, since Unicode word characters
+// take more than one byte to recognize.
+#[allow(clippy::upper_case_acronyms)]
+pub struct DFA {
+    // The transitions of all states, one per byte class. States are
+    // identified by the offset of their row, so that the next state is
+    // found by adding the class of the byte, without multiplying.
+    table: Table,
+    classes: ByteClasses,
+    // The length of a row, which is also the identifier of `MATCH`.
+    stride: usize,
+    // Whether a state matches if the haystack ends in it, by its index.
+    accepts_at_end: Vec<bool>,
+    start: usize,
+}
+
+impl DFA {
+    // Builds the automaton for unanchored search, giving up if it would take
+    // up more than `size_limit` bytes.
+    pub fn new(bytes: &ByteFSM, size_limit: usize) -> Option<Self> {
+        let mut builder = Builder::new(bytes, Semantics::Earliest)?;
+        let stride = builder.classes.len();
+        let mut table = vec![DEAD; stride];
+        table.resize(2 * stride, MATCH);
+        let mut accepts_at_end = vec![false, true];
+        let start = builder.start(bytes, Context::Start);
+        let mut next = MATCH + 1;
+        while next < builder.sets.len() {
+            accepts_at_end.push(builder.accepts_at_end(bytes, next));
+            table.extend(builder.row(bytes, next));
+            // The table ends up with the narrowest identifiers that fit.
+            if table.len() * Table::width(table.len()) > size_limit {
+                return None;
+            }
+            next += 1;
+        }
+        for target in &mut table {
+            *target *= stride;
+        }
+        Some(Self {
+            table: Table::new(table),
+            classes: builder.classes,
+            stride,
+            accepts_at_end,
+            start: start * stride,
+        })
+    }
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        match &self.table {
+            Table::U8(table) => self.run(table, haystack),
+            Table::U16(table) => self.run(table, haystack),
+            Table::U32(table) => self.run(table, haystack),
+            Table::Usize(table) => self.run(table, haystack),
+        }
+    }
+    fn run<S: StateId>(&self, table: &[S], haystack: &[u8]) -> bool {
+        let mut state = self.start;
+        for &byte in haystack {
+            if let Some(matched) = self.settled(state) {
+                return matched;
+            }
+            state = table[state + self.classes.get(byte)].index();
+        }
+        self.accepts_at_end(state)
+    }
+    fn next(&self, state: usize, byte: u8) -> usize {
+        self.table.get(state + self.classes.get(byte))
+    }
+    // Whether the haystack matches once it has led to `state`, if that no
+    // longer depends on the rest of it.
+    fn settled(&self, state: usize) -> Option<bool> {
+        (state <= self.stride).then_some(state == self.stride)
+    }
+    fn accepts_at_end(&self, state: usize) -> bool {
+        self.accepts_at_end[state / self.stride]
+    }
+    // The automaton in the format `Dfa::from_bytes` loads, or `None` if it
+    // is too large for it.
+    pub fn to_bytes(&self) -> Option<Vec<u8>> {
+        let word = |value: usize| u32::try_from(value).ok().map(u32::to_le_bytes);
+        let mut bytes = MAGIC.to_vec();
+        for value in [
+            VERSION as usize,
+            self.stride,
+            self.accepts_at_end.len(),
+            self.start,
+        ] {
+            bytes.extend(word(value)?);
+        }
+        bytes.extend(self.classes.0);
+        bytes.extend(self.accepts_at_end.iter().map(|&accepts| accepts as u8));
+        bytes.resize(bytes.len().next_multiple_of(4), 0);
+        for idx in 0..self.table.len() {
+            bytes.extend(word(self.table.get(idx))?);
+        }
+        Some(bytes)
+    }
+}
+
+// The transitions of a `DFA`, in the narrowest type that holds the
+// identifiers of all its states. Small patterns get by with a byte each.
+enum Table {
+    U8(Vec<u8>),
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+    Usize(Vec<usize>),
+}
+
+impl Table {
+    fn new(table: Vec<usize>) -> Self {
+        let max = table.iter().copied().max().unwrap_or(0);
+        if u8::try_from(max).is_ok() {
+            Self::U8(table.into_iter().map(|target| target as u8).collect())
+        } else if u16::try_from(max).is_ok() {
+            Self::U16(table.into_iter().map(|target| target as u16).collect())
+        } else if u32::try_from(max).is_ok() {
+            Self::U32(table.into_iter().map(|target| target as u32).collect())
+        } else {
+            Self::Usize(table)
+        }
+    }
+    // The bytes a transition takes up if the identifiers go up to `max`.
+    fn width(max: usize) -> usize {
+        match max {
+            0..=0xFF => 1,
+            0x100..=0xFFFF => 2,
+            _ if u32::try_from(max).is_ok() => 4,
+            _ => core::mem::size_of::<usize>(),
+        }
+    }
+    fn len(&self) -> usize {
+        match self {
+            Self::U8(table) => table.len(),
+            Self::U16(table) => table.len(),
+            Self::U32(table) => table.len(),
+            Self::Usize(table) => table.len(),
+        }
+    }
+    fn get(&self, idx: usize) -> usize {
+        match self {
+            Self::U8(table) => table[idx].index(),
+            Self::U16(table) => table[idx].index(),
+            Self::U32(table) => table[idx].index(),
+            Self::Usize(table) => table[idx],
+        }
+    }
+}
+
+// The types a `Table` stores identifiers in.
+trait StateId: Copy {
+    fn index(self) -> usize;
+}
+
+impl StateId for u8 {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl StateId for u16 {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl StateId for u32 {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl StateId for usize {
+    fn index(self) -> usize {
+        self
+    }
+}
+
+// Bytes that no transition tells apart share a class, so that rows only
+// need a transition per class. Classes are numbered in the order of their
+// bytes.
+#[derive(Clone)]
+struct ByteClasses([u8; 256]);
+
+impl ByteClasses {
+    // Bytes are told apart by the ranges of `bytes`, and by whether they
+    // end a line or, if `words` is set, are word characters.
+    fn new(bytes: &ByteFSM, words: bool) -> Self {
+        // Whether a class starts at each byte, other than the first.
+        let mut starts = [false; 256];
+        let mut split = |low: u8, high: u8| {
+            starts[low as usize] = true;
+            if let Some(next) = high.checked_add(1) {
+                starts[next as usize] = true;
+            }
+        };
+        split(b'\n', b'\n');
+        for node in bytes.graph() {
+            if let ByteNode::Range(low, high, _) = node {
+                split(*low, *high);
+            }
+        }
+        if words {
+            for (low, high) in [(b'0', b'9'), (b'A', b'Z'), (b'_', b'_'), (b'a', b'z')] {
+                split(low, high);
+            }
+        }
+        let mut classes = [0; 256];
+        for byte in 1..256 {
+            classes[byte] = classes[byte - 1] + starts[byte] as u8;
+        }
+        Self(classes)
+    }
+    fn get(&self, byte: u8) -> usize {
+        self.0[byte as usize] as usize
+    }
+    fn len(&self) -> usize {
+        self.get(255) + 1
+    }
+    // The first byte of every class.
+    fn representatives(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..=255u8).filter(|&byte| byte == 0 || self.0[byte as usize] != self.0[byte as usize - 1])
+    }
+}
+
+// A DFA whose states are only built once a search reaches them, so that
+// patterns like `[01]*1[01]{20}`, whose full DFA is huge, only pay for the
+// states the haystacks actually need.
+//
+// The states are kept in a cache of bounded size, which the searches pass
+// in. When it fills up, it is cleared and the search is left to the byte
+// automaton; if that keeps happening, the lazy DFA gives up on the cache
+// for good.
+pub struct LazyDFA {
+    capacity: usize,
+}
+
+pub struct Cache {
+    builder: Builder,
+    table: Vec<usize>,
+    accepts_at_end: Vec<bool>,
+    // The start states for every context, once they are known.
+    starts: [usize; 4],
+    // The approximate number of bytes taken up by the states.
+    size: usize,
+    // How often the cache has filled up.
+    resets: usize,
+}
+
+impl LazyDFA {
+    pub fn new(bytes: &ByteFSM, capacity: usize) -> Option<Self> {
+        Builder::new(bytes, Semantics::Earliest)?;
+        Some(Self { capacity })
+    }
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+    pub fn cache(&self, bytes: &ByteFSM) -> Cache {
+        Cache::new(bytes, Semantics::Earliest, 0)
+    }
+    // Whether the pattern matches anywhere in `haystack`, or where the
+    // search was abandoned.
+    pub fn is_match(
+        &self,
+        bytes: &ByteFSM,
+        cache: &mut Cache,
+        haystack: &[u8],
+    ) -> Result<bool, Abandoned> {
+        if cache.resets >= MAX_RESETS {
+            return Err(Abandoned {
+                position: 0,
+                nodes: vec![bytes.entries().0],
+            });
+        }
+        let mut state = cache.start(bytes, Context::Start);
+        // The last state at a character boundary, and its position.
+        let mut boundary = (0, state);
+        for (position, &byte) in haystack.iter().enumerate() {
+            match state {
+                MATCH => return Ok(true),
+                DEAD => return Ok(false),
+                _ => {}
+            }
+            if !(0x80..0xC0).contains(&byte) {
+                boundary = (position, state);
+            }
+            match cache.advance(bytes, state, byte, self.capacity) {
+                Some(next) => state = next,
+                None => {
+                    let (position, state) = boundary;
+                    let nodes = cache.builder.sets[state].nodes.clone();
+                    *cache = Cache::new(bytes, Semantics::Earliest, cache.resets + 1);
+                    return Err(Abandoned { position, nodes });
+                }
+            }
+        }
+        Ok(cache.accepts_at_end[state])
+    }
+}
+
+// Where a lazy DFA gave up on a search: the last character boundary it
+// passed, and the nodes of the byte automaton it was in there, so that
+// another simulation can go on from there.
+pub struct Abandoned {
+    pub position: usize,
+    pub nodes: Vec<usize>,
+}
+
+impl Cache {
+    fn new(bytes: &ByteFSM, semantics: Semantics, resets: usize) -> Self {
+        let builder = Builder::new(bytes, semantics).expect("checked by the owner of the cache");
+        let stride = builder.classes.len();
+        Self {
+            builder,
+            table: [DEAD, MATCH]
+                .into_iter()
+                .flat_map(|state| vec![state; stride])
+                .collect(),
+            accepts_at_end: vec![false, true],
+            starts: [UNKNOWN; 4],
+            size: 0,
+            resets,
+        }
+    }
+    fn start(&mut self, bytes: &ByteFSM, context: Context) -> usize {
+        let idx = context as usize;
+        if self.starts[idx] == UNKNOWN {
+            self.starts[idx] = self.builder.start(bytes, context);
+            self.add_states(bytes);
+        }
+        self.starts[idx]
+    }
+    // The transition of `state` on `byte`, worked out if it is new. If that
+    // fills up the cache, it is cleared, and the search can't go on.
+    fn next(&mut self, bytes: &ByteFSM, state: usize, byte: u8, capacity: usize) -> Option<usize> {
+        let next = self.advance(bytes, state, byte, capacity);
+        if next.is_none() {
+            *self = Cache::new(bytes, self.builder.semantics, self.resets + 1);
+        }
+        next
+    }
+    // Like `next`, but leaves clearing the full cache to the caller, who may
+    // still need its states.
+    fn advance(
+        &mut self,
+        bytes: &ByteFSM,
+        state: usize,
+        byte: u8,
+        capacity: usize,
+    ) -> Option<usize> {
+        let mut next = self.table[self.slot(state, byte)];
+        if next == UNKNOWN {
+            next = self.builder.transition(bytes, state, byte);
+            let slot = self.slot(state, byte);
+            self.table[slot] = next;
+            self.add_states(bytes);
+            if self.size > capacity {
+                return None;
+            }
+        }
+        Some(next)
+    }
+    // Where the transition of `state` on `byte` goes in the table.
+    fn slot(&self, state: usize, byte: u8) -> usize {
+        state * self.builder.classes.len() + self.builder.classes.get(byte)
+    }
+    // Makes room for the states the builder has found since the last call.
+    fn add_states(&mut self, bytes: &ByteFSM) {
+        let stride = self.builder.classes.len();
+        for state in self.accepts_at_end.len()..self.builder.sets.len() {
+            let accepts = self.builder.accepts_at_end(bytes, state);
+            self.accepts_at_end.push(accepts);
+            self.table.resize(self.table.len() + stride, UNKNOWN);
+            // The row of transitions, and the node set both as a key and in
+            // the list of states.
+            self.size += stride * core::mem::size_of::<usize>()
+                + 2 * core::mem::size_of_val(self.builder.sets[state].nodes.as_slice());
+        }
+    }
+    // Where the leftmost-first match at or after `start` ends, running
+    // forward over the haystack from there. `None` if the search had to be
+    // abandoned.
+    fn find_end(
+        &mut self,
+        bytes: &ByteFSM,
+        haystack: &[u8],
+        start: usize,
+        capacity: usize,
+    ) -> Option<Option<usize>> {
+        if self.resets >= MAX_RESETS {
+            return None;
+        }
+        let context = match start {
+            0 => Context::Start,
+            _ => self.builder.context(haystack[start - 1]),
+        };
+        let mut state = self.start(bytes, context);
+        let mut end = None;
+        for (position, &byte) in haystack.iter().enumerate().skip(start) {
+            if state == DEAD {
+                return Some(end);
+            }
+            let next = self.next(bytes, state, byte, capacity)?;
+            if next & 1 == 1 {
+                end = Some(position);
+            }
+            state = next >> 1;
+        }
+        if self.accepts_at_end[state] {
+            end = Some(haystack.len());
+        }
+        Some(end)
+    }
+    // Where the leftmost match ending at `end` starts, no earlier than
+    // `start`, running backwards over the haystack from `end` with the
+    // automaton of the reversed pattern.
+    fn find_start(
+        &mut self,
+        reversed: &ByteFSM,
+        haystack: &[u8],
+        start: usize,
+        end: usize,
+        capacity: usize,
+    ) -> Option<Option<usize>> {
+        if self.resets >= MAX_RESETS {
+            return None;
+        }
+        // Looking backwards, the context is what comes after the position.
+        let context = match haystack.get(end) {
+            Some(&byte) => self.builder.context(byte),
+            None => Context::Start,
+        };
+        let mut state = self.start(reversed, context);
+        let mut from = None;
+        for position in (start..end).rev() {
+            if state == DEAD {
+                return Some(from);
+            }
+            let next = self.next(reversed, state, haystack[position], capacity)?;
+            if next & 1 == 1 {
+                from = Some(position + 1);
+            }
+            state = next >> 1;
+        }
+        // Whether a match starts right at `start` depends on what comes
+        // before it.
+        let matched = match start {
+            0 => self.accepts_at_end[state],
+            _ => self.next(reversed, state, haystack[start - 1], capacity)? & 1 == 1,
+        };
+        if matched {
+            from = Some(start);
+        }
+        Some(from)
+    }
+}
+
+// Finds the leftmost-first match with two lazy DFAs: one running forward to
+// where the match ends, and one running backwards from there with the
+// reversed pattern to where it starts. Of all matches ending there, the
+// leftmost-first one is the one starting leftmost, since any match starting
+// further left would have been preferred.
+pub struct Search {
+    reversed: ByteFSM,
+    capacity: usize,
+    inner: Option<InnerLiteral>,
+}
+
+// A literal that every match has in the middle, and the automaton of the
+// reversed part before it, which finds where matches start back from where
+// the literal occurs.
+struct InnerLiteral {
+    literal: String,
+    reversed: ByteFSM,
+}
+
+// The states of both directions of a `Search`, and of the part before its
+// inner literal.
+pub struct SearchCache {
+    forward: Cache,
+    backward: Cache,
+    inner: Option<Cache>,
+}
+
+impl Search {
+    // Gives up on patterns with `\G`, which needs to know where the search
+    // started, and on ASCII-only `\B`, which may hold in the middle of a
+    // character, as well as if the reversed pattern would take up more than
+    // `size_limit` bytes.
+    pub fn new(bytes: &ByteFSM, size_limit: usize, capacity: usize) -> Option<Self> {
+        Builder::new(bytes, Semantics::LeftmostFirst)?;
+        if bytes.graph().iter().any(|node| {
+            matches!(
+                node,
+                ByteNode::Assert(Look::SearchStart | Look::NotWordBoundary(true), _)
+            )
+        }) {
+            return None;
+        }
+        let reversed = bytes.reversed(size_limit)?;
+        Some(Self {
+            reversed,
+            capacity,
+            inner: None,
+        })
+    }
+    // Lets searches look for `literal` first, which every match has right
+    // after what `before` matches, see `Hir::inner_literal`.
+    pub fn with_inner_literal(
+        mut self,
+        literal: String,
+        before: &ByteFSM,
+        size_limit: usize,
+    ) -> Self {
+        self.inner = before
+            .reversed(size_limit)
+            .filter(|reversed| Builder::new(reversed, Semantics::All).is_some())
+            .map(|reversed| InnerLiteral { literal, reversed });
+        self
+    }
+    pub fn cache(&self, bytes: &ByteFSM) -> SearchCache {
+        SearchCache {
+            forward: Cache::new(bytes, Semantics::LeftmostFirst, 0),
+            backward: Cache::new(&self.reversed, Semantics::All, 0),
+            inner: self
+                .inner
+                .as_ref()
+                .map(|inner| Cache::new(&inner.reversed, Semantics::All, 0)),
+        }
+    }
+    // The span of the leftmost-first match at or after `start`, or `None` if
+    // the search had to be abandoned.
+    pub fn find(
+        &self,
+        bytes: &ByteFSM,
+        cache: &mut SearchCache,
+        haystack: &[u8],
+        start: usize,
+    ) -> Option<Option<(usize, usize)>> {
+        let end = match cache
+            .forward
+            .find_end(bytes, haystack, start, self.capacity)?
+        {
+            Some(end) => end,
+            None => return Some(None),
+        };
+        let backward = &mut cache.backward;
+        let from = backward.find_start(&self.reversed, haystack, start, end, self.capacity)??;
+        Some(Some((from, end)))
+    }
+    // Like `find`, but looks for the occurrences of `suffix` first and runs
+    // the reversed pattern back from them, so that the forward automaton
+    // only has to run over the match.
+    pub fn find_by_suffix(
+        &self,
+        bytes: &ByteFSM,
+        cache: &mut SearchCache,
+        haystack: &str,
+        start: usize,
+        suffix: &str,
+        kind: ReverseSuffix,
+    ) -> Option<Option<(usize, usize)>> {
+        let Some(from) = self.start_by_suffix(cache, haystack, start, suffix, kind)? else {
+            return Some(None);
+        };
+        match kind {
+            ReverseSuffix::AtEnd => Some(Some((from, haystack.len()))),
+            ReverseSuffix::FirstEnding => self.find(bytes, cache, haystack.as_bytes(), from),
+        }
+    }
+    // Where the leftmost match at or after `start` begins, found back from
+    // the occurrences of `suffix`, or `None` if the search had to be
+    // abandoned.
+    pub fn start_by_suffix(
+        &self,
+        cache: &mut SearchCache,
+        haystack: &str,
+        start: usize,
+        suffix: &str,
+        kind: ReverseSuffix,
+    ) -> Option<Option<usize>> {
+        let backward = &mut cache.backward;
+        if kind == ReverseSuffix::AtEnd {
+            if !haystack[start..].ends_with(suffix) {
+                return Some(None);
+            }
+            return backward.find_start(
+                &self.reversed,
+                haystack.as_bytes(),
+                start,
+                haystack.len(),
+                self.capacity,
+            );
+        }
+        // A match ending at an occurrence starts after the one before, which
+        // it would contain otherwise, so the scans back hardly overlap.
+        let step = suffix.chars().next().map_or(1, char::len_utf8);
+        let mut from = start;
+        while let Some(found) = haystack[from..].find(suffix) {
+            let end = from + found + suffix.len();
+            if let Some(start) = backward.find_start(
+                &self.reversed,
+                haystack.as_bytes(),
+                from,
+                end,
+                self.capacity,
+            )? {
+                return Some(Some(start));
+            }
+            from += found + step;
+        }
+        Some(None)
+    }
+    // Like `find`, but looks for the occurrences of the inner literal first.
+    // `None` if there is none, or if the search had to be abandoned.
+    pub fn find_by_inner_literal(
+        &self,
+        bytes: &ByteFSM,
+        cache: &mut SearchCache,
+        haystack: &str,
+        start: usize,
+    ) -> Option<Option<(usize, usize)>> {
+        match self.start_by_inner_literal(cache, haystack, start)? {
+            Some(from) => self.find(bytes, cache, haystack.as_bytes(), from),
+            None => Some(None),
+        }
+    }
+    // How far searches from `start` can skip ahead: to where the part before
+    // the first occurrence of the inner literal it can be followed by
+    // starts, or `None` if there is none. As that part never reaches past an
+    // occurrence, no match starts earlier. `None` if there is no inner
+    // literal, or if the search had to be abandoned.
+    pub fn start_by_inner_literal(
+        &self,
+        cache: &mut SearchCache,
+        haystack: &str,
+        start: usize,
+    ) -> Option<Option<usize>> {
+        let inner = self.inner.as_ref()?;
+        let backward = cache.inner.as_mut()?;
+        let step = inner.literal.chars().next().map_or(1, char::len_utf8);
+        let mut from = start;
+        while let Some(found) = haystack[from..].find(inner.literal.as_str()) {
+            let end = from + found;
+            if let Some(start) = backward.find_start(
+                &inner.reversed,
+                haystack.as_bytes(),
+                start,
+                end,
+                self.capacity,
+            )? {
+                return Some(Some(start));
+            }
+            from = end + step;
+        }
+        Some(None)
+    }
+}
+
+// A search through a haystack that arrives in chunks, which remembers its
+// state in between. Without a full DFA, the stream builds the states it
+// needs in a cache of its own. When that fills up, it is cleared, keeping
+// only the current state, so that a stream never gives up.
+pub enum Stream<'r> {
+    Full(&'r DFA, usize),
+    Lazy {
+        bytes: &'r ByteFSM,
+        cache: Box<Cache>,
+        state: usize,
+        capacity: usize,
+    },
+}
+
+impl<'r> Stream<'r> {
+    pub fn full(dfa: &'r DFA) -> Self {
+        Self::Full(dfa, dfa.start)
+    }
+    pub fn lazy(bytes: &'r ByteFSM, capacity: usize) -> Self {
+        let mut cache = Box::new(Cache::new(bytes, Semantics::Earliest, 0));
+        let state = cache.start(bytes, Context::Start);
+        Self::Lazy {
+            bytes,
+            cache,
+            state,
+            capacity,
+        }
+    }
+    // Moves on over `chunk`, returning whether the pattern matches once
+    // that no longer depends on the rest of the haystack.
+    pub fn feed(&mut self, chunk: &[u8]) -> Option<bool> {
+        let mut chunk = chunk.iter();
+        loop {
+            let settled = match self {
+                Self::Full(dfa, state) => dfa.settled(*state),
+                Self::Lazy { state, .. } => match *state {
+                    DEAD => Some(false),
+                    MATCH => Some(true),
+                    _ => None,
+                },
+            };
+            if settled.is_some() {
+                return settled;
+            }
+            let &byte = chunk.next()?;
+            match self {
+                Self::Full(dfa, state) => *state = dfa.next(*state, byte),
+                Self::Lazy {
+                    bytes,
+                    cache,
+                    state,
+                    capacity,
+                } => {
+                    let slot = cache.slot(*state, byte);
+                    let mut next = cache.table[slot];
+                    if next == UNKNOWN {
+                        next = cache.builder.transition(bytes, *state, byte);
+                        cache.table[slot] = next;
+                        cache.add_states(bytes);
+                    }
+                    *state = next;
+                    if cache.size > *capacity && next > MATCH {
+                        let key = cache.builder.sets[next].clone();
+                        **cache = Cache::new(bytes, Semantics::Earliest, 0);
+                        *state = cache
+                            .builder
+                            .state(bytes, &key.nodes, key.context, key.restart);
+                        cache.add_states(bytes);
+                    }
+                }
+            }
+        }
+    }
+    // Whether the pattern matches if the haystack ends here.
+    pub fn accepts_at_end(&self) -> bool {
+        match self {
+            Self::Full(dfa, state) => dfa.accepts_at_end(*state),
+            Self::Lazy { cache, state, .. } => cache.accepts_at_end[*state],
+        }
+    }
+}
+
+// A state of a DFA: what comes before its position, and the nodes the
+// simulation is in, in the order of their priority for leftmost-first
+// semantics. Unless the DFA stops at the first match, states also know
+// whether a match ends at their position, and whether matches may still
+// start after it.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+struct Key {
+    context: Context,
+    nodes: Vec<usize>,
+    matched: bool,
+    restart: bool,
+}
+
+// Works out the states of a DFA and their transitions. The states are
+// numbered in the order they are found, after `DEAD` and `MATCH`. Unless
+// the DFA stops at the first match, transitions are shifted left by one
+// bit, which tells whether a match ends before the byte.
+struct Builder {
+    semantics: Semantics,
+    restart: Option<usize>,
+    // Whether the pattern has word boundaries, so that states need to know
+    // whether they follow a word character.
+    words: bool,
+    classes: ByteClasses,
+    // The states found so far, by their keys and their index.
+    states: Map<Key, usize>,
+    sets: Vec<Key>,
+    // The states found so far by the nodes they were reached from, which
+    // many bytes share, and whether matches may still start.
+    roots: Map<(Context, Vec<usize>, bool), usize>,
+    // When each node was last visited by `closure`.
+    seen: Vec<usize>,
+    stamp: usize,
+}
+
+impl Builder {
+    // Gives up on patterns with Unicode word boundaries, and with `\Z` or
+    // line anchors aware of `\r\n`, which would have to look two bytes
+    // ahead or behind.
+    fn new(bytes: &ByteFSM, semantics: Semantics) -> Option<Self> {
+        let mut words = false;
+        for node in bytes.graph() {
+            match node {
+                ByteNode::Assert(
+                    Look::WordBoundary(false)
+                    | Look::NotWordBoundary(false)
+                    | Look::EndBeforeNewline
+                    | Look::StartCrlfLine
+                    | Look::EndCrlfLine,
+                    _,
+                ) => return None,
+                ByteNode::Assert(Look::WordBoundary(true) | Look::NotWordBoundary(true), _) => {
+                    words = true
+                }
+                _ => {}
+            }
+        }
+        let placeholder = Key {
+            context: Context::Other,
+            nodes: Vec::new(),
+            matched: false,
+            restart: false,
+        };
+        Some(Self {
+            semantics,
+            restart: bytes.entries().1,
+            words,
+            classes: ByteClasses::new(bytes, words),
+            states: Map::new(),
+            sets: vec![placeholder.clone(), placeholder],
+            roots: Map::new(),
+            seen: vec![0; bytes.graph().len()],
+            stamp: 0,
+        })
+    }
+    fn start(&mut self, bytes: &ByteFSM, context: Context) -> usize {
+        self.state(bytes, &[bytes.entries().0], context, true)
+    }
+    fn accepts_at_end(&mut self, bytes: &ByteFSM, state: usize) -> bool {
+        let key = self.sets[state].clone();
+        let (_, matched) = self.closure(bytes, &key.nodes, key.context, Some(Ahead::End));
+        matched || key.matched
+    }
+    // All transitions of `state` at once, one per byte class, for DFAs
+    // stopping at the first match.
+    fn row(&mut self, bytes: &ByteFSM, state: usize) -> Vec<usize> {
+        let key = self.sets[state].clone();
+        // Waiting assertions only care whether the next byte ends a line or
+        // is a word character.
+        let [newline, word, other] = [b'\n', b'a', 0].map(|ahead| {
+            let (settled, matched) =
+                self.closure(bytes, &key.nodes, key.context, Some(Ahead::Byte(ahead)));
+            (!matched).then(|| targets(bytes, &settled))
+        });
+        let representatives: Vec<u8> = self.classes.representatives().collect();
+        representatives
+            .into_iter()
+            .map(|byte| {
+                let targets = match byte {
+                    b'\n' => &newline,
+                    _ if is_word_byte(byte) => &word,
+                    _ => &other,
+                };
+                match targets {
+                    Some(targets) => self.step(bytes, targets[byte as usize].clone(), byte, true),
+                    None => MATCH,
+                }
+            })
+            .collect()
+    }
+    // The transition of `state` on a single byte.
+    fn transition(&mut self, bytes: &ByteFSM, state: usize, byte: u8) -> usize {
+        let key = self.sets[state].clone();
+        let (settled, matched) =
+            self.closure(bytes, &key.nodes, key.context, Some(Ahead::Byte(byte)));
+        if matched && self.semantics == Semantics::Earliest {
+            return MATCH;
+        }
+        let targets = settled
+            .iter()
+            .filter_map(|&idx| match bytes.graph()[idx] {
+                ByteNode::Range(low, high, next) if (low..=high).contains(&byte) => Some(next),
+                _ => None,
+            })
+            .collect();
+        // Matches starting later lose to one that has ended.
+        let restart = key.restart && !(matched && self.semantics == Semantics::LeftmostFirst);
+        let next = self.step(bytes, targets, byte, restart);
+        match self.semantics {
+            Semantics::Earliest => next,
+            _ => next << 1 | (matched || key.matched) as usize,
+        }
+    }
+    // The state reached on `byte` from a state whose nodes lead to `targets`.
+    fn step(&mut self, bytes: &ByteFSM, mut targets: Vec<usize>, byte: u8, restart: bool) -> usize {
+        if restart {
+            targets.extend(self.restart);
+        }
+        let context = self.context(byte);
+        let key = (context, targets, restart);
+        if let Some(&state) = self.roots.get(&key) {
+            return state;
+        }
+        let state = self.state(bytes, &key.1, context, restart);
+        self.roots.insert(key, state);
+        state
+    }
+    // The context of the position after `byte`.
+    fn context(&self, byte: u8) -> Context {
+        match byte {
+            b'\n' => Context::LineStart,
+            _ if self.words && is_word_byte(byte) => Context::Word,
+            _ => Context::Other,
+        }
+    }
+    // The state reached from `nodes` by following epsilon moves, creating
+    // it if it is new. `restart` tells whether matches may still start
+    // after its position.
+    fn state(
+        &mut self,
+        bytes: &ByteFSM,
+        nodes: &[usize],
+        context: Context,
+        restart: bool,
+    ) -> usize {
+        let (nodes, matched) = self.closure(bytes, nodes, context, None);
+        if matched && self.semantics == Semantics::Earliest {
+            return MATCH;
+        }
+        let restart = restart
+            && self.restart.is_some()
+            && !(matched && self.semantics == Semantics::LeftmostFirst);
+        // Without restarts, nothing can come of an empty set.
+        if nodes.is_empty() && !restart && !matched {
+            return DEAD;
+        }
+        let key = Key {
+            context,
+            nodes,
+            matched,
+            restart,
+        };
+        if let Some(&state) = self.states.get(&key) {
+            return state;
+        }
+        self.sets.push(key.clone());
+        self.states.insert(key, self.sets.len() - 1);
+        self.sets.len() - 1
+    }
+    // Follows the epsilon moves from `roots`, returning the nodes that
+    // consume a byte along with the assertions that can't be decided yet,
+    // and whether the pattern has matched. Nodes are visited depth first in
+    // the order of their priority. Unless all matches count, those after a
+    // match are dropped, and the rest sorted where their order doesn't
+    // matter.
+    fn closure(
+        &mut self,
+        bytes: &ByteFSM,
+        roots: &[usize],
+        context: Context,
+        ahead: Option<Ahead>,
+    ) -> (Vec<usize>, bool) {
+        let graph = bytes.graph();
+        self.stamp += 1;
+        let mut nodes = Vec::new();
+        let mut matched = false;
+        let mut stack: Vec<usize> = roots.iter().rev().copied().collect();
+        while let Some(idx) = stack.pop() {
+            if core::mem::replace(&mut self.seen[idx], self.stamp) == self.stamp {
+                continue;
+            }
+            match &graph[idx] {
+                ByteNode::Range(..) => nodes.push(idx),
+                ByteNode::Epsilon(targets) => stack.extend(targets.iter().rev()),
+                ByteNode::Assert(look, next) => match holds(*look, context, ahead) {
+                    Some(true) => stack.push(*next),
+                    Some(false) => {}
+                    None => nodes.push(idx),
+                },
+                ByteNode::Match => {
+                    matched = true;
+                    if self.semantics != Semantics::All {
+                        break;
+                    }
+                }
+            }
+        }
+        if self.semantics != Semantics::LeftmostFirst {
+            nodes.sort_unstable();
+        }
+        (nodes, matched)
+    }
+}
+
+// Where the `settled` nodes of a state lead on every byte.
+fn targets(bytes: &ByteFSM, settled: &[usize]) -> Vec<Vec<usize>> {
+    let mut targets = vec![Vec::new(); 256];
+    for &idx in settled {
+        if let ByteNode::Range(low, high, next) = bytes.graph()[idx] {
+            for byte in low..=high {
+                targets[byte as usize].push(next);
+            }
+        }
+    }
+    targets
+}
+
+// Whether `look` holds between `context` and `ahead`, or `None` if that
+// depends on input that hasn't been seen yet.
+fn holds(look: Look, context: Context, ahead: Option<Ahead>) -> Option<bool> {
+    let word_ahead = || ahead.map(|ahead| matches!(ahead, Ahead::Byte(byte) if is_word_byte(byte)));
+    match look {
+        // Searches always start at the beginning of the haystack.
+        Look::Start | Look::SearchStart => Some(context == Context::Start),
+        Look::StartLine => Some(matches!(context, Context::Start | Context::LineStart)),
+        Look::End => ahead.map(|ahead| ahead == Ahead::End),
+        Look::EndLine => ahead.map(|ahead| matches!(ahead, Ahead::End | Ahead::Byte(b'\n'))),
+        Look::EndBeforeNewline | Look::StartCrlfLine | Look::EndCrlfLine => None,
+        Look::WordBoundary(_) => word_ahead().map(|word| word != (context == Context::Word)),
+        Look::NotWordBoundary(_) => word_ahead().map(|word| word == (context == Context::Word)),
+    }
+}
+
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b'_'
+}
+
+// Whether `a` and `b` match the same haystacks in full, decided on the
+// product of their anchored DFAs, or `None` if their DFAs can't be built or
+// the product would take up more than `size_limit` bytes.
+pub fn equivalent(a: &ByteFSM, b: &ByteFSM, size_limit: usize) -> Option<bool> {
+    let (mut a, mut b) = (Anchored::new(a)?, Anchored::new(b)?);
+    // The bytes that the classes of either automaton tell apart.
+    let representatives: Vec<u8> = (0..=255u8)
+        .filter(|&byte| {
+            byte == 0 || a.class(byte) != a.class(byte - 1) || b.class(byte) != b.class(byte - 1)
+        })
+        .collect();
+    let start = (a.start, b.start);
+    let mut seen = Set::new();
+    seen.insert(start);
+    let mut pending = vec![start];
+    while let Some((left, right)) = pending.pop() {
+        if a.accepts_at_end(left) != b.accepts_at_end(right) {
+            return Some(false);
+        }
+        for &byte in &representatives {
+            let next = (a.next(left, byte), b.next(right, byte));
+            if seen.insert(next) {
+                pending.push(next);
+            }
+        }
+        if seen.len() * representatives.len() * core::mem::size_of::<usize>() > size_limit {
+            return None;
+        }
+    }
+    Some(true)
+}
+
+// A DFA that only matches from the beginning of the haystack and tells
+// whether all of it matches, whose states are built as they are reached.
+pub struct Anchored<'a> {
+    bytes: &'a ByteFSM,
+    builder: Builder,
+    start: usize,
+    // The transitions worked out so far, by state and byte class.
+    transitions: Map<(usize, usize), usize>,
+}
+
+impl<'a> Anchored<'a> {
+    pub fn new(bytes: &'a ByteFSM) -> Option<Self> {
+        let mut builder = Builder::new(bytes, Semantics::All)?;
+        let start = builder.state(bytes, &[bytes.entries().0], Context::Start, false);
+        Some(Self {
+            bytes,
+            builder,
+            start,
+            transitions: Map::new(),
+        })
+    }
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    pub fn class(&self, byte: u8) -> usize {
+        self.builder.classes.get(byte)
+    }
+    pub fn next(&mut self, state: usize, byte: u8) -> usize {
+        let key = (state, self.builder.classes.get(byte));
+        if let Some(&next) = self.transitions.get(&key) {
+            return next;
+        }
+        // Whether a match ends before the byte doesn't matter.
+        let next = match state {
+            DEAD => DEAD,
+            state => self.builder.transition(self.bytes, state, byte) >> 1,
+        };
+        self.transitions.insert(key, next);
+        next
+    }
+    pub fn accepts_at_end(&mut self, state: usize) -> bool {
+        state != DEAD && self.builder.accepts_at_end(self.bytes, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Table;
+    use crate::RegexBuilder;
+
+    #[test]
+    fn shares_transitions_between_byte_classes() {
+        let regex = RegexBuilder::new("[a-c]+x")
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        let dfa = regex.inner.dfa.as_ref().unwrap();
+        // Newlines, `[a-c]`, `x`, and the bytes before, in between and after.
+        assert_eq!(dfa.stride, 7);
+        assert_eq!(dfa.table.len() % dfa.stride, 0);
+        assert!((0..dfa.table.len()).all(|idx| dfa.table.get(idx).is_multiple_of(dfa.stride)));
+        assert!(dfa.is_match(b"zzabcx"));
+        assert!(!dfa.is_match(b"zzabc"));
+        let regex = RegexBuilder::new(r"(?-u)\bcat\b")
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        let dfa = regex.inner.dfa.as_ref().unwrap();
+        assert_eq!(dfa.classes.get(b'c'), dfa.classes.get(b'b') + 1);
+        assert_ne!(dfa.classes.get(b'_'), dfa.classes.get(b'^'));
+        assert!(dfa.is_match(b"a cat!"));
+        assert!(!dfa.is_match(b"a cat_"));
+        let regex = RegexBuilder::new(r"\w+").prefer_dfa(true).build().unwrap();
+        let dfa = regex.inner.dfa.as_ref().unwrap();
+        assert!(dfa.stride < 256);
+        assert!(dfa.is_match("ä".as_bytes()));
+        assert!(!dfa.is_match(b" !"));
+    }
+
+    #[test]
+    fn stores_states_in_the_narrowest_type() {
+        let regex = RegexBuilder::new("[a-c]+x")
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        let dfa = regex.inner.dfa.as_ref().unwrap();
+        assert!(matches!(dfa.table, Table::U8(_)));
+        // Which of the last nine bytes were ones takes 512 states.
+        let regex = RegexBuilder::new("[01]*1[01]{8}")
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        let dfa = regex.inner.dfa.as_ref().unwrap();
+        assert!(matches!(dfa.table, Table::U16(_)));
+        assert!(dfa.is_match(b"0100000000"));
+        assert!(!dfa.is_match(b"0000000001"));
+        assert_eq!(Table::width(1 << 16), 4);
+    }
+}
@@ -0,0 +1,228 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::collections::Map;
+use crate::dfa::Anchored;
+use crate::utf8::ByteFSM;
+
+/// An iterator over all strings of at most a given number of characters that
+/// a regex fully matches, returned by [`Regex::strings`](crate::Regex::strings).
+///
+/// Shorter strings come first, and strings of the same length in the order
+/// of their characters. Every string comes up once, however many ways the
+/// pattern has of matching it, and loops in the pattern that can't lead to a
+/// match within the bound are never followed.
+#[derive(Debug, Clone)]
+pub struct Strings {
+    // The anchored DFA, with states numbered from zero in the order they
+    // were found, and the start state first.
+    table: Vec<usize>,
+    classes: [u8; 256],
+    stride: usize,
+    accepts: Vec<bool>,
+    // Whether some string of exactly as many characters as the index of the
+    // row leads from each state to a match.
+    finishes: Vec<Vec<bool>>,
+    max_len: usize,
+    // The length of the strings currently enumerated.
+    len: usize,
+    // The states on the way to the current string, with the number of
+    // characters still to come and the byte to try next from each.
+    stack: Vec<Frame>,
+    current: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    state: usize,
+    remaining: usize,
+    // The next byte to try, or 256 once all have been.
+    byte: usize,
+}
+
+impl Strings {
+    // Builds the anchored DFA up front, giving up if it would take up more
+    // than `size_limit` bytes.
+    pub(crate) fn new(bytes: &ByteFSM, max_len: usize, size_limit: usize) -> Option<Self> {
+        let mut anchored = Anchored::new(bytes)?;
+        let mut classes = [0u8; 256];
+        for byte in 0..=255u8 {
+            classes[byte as usize] = anchored.class(byte) as u8;
+        }
+        let stride = classes[255] as usize + 1;
+        let representatives: Vec<u8> = (0..=255u8)
+            .filter(|&byte| byte == 0 || classes[byte as usize] != classes[byte as usize - 1])
+            .collect();
+        let mut numbers = Map::new();
+        numbers.insert(anchored.start(), 0);
+        let mut states = vec![anchored.start()];
+        let (mut table, mut accepts) = (Vec::new(), Vec::new());
+        let mut next = 0;
+        while next < states.len() {
+            let state = states[next];
+            accepts.push(anchored.accepts_at_end(state));
+            for &byte in &representatives {
+                let target = anchored.next(state, byte);
+                let number = *numbers.entry(target).or_insert_with(|| {
+                    states.push(target);
+                    states.len() - 1
+                });
+                table.push(number);
+            }
+            if table.len() * core::mem::size_of::<usize>() > size_limit {
+                return None;
+            }
+            next += 1;
+        }
+        let mut strings = Self {
+            table,
+            classes,
+            stride,
+            accepts,
+            finishes: Vec::new(),
+            max_len,
+            len: 0,
+            stack: Vec::new(),
+            current: Vec::new(),
+        };
+        strings.finishes = strings.finishes(max_len);
+        strings.start();
+        Some(strings)
+    }
+    // Works out which states lead to a match with how many more characters.
+    // Bytes continuing a character don't count, and no more than three of
+    // them follow each other, so a few rounds settle every length.
+    fn finishes(&self, max_len: usize) -> Vec<Vec<bool>> {
+        let states = self.accepts.len();
+        let mut finishes: Vec<Vec<bool>> = Vec::with_capacity(max_len + 1);
+        for remaining in 0..=max_len {
+            let mut row = if remaining == 0 {
+                self.accepts.clone()
+            } else {
+                vec![false; states]
+            };
+            let mut changed = true;
+            while changed {
+                changed = false;
+                for state in 0..states {
+                    if row[state] {
+                        continue;
+                    }
+                    row[state] = (0..=255u8).any(|byte| {
+                        let target = self.target(state, byte);
+                        match starts_character(byte) {
+                            false => row[target],
+                            true => remaining > 0 && finishes[remaining - 1][target],
+                        }
+                    });
+                    changed |= row[state];
+                }
+            }
+            finishes.push(row);
+        }
+        finishes
+    }
+    fn target(&self, state: usize, byte: u8) -> usize {
+        self.table[state * self.stride + self.classes[byte as usize] as usize]
+    }
+    // Starts on the strings of the current length, if any match.
+    fn start(&mut self) {
+        if self.finishes[self.len][0] {
+            self.stack.push(Frame {
+                state: 0,
+                remaining: self.len,
+                byte: 0,
+            });
+        }
+    }
+}
+
+impl Iterator for Strings {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        loop {
+            let Some(&Frame {
+                state,
+                remaining,
+                byte,
+            }) = self.stack.last()
+            else {
+                if self.len == self.max_len {
+                    return None;
+                }
+                self.len += 1;
+                self.start();
+                continue;
+            };
+            if byte == 0 && remaining == 0 && self.accepts[state] {
+                // Nothing follows a string that matches in full.
+                self.stack.pop();
+                let string = String::from_utf8(self.current.clone())
+                    .expect("the automaton only matches UTF-8");
+                self.current.pop();
+                return Some(string);
+            }
+            let found = (byte..256).find(|&byte| {
+                let spent = starts_character(byte as u8) as usize;
+                spent <= remaining
+                    && self.finishes[remaining - spent][self.target(state, byte as u8)]
+            });
+            match found {
+                Some(byte) => {
+                    self.stack.last_mut().unwrap().byte = byte + 1;
+                    let byte = byte as u8;
+                    self.current.push(byte);
+                    self.stack.push(Frame {
+                        state: self.target(state, byte),
+                        remaining: remaining - starts_character(byte) as usize,
+                        byte: 0,
+                    });
+                }
+                None => {
+                    self.stack.pop();
+                    self.current.pop();
+                }
+            }
+        }
+    }
+}
+
+// Whether the byte starts a character in UTF-8, rather than continuing one.
+fn starts_character(byte: u8) -> bool {
+    !matches!(byte, 0x80..=0xBF)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    #[test]
+    fn enumerates_full_matches() {
+        let strings = |pattern, max_len| -> Vec<String> {
+            Regex::new(pattern)
+                .unwrap()
+                .strings(max_len)
+                .unwrap()
+                .collect()
+        };
+        assert_eq!(
+            strings("[ab]{1,2}|c", 3),
+            ["a", "b", "c", "aa", "ab", "ba", "bb"]
+        );
+        assert_eq!(strings("(?:a*)*b?", 2), ["", "a", "b", "aa", "ab"]);
+        assert_eq!(strings("a|a|(a)", 5), ["a"]);
+        assert_eq!(strings("(?:aa)+", 5), ["aa", "aaaa"]);
+        assert_eq!(strings("[é€😀]x?", 1), ["é", "€", "😀"]);
+        assert_eq!(strings(r"(?-u)\bab?\b", 2), ["a", "ab"]);
+        assert_eq!(strings("a{3}", 2), Vec::<String>::new());
+        assert!(Regex::new(r"(a)\1").unwrap().strings(2).is_none());
+    }
+
+    #[test]
+    fn counts_every_string() {
+        let regex = Regex::new("[a-c]*").unwrap();
+        assert_eq!(regex.strings(4).unwrap().count(), 1 + 3 + 9 + 27 + 81);
+    }
+}
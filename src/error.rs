@@ -0,0 +1,240 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use core::fmt::Display;
+use core::time::Duration;
+
+/// An error that occurred while compiling a pattern.
+///
+/// Besides the [`ErrorKind`], it records the pattern and the byte offset at
+/// which the problem was found, and displays itself with a caret pointing
+/// at that position.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    kind: ErrorKind,
+    pattern: String,
+    position: usize,
+}
+
+/// The different ways in which compiling a pattern can fail.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The pattern contains input that doesn't fit the grammar. The message
+    /// describes what was expected instead.
+    Syntax(String),
+    /// A group was opened but never closed, or closed but never opened.
+    UnbalancedParenthesis,
+    /// A character class was opened with `[` but never closed.
+    UnclosedCharacterClass,
+    /// A class range whose end comes before its start, e.g. `[z-a]`.
+    InvalidRange(char, char),
+    /// An escape like `\u{D800}` for a value that isn't a Unicode scalar
+    /// value.
+    InvalidCodePoint(u32),
+    /// An escape like `\N{LATIN SMALL LETTER Q}` with a name that no
+    /// Unicode character has.
+    UnknownCharacterName(String),
+    /// A counted repetition whose minimum exceeds its maximum, e.g. `a{5,3}`.
+    InvalidRepetition(usize, usize),
+    /// A quantifier that doesn't follow anything it could repeat.
+    NothingToRepeat,
+    /// A group name that starts with a digit.
+    InvalidGroupName(String),
+    /// Two groups with the same name.
+    DuplicateGroupName(String),
+    /// A backreference to a group that doesn't exist.
+    InvalidBackreference(usize),
+    /// A named backreference, like `(?P=word)`, to a name that no group
+    /// has.
+    UnknownGroupName(String),
+    /// A named class, like `[[:hexdigit:]]` or `\p{hexdigit}`, that isn't
+    /// defined with [`RegexBuilder::define_class`](crate::RegexBuilder::define_class).
+    UnknownClassName(String),
+    /// Groups nested deeper than the configured limit.
+    NestLimitExceeded(usize),
+    /// A counted repetition like `a{5000}` with a bound above the configured
+    /// limit.
+    RepetitionLimitExceeded(usize),
+    /// The compiled pattern would exceed the configured size limit in bytes.
+    CompiledTooBig(usize),
+    /// The compiled pattern doesn't fit into the number of states of a
+    /// [`FixedRegex`](crate::FixedRegex), which is given.
+    StateCapacityExceeded(usize),
+    /// The pattern has more groups than the capture slots of a
+    /// [`FixedRegex`](crate::FixedRegex), which are given, have room for.
+    SlotCapacityExceeded(usize),
+    /// The pattern uses a feature that a
+    /// [`FixedRegex`](crate::FixedRegex) can't match, e.g. `backreferences`.
+    Unsupported(String),
+}
+
+impl Error {
+    pub(crate) fn new(kind: ErrorKind, pattern: &str, position: usize) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_owned(),
+            position,
+        }
+    }
+    /// What went wrong.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+    /// The pattern that failed to compile.
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+    /// The byte offset into the pattern at which the error was found.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let line_start = self.pattern[..self.position]
+            .rfind('\n')
+            .map_or(0, |idx| idx + 1);
+        let line_end = self.pattern[self.position..]
+            .find('\n')
+            .map_or(self.pattern.len(), |idx| self.position + idx);
+        let column = self.pattern[line_start..self.position].chars().count();
+        writeln!(f, "regex parse error:")?;
+        writeln!(f, "    {}", &self.pattern[line_start..line_end])?;
+        writeln!(f, "    {}^", " ".repeat(column))?;
+        write!(f, "error: {}", self.kind)
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Syntax(msg) => write!(f, "syntax error: {msg}"),
+            Self::UnbalancedParenthesis => write!(f, "unbalanced parenthesis"),
+            Self::UnclosedCharacterClass => write!(f, "unclosed character class"),
+            Self::InvalidRange(from, to) => write!(f, "invalid class range {from}-{to}"),
+            Self::InvalidCodePoint(value) => write!(f, "invalid code point U+{value:X}"),
+            Self::UnknownCharacterName(name) => write!(f, "unknown character name `{name}`"),
+            Self::InvalidRepetition(min, max) => {
+                write!(
+                    f,
+                    "invalid repetition {{{min},{max}}}: minimum exceeds maximum"
+                )
+            }
+            Self::NothingToRepeat => write!(f, "nothing to repeat"),
+            Self::InvalidGroupName(name) => write!(f, "invalid group name '{name}'"),
+            Self::DuplicateGroupName(name) => write!(f, "duplicate group name '{name}'"),
+            Self::InvalidBackreference(group) => {
+                write!(f, "invalid backreference to group {group}")
+            }
+            Self::UnknownGroupName(name) => write!(f, "unknown group name '{name}'"),
+            Self::UnknownClassName(name) => write!(f, "unknown class name `{name}`"),
+            Self::NestLimitExceeded(limit) => write!(f, "groups nested deeper than {limit} levels"),
+            Self::RepetitionLimitExceeded(limit) => {
+                write!(f, "counted repetition exceeds the limit of {limit}")
+            }
+            Self::CompiledTooBig(limit) => {
+                write!(
+                    f,
+                    "compiled pattern exceeds the size limit of {limit} bytes"
+                )
+            }
+            Self::StateCapacityExceeded(states) => {
+                write!(f, "compiled pattern needs more than {states} states")
+            }
+            Self::SlotCapacityExceeded(slots) => {
+                write!(f, "capture groups need more than {slots} slots")
+            }
+            Self::Unsupported(what) => {
+                write!(f, "{what} aren't supported by fixed-capacity regexes")
+            }
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+/// Why a search gave up before finding out whether the pattern matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchError {
+    /// Matching the pattern by backtracking took more steps than the
+    /// configured limit, see
+    /// [`RegexBuilder::backtrack_limit`](crate::RegexBuilder::backtrack_limit).
+    BacktrackLimitExceeded(usize),
+    /// The search ran for longer than the timeout passed to
+    /// [`Regex::find_with_deadline`](crate::Regex::find_with_deadline).
+    DeadlineExceeded(Duration),
+    /// The haystack of [`Regex::find_bytes`](crate::Regex::find_bytes) has
+    /// a byte at this offset that isn't part of valid UTF-8, or is a quit
+    /// byte, and the regex is set to fail there, see
+    /// [`InvalidBytes::Error`](crate::InvalidBytes::Error).
+    InvalidByte(usize),
+}
+
+impl Display for MatchError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::BacktrackLimitExceeded(limit) => {
+                write!(f, "backtracking exceeded the limit of {limit} steps")
+            }
+            Self::DeadlineExceeded(timeout) => {
+                write!(f, "the search took longer than {timeout:?}")
+            }
+            Self::InvalidByte(offset) => write!(f, "invalid UTF-8 or quit byte at offset {offset}"),
+        }
+    }
+}
+
+impl core::error::Error for MatchError {}
+
+/// Why [`Dfa::from_bytes`](crate::Dfa::from_bytes) rejected its input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeError {
+    /// The input doesn't start like a serialized DFA.
+    NotADfa,
+    /// The DFA was serialized in another version of the format.
+    UnsupportedVersion(u32),
+    /// The input is truncated, or its transitions lead to states that don't
+    /// exist.
+    Corrupt,
+}
+
+impl Display for DeserializeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::NotADfa => write!(f, "not a serialized DFA"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {version} of the DFA format")
+            }
+            Self::Corrupt => write!(f, "corrupt serialized DFA"),
+        }
+    }
+}
+
+impl core::error::Error for DeserializeError {}
+
+/// Why [`Automaton::from_json`](crate::Automaton::from_json) rejected its
+/// input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonError {
+    /// The input isn't valid JSON. The byte offset at which it stops being
+    /// valid is given.
+    Syntax(usize),
+    /// The JSON was written in another version of the schema.
+    UnsupportedVersion(usize),
+    /// The JSON doesn't describe an automaton. The message says why not.
+    Invalid(String),
+}
+
+impl Display for JsonError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Syntax(position) => write!(f, "invalid JSON at byte {position}"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported version {version} of the automaton schema")
+            }
+            Self::Invalid(message) => write!(f, "invalid automaton: {message}"),
+        }
+    }
+}
+
+impl core::error::Error for JsonError {}
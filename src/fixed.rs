@@ -0,0 +1,530 @@
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::captures::CaptureLocations;
+use crate::error::ErrorKind;
+use crate::fsm::{Look, Node, NFA};
+use crate::{Anchored, Match, Matcher};
+
+// The compiled pattern is flattened into a fixed array of instructions. A
+// node that consumes a character is followed by its ranges, and one that
+// forks by the instructions it moves to, so that every instruction has a
+// fixed size.
+#[derive(Debug, Clone, Copy)]
+enum Inst {
+    // Consumes a character in one of the `len` ranges after it, then moves
+    // on.
+    Consume(usize, usize),
+    // Moves to each of the `len` targets after it, in order.
+    Fork(usize),
+    Save(usize, usize),
+    Assert(Look, usize),
+    Match,
+    // The operands of `Consume` and `Fork`.
+    Range(char, char),
+    Target(usize),
+}
+
+/// A regex that is matched within fixed-size arrays, for targets where
+/// searches can't allocate.
+///
+/// `STATES` bounds the size of the compiled pattern, and `SLOTS` the number
+/// of capture slots, two per group including the whole match. Building one
+/// with [`RegexBuilder::build_fixed`](crate::RegexBuilder::build_fixed)
+/// fails with [`ErrorKind::StateCapacityExceeded`] or
+/// [`ErrorKind::SlotCapacityExceeded`] if the pattern doesn't fit, so that
+/// searches never run out of room. Building still allocates; searching
+/// never does, and takes stack space proportional to `STATES * SLOTS`.
+///
+/// Searches simulate the pattern like [`Regex`](crate::Regex) does without
+/// a DFA, in time linear in the length of the haystack. Lookaheads,
+/// backreferences, atomic groups and conditionals aren't supported, since
+/// they need more room than the pattern itself.
+///
+/// ```
+/// use regexrs::{FixedRegex, RegexBuilder};
+///
+/// let regex: FixedRegex<32, 4> = RegexBuilder::new(r"(\d+)ms").unicode(false).build_fixed().unwrap();
+/// let caps = regex.captures("took 25ms").unwrap();
+/// assert_eq!(caps.get(1).unwrap().as_str(), "25");
+/// ```
+///
+/// [`ErrorKind::StateCapacityExceeded`]: crate::ErrorKind::StateCapacityExceeded
+/// [`ErrorKind::SlotCapacityExceeded`]: crate::ErrorKind::SlotCapacityExceeded
+#[derive(Clone)]
+pub struct FixedRegex<const STATES: usize, const SLOTS: usize> {
+    program: [Inst; STATES],
+    start: usize,
+    restart: Option<usize>,
+    slots: usize,
+    longest: bool,
+}
+
+/// The spans of the capture groups in a match of a [`FixedRegex`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixedCaptures<'h, const SLOTS: usize> {
+    haystack: &'h str,
+    slots: [Option<usize>; SLOTS],
+    len: usize,
+}
+
+// Threads at the same position, in the order of their priority.
+struct Threads<const STATES: usize, const SLOTS: usize> {
+    len: usize,
+    insts: [usize; STATES],
+    slots: [[Option<usize>; SLOTS]; STATES],
+}
+
+// Space for following epsilon moves.
+struct Closure<const STATES: usize, const SLOTS: usize> {
+    // The step in which each instruction was last visited.
+    visited: [usize; STATES],
+    step: usize,
+    // Where the search started, for `\G`.
+    start: usize,
+    stack: [(usize, [Option<usize>; SLOTS]); STATES],
+}
+
+impl<const STATES: usize, const SLOTS: usize> FixedRegex<STATES, SLOTS> {
+    // Flattens `nfa`, whose groups take up `slots` capture slots.
+    pub(crate) fn new(nfa: &NFA, slots: usize) -> Result<Self, ErrorKind> {
+        if slots > SLOTS {
+            return Err(ErrorKind::SlotCapacityExceeded(SLOTS));
+        }
+        let graph = nfa.graph();
+        let mut offsets = Vec::with_capacity(graph.len());
+        let mut len = 0;
+        for node in graph {
+            offsets.push(len);
+            len += 1 + match node {
+                Node::Consume(set, _) => set.ranges().len(),
+                Node::Epsilon(targets) => targets.len(),
+                Node::Lookahead(..) => return Err(unsupported("lookaheads")),
+                Node::Backref(..) => return Err(unsupported("backreferences")),
+                Node::Atomic(..) => return Err(unsupported("atomic groups")),
+                Node::Conditional(..) => return Err(unsupported("conditionals")),
+                Node::Save(..) | Node::Assert(..) | Node::Match => 0,
+            };
+        }
+        if len > STATES {
+            return Err(ErrorKind::StateCapacityExceeded(STATES));
+        }
+        let mut program = [Inst::Match; STATES];
+        for (node, &at) in graph.iter().zip(&offsets) {
+            program[at] = match node {
+                Node::Consume(set, next) => {
+                    for (idx, &(from, to)) in set.ranges().iter().enumerate() {
+                        program[at + 1 + idx] = Inst::Range(from, to);
+                    }
+                    Inst::Consume(set.ranges().len(), offsets[*next])
+                }
+                Node::Epsilon(targets) => {
+                    for (idx, &target) in targets.iter().enumerate() {
+                        program[at + 1 + idx] = Inst::Target(offsets[target]);
+                    }
+                    Inst::Fork(targets.len())
+                }
+                Node::Save(slot, next) => Inst::Save(*slot, offsets[*next]),
+                Node::Assert(look, next) => Inst::Assert(*look, offsets[*next]),
+                _ => Inst::Match,
+            };
+        }
+        let (start, restart) = nfa.entries();
+        Ok(Self {
+            program,
+            start: offsets[start],
+            restart: restart.map(|restart| offsets[restart]),
+            slots,
+            longest: nfa.longest(),
+        })
+    }
+    /// Returns true if the pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.is_match_at(haystack, 0, Anchored::No)
+    }
+    /// Returns the leftmost match in `haystack`, if there is one.
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_at(haystack, 0, Anchored::No)
+    }
+    /// Returns the capture groups of the leftmost match in `haystack`, if
+    /// there is one.
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<FixedCaptures<'h, SLOTS>> {
+        Some(FixedCaptures {
+            haystack,
+            slots: self.search(haystack, 0, false, false)?,
+            len: self.slots / 2,
+        })
+    }
+    // The captures of the leftmost match at or after byte offset `start`,
+    // or right at it if the search is `anchored`, like those of the Pike VM.
+    // With `earliest`, the search stops at the first match it comes across.
+    fn search(
+        &self,
+        haystack: &str,
+        start: usize,
+        anchored: bool,
+        earliest: bool,
+    ) -> Option<[Option<usize>; SLOTS]> {
+        let bytes = haystack.as_bytes();
+        let mut threads = Threads::<STATES, SLOTS>::new();
+        let mut targets = Threads::<STATES, SLOTS>::new();
+        let mut closure = Closure::<STATES, SLOTS>::new(start);
+        let spawn = |position| {
+            let mut captured = [None; SLOTS];
+            if let Some(first) = captured.first_mut() {
+                *first = Some(position);
+            }
+            captured
+        };
+        let restart = if anchored { None } else { self.restart };
+        let entry = if start == 0 || anchored {
+            Some(self.start)
+        } else {
+            restart
+        };
+        closure.step += 1;
+        if let Some(entry) = entry {
+            closure.follow(self, entry, spawn(start), &mut threads, bytes, start);
+        }
+        let mut matched = None;
+        let mut position = start;
+        for (idx, c) in haystack[start..].char_indices() {
+            let idx = start + idx;
+            if let Some(idx) = (0..threads.len)
+                .find(|&idx| matches!(self.program[threads.insts[idx]], Inst::Match))
+            {
+                let mut captured = threads.slots[idx];
+                finish(&mut captured, position);
+                if earliest {
+                    return Some(captured);
+                }
+                if self.longest {
+                    threads.remove(idx);
+                    threads.retain(|slots| slots[0] <= captured[0]);
+                } else {
+                    threads.len = idx;
+                }
+                matched = Some(captured);
+            }
+            if threads.len == 0 && matched.is_some() {
+                break;
+            }
+            let next = idx + c.len_utf8();
+            closure.step += 1;
+            targets.len = 0;
+            for thread in 0..threads.len {
+                let at = threads.insts[thread];
+                if let Some(target) = self.target(at, c) {
+                    closure.follow(
+                        self,
+                        target,
+                        threads.slots[thread],
+                        &mut targets,
+                        bytes,
+                        next,
+                    );
+                }
+            }
+            if let Some(restart) = restart.filter(|_| matched.is_none()) {
+                closure.follow(self, restart, spawn(next), &mut targets, bytes, next);
+            }
+            core::mem::swap(&mut threads, &mut targets);
+            position = next;
+        }
+        if let Some(idx) =
+            (0..threads.len).find(|&idx| matches!(self.program[threads.insts[idx]], Inst::Match))
+        {
+            let mut captured = threads.slots[idx];
+            finish(&mut captured, position);
+            matched = Some(captured);
+        }
+        matched
+    }
+    // Where the thread at the instruction `at` moves to on `c`.
+    fn target(&self, at: usize, c: char) -> Option<usize> {
+        let Inst::Consume(len, next) = self.program[at] else {
+            return None;
+        };
+        let ranges = &self.program[at + 1..at + 1 + len];
+        let idx = ranges.partition_point(|range| matches!(*range, Inst::Range(_, to) if to < c));
+        match ranges.get(idx) {
+            Some(&Inst::Range(from, _)) if from <= c => Some(next),
+            _ => None,
+        }
+    }
+}
+
+impl<const STATES: usize, const SLOTS: usize> Matcher for FixedRegex<STATES, SLOTS> {
+    fn is_match_at(&self, haystack: &str, start: usize, anchored: Anchored) -> bool {
+        self.search(haystack, start, anchored == Anchored::Yes, true)
+            .is_some()
+    }
+    fn find_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let slots = self.search(haystack, start, anchored == Anchored::Yes, false)?;
+        Some(Match {
+            haystack,
+            start: slots[0]?,
+            end: slots[1]?,
+        })
+    }
+    fn captures_at<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let found = self.search(haystack, start, anchored == Anchored::Yes, false);
+        locations.read(haystack, found.map(|slots| slots[..self.slots].to_vec()))
+    }
+    fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations(vec![None; self.slots])
+    }
+}
+
+impl<const STATES: usize, const SLOTS: usize> core::fmt::Debug for FixedRegex<STATES, SLOTS> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("FixedRegex")
+            .field("slots", &self.slots)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'h, const SLOTS: usize> FixedCaptures<'h, SLOTS> {
+    /// Returns the match of group `idx`, or `None` if the group did not
+    /// take part in the match or doesn't exist.
+    pub fn get(&self, idx: usize) -> Option<Match<'h>> {
+        if idx >= self.len {
+            return None;
+        }
+        match (self.slots.get(2 * idx)?, self.slots.get(2 * idx + 1)?) {
+            (&Some(start), &Some(end)) => Some(Match {
+                haystack: self.haystack,
+                start,
+                end,
+            }),
+            _ => None,
+        }
+    }
+    /// The number of groups, including the whole match.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Always false, since group 0 is part of every match.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl<const STATES: usize, const SLOTS: usize> Threads<STATES, SLOTS> {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            insts: [0; STATES],
+            slots: [[None; SLOTS]; STATES],
+        }
+    }
+    fn push(&mut self, inst: usize, slots: [Option<usize>; SLOTS]) {
+        self.insts[self.len] = inst;
+        self.slots[self.len] = slots;
+        self.len += 1;
+    }
+    fn remove(&mut self, idx: usize) {
+        self.insts.copy_within(idx + 1..self.len, idx);
+        self.slots.copy_within(idx + 1..self.len, idx);
+        self.len -= 1;
+    }
+    fn retain<F: Fn(&[Option<usize>; SLOTS]) -> bool>(&mut self, keep: F) {
+        let mut kept = 0;
+        for idx in 0..self.len {
+            if keep(&self.slots[idx]) {
+                self.insts[kept] = self.insts[idx];
+                self.slots[kept] = self.slots[idx];
+                kept += 1;
+            }
+        }
+        self.len = kept;
+    }
+}
+
+impl<const STATES: usize, const SLOTS: usize> Closure<STATES, SLOTS> {
+    fn new(start: usize) -> Self {
+        Self {
+            visited: [0; STATES],
+            step: 0,
+            start,
+            stack: [(0, [None; SLOTS]); STATES],
+        }
+    }
+    // Follows all epsilon moves from `root` at `position`, adding the
+    // instructions that consume a character or match to `threads` in the
+    // order they are reached. Within a step, every instruction is visited
+    // once. An instruction only pushes the moves out of it on the stack,
+    // and there are fewer of those than instructions, so the stack never
+    // overflows.
+    fn follow(
+        &mut self,
+        regex: &FixedRegex<STATES, SLOTS>,
+        root: usize,
+        captured: [Option<usize>; SLOTS],
+        threads: &mut Threads<STATES, SLOTS>,
+        haystack: &[u8],
+        position: usize,
+    ) {
+        self.stack[0] = (root, captured);
+        let mut depth = 1;
+        while depth > 0 {
+            depth -= 1;
+            let (at, mut captured) = self.stack[depth];
+            if self.visited[at] == self.step {
+                continue;
+            }
+            self.visited[at] = self.step;
+            let next = match regex.program[at] {
+                Inst::Consume(..) | Inst::Match => {
+                    threads.push(at, captured);
+                    continue;
+                }
+                Inst::Fork(len) => {
+                    for target in regex.program[at + 1..at + 1 + len].iter().rev() {
+                        if let &Inst::Target(target) = target {
+                            self.stack[depth] = (target, captured);
+                            depth += 1;
+                        }
+                    }
+                    continue;
+                }
+                Inst::Save(slot, next) => {
+                    if let Some(value) = captured.get_mut(slot) {
+                        *value = Some(position);
+                    }
+                    next
+                }
+                Inst::Assert(look, next) => {
+                    if !look.holds(haystack, self.start, position) {
+                        continue;
+                    }
+                    next
+                }
+                Inst::Range(..) | Inst::Target(_) => continue,
+            };
+            self.stack[depth] = (next, captured);
+            depth += 1;
+        }
+    }
+}
+
+// Records where a successful thread's match ends.
+fn finish(captured: &mut [Option<usize>], position: usize) {
+    if let Some(end) = captured.get_mut(1) {
+        *end = Some(position);
+    }
+}
+
+fn unsupported(what: &str) -> ErrorKind {
+    ErrorKind::Unsupported(what.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Anchored, ErrorKind, FixedRegex, MatchKind, Matcher, Regex, RegexBuilder};
+
+    #[test]
+    fn matches_like_the_regex() {
+        let patterns = [
+            r"(\w+)@(\w+)\.com",
+            "a|ab",
+            r"(?m)^b$",
+            r"\bcat\b",
+            "(a*)*b",
+            "x*",
+            "(a)|(b)",
+            "ü+",
+            r"\Ba",
+        ];
+        let haystacks = [
+            "",
+            "mail me@example.com",
+            "ab",
+            "a\nb\n",
+            "a cat!",
+            "concat",
+            "aaab",
+            "xx",
+            "b",
+            "ñüü",
+            "ba",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            let fixed: FixedRegex<2048, 8> = RegexBuilder::new(pattern).build_fixed().unwrap();
+            for haystack in haystacks {
+                assert_eq!(
+                    fixed.is_match(haystack),
+                    regex.is_match(haystack),
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    fixed.find(haystack),
+                    regex.find(haystack),
+                    "{pattern} on {haystack:?}"
+                );
+                let expected = regex.captures(haystack);
+                let found = fixed.captures(haystack);
+                assert_eq!(
+                    found.as_ref().map(|caps| caps.len()),
+                    expected.as_ref().map(|caps| caps.len())
+                );
+                for group in 0..regex.captures_len() {
+                    assert_eq!(
+                        found.as_ref().and_then(|caps| caps.get(group)),
+                        expected.as_ref().and_then(|caps| caps.get(group)),
+                        "{pattern} on {haystack:?}"
+                    );
+                }
+                for start in (0..=haystack.len()).filter(|&start| haystack.is_char_boundary(start))
+                {
+                    for anchored in [Anchored::No, Anchored::Yes] {
+                        let expected = regex.find_at(haystack, start, anchored);
+                        assert_eq!(
+                            fixed.find_at(haystack, start, anchored),
+                            expected,
+                            "{pattern} on {haystack:?} at {start}"
+                        );
+                    }
+                }
+            }
+        }
+        let longest: FixedRegex<32, 2> = RegexBuilder::new("foo|foobar")
+            .match_kind(MatchKind::LeftmostLongest)
+            .build_fixed()
+            .unwrap();
+        assert_eq!(longest.find("a foobar").unwrap().as_str(), "foobar");
+    }
+
+    #[test]
+    fn fails_to_build_what_doesnt_fit() {
+        let err = RegexBuilder::new(r"\w+")
+            .build_fixed::<64, 2>()
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::StateCapacityExceeded(64));
+        assert!(RegexBuilder::new(r"\w+")
+            .unicode(false)
+            .build_fixed::<64, 2>()
+            .is_ok());
+        let err = RegexBuilder::new("(a)(b)")
+            .build_fixed::<64, 4>()
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::SlotCapacityExceeded(4));
+        let err = RegexBuilder::new(r"(a)\1")
+            .build_fixed::<64, 4>()
+            .unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::Unsupported("backreferences".into()));
+    }
+}
@@ -1,95 +1,1167 @@
-use core::ops::{Index, IndexMut};
-use std::fmt::Display;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::Cell;
+use core::fmt::Display;
+use core::mem;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
 
-#[derive(Debug, PartialEq, Copy, Clone)]
+use crate::charset::CharSet;
+use crate::collections::Set;
+use crate::hir::{case_variants, is_word_character, Hir};
+use crate::json::Automaton;
+use crate::parser::Sign;
+use crate::ErrorKind;
+
+/// A state of the automaton a pattern is compiled to, as passed to a
+/// [`Tracer`](crate::Tracer). It displays as `(7)` or `(S)`.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub enum State {
-    Failed,
+    /// Waiting to consume a character at the node with this index.
     Intermediate(usize),
+    /// The pattern has matched.
     Success,
 }
 
 impl Display for State {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let s = match self {
-            Self::Failed => "F".to_owned(),
-            Self::Success => "S".to_owned(),
-            Self::Intermediate(x) => format!("{x}"),
-        };
-        write!(f, "{}", format!("({s})"))
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Success => write!(f, "(S)"),
+            Self::Intermediate(idx) => write!(f, "({idx})"),
+        }
     }
 }
 
-pub struct Transitions([State; 256]);
+pub enum Node {
+    // Consumes a single character from the set, then moves on.
+    Consume(CharSet, usize),
+    // Moves to each of the given nodes without consuming any input.
+    Epsilon(Vec<usize>),
+    // Records the current position in a capture slot, then moves on.
+    Save(usize, usize),
+    // Moves on if the automaton matches (or, if negative, doesn't match) at
+    // the current position, without consuming any input.
+    Lookahead(Sign, Box<NFA>, usize),
+    // Consumes the text last captured by the given group.
+    Backref(usize, usize),
+    // Consumes what the automaton matches first at the current position, and
+    // never tries any of its other matches.
+    Atomic(Box<NFA>, usize),
+    // Moves to the first node if the given group has captured anything, and
+    // to the second one otherwise.
+    Conditional(usize, usize, usize),
+    // Moves on if the assertion holds at the current position.
+    Assert(Look, usize),
+    // Reaching this node means the pattern has matched.
+    Match,
+}
 
-impl Default for Transitions {
-    fn default() -> Self {
-        Self([State::Failed; 256])
+impl Node {
+    // The same node, with the nodes it moves to shifted by `offset`.
+    fn shifted(self, offset: usize) -> Self {
+        match self {
+            Node::Consume(set, next) => Node::Consume(set, next + offset),
+            Node::Epsilon(targets) => Node::Epsilon(targets.iter().map(|t| t + offset).collect()),
+            Node::Save(slot, next) => Node::Save(slot, next + offset),
+            Node::Lookahead(sign, sub, next) => Node::Lookahead(sign, sub, next + offset),
+            Node::Backref(group, next) => Node::Backref(group, next + offset),
+            Node::Atomic(sub, next) => Node::Atomic(sub, next + offset),
+            Node::Conditional(group, yes, no) => {
+                Node::Conditional(group, yes + offset, no + offset)
+            }
+            Node::Assert(look, next) => Node::Assert(look, next + offset),
+            Node::Match => Node::Match,
+        }
     }
 }
 
-impl Index<usize> for Transitions {
-    type Output = State;
-    fn index(&self, idx: usize) -> &Self::Output {
-        &self.0[idx]
+// Zero-width assertions about the current position in the haystack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Look {
+    // The beginning of the haystack.
+    Start,
+    // The end of the haystack.
+    End,
+    // The beginning of the haystack or of a line.
+    StartLine,
+    // The end of the haystack or of a line.
+    EndLine,
+    // Like `StartLine` and `EndLine`, but lines may also end with `\r`, and
+    // `\r\n` counts as a single line terminator.
+    StartCrlfLine,
+    EndCrlfLine,
+    // The end of the haystack, or right before a newline ending it.
+    EndBeforeNewline,
+    // The position at which the search started.
+    SearchStart,
+    // Between a word character and something else, or anywhere else. The
+    // flag restricts word characters to ASCII.
+    WordBoundary(bool),
+    NotWordBoundary(bool),
+}
+
+impl Look {
+    // The haystack is the UTF-8 encoding of a string, and `position` lies on
+    // a character boundary. The search began at `start`.
+    pub fn holds(self, haystack: &[u8], start: usize, position: usize) -> bool {
+        let before = haystack.get(..position).unwrap_or_default();
+        let after = haystack.get(position..).unwrap_or_default();
+        match self {
+            Self::Start => position == 0,
+            Self::End => after.is_empty(),
+            Self::StartLine => position == 0 || before.ends_with(b"\n"),
+            Self::EndLine => after.is_empty() || after.starts_with(b"\n"),
+            Self::StartCrlfLine => {
+                position == 0
+                    || before.ends_with(b"\n")
+                    || before.ends_with(b"\r") && !after.starts_with(b"\n")
+            }
+            Self::EndCrlfLine => {
+                after.is_empty()
+                    || after.starts_with(b"\r")
+                    || after.starts_with(b"\n") && !before.ends_with(b"\r")
+            }
+            Self::EndBeforeNewline => after.is_empty() || after == b"\n",
+            Self::SearchStart => position == start,
+            Self::WordBoundary(ascii) => is_word_end(before, ascii) != is_word_start(after, ascii),
+            Self::NotWordBoundary(ascii) => {
+                is_word_end(before, ascii) == is_word_start(after, ascii)
+            }
+        }
     }
+    // The assertion that holds at the same position of the reversed
+    // haystack. `\Z`, `\G` and the line anchors aware of `\r\n` have no
+    // such counterpart, and stay as they are.
+    pub fn reversed(self) -> Self {
+        match self {
+            Self::Start => Self::End,
+            Self::End => Self::Start,
+            Self::StartLine => Self::EndLine,
+            Self::EndLine => Self::StartLine,
+            look => look,
+        }
+    }
+}
+
+// Whether `bytes` ends or starts with a word character.
+fn is_word_end(bytes: &[u8], ascii: bool) -> bool {
+    let start = (bytes.len().saturating_sub(4)..bytes.len())
+        .rev()
+        .find(|&idx| bytes[idx] & 0xC0 != 0x80)
+        .unwrap_or(bytes.len());
+    is_word_start(&bytes[start..], ascii)
+}
+
+fn is_word_start(bytes: &[u8], ascii: bool) -> bool {
+    let len = match bytes.first() {
+        None => return false,
+        Some(0..=0x7F) => 1,
+        Some(0x80..=0xDF) => 2,
+        Some(0xE0..=0xEF) => 3,
+        Some(_) => 4,
+    };
+    bytes
+        .get(..len)
+        .and_then(|encoded| core::str::from_utf8(encoded).ok())
+        .and_then(|encoded| encoded.chars().next())
+        .is_some_and(|c| is_word_character(c, ascii))
 }
 
-impl IndexMut<usize> for Transitions {
-    fn index_mut(&mut self, idx: usize) -> &mut Self::Output {
-        &mut self.0[idx]
+// How many more steps backtracking may take in a search, and until when
+// the search may run. The budget is shared by all the automata the search
+// runs, including those of lookaheads and atomic groups. Once it has run
+// out, backtracking finds nothing, and the search reports that it gave up.
+pub struct Budget {
+    steps: Cell<usize>,
+    exhausted: Cell<bool>,
+    // There is no clock without the standard library.
+    #[cfg(feature = "std")]
+    deadline: Option<Deadline>,
+}
+
+#[cfg(feature = "std")]
+struct Deadline {
+    at: Instant,
+    timeout: Duration,
+    // Reading the clock takes a while, so it is only read every
+    // `CLOCK_INTERVAL` checks.
+    checks: Cell<usize>,
+    expired: Cell<bool>,
+}
+
+#[cfg(feature = "std")]
+impl Deadline {
+    const CLOCK_INTERVAL: usize = 256;
+
+    fn expired(&self) -> bool {
+        let checks = self.checks.get();
+        self.checks.set(checks.wrapping_add(1));
+        if checks.is_multiple_of(Self::CLOCK_INTERVAL) && !self.expired.get() {
+            self.expired.set(Instant::now() >= self.at);
+        }
+        self.expired.get()
     }
 }
 
-pub struct FSM {
-    graph: Vec<Transitions>,
+impl Budget {
+    pub const DEFAULT_STEPS: usize = 1_000_000;
+
+    pub fn new(steps: usize) -> Self {
+        Self {
+            steps: Cell::new(steps),
+            exhausted: Cell::new(false),
+            #[cfg(feature = "std")]
+            deadline: None,
+        }
+    }
+    // Also gives up once `timeout` has passed from now.
+    #[cfg(feature = "std")]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        let deadline = Instant::now().checked_add(timeout).map(|at| Deadline {
+            at,
+            timeout,
+            checks: Cell::new(0),
+            expired: Cell::new(false),
+        });
+        Self { deadline, ..self }
+    }
+    pub fn exhausted(&self) -> bool {
+        self.exhausted.get() || self.timed_out().is_some()
+    }
+    // The timeout, if the search has run past it.
+    pub fn timed_out(&self) -> Option<Duration> {
+        #[cfg(feature = "std")]
+        if let Some(deadline) = self
+            .deadline
+            .as_ref()
+            .filter(|deadline| deadline.expired.get())
+        {
+            return Some(deadline.timeout);
+        }
+        None
+    }
+    // Whether the search has run past its deadline. The engines that
+    // don't backtrack check this once per character.
+    pub fn expired(&self) -> bool {
+        #[cfg(feature = "std")]
+        if let Some(deadline) = &self.deadline {
+            return deadline.expired();
+        }
+        false
+    }
+    // Takes a step, unless there are none left.
+    fn spend(&self) -> bool {
+        if self.expired() {
+            return false;
+        }
+        match self.steps.get() {
+            0 => {
+                self.exhausted.set(true);
+                false
+            }
+            steps => {
+                self.steps.set(steps - 1);
+                true
+            }
+        }
+    }
+}
+
+// Where the literal suffix tells searches that matches end, so that they can
+// look for it first and run the reversed pattern back from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReverseSuffix {
+    // Every match ends at the end of the haystack.
+    AtEnd,
+    // No match contains the suffix anywhere but at its end, so no match
+    // starts before one ending at an earlier occurrence of it.
+    FirstEnding,
+}
+
+// A Thompson automaton: each node either consumes one character or forks
+// into several nodes without consuming anything. It is what patterns are
+// compiled to, and what all engines work on: the state-set simulation, the
+// Pike VM and the backtracker below, and the byte automaton derived from it.
+#[allow(clippy::upper_case_acronyms)]
+pub struct NFA {
+    graph: Vec<Node>,
+    start: usize,
+    // Entry into the branches that may start after the beginning of the
+    // input, i.e. those not anchored with `^`.
+    restart: Option<usize>,
+    // The number of capture groups, not counting the whole match.
+    groups: usize,
+    // The number of capture slots up to and including those of the last
+    // group in this automaton.
+    slots: usize,
+    // Whether matching needs to look at the input around the current
+    // position, so that it can't work on a stream of characters.
+    looks_ahead: bool,
+    // Whether the pattern has backreferences, so that it must be matched by
+    // backtracking.
+    backtracks: bool,
+    // Whether searches prefer the longest match at the leftmost position
+    // over the one the alternatives are tried first.
+    longest: bool,
+    // The approximate number of bytes taken up.
+    size: usize,
 }
 
-impl FSM {
-    pub fn new() -> Self {
-        Self { graph: Vec::new() }
+// Capture positions of a thread: the start and end of the whole match,
+// followed by the start and end of every group.
+pub type Slots = Vec<Option<usize>>;
+
+impl NFA {
+    // Compiles `hir`, failing if the automaton would take up more than
+    // `limit` bytes. Errors come with the element of the outermost
+    // repetition that was being compiled, if any, numbered as in
+    // `Hir::Repeat`.
+    pub fn compile(hir: &Hir, limit: usize) -> Result<Self, (ErrorKind, Option<usize>)> {
+        let mut compiler = Compiler {
+            nfa: Self::empty(),
+            limit,
+            element: None,
+        };
+        compiler
+            .compile_all(hir)
+            .map_err(|kind| (kind, compiler.element))?;
+        Ok(compiler.nfa)
     }
-    pub fn final_state(&self) -> usize {
-        self.graph.len()
+    fn empty() -> Self {
+        Self {
+            graph: Vec::new(),
+            start: 0,
+            restart: None,
+            groups: 0,
+            slots: 0,
+            looks_ahead: false,
+            backtracks: false,
+            longest: false,
+            size: 0,
+        }
     }
-    pub fn push(&mut self, ts: Transitions) {
-        self.graph.push(ts);
+}
+
+// Builds an `NFA` back to front, so that every part knows the node it has
+// to continue with, keeping track of its size.
+struct Compiler {
+    nfa: NFA,
+    limit: usize,
+    // The element of the outermost repetition being compiled.
+    element: Option<usize>,
+}
+
+impl Compiler {
+    fn compile_all(&mut self, hir: &Hir) -> Result<(), ErrorKind> {
+        self.nfa.groups = hir.groups();
+        self.nfa.slots = 2 * (1 + self.nfa.groups);
+        self.push(Node::Match)?;
+        let branches = match hir {
+            Hir::Alternation(branches) => branches.as_slice(),
+            hir => core::slice::from_ref(hir),
+        };
+        let mut entries = Vec::with_capacity(branches.len());
+        let mut unanchored = Vec::new();
+        for branch in branches {
+            let entry = self.compile(branch, 0)?;
+            entries.push(entry);
+            // A branch that only matches at the beginning of the haystack
+            // isn't tried anywhere else.
+            if !branch.is_anchored() {
+                unanchored.push(entry);
+            }
+        }
+        if !unanchored.is_empty() {
+            self.nfa.restart = Some(self.push(Node::Epsilon(unanchored))?);
+        }
+        self.nfa.start = self.push(Node::Epsilon(entries))?;
+        Ok(())
     }
-    pub fn next(&self, state: State, char: char) -> State {
-        match state {
-            State::Failed => State::Failed,
-            State::Success => State::Success,
-            State::Intermediate(idx) => {
-                if idx == self.final_state() {
-                    return State::Success;
-                }
-                let nxt = self.graph[idx][char as usize];
-                if let State::Intermediate(n) = nxt {
-                    if n == self.final_state() {
-                        return State::Success;
-                    }
+    fn push(&mut self, node: Node) -> Result<usize, ErrorKind> {
+        self.nfa.size += mem::size_of::<Node>()
+            + match &node {
+                Node::Consume(set, _) => mem::size_of_val(set.ranges()),
+                Node::Epsilon(targets) => targets.len() * mem::size_of::<usize>(),
+                Node::Lookahead(_, sub, _) | Node::Atomic(sub, _) => sub.size,
+                _ => 0,
+            };
+        if self.nfa.size > self.limit {
+            return Err(ErrorKind::CompiledTooBig(self.limit));
+        }
+        self.nfa.graph.push(node);
+        Ok(self.nfa.graph.len() - 1)
+    }
+    fn assert(&mut self, look: Look, next: usize) -> Result<usize, ErrorKind> {
+        if !matches!(look, Look::Start | Look::SearchStart) {
+            self.nfa.looks_ahead = true;
+        }
+        self.push(Node::Assert(look, next))
+    }
+    // Compiles `hir` into an automaton of its own, continuing with the match
+    // node 0, for nodes that run it separately. Its size counts towards the
+    // limit of the whole pattern, and it has all the capture slots.
+    fn compile_nested(&mut self, hir: &Hir) -> Result<NFA, ErrorKind> {
+        let mut compiler = Compiler {
+            nfa: NFA::empty(),
+            limit: self.limit - self.nfa.size,
+            element: self.element,
+        };
+        compiler.nfa.slots = self.nfa.slots;
+        let start = compiler
+            .push(Node::Match)
+            .and_then(|_| compiler.compile(hir, 0));
+        self.element = compiler.element;
+        compiler.nfa.start = start.map_err(|kind| match kind {
+            ErrorKind::CompiledTooBig(_) => ErrorKind::CompiledTooBig(self.limit),
+            kind => kind,
+        })?;
+        Ok(compiler.nfa)
+    }
+    fn compile(&mut self, hir: &Hir, next: usize) -> Result<usize, ErrorKind> {
+        match hir {
+            Hir::Empty => Ok(next),
+            Hir::Class(set) => self.push(Node::Consume(set.clone(), next)),
+            Hir::Look(look) => self.assert(*look, next),
+            Hir::Concat(parts) => parts
+                .iter()
+                .rev()
+                .try_fold(next, |next, part| self.compile(part, next)),
+            Hir::Alternation(branches) => {
+                let targets = branches
+                    .iter()
+                    .map(|branch| self.compile(branch, next))
+                    .collect::<Result<_, _>>()?;
+                self.push(Node::Epsilon(targets))
+            }
+            Hir::Repeat {
+                sub,
+                min,
+                max,
+                greedy,
+                element,
+            } => {
+                let outermost = self.element.is_none();
+                if outermost {
+                    self.element = *element;
+                }
+                let entry = self.compile_repeat(sub, *min, *max, !greedy, next)?;
+                if outermost {
+                    self.element = None;
+                }
+                Ok(entry)
+            }
+            Hir::Capture(group, sub) => {
+                let end = self.push(Node::Save(2 * group + 1, next))?;
+                let start = self.compile(sub, end)?;
+                self.push(Node::Save(2 * group, start))
+            }
+            Hir::Lookahead(sign, sub) => {
+                self.nfa.looks_ahead = true;
+                let sub = self.compile_nested(sub)?;
+                self.push(Node::Lookahead(*sign, Box::new(sub), next))
+            }
+            Hir::Atomic(sub) => {
+                self.nfa.backtracks = true;
+                let sub = self.compile_nested(sub)?;
+                self.push(Node::Atomic(Box::new(sub), next))
+            }
+            Hir::Backreference(group) => {
+                self.nfa.backtracks = true;
+                self.push(Node::Backref(*group, next))
+            }
+            Hir::Conditional(group, yes, no) => {
+                self.nfa.backtracks = true;
+                let yes = self.compile(yes, next)?;
+                let no = self.compile(no, next)?;
+                self.push(Node::Conditional(*group, yes, no))
+            }
+        }
+    }
+    // Counted repetitions are compiled by copying the part: `min` times, and
+    // then either a loop or another `max - min` times, each of which may be
+    // skipped to what follows.
+    fn compile_repeat(
+        &mut self,
+        sub: &Hir,
+        min: usize,
+        max: Option<usize>,
+        lazy: bool,
+        next: usize,
+    ) -> Result<usize, ErrorKind> {
+        let mut entry = match (min, max) {
+            (1, None) => return Ok(self.compile_loop(sub, next, lazy)?.1),
+            (min, None) => {
+                // An iteration matching the empty string leads back to the
+                // loop node, which the simulation has then visited at that
+                // position already, so that the way out of the loop would
+                // be lost: `(?:a*?)*` would match `a` in `a` rather than
+                // nothing. Parts that may match the empty string are entered
+                // as in `(?:x+)?` instead, which reaches the loop node only
+                // after an iteration.
+                let (repeat, atom) = self.compile_loop(sub, next, lazy)?;
+                let entry = match sub.can_be_empty() {
+                    true => self.push(Node::Epsilon(prioritize(atom, next, lazy)))?,
+                    false => repeat,
+                };
+                if min == 0 {
+                    return Ok(entry);
+                }
+                entry
+            }
+            (min, Some(max)) => {
+                let mut optional = next;
+                for _ in min..max {
+                    let atom = self.compile(sub, optional)?;
+                    optional = self.push(Node::Epsilon(prioritize(atom, next, lazy)))?;
                 }
-                nxt
+                optional
             }
+        };
+        for _ in 0..min {
+            entry = self.compile(sub, entry)?;
         }
+        Ok(entry)
+    }
+    // Returns the loop node and the entry into the repeated part.
+    fn compile_loop(
+        &mut self,
+        sub: &Hir,
+        next: usize,
+        lazy: bool,
+    ) -> Result<(usize, usize), ErrorKind> {
+        let repeat = self.push(Node::Epsilon(Vec::new()))?;
+        let atom = self.compile(sub, repeat)?;
+        self.nfa.graph[repeat] = Node::Epsilon(prioritize(atom, next, lazy));
+        Ok((repeat, atom))
     }
 }
 
-impl Display for FSM {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let lines: Vec<String> = (0..=255usize)
-            .filter(|&j| self.graph.iter().map(|v| v[j]).any(|x| x != State::Failed))
-            .map(|j| {
-                format!(
-                    "{j:03} => {ts}",
-                    ts = self
-                        .graph
+impl NFA {
+    pub fn graph(&self) -> &[Node] {
+        &self.graph
+    }
+    // The entry into all branches, and into those that may start after the
+    // beginning of the input.
+    pub fn entries(&self) -> (usize, Option<usize>) {
+        (self.start, self.restart)
+    }
+    pub fn looks_ahead(&self) -> bool {
+        self.looks_ahead
+    }
+    pub fn backtracks(&self) -> bool {
+        self.backtracks
+    }
+    pub fn longest(&self) -> bool {
+        self.longest
+    }
+    pub fn set_longest(&mut self, longest: bool) {
+        self.longest = longest;
+    }
+    // The literal that every match starts with, if there is one and the
+    // pattern may match anywhere, so that searches can skip ahead to where
+    // it occurs. Assertions on the way don't change where a match can
+    // start, except for `\G`, which depends on where the search did.
+    pub fn literal_prefix(&self) -> Option<String> {
+        self.restart?;
+        if self.uses_search_start() {
+            return None;
+        }
+        let mut prefix = String::new();
+        let mut idx = self.start;
+        // Bounded, since empty loops may lead back to where they began.
+        for _ in 0..self.graph.len() {
+            idx = match &self.graph[idx] {
+                Node::Epsilon(targets) if targets.len() == 1 => targets[0],
+                Node::Save(_, next) | Node::Assert(_, next) => *next,
+                Node::Consume(set, next) => match set.ranges() {
+                    &[(from, to)] if from == to => {
+                        prefix.push(from);
+                        *next
+                    }
+                    _ => break,
+                },
+                _ => break,
+            };
+        }
+        Some(prefix).filter(|prefix| !prefix.is_empty())
+    }
+    // The ways the prefix every match starts with can be written, if it has
+    // letters that match in any case, like `(?i)get `, as long as there are
+    // at most `limit` of them. A prefix with more is cut short.
+    pub fn prefix_variants(&self, limit: usize) -> Option<Vec<String>> {
+        self.restart?;
+        if self.uses_search_start() {
+            return None;
+        }
+        let mut variants = vec![String::new()];
+        let mut idx = self.start;
+        for _ in 0..self.graph.len() {
+            idx = match &self.graph[idx] {
+                Node::Epsilon(targets) if targets.len() == 1 => targets[0],
+                Node::Save(_, next) | Node::Assert(_, next) => *next,
+                Node::Consume(set, next) => {
+                    let Some(&(first, _)) = set.ranges().first() else {
+                        break;
+                    };
+                    if !set.is_subset(&CharSet::new([(first, first)]).close_over(case_variants)) {
+                        break;
+                    }
+                    let chars: Vec<char> = set
+                        .ranges()
                         .iter()
-                        .map(|v| format!("{:>5}", v[j]))
-                        .collect::<Vec<String>>()
-                        .join(" ")
-                )
+                        .flat_map(|&(from, to)| from..=to)
+                        .collect();
+                    if variants.len() * chars.len() > limit {
+                        break;
+                    }
+                    variants = variants
+                        .iter()
+                        .flat_map(|variant| chars.iter().map(move |&c| format!("{variant}{c}")))
+                        .collect();
+                    *next
+                }
+                _ => break,
+            };
+        }
+        Some(variants).filter(|variants| variants.len() > 1)
+    }
+    // The literal that every match ends with, if there is one, found by
+    // walking back from the match node for as long as there is only one way
+    // to get there.
+    pub fn literal_suffix(&self) -> Option<String> {
+        let suffix: String = self
+            .suffix_nodes()?
+            .iter()
+            .rev()
+            .filter_map(|&idx| match &self.graph[idx] {
+                Node::Consume(set, _) => Some(set.ranges()[0].0),
+                _ => None,
             })
             .collect();
-        write!(f, "{}", lines.join("\n"))
+        Some(suffix).filter(|suffix| !suffix.is_empty())
+    }
+    // Whether searches can find where matches start by running the reversed
+    // pattern back from where the literal suffix occurs, and how.
+    pub fn reverse_suffix(&self) -> Option<ReverseSuffix> {
+        let nodes = self.suffix_nodes()?;
+        let first = self.literal_suffix()?.chars().next()?;
+        if nodes
+            .iter()
+            .any(|&idx| matches!(self.graph[idx], Node::Assert(Look::End, _)))
+        {
+            return Some(ReverseSuffix::AtEnd);
+        }
+        // The suffix is only consumed right before the match ends if nothing
+        // on the way there leads elsewhere, and then nothing before it may
+        // consume its first character.
+        let head = nodes
+            .iter()
+            .rposition(|&idx| matches!(self.graph[idx], Node::Consume(..)))?;
+        let (chain, _) = nodes.split_at(head + 1);
+        if chain
+            .iter()
+            .any(|&idx| matches!(&self.graph[idx], Node::Epsilon(targets) if targets.len() > 1))
+        {
+            return None;
+        }
+        let earlier = self.graph.iter().enumerate().any(|(idx, node)| {
+            matches!(node, Node::Consume(set, _) if set.contains(first) && !chain.contains(&idx))
+        });
+        (!earlier).then_some(ReverseSuffix::FirstEnding)
+    }
+    // The nodes on the only way to the match node, nearest first, up to the
+    // first one consuming more than a single character.
+    fn suffix_nodes(&self) -> Option<Vec<usize>> {
+        let mut predecessors = vec![Vec::new(); self.graph.len()];
+        for (idx, node) in self.graph.iter().enumerate() {
+            match node {
+                Node::Consume(_, next) | Node::Save(_, next) | Node::Assert(_, next) => {
+                    predecessors[*next].push(idx)
+                }
+                Node::Epsilon(targets) => {
+                    for &target in targets {
+                        predecessors[target].push(idx);
+                    }
+                }
+                // Anything else ends the walk, as if it were a second way in.
+                Node::Lookahead(_, _, next) | Node::Backref(_, next) | Node::Atomic(_, next) => {
+                    predecessors[*next].extend([idx, idx])
+                }
+                Node::Conditional(_, yes, no) => {
+                    predecessors[*yes].extend([idx, idx]);
+                    predecessors[*no].extend([idx, idx]);
+                }
+                Node::Match => {}
+            }
+        }
+        let mut nodes = Vec::new();
+        let mut idx = self
+            .graph
+            .iter()
+            .position(|node| matches!(node, Node::Match))?;
+        for _ in 0..self.graph.len() {
+            // The automaton is also entered at its entries.
+            if idx == self.start || Some(idx) == self.restart {
+                break;
+            }
+            let &[previous] = predecessors[idx].as_slice() else {
+                break;
+            };
+            if let Node::Consume(set, _) = &self.graph[previous] {
+                if !matches!(set.ranges(), &[(from, to)] if from == to) {
+                    break;
+                }
+            }
+            nodes.push(previous);
+            idx = previous;
+        }
+        Some(nodes)
+    }
+    fn uses_search_start(&self) -> bool {
+        self.graph.iter().any(|node| match node {
+            Node::Assert(Look::SearchStart, _) => true,
+            Node::Atomic(sub, _) => sub.uses_search_start(),
+            _ => false,
+        })
+    }
+    // Combines `nfas` into a single automaton that matches wherever one of
+    // them does, and returns it along with the match node of each. Their
+    // capture slots overlap, so the result can't be used for captures.
+    pub fn union(nfas: Vec<NFA>) -> (Self, Vec<usize>) {
+        let mut union = Self::empty();
+        let mut ends = Vec::new();
+        let mut starts = Vec::new();
+        let mut restarts = Vec::new();
+        for nfa in nfas {
+            let offset = union.graph.len();
+            let end = nfa
+                .graph
+                .iter()
+                .position(|node| matches!(node, Node::Match));
+            ends.extend(end.map(|end| end + offset));
+            starts.push(nfa.start + offset);
+            restarts.extend(nfa.restart.map(|restart| restart + offset));
+            union.looks_ahead |= nfa.looks_ahead;
+            union.backtracks |= nfa.backtracks;
+            union.size += nfa.size;
+            union
+                .graph
+                .extend(nfa.graph.into_iter().map(|node| node.shifted(offset)));
+        }
+        if !restarts.is_empty() {
+            union.graph.push(Node::Epsilon(restarts));
+            union.restart = Some(union.graph.len() - 1);
+        }
+        union.graph.push(Node::Epsilon(starts));
+        union.start = union.graph.len() - 1;
+        (union, ends)
+    }
+    // The state-set functions take the haystack and the position after the
+    // character just consumed, which lookaheads need to peek at the input.
+    // Without lookaheads, both may be left empty.
+    pub fn start(&self, haystack: &str, budget: &Budget) -> Vec<State> {
+        self.closure([self.start], haystack, 0, budget)
+    }
+    // The states reached from the nodes with the given indices at `position`.
+    pub fn resume(
+        &self,
+        nodes: &[usize],
+        haystack: &str,
+        position: usize,
+        budget: &Budget,
+    ) -> Vec<State> {
+        self.closure(nodes.iter().copied(), haystack, position, budget)
+    }
+    pub fn next(
+        &self,
+        states: &[State],
+        char: char,
+        haystack: &str,
+        position: usize,
+        budget: &Budget,
+    ) -> Vec<State> {
+        self.closure(self.targets(states, char), haystack, position, budget)
+    }
+    // Like `next`, but also begins a new match attempt after `char`.
+    pub fn next_unanchored(
+        &self,
+        states: &[State],
+        char: char,
+        haystack: &str,
+        position: usize,
+        budget: &Budget,
+    ) -> Vec<State> {
+        self.closure(
+            self.targets(states, char).chain(self.restart),
+            haystack,
+            position,
+            budget,
+        )
+    }
+    fn targets<'a>(&'a self, states: &'a [State], char: char) -> impl Iterator<Item = usize> + 'a {
+        states
+            .iter()
+            .filter_map(move |&state| self.target(state, char))
+    }
+    fn target(&self, state: State, char: char) -> Option<usize> {
+        match state {
+            State::Intermediate(idx) => match &self.graph[idx] {
+                Node::Consume(set, next) if set.contains(char) => Some(*next),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+    // The leftmost-first match at or after `start`, however long it takes.
+    #[cfg(test)]
+    pub fn find_at(&self, haystack: &str, start: usize) -> Option<(usize, usize)> {
+        let slots = self.captures_at(haystack, start, 2, false, &Budget::new(usize::MAX))?;
+        Some((slots[0]?, slots[1]?))
+    }
+    // Leftmost-first search for the first match, filling in the first
+    // `slots` capture slots. Threads are kept in priority order like in a
+    // Pike VM: once a thread matches, all threads of lower priority are
+    // dropped, while those of higher priority may still go on to find a
+    // preferred match. When searching for the longest match, only threads
+    // that started later are dropped, since threads are ordered by where
+    // they started first.
+    //
+    // The search begins at byte offset `start`, where only unanchored
+    // branches can match unless it is the beginning of the haystack. An
+    // anchored search only tries to match right at `start`.
+    pub fn captures_at(
+        &self,
+        haystack: &str,
+        start: usize,
+        slots: usize,
+        anchored: bool,
+        budget: &Budget,
+    ) -> Option<Slots> {
+        if self.backtracks {
+            return self.backtrack(haystack, start, slots, anchored, false, budget);
+        }
+        // The longest match needs to know where threads started.
+        let wanted = slots;
+        let slots = if self.longest { slots.max(2) } else { slots };
+        let spawn = |idx: usize, position: usize| {
+            let mut captured = vec![None; slots];
+            if let Some(first) = captured.first_mut() {
+                *first = Some(position);
+            }
+            (idx, captured)
+        };
+        let restart = if anchored { None } else { self.restart };
+        let mut matched = None;
+        let entry = if start == 0 || anchored {
+            Some(self.start)
+        } else {
+            restart
+        };
+        let mut threads = self.threads(
+            entry.map(|idx| spawn(idx, start)),
+            haystack,
+            start,
+            start,
+            budget,
+        );
+        let steps = haystack[start..]
+            .char_indices()
+            .map(|(idx, c)| (c, start + idx + c.len_utf8()));
+        let mut position = start;
+        for (char, next) in steps {
+            if budget.expired() {
+                return None;
+            }
+            if let Some(idx) = threads.iter().position(|t| t.0 == State::Success) {
+                let (_, mut captured) = threads.remove(idx);
+                finish(Some(&mut captured), position);
+                if self.longest {
+                    threads.retain(|t| t.1[0] <= captured[0]);
+                } else {
+                    threads.truncate(idx);
+                }
+                matched = Some(captured);
+            }
+            if threads.is_empty() && matched.is_some() {
+                break;
+            }
+            let mut targets: Vec<(usize, Slots)> = threads
+                .into_iter()
+                .filter_map(|(state, captured)| Some((self.target(state, char)?, captured)))
+                .collect();
+            if matched.is_none() {
+                targets.extend(restart.map(|idx| spawn(idx, next)));
+            }
+            threads = self.threads(targets, haystack, start, next, budget);
+            position = next;
+        }
+        if let Some((_, captured)) = threads.into_iter().find(|t| t.0 == State::Success) {
+            matched = Some(captured);
+            finish(matched.as_mut(), position);
+        }
+        if let Some(captured) = matched.as_mut() {
+            captured.truncate(wanted);
+        }
+        matched
+    }
+    // Backtracking search for patterns with backreferences, which the Pike
+    // VM can't simulate. It tries the same start positions as
+    // `captures_at`, and alternatives in priority order, so that the first
+    // match found is the leftmost-first one. When searching for the longest
+    // match, all alternatives at a start position are tried. With `full`,
+    // only matches that reach the end of the haystack count. Every node
+    // visited takes a step from `budget`, and once it has run out, there is
+    // no match.
+    pub fn backtrack(
+        &self,
+        haystack: &str,
+        start: usize,
+        slots: usize,
+        anchored: bool,
+        full: bool,
+        budget: &Budget,
+    ) -> Option<Slots> {
+        let mut position = start;
+        loop {
+            let entry = if position == 0 || anchored {
+                Some(self.start)
+            } else {
+                self.restart
+            };
+            if let Some(entry) = entry {
+                // Backreferences need all groups, even if the caller doesn't.
+                let mut captured = vec![None; self.slots.max(slots).max(2)];
+                captured[0] = Some(position);
+                let found =
+                    self.backtrack_from(haystack, entry, start, position, captured, full, budget);
+                if let Some(mut found) = found {
+                    found.truncate(slots);
+                    return Some(found);
+                }
+            }
+            if anchored || self.restart.is_none() || budget.exhausted() {
+                return None;
+            }
+            position += haystack[position..].chars().next()?.len_utf8();
+        }
+    }
+    // Tries to match from byte offset `from` in a search that began at
+    // `start`.
+    #[allow(clippy::too_many_arguments)]
+    fn backtrack_from(
+        &self,
+        haystack: &str,
+        entry: usize,
+        start: usize,
+        from: usize,
+        captured: Slots,
+        full: bool,
+        budget: &Budget,
+    ) -> Option<Slots> {
+        let end = haystack.len();
+        // A state reached twice leads to the same outcome both times, and
+        // skipping it also stops loops that make no progress.
+        let mut seen = Set::new();
+        let mut longest: Option<Slots> = None;
+        let mut stack = vec![(entry, from, captured)];
+        while let Some((idx, position, mut captured)) = stack.pop() {
+            if !seen.insert((idx, position, captured.clone())) {
+                continue;
+            }
+            if !budget.spend() {
+                return None;
+            }
+            match &self.graph[idx] {
+                Node::Consume(set, next) => {
+                    if let Some(c) = haystack[position..]
+                        .chars()
+                        .next()
+                        .filter(|&c| set.contains(c))
+                    {
+                        stack.push((*next, position + c.len_utf8(), captured));
+                    }
+                }
+                Node::Epsilon(targets) => stack.extend(
+                    targets
+                        .iter()
+                        .rev()
+                        .map(|&t| (t, position, captured.clone())),
+                ),
+                Node::Save(slot, next) => {
+                    captured[*slot] = Some(position);
+                    stack.push((*next, position, captured));
+                }
+                Node::Lookahead(sign, sub, next) => {
+                    let found = sub.captures_at(haystack, position, captured.len(), true, budget);
+                    if look(*sign, found, &mut captured) {
+                        stack.push((*next, position, captured));
+                    }
+                }
+                Node::Atomic(sub, next) => {
+                    let found = sub.backtrack_from(
+                        haystack,
+                        sub.start,
+                        start,
+                        position,
+                        captured.clone(),
+                        false,
+                        budget,
+                    );
+                    if let Some(mut found) = found {
+                        // The end of the match doesn't belong in the slots yet.
+                        let end = mem::replace(&mut found[1], captured[1]);
+                        stack.push((*next, end.unwrap_or(position), found));
+                    }
+                }
+                Node::Conditional(group, yes, no) => {
+                    let next = if captured[2 * group + 1].is_some() {
+                        yes
+                    } else {
+                        no
+                    };
+                    stack.push((*next, position, captured));
+                }
+                Node::Backref(group, next) => {
+                    if let (Some(from), Some(to)) = (captured[2 * group], captured[2 * group + 1]) {
+                        let text = &haystack[from..to];
+                        if haystack
+                            .get(position..)
+                            .is_some_and(|rest| rest.starts_with(text))
+                        {
+                            stack.push((*next, position + text.len(), captured));
+                        }
+                    }
+                }
+                Node::Assert(look, next) => {
+                    if look.holds(haystack.as_bytes(), start, position) {
+                        stack.push((*next, position, captured));
+                    }
+                }
+                Node::Match => {
+                    if !full || position == end {
+                        captured[1] = Some(position);
+                        if !self.longest {
+                            return Some(captured);
+                        }
+                        if longest.as_ref().is_none_or(|found| found[1] < captured[1]) {
+                            longest = Some(captured);
+                        }
+                    }
+                }
+            }
+        }
+        longest
+    }
+    fn closure<I: IntoIterator<Item = usize>>(
+        &self,
+        nodes: I,
+        haystack: &str,
+        position: usize,
+        budget: &Budget,
+    ) -> Vec<State> {
+        self.threads(
+            nodes.into_iter().map(|idx| (idx, Vec::new())),
+            haystack,
+            0,
+            position,
+            budget,
+        )
+        .into_iter()
+        .map(|(state, _)| state)
+        .collect()
+    }
+    // Follows all epsilon moves from `position`, keeping the consuming nodes
+    // and the success state in the order they were reached, along with the
+    // captures of the thread that reached them first. The search began at
+    // `start`.
+    fn threads<I: IntoIterator<Item = (usize, Slots)>>(
+        &self,
+        nodes: I,
+        haystack: &str,
+        start: usize,
+        position: usize,
+        budget: &Budget,
+    ) -> Vec<(State, Slots)> {
+        let mut visited = vec![false; self.graph.len()];
+        let mut threads = Vec::new();
+        let mut stack: Vec<(usize, Slots)> = nodes.into_iter().collect();
+        stack.reverse();
+        while let Some((idx, mut captured)) = stack.pop() {
+            if visited[idx] {
+                continue;
+            }
+            visited[idx] = true;
+            match &self.graph[idx] {
+                Node::Consume(..) => threads.push((State::Intermediate(idx), captured)),
+                Node::Epsilon(targets) => {
+                    stack.extend(targets.iter().rev().map(|&t| (t, captured.clone())))
+                }
+                Node::Save(slot, next) => {
+                    if let Some(value) = captured.get_mut(*slot) {
+                        *value = Some(position);
+                    }
+                    stack.push((*next, captured));
+                }
+                Node::Lookahead(sign, sub, next) => {
+                    let found = sub.captures_at(haystack, position, captured.len(), true, budget);
+                    if look(*sign, found, &mut captured) {
+                        stack.push((*next, captured));
+                    }
+                }
+                Node::Assert(look, next) => {
+                    if look.holds(haystack.as_bytes(), start, position) {
+                        stack.push((*next, captured));
+                    }
+                }
+                // Only reachable when backtracking.
+                Node::Backref(..) | Node::Atomic(..) | Node::Conditional(..) => {}
+                Node::Match => threads.push((State::Success, captured)),
+            }
+        }
+        threads
+    }
+}
+
+// Decides whether a lookahead passes, given what its automaton found at the
+// current position. Groups inside a positive lookahead keep their spans.
+fn look(sign: Sign, found: Option<Slots>, captured: &mut Slots) -> bool {
+    match (sign, found) {
+        (Sign::Inclusive, Some(found)) => {
+            for (slot, value) in found.into_iter().enumerate().skip(2) {
+                if value.is_some() {
+                    captured[slot] = value;
+                }
+            }
+            true
+        }
+        (Sign::Exclusive, None) => true,
+        _ => false,
+    }
+}
+
+// The branches of a quantifier, in the order they are tried: the element
+// first if greedy, what follows it first if lazy.
+fn prioritize(atom: usize, next: usize, lazy: bool) -> Vec<usize> {
+    if lazy {
+        vec![next, atom]
+    } else {
+        vec![atom, next]
+    }
+}
+
+// Records where a successful thread's match ends.
+fn finish(matched: Option<&mut Slots>, position: usize) {
+    if let Some(end) = matched.and_then(|captured| captured.get_mut(1)) {
+        *end = Some(position);
+    }
+}
+
+impl Display for NFA {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Automaton::new(self).fmt(f)
     }
 }
@@ -0,0 +1,261 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::charset::CharSet;
+use crate::fsm::{Node, Slots, NFA};
+
+/// A source of random numbers for [`Regex::generate`](crate::Regex::generate).
+/// Closures returning random `u64`s are one, so that any generator can be
+/// plugged in, like `|| rng.next_u64()` with the `rand` crate.
+///
+/// ```
+/// use regexrs::Regex;
+///
+/// // A xorshift generator, good enough for tests.
+/// let mut state = 0x2545_f491_4f6c_dd1d_u64;
+/// let mut rng = || {
+///     state ^= state << 13;
+///     state ^= state >> 7;
+///     state ^= state << 17;
+///     state
+/// };
+/// let regex = Regex::new(r"[a-z]{3,8}@example\.(?:com|org)").unwrap();
+/// let email = regex.generate(&mut rng, 32).unwrap();
+/// assert!(regex.is_full_match(&email));
+/// ```
+pub trait Rng {
+    /// Returns the next random number.
+    fn next_u64(&mut self) -> u64;
+}
+
+impl<F: FnMut() -> u64> Rng for F {
+    fn next_u64(&mut self) -> u64 {
+        self()
+    }
+}
+
+// How many strings are built at most before giving up on finding one that
+// matches.
+pub(crate) const ATTEMPTS: usize = 64;
+
+// Builds a string along a random path through the automaton, of at most
+// `max_len` characters. Only the lengths of the paths are planned ahead, so
+// the string may still fail assertions and lookaheads on the way.
+pub(crate) fn walk<R: Rng + ?Sized>(nfa: &NFA, rng: &mut R, max_len: usize) -> Option<String> {
+    let mut walk = Walk {
+        rng,
+        generated: String::new(),
+        len: 0,
+        captured: Vec::new(),
+        steps: (max_len + 1).saturating_mul(nfa.graph().len()),
+    };
+    walk.from(nfa, nfa.entries().0, max_len)?;
+    Some(walk.generated)
+}
+
+struct Walk<'r, R: ?Sized> {
+    rng: &'r mut R,
+    generated: String,
+    // The number of characters generated.
+    len: usize,
+    // Where groups start and end in the generated string.
+    captured: Slots,
+    // How many more nodes may be visited, which stops walks that keep
+    // going around loops that don't consume anything.
+    steps: usize,
+}
+
+impl<R: Rng + ?Sized> Walk<'_, R> {
+    // Walks from the node with index `idx` to the end of the automaton,
+    // until `limit` characters have been generated in total at most.
+    fn from(&mut self, nfa: &NFA, mut idx: usize, limit: usize) -> Option<()> {
+        let distances = distances(nfa);
+        loop {
+            self.steps = self.steps.checked_sub(1)?;
+            match &nfa.graph()[idx] {
+                Node::Consume(set, next) => {
+                    let c = self.pick(set)?;
+                    self.generated.push(c);
+                    self.len += 1;
+                    idx = *next;
+                }
+                Node::Epsilon(targets) => {
+                    let remaining = limit.checked_sub(self.len)?;
+                    let reachable: Vec<usize> = targets
+                        .iter()
+                        .copied()
+                        .filter(|&target| distances[target] <= remaining)
+                        .collect();
+                    idx = *reachable.get(self.below(reachable.len())?)?;
+                }
+                Node::Save(slot, next) => {
+                    if self.captured.len() <= *slot {
+                        self.captured.resize(slot + 1, None);
+                    }
+                    self.captured[*slot] = Some(self.generated.len());
+                    idx = *next;
+                }
+                Node::Lookahead(_, _, next) | Node::Assert(_, next) => idx = *next,
+                Node::Backref(group, next) => {
+                    let span = self.captured.get(2 * group..2 * group + 2);
+                    if let Some(&[Some(from), Some(to)]) = span {
+                        let text = String::from(&self.generated[from..to]);
+                        self.len += text.chars().count();
+                        self.generated.push_str(&text);
+                    }
+                    idx = *next;
+                }
+                Node::Atomic(sub, next) => {
+                    let limit = limit.checked_sub(distances[*next])?;
+                    self.from(sub, sub.entries().0, limit)?;
+                    idx = *next;
+                }
+                Node::Conditional(group, yes, no) => {
+                    let captured = self
+                        .captured
+                        .get(2 * group + 1)
+                        .is_some_and(Option::is_some);
+                    idx = if captured { *yes } else { *no };
+                }
+                Node::Match => return (self.len <= limit).then_some(()),
+            }
+        }
+    }
+    // A random character from the set, or none if it is empty.
+    fn pick(&mut self, set: &CharSet) -> Option<char> {
+        let sizes: Vec<u64> = set
+            .ranges()
+            .iter()
+            .map(|&(from, to)| to as u64 - from as u64 + 1)
+            .collect();
+        let total = sizes.iter().sum::<u64>();
+        loop {
+            let mut offset = self.rng.next_u64() % total.max(1);
+            for (&(from, _), &size) in set.ranges().iter().zip(&sizes) {
+                if offset < size {
+                    // Ranges may span the surrogates, which aren't characters.
+                    match char::from_u32(from as u32 + offset as u32) {
+                        Some(c) => return Some(c),
+                        None => break,
+                    }
+                }
+                offset -= size;
+            }
+            if total == 0 {
+                return None;
+            }
+        }
+    }
+    // A random number below `n`, if there is one.
+    fn below(&mut self, n: usize) -> Option<usize> {
+        (n > 0).then(|| (self.rng.next_u64() % n as u64) as usize)
+    }
+}
+
+// The least number of characters consumed on the way from each node to the
+// end of the automaton, not counting backreferences, or `usize::MAX` if it
+// can't be reached.
+fn distances(nfa: &NFA) -> Vec<usize> {
+    let graph = nfa.graph();
+    let atomic: Vec<usize> = graph
+        .iter()
+        .map(|node| match node {
+            Node::Atomic(sub, _) => distances(sub)[sub.entries().0],
+            _ => 0,
+        })
+        .collect();
+    let mut distances = vec![usize::MAX; graph.len()];
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // Nodes mostly lead to later ones, so going backwards settles most
+        // of the distances in the first round.
+        for (idx, node) in graph.iter().enumerate().rev() {
+            let distance = match node {
+                Node::Consume(_, next) => distances[*next].saturating_add(1),
+                Node::Epsilon(targets) => targets
+                    .iter()
+                    .map(|&t| distances[t])
+                    .min()
+                    .unwrap_or(usize::MAX),
+                Node::Save(_, next)
+                | Node::Lookahead(_, _, next)
+                | Node::Backref(_, next)
+                | Node::Assert(_, next) => distances[*next],
+                Node::Atomic(_, next) => atomic[idx].saturating_add(distances[*next]),
+                Node::Conditional(_, yes, no) => distances[*yes].min(distances[*no]),
+                Node::Match => 0,
+            };
+            if distance < distances[idx] {
+                distances[idx] = distance;
+                changed = true;
+            }
+        }
+    }
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Regex;
+
+    fn xorshift(mut state: u64) -> impl FnMut() -> u64 {
+        move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        }
+    }
+
+    #[test]
+    fn generates_full_matches() {
+        let mut rng = xorshift(0x9e37_79b9_7f4a_7c15);
+        let patterns = [
+            r"\d{3}-\d{4}",
+            "(a|bc)*d",
+            r"(?i)(\w)\1",
+            "(?>a+)b",
+            "(a)?(?(1)b|c)",
+            r"(?-u)(?=\w*\d)\w{4}",
+            r"^\bfoo\b$",
+            "(?s).*",
+            "",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            for _ in 0..20 {
+                let generated = regex.generate(&mut rng, 10).unwrap();
+                assert!(
+                    regex.is_full_match(&generated),
+                    "{pattern} generated {generated:?}"
+                );
+                assert!(
+                    generated.chars().count() <= 10,
+                    "{pattern} generated {generated:?}"
+                );
+            }
+        }
+        // Both alternatives come up.
+        let regex = Regex::new("a|b").unwrap();
+        let generated: Vec<String> = (0..20)
+            .map(|_| regex.generate(&mut rng, 1).unwrap())
+            .collect();
+        assert!(generated.iter().any(|s| s == "a") && generated.iter().any(|s| s == "b"));
+    }
+
+    #[test]
+    fn generates_nothing_when_nothing_fits() {
+        let mut rng = xorshift(1);
+        for (pattern, max_len) in [
+            ("a{5}", 4),
+            ("[^\\d\\D]", 10),
+            ("a(?!b)b", 10),
+            (r"(a)\1", 1),
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            assert_eq!(regex.generate(&mut rng, max_len), None, "{pattern}");
+        }
+    }
+}
@@ -0,0 +1,1246 @@
+// Character properties for segmenting text into extended grapheme clusters
+// as described in Unicode Standard Annex #29, generated from the Unicode
+// Character Database, version 17.0. Hangul syllables of type LV and LVT are
+// worked out arithmetically instead, see `hir::hangul_syllables`.
+
+pub const PREPEND: &[(char, char)] = &[
+    ('\u{600}', '\u{605}'),
+    ('\u{6dd}', '\u{6dd}'),
+    ('\u{70f}', '\u{70f}'),
+    ('\u{890}', '\u{891}'),
+    ('\u{8e2}', '\u{8e2}'),
+    ('\u{d4e}', '\u{d4e}'),
+    ('\u{110bd}', '\u{110bd}'),
+    ('\u{110cd}', '\u{110cd}'),
+    ('\u{111c2}', '\u{111c3}'),
+    ('\u{113d1}', '\u{113d1}'),
+    ('\u{1193f}', '\u{1193f}'),
+    ('\u{11941}', '\u{11941}'),
+    ('\u{11a84}', '\u{11a89}'),
+    ('\u{11d46}', '\u{11d46}'),
+    ('\u{11f02}', '\u{11f02}'),
+];
+
+pub const CONTROL: &[(char, char)] = &[
+    ('\u{0}', '\u{9}'),
+    ('\u{b}', '\u{c}'),
+    ('\u{e}', '\u{1f}'),
+    ('\u{7f}', '\u{9f}'),
+    ('\u{ad}', '\u{ad}'),
+    ('\u{61c}', '\u{61c}'),
+    ('\u{180e}', '\u{180e}'),
+    ('\u{200b}', '\u{200b}'),
+    ('\u{200e}', '\u{200f}'),
+    ('\u{2028}', '\u{202e}'),
+    ('\u{2060}', '\u{206f}'),
+    ('\u{feff}', '\u{feff}'),
+    ('\u{fff0}', '\u{fffb}'),
+    ('\u{13430}', '\u{1343f}'),
+    ('\u{1bca0}', '\u{1bca3}'),
+    ('\u{1d173}', '\u{1d17a}'),
+    ('\u{e0000}', '\u{e001f}'),
+    ('\u{e0080}', '\u{e00ff}'),
+    ('\u{e01f0}', '\u{e0fff}'),
+];
+
+pub const EXTEND: &[(char, char)] = &[
+    ('\u{300}', '\u{36f}'),
+    ('\u{483}', '\u{489}'),
+    ('\u{591}', '\u{5bd}'),
+    ('\u{5bf}', '\u{5bf}'),
+    ('\u{5c1}', '\u{5c2}'),
+    ('\u{5c4}', '\u{5c5}'),
+    ('\u{5c7}', '\u{5c7}'),
+    ('\u{610}', '\u{61a}'),
+    ('\u{64b}', '\u{65f}'),
+    ('\u{670}', '\u{670}'),
+    ('\u{6d6}', '\u{6dc}'),
+    ('\u{6df}', '\u{6e4}'),
+    ('\u{6e7}', '\u{6e8}'),
+    ('\u{6ea}', '\u{6ed}'),
+    ('\u{711}', '\u{711}'),
+    ('\u{730}', '\u{74a}'),
+    ('\u{7a6}', '\u{7b0}'),
+    ('\u{7eb}', '\u{7f3}'),
+    ('\u{7fd}', '\u{7fd}'),
+    ('\u{816}', '\u{819}'),
+    ('\u{81b}', '\u{823}'),
+    ('\u{825}', '\u{827}'),
+    ('\u{829}', '\u{82d}'),
+    ('\u{859}', '\u{85b}'),
+    ('\u{897}', '\u{89f}'),
+    ('\u{8ca}', '\u{8e1}'),
+    ('\u{8e3}', '\u{902}'),
+    ('\u{93a}', '\u{93a}'),
+    ('\u{93c}', '\u{93c}'),
+    ('\u{941}', '\u{948}'),
+    ('\u{94d}', '\u{94d}'),
+    ('\u{951}', '\u{957}'),
+    ('\u{962}', '\u{963}'),
+    ('\u{981}', '\u{981}'),
+    ('\u{9bc}', '\u{9bc}'),
+    ('\u{9be}', '\u{9be}'),
+    ('\u{9c1}', '\u{9c4}'),
+    ('\u{9cd}', '\u{9cd}'),
+    ('\u{9d7}', '\u{9d7}'),
+    ('\u{9e2}', '\u{9e3}'),
+    ('\u{9fe}', '\u{9fe}'),
+    ('\u{a01}', '\u{a02}'),
+    ('\u{a3c}', '\u{a3c}'),
+    ('\u{a41}', '\u{a42}'),
+    ('\u{a47}', '\u{a48}'),
+    ('\u{a4b}', '\u{a4d}'),
+    ('\u{a51}', '\u{a51}'),
+    ('\u{a70}', '\u{a71}'),
+    ('\u{a75}', '\u{a75}'),
+    ('\u{a81}', '\u{a82}'),
+    ('\u{abc}', '\u{abc}'),
+    ('\u{ac1}', '\u{ac5}'),
+    ('\u{ac7}', '\u{ac8}'),
+    ('\u{acd}', '\u{acd}'),
+    ('\u{ae2}', '\u{ae3}'),
+    ('\u{afa}', '\u{aff}'),
+    ('\u{b01}', '\u{b01}'),
+    ('\u{b3c}', '\u{b3c}'),
+    ('\u{b3e}', '\u{b3f}'),
+    ('\u{b41}', '\u{b44}'),
+    ('\u{b4d}', '\u{b4d}'),
+    ('\u{b55}', '\u{b57}'),
+    ('\u{b62}', '\u{b63}'),
+    ('\u{b82}', '\u{b82}'),
+    ('\u{bbe}', '\u{bbe}'),
+    ('\u{bc0}', '\u{bc0}'),
+    ('\u{bcd}', '\u{bcd}'),
+    ('\u{bd7}', '\u{bd7}'),
+    ('\u{c00}', '\u{c00}'),
+    ('\u{c04}', '\u{c04}'),
+    ('\u{c3c}', '\u{c3c}'),
+    ('\u{c3e}', '\u{c40}'),
+    ('\u{c46}', '\u{c48}'),
+    ('\u{c4a}', '\u{c4d}'),
+    ('\u{c55}', '\u{c56}'),
+    ('\u{c62}', '\u{c63}'),
+    ('\u{c81}', '\u{c81}'),
+    ('\u{cbc}', '\u{cbc}'),
+    ('\u{cbf}', '\u{cc0}'),
+    ('\u{cc2}', '\u{cc2}'),
+    ('\u{cc6}', '\u{cc8}'),
+    ('\u{cca}', '\u{ccd}'),
+    ('\u{cd5}', '\u{cd6}'),
+    ('\u{ce2}', '\u{ce3}'),
+    ('\u{d00}', '\u{d01}'),
+    ('\u{d3b}', '\u{d3c}'),
+    ('\u{d3e}', '\u{d3e}'),
+    ('\u{d41}', '\u{d44}'),
+    ('\u{d4d}', '\u{d4d}'),
+    ('\u{d57}', '\u{d57}'),
+    ('\u{d62}', '\u{d63}'),
+    ('\u{d81}', '\u{d81}'),
+    ('\u{dca}', '\u{dca}'),
+    ('\u{dcf}', '\u{dcf}'),
+    ('\u{dd2}', '\u{dd4}'),
+    ('\u{dd6}', '\u{dd6}'),
+    ('\u{ddf}', '\u{ddf}'),
+    ('\u{e31}', '\u{e31}'),
+    ('\u{e34}', '\u{e3a}'),
+    ('\u{e47}', '\u{e4e}'),
+    ('\u{eb1}', '\u{eb1}'),
+    ('\u{eb4}', '\u{ebc}'),
+    ('\u{ec8}', '\u{ece}'),
+    ('\u{f18}', '\u{f19}'),
+    ('\u{f35}', '\u{f35}'),
+    ('\u{f37}', '\u{f37}'),
+    ('\u{f39}', '\u{f39}'),
+    ('\u{f71}', '\u{f7e}'),
+    ('\u{f80}', '\u{f84}'),
+    ('\u{f86}', '\u{f87}'),
+    ('\u{f8d}', '\u{f97}'),
+    ('\u{f99}', '\u{fbc}'),
+    ('\u{fc6}', '\u{fc6}'),
+    ('\u{102d}', '\u{1030}'),
+    ('\u{1032}', '\u{1037}'),
+    ('\u{1039}', '\u{103a}'),
+    ('\u{103d}', '\u{103e}'),
+    ('\u{1058}', '\u{1059}'),
+    ('\u{105e}', '\u{1060}'),
+    ('\u{1071}', '\u{1074}'),
+    ('\u{1082}', '\u{1082}'),
+    ('\u{1085}', '\u{1086}'),
+    ('\u{108d}', '\u{108d}'),
+    ('\u{109d}', '\u{109d}'),
+    ('\u{135d}', '\u{135f}'),
+    ('\u{1712}', '\u{1715}'),
+    ('\u{1732}', '\u{1734}'),
+    ('\u{1752}', '\u{1753}'),
+    ('\u{1772}', '\u{1773}'),
+    ('\u{17b4}', '\u{17b5}'),
+    ('\u{17b7}', '\u{17bd}'),
+    ('\u{17c6}', '\u{17c6}'),
+    ('\u{17c9}', '\u{17d3}'),
+    ('\u{17dd}', '\u{17dd}'),
+    ('\u{180b}', '\u{180d}'),
+    ('\u{180f}', '\u{180f}'),
+    ('\u{1885}', '\u{1886}'),
+    ('\u{18a9}', '\u{18a9}'),
+    ('\u{1920}', '\u{1922}'),
+    ('\u{1927}', '\u{1928}'),
+    ('\u{1932}', '\u{1932}'),
+    ('\u{1939}', '\u{193b}'),
+    ('\u{1a17}', '\u{1a18}'),
+    ('\u{1a1b}', '\u{1a1b}'),
+    ('\u{1a56}', '\u{1a56}'),
+    ('\u{1a58}', '\u{1a5e}'),
+    ('\u{1a60}', '\u{1a60}'),
+    ('\u{1a62}', '\u{1a62}'),
+    ('\u{1a65}', '\u{1a6c}'),
+    ('\u{1a73}', '\u{1a7c}'),
+    ('\u{1a7f}', '\u{1a7f}'),
+    ('\u{1ab0}', '\u{1add}'),
+    ('\u{1ae0}', '\u{1aeb}'),
+    ('\u{1b00}', '\u{1b03}'),
+    ('\u{1b34}', '\u{1b3d}'),
+    ('\u{1b42}', '\u{1b44}'),
+    ('\u{1b6b}', '\u{1b73}'),
+    ('\u{1b80}', '\u{1b81}'),
+    ('\u{1ba2}', '\u{1ba5}'),
+    ('\u{1ba8}', '\u{1bad}'),
+    ('\u{1be6}', '\u{1be6}'),
+    ('\u{1be8}', '\u{1be9}'),
+    ('\u{1bed}', '\u{1bed}'),
+    ('\u{1bef}', '\u{1bf3}'),
+    ('\u{1c2c}', '\u{1c33}'),
+    ('\u{1c36}', '\u{1c37}'),
+    ('\u{1cd0}', '\u{1cd2}'),
+    ('\u{1cd4}', '\u{1ce0}'),
+    ('\u{1ce2}', '\u{1ce8}'),
+    ('\u{1ced}', '\u{1ced}'),
+    ('\u{1cf4}', '\u{1cf4}'),
+    ('\u{1cf8}', '\u{1cf9}'),
+    ('\u{1dc0}', '\u{1dff}'),
+    ('\u{200c}', '\u{200c}'),
+    ('\u{20d0}', '\u{20f0}'),
+    ('\u{2cef}', '\u{2cf1}'),
+    ('\u{2d7f}', '\u{2d7f}'),
+    ('\u{2de0}', '\u{2dff}'),
+    ('\u{302a}', '\u{302f}'),
+    ('\u{3099}', '\u{309a}'),
+    ('\u{a66f}', '\u{a672}'),
+    ('\u{a674}', '\u{a67d}'),
+    ('\u{a69e}', '\u{a69f}'),
+    ('\u{a6f0}', '\u{a6f1}'),
+    ('\u{a802}', '\u{a802}'),
+    ('\u{a806}', '\u{a806}'),
+    ('\u{a80b}', '\u{a80b}'),
+    ('\u{a825}', '\u{a826}'),
+    ('\u{a82c}', '\u{a82c}'),
+    ('\u{a8c4}', '\u{a8c5}'),
+    ('\u{a8e0}', '\u{a8f1}'),
+    ('\u{a8ff}', '\u{a8ff}'),
+    ('\u{a926}', '\u{a92d}'),
+    ('\u{a947}', '\u{a951}'),
+    ('\u{a953}', '\u{a953}'),
+    ('\u{a980}', '\u{a982}'),
+    ('\u{a9b3}', '\u{a9b3}'),
+    ('\u{a9b6}', '\u{a9b9}'),
+    ('\u{a9bc}', '\u{a9bd}'),
+    ('\u{a9c0}', '\u{a9c0}'),
+    ('\u{a9e5}', '\u{a9e5}'),
+    ('\u{aa29}', '\u{aa2e}'),
+    ('\u{aa31}', '\u{aa32}'),
+    ('\u{aa35}', '\u{aa36}'),
+    ('\u{aa43}', '\u{aa43}'),
+    ('\u{aa4c}', '\u{aa4c}'),
+    ('\u{aa7c}', '\u{aa7c}'),
+    ('\u{aab0}', '\u{aab0}'),
+    ('\u{aab2}', '\u{aab4}'),
+    ('\u{aab7}', '\u{aab8}'),
+    ('\u{aabe}', '\u{aabf}'),
+    ('\u{aac1}', '\u{aac1}'),
+    ('\u{aaec}', '\u{aaed}'),
+    ('\u{aaf6}', '\u{aaf6}'),
+    ('\u{abe5}', '\u{abe5}'),
+    ('\u{abe8}', '\u{abe8}'),
+    ('\u{abed}', '\u{abed}'),
+    ('\u{fb1e}', '\u{fb1e}'),
+    ('\u{fe00}', '\u{fe0f}'),
+    ('\u{fe20}', '\u{fe2f}'),
+    ('\u{ff9e}', '\u{ff9f}'),
+    ('\u{101fd}', '\u{101fd}'),
+    ('\u{102e0}', '\u{102e0}'),
+    ('\u{10376}', '\u{1037a}'),
+    ('\u{10a01}', '\u{10a03}'),
+    ('\u{10a05}', '\u{10a06}'),
+    ('\u{10a0c}', '\u{10a0f}'),
+    ('\u{10a38}', '\u{10a3a}'),
+    ('\u{10a3f}', '\u{10a3f}'),
+    ('\u{10ae5}', '\u{10ae6}'),
+    ('\u{10d24}', '\u{10d27}'),
+    ('\u{10d69}', '\u{10d6d}'),
+    ('\u{10eab}', '\u{10eac}'),
+    ('\u{10efa}', '\u{10eff}'),
+    ('\u{10f46}', '\u{10f50}'),
+    ('\u{10f82}', '\u{10f85}'),
+    ('\u{11001}', '\u{11001}'),
+    ('\u{11038}', '\u{11046}'),
+    ('\u{11070}', '\u{11070}'),
+    ('\u{11073}', '\u{11074}'),
+    ('\u{1107f}', '\u{11081}'),
+    ('\u{110b3}', '\u{110b6}'),
+    ('\u{110b9}', '\u{110ba}'),
+    ('\u{110c2}', '\u{110c2}'),
+    ('\u{11100}', '\u{11102}'),
+    ('\u{11127}', '\u{1112b}'),
+    ('\u{1112d}', '\u{11134}'),
+    ('\u{11173}', '\u{11173}'),
+    ('\u{11180}', '\u{11181}'),
+    ('\u{111b6}', '\u{111be}'),
+    ('\u{111c0}', '\u{111c0}'),
+    ('\u{111c9}', '\u{111cc}'),
+    ('\u{111cf}', '\u{111cf}'),
+    ('\u{1122f}', '\u{11231}'),
+    ('\u{11234}', '\u{11237}'),
+    ('\u{1123e}', '\u{1123e}'),
+    ('\u{11241}', '\u{11241}'),
+    ('\u{112df}', '\u{112df}'),
+    ('\u{112e3}', '\u{112ea}'),
+    ('\u{11300}', '\u{11301}'),
+    ('\u{1133b}', '\u{1133c}'),
+    ('\u{1133e}', '\u{1133e}'),
+    ('\u{11340}', '\u{11340}'),
+    ('\u{1134d}', '\u{1134d}'),
+    ('\u{11357}', '\u{11357}'),
+    ('\u{11366}', '\u{1136c}'),
+    ('\u{11370}', '\u{11374}'),
+    ('\u{113b8}', '\u{113b8}'),
+    ('\u{113bb}', '\u{113c0}'),
+    ('\u{113c2}', '\u{113c2}'),
+    ('\u{113c5}', '\u{113c5}'),
+    ('\u{113c7}', '\u{113c9}'),
+    ('\u{113ce}', '\u{113d0}'),
+    ('\u{113d2}', '\u{113d2}'),
+    ('\u{113e1}', '\u{113e2}'),
+    ('\u{11438}', '\u{1143f}'),
+    ('\u{11442}', '\u{11444}'),
+    ('\u{11446}', '\u{11446}'),
+    ('\u{1145e}', '\u{1145e}'),
+    ('\u{114b0}', '\u{114b0}'),
+    ('\u{114b3}', '\u{114b8}'),
+    ('\u{114ba}', '\u{114ba}'),
+    ('\u{114bd}', '\u{114bd}'),
+    ('\u{114bf}', '\u{114c0}'),
+    ('\u{114c2}', '\u{114c3}'),
+    ('\u{115af}', '\u{115af}'),
+    ('\u{115b2}', '\u{115b5}'),
+    ('\u{115bc}', '\u{115bd}'),
+    ('\u{115bf}', '\u{115c0}'),
+    ('\u{115dc}', '\u{115dd}'),
+    ('\u{11633}', '\u{1163a}'),
+    ('\u{1163d}', '\u{1163d}'),
+    ('\u{1163f}', '\u{11640}'),
+    ('\u{116ab}', '\u{116ab}'),
+    ('\u{116ad}', '\u{116ad}'),
+    ('\u{116b0}', '\u{116b7}'),
+    ('\u{1171d}', '\u{1171d}'),
+    ('\u{1171f}', '\u{1171f}'),
+    ('\u{11722}', '\u{11725}'),
+    ('\u{11727}', '\u{1172b}'),
+    ('\u{1182f}', '\u{11837}'),
+    ('\u{11839}', '\u{1183a}'),
+    ('\u{11930}', '\u{11930}'),
+    ('\u{1193b}', '\u{1193e}'),
+    ('\u{11943}', '\u{11943}'),
+    ('\u{119d4}', '\u{119d7}'),
+    ('\u{119da}', '\u{119db}'),
+    ('\u{119e0}', '\u{119e0}'),
+    ('\u{11a01}', '\u{11a0a}'),
+    ('\u{11a33}', '\u{11a38}'),
+    ('\u{11a3b}', '\u{11a3e}'),
+    ('\u{11a47}', '\u{11a47}'),
+    ('\u{11a51}', '\u{11a56}'),
+    ('\u{11a59}', '\u{11a5b}'),
+    ('\u{11a8a}', '\u{11a96}'),
+    ('\u{11a98}', '\u{11a99}'),
+    ('\u{11b60}', '\u{11b60}'),
+    ('\u{11b62}', '\u{11b64}'),
+    ('\u{11b66}', '\u{11b66}'),
+    ('\u{11c30}', '\u{11c36}'),
+    ('\u{11c38}', '\u{11c3d}'),
+    ('\u{11c3f}', '\u{11c3f}'),
+    ('\u{11c92}', '\u{11ca7}'),
+    ('\u{11caa}', '\u{11cb0}'),
+    ('\u{11cb2}', '\u{11cb3}'),
+    ('\u{11cb5}', '\u{11cb6}'),
+    ('\u{11d31}', '\u{11d36}'),
+    ('\u{11d3a}', '\u{11d3a}'),
+    ('\u{11d3c}', '\u{11d3d}'),
+    ('\u{11d3f}', '\u{11d45}'),
+    ('\u{11d47}', '\u{11d47}'),
+    ('\u{11d90}', '\u{11d91}'),
+    ('\u{11d95}', '\u{11d95}'),
+    ('\u{11d97}', '\u{11d97}'),
+    ('\u{11ef3}', '\u{11ef4}'),
+    ('\u{11f00}', '\u{11f01}'),
+    ('\u{11f36}', '\u{11f3a}'),
+    ('\u{11f40}', '\u{11f42}'),
+    ('\u{11f5a}', '\u{11f5a}'),
+    ('\u{13440}', '\u{13440}'),
+    ('\u{13447}', '\u{13455}'),
+    ('\u{1611e}', '\u{16129}'),
+    ('\u{1612d}', '\u{1612f}'),
+    ('\u{16af0}', '\u{16af4}'),
+    ('\u{16b30}', '\u{16b36}'),
+    ('\u{16f4f}', '\u{16f4f}'),
+    ('\u{16f8f}', '\u{16f92}'),
+    ('\u{16fe4}', '\u{16fe4}'),
+    ('\u{16ff0}', '\u{16ff1}'),
+    ('\u{1bc9d}', '\u{1bc9e}'),
+    ('\u{1cf00}', '\u{1cf2d}'),
+    ('\u{1cf30}', '\u{1cf46}'),
+    ('\u{1d165}', '\u{1d169}'),
+    ('\u{1d16d}', '\u{1d172}'),
+    ('\u{1d17b}', '\u{1d182}'),
+    ('\u{1d185}', '\u{1d18b}'),
+    ('\u{1d1aa}', '\u{1d1ad}'),
+    ('\u{1d242}', '\u{1d244}'),
+    ('\u{1da00}', '\u{1da36}'),
+    ('\u{1da3b}', '\u{1da6c}'),
+    ('\u{1da75}', '\u{1da75}'),
+    ('\u{1da84}', '\u{1da84}'),
+    ('\u{1da9b}', '\u{1da9f}'),
+    ('\u{1daa1}', '\u{1daaf}'),
+    ('\u{1e000}', '\u{1e006}'),
+    ('\u{1e008}', '\u{1e018}'),
+    ('\u{1e01b}', '\u{1e021}'),
+    ('\u{1e023}', '\u{1e024}'),
+    ('\u{1e026}', '\u{1e02a}'),
+    ('\u{1e08f}', '\u{1e08f}'),
+    ('\u{1e130}', '\u{1e136}'),
+    ('\u{1e2ae}', '\u{1e2ae}'),
+    ('\u{1e2ec}', '\u{1e2ef}'),
+    ('\u{1e4ec}', '\u{1e4ef}'),
+    ('\u{1e5ee}', '\u{1e5ef}'),
+    ('\u{1e6e3}', '\u{1e6e3}'),
+    ('\u{1e6e6}', '\u{1e6e6}'),
+    ('\u{1e6ee}', '\u{1e6ef}'),
+    ('\u{1e6f5}', '\u{1e6f5}'),
+    ('\u{1e8d0}', '\u{1e8d6}'),
+    ('\u{1e944}', '\u{1e94a}'),
+    ('\u{1f3fb}', '\u{1f3ff}'),
+    ('\u{e0020}', '\u{e007f}'),
+    ('\u{e0100}', '\u{e01ef}'),
+];
+
+pub const SPACING_MARK: &[(char, char)] = &[
+    ('\u{903}', '\u{903}'),
+    ('\u{93b}', '\u{93b}'),
+    ('\u{93e}', '\u{940}'),
+    ('\u{949}', '\u{94c}'),
+    ('\u{94e}', '\u{94f}'),
+    ('\u{982}', '\u{983}'),
+    ('\u{9bf}', '\u{9c0}'),
+    ('\u{9c7}', '\u{9c8}'),
+    ('\u{9cb}', '\u{9cc}'),
+    ('\u{a03}', '\u{a03}'),
+    ('\u{a3e}', '\u{a40}'),
+    ('\u{a83}', '\u{a83}'),
+    ('\u{abe}', '\u{ac0}'),
+    ('\u{ac9}', '\u{ac9}'),
+    ('\u{acb}', '\u{acc}'),
+    ('\u{b02}', '\u{b03}'),
+    ('\u{b40}', '\u{b40}'),
+    ('\u{b47}', '\u{b48}'),
+    ('\u{b4b}', '\u{b4c}'),
+    ('\u{bbf}', '\u{bbf}'),
+    ('\u{bc1}', '\u{bc2}'),
+    ('\u{bc6}', '\u{bc8}'),
+    ('\u{bca}', '\u{bcc}'),
+    ('\u{c01}', '\u{c03}'),
+    ('\u{c41}', '\u{c44}'),
+    ('\u{c82}', '\u{c83}'),
+    ('\u{cbe}', '\u{cbe}'),
+    ('\u{cc1}', '\u{cc1}'),
+    ('\u{cc3}', '\u{cc4}'),
+    ('\u{cf3}', '\u{cf3}'),
+    ('\u{d02}', '\u{d03}'),
+    ('\u{d3f}', '\u{d40}'),
+    ('\u{d46}', '\u{d48}'),
+    ('\u{d4a}', '\u{d4c}'),
+    ('\u{d82}', '\u{d83}'),
+    ('\u{dd0}', '\u{dd1}'),
+    ('\u{dd8}', '\u{dde}'),
+    ('\u{df2}', '\u{df3}'),
+    ('\u{e33}', '\u{e33}'),
+    ('\u{eb3}', '\u{eb3}'),
+    ('\u{f3e}', '\u{f3f}'),
+    ('\u{f7f}', '\u{f7f}'),
+    ('\u{1031}', '\u{1031}'),
+    ('\u{103b}', '\u{103c}'),
+    ('\u{1056}', '\u{1057}'),
+    ('\u{1084}', '\u{1084}'),
+    ('\u{17b6}', '\u{17b6}'),
+    ('\u{17be}', '\u{17c5}'),
+    ('\u{17c7}', '\u{17c8}'),
+    ('\u{1923}', '\u{1926}'),
+    ('\u{1929}', '\u{192b}'),
+    ('\u{1930}', '\u{1931}'),
+    ('\u{1933}', '\u{1938}'),
+    ('\u{1a19}', '\u{1a1a}'),
+    ('\u{1a55}', '\u{1a55}'),
+    ('\u{1a57}', '\u{1a57}'),
+    ('\u{1a6d}', '\u{1a72}'),
+    ('\u{1b04}', '\u{1b04}'),
+    ('\u{1b3e}', '\u{1b41}'),
+    ('\u{1b82}', '\u{1b82}'),
+    ('\u{1ba1}', '\u{1ba1}'),
+    ('\u{1ba6}', '\u{1ba7}'),
+    ('\u{1be7}', '\u{1be7}'),
+    ('\u{1bea}', '\u{1bec}'),
+    ('\u{1bee}', '\u{1bee}'),
+    ('\u{1c24}', '\u{1c2b}'),
+    ('\u{1c34}', '\u{1c35}'),
+    ('\u{1ce1}', '\u{1ce1}'),
+    ('\u{1cf7}', '\u{1cf7}'),
+    ('\u{a823}', '\u{a824}'),
+    ('\u{a827}', '\u{a827}'),
+    ('\u{a880}', '\u{a881}'),
+    ('\u{a8b4}', '\u{a8c3}'),
+    ('\u{a952}', '\u{a952}'),
+    ('\u{a983}', '\u{a983}'),
+    ('\u{a9b4}', '\u{a9b5}'),
+    ('\u{a9ba}', '\u{a9bb}'),
+    ('\u{a9be}', '\u{a9bf}'),
+    ('\u{aa2f}', '\u{aa30}'),
+    ('\u{aa33}', '\u{aa34}'),
+    ('\u{aa4d}', '\u{aa4d}'),
+    ('\u{aaeb}', '\u{aaeb}'),
+    ('\u{aaee}', '\u{aaef}'),
+    ('\u{aaf5}', '\u{aaf5}'),
+    ('\u{abe3}', '\u{abe4}'),
+    ('\u{abe6}', '\u{abe7}'),
+    ('\u{abe9}', '\u{abea}'),
+    ('\u{abec}', '\u{abec}'),
+    ('\u{11000}', '\u{11000}'),
+    ('\u{11002}', '\u{11002}'),
+    ('\u{11082}', '\u{11082}'),
+    ('\u{110b0}', '\u{110b2}'),
+    ('\u{110b7}', '\u{110b8}'),
+    ('\u{1112c}', '\u{1112c}'),
+    ('\u{11145}', '\u{11146}'),
+    ('\u{11182}', '\u{11182}'),
+    ('\u{111b3}', '\u{111b5}'),
+    ('\u{111bf}', '\u{111bf}'),
+    ('\u{111ce}', '\u{111ce}'),
+    ('\u{1122c}', '\u{1122e}'),
+    ('\u{11232}', '\u{11233}'),
+    ('\u{112e0}', '\u{112e2}'),
+    ('\u{11302}', '\u{11303}'),
+    ('\u{1133f}', '\u{1133f}'),
+    ('\u{11341}', '\u{11344}'),
+    ('\u{11347}', '\u{11348}'),
+    ('\u{1134b}', '\u{1134c}'),
+    ('\u{11362}', '\u{11363}'),
+    ('\u{113b9}', '\u{113ba}'),
+    ('\u{113ca}', '\u{113ca}'),
+    ('\u{113cc}', '\u{113cd}'),
+    ('\u{11435}', '\u{11437}'),
+    ('\u{11440}', '\u{11441}'),
+    ('\u{11445}', '\u{11445}'),
+    ('\u{114b1}', '\u{114b2}'),
+    ('\u{114b9}', '\u{114b9}'),
+    ('\u{114bb}', '\u{114bc}'),
+    ('\u{114be}', '\u{114be}'),
+    ('\u{114c1}', '\u{114c1}'),
+    ('\u{115b0}', '\u{115b1}'),
+    ('\u{115b8}', '\u{115bb}'),
+    ('\u{115be}', '\u{115be}'),
+    ('\u{11630}', '\u{11632}'),
+    ('\u{1163b}', '\u{1163c}'),
+    ('\u{1163e}', '\u{1163e}'),
+    ('\u{116ac}', '\u{116ac}'),
+    ('\u{116ae}', '\u{116af}'),
+    ('\u{1171e}', '\u{1171e}'),
+    ('\u{11726}', '\u{11726}'),
+    ('\u{1182c}', '\u{1182e}'),
+    ('\u{11838}', '\u{11838}'),
+    ('\u{11931}', '\u{11935}'),
+    ('\u{11937}', '\u{11938}'),
+    ('\u{11940}', '\u{11940}'),
+    ('\u{11942}', '\u{11942}'),
+    ('\u{119d1}', '\u{119d3}'),
+    ('\u{119dc}', '\u{119df}'),
+    ('\u{119e4}', '\u{119e4}'),
+    ('\u{11a39}', '\u{11a39}'),
+    ('\u{11a57}', '\u{11a58}'),
+    ('\u{11a97}', '\u{11a97}'),
+    ('\u{11b61}', '\u{11b61}'),
+    ('\u{11b65}', '\u{11b65}'),
+    ('\u{11b67}', '\u{11b67}'),
+    ('\u{11c2f}', '\u{11c2f}'),
+    ('\u{11c3e}', '\u{11c3e}'),
+    ('\u{11ca9}', '\u{11ca9}'),
+    ('\u{11cb1}', '\u{11cb1}'),
+    ('\u{11cb4}', '\u{11cb4}'),
+    ('\u{11d8a}', '\u{11d8e}'),
+    ('\u{11d93}', '\u{11d94}'),
+    ('\u{11d96}', '\u{11d96}'),
+    ('\u{11ef5}', '\u{11ef6}'),
+    ('\u{11f03}', '\u{11f03}'),
+    ('\u{11f34}', '\u{11f35}'),
+    ('\u{11f3e}', '\u{11f3f}'),
+    ('\u{1612a}', '\u{1612c}'),
+    ('\u{16f51}', '\u{16f87}'),
+];
+
+pub const REGIONAL_INDICATOR: &[(char, char)] = &[('\u{1f1e6}', '\u{1f1ff}')];
+
+pub const L: &[(char, char)] = &[('\u{1100}', '\u{115f}'), ('\u{a960}', '\u{a97c}')];
+
+pub const V: &[(char, char)] = &[
+    ('\u{1160}', '\u{11a7}'),
+    ('\u{d7b0}', '\u{d7c6}'),
+    ('\u{16d63}', '\u{16d63}'),
+    ('\u{16d67}', '\u{16d6a}'),
+];
+
+pub const T: &[(char, char)] = &[('\u{11a8}', '\u{11ff}'), ('\u{d7cb}', '\u{d7fb}')];
+
+pub const EXTENDED_PICTOGRAPHIC: &[(char, char)] = &[
+    ('\u{a9}', '\u{a9}'),
+    ('\u{ae}', '\u{ae}'),
+    ('\u{203c}', '\u{203c}'),
+    ('\u{2049}', '\u{2049}'),
+    ('\u{2122}', '\u{2122}'),
+    ('\u{2139}', '\u{2139}'),
+    ('\u{2194}', '\u{2199}'),
+    ('\u{21a9}', '\u{21aa}'),
+    ('\u{231a}', '\u{231b}'),
+    ('\u{2328}', '\u{2328}'),
+    ('\u{23cf}', '\u{23cf}'),
+    ('\u{23e9}', '\u{23f3}'),
+    ('\u{23f8}', '\u{23fa}'),
+    ('\u{24c2}', '\u{24c2}'),
+    ('\u{25aa}', '\u{25ab}'),
+    ('\u{25b6}', '\u{25b6}'),
+    ('\u{25c0}', '\u{25c0}'),
+    ('\u{25fb}', '\u{25fe}'),
+    ('\u{2600}', '\u{2604}'),
+    ('\u{260e}', '\u{260e}'),
+    ('\u{2611}', '\u{2611}'),
+    ('\u{2614}', '\u{2615}'),
+    ('\u{2618}', '\u{2618}'),
+    ('\u{261d}', '\u{261d}'),
+    ('\u{2620}', '\u{2620}'),
+    ('\u{2622}', '\u{2623}'),
+    ('\u{2626}', '\u{2626}'),
+    ('\u{262a}', '\u{262a}'),
+    ('\u{262e}', '\u{262f}'),
+    ('\u{2638}', '\u{263a}'),
+    ('\u{2640}', '\u{2640}'),
+    ('\u{2642}', '\u{2642}'),
+    ('\u{2648}', '\u{2653}'),
+    ('\u{265f}', '\u{2660}'),
+    ('\u{2663}', '\u{2663}'),
+    ('\u{2665}', '\u{2666}'),
+    ('\u{2668}', '\u{2668}'),
+    ('\u{267b}', '\u{267b}'),
+    ('\u{267e}', '\u{267f}'),
+    ('\u{2692}', '\u{2697}'),
+    ('\u{2699}', '\u{2699}'),
+    ('\u{269b}', '\u{269c}'),
+    ('\u{26a0}', '\u{26a1}'),
+    ('\u{26a7}', '\u{26a7}'),
+    ('\u{26aa}', '\u{26ab}'),
+    ('\u{26b0}', '\u{26b1}'),
+    ('\u{26bd}', '\u{26be}'),
+    ('\u{26c4}', '\u{26c5}'),
+    ('\u{26c8}', '\u{26c8}'),
+    ('\u{26ce}', '\u{26cf}'),
+    ('\u{26d1}', '\u{26d1}'),
+    ('\u{26d3}', '\u{26d4}'),
+    ('\u{26e9}', '\u{26ea}'),
+    ('\u{26f0}', '\u{26f5}'),
+    ('\u{26f7}', '\u{26fa}'),
+    ('\u{26fd}', '\u{26fd}'),
+    ('\u{2702}', '\u{2702}'),
+    ('\u{2705}', '\u{2705}'),
+    ('\u{2708}', '\u{270d}'),
+    ('\u{270f}', '\u{270f}'),
+    ('\u{2712}', '\u{2712}'),
+    ('\u{2714}', '\u{2714}'),
+    ('\u{2716}', '\u{2716}'),
+    ('\u{271d}', '\u{271d}'),
+    ('\u{2721}', '\u{2721}'),
+    ('\u{2728}', '\u{2728}'),
+    ('\u{2733}', '\u{2734}'),
+    ('\u{2744}', '\u{2744}'),
+    ('\u{2747}', '\u{2747}'),
+    ('\u{274c}', '\u{274c}'),
+    ('\u{274e}', '\u{274e}'),
+    ('\u{2753}', '\u{2755}'),
+    ('\u{2757}', '\u{2757}'),
+    ('\u{2763}', '\u{2764}'),
+    ('\u{2795}', '\u{2797}'),
+    ('\u{27a1}', '\u{27a1}'),
+    ('\u{27b0}', '\u{27b0}'),
+    ('\u{27bf}', '\u{27bf}'),
+    ('\u{2934}', '\u{2935}'),
+    ('\u{2b05}', '\u{2b07}'),
+    ('\u{2b1b}', '\u{2b1c}'),
+    ('\u{2b50}', '\u{2b50}'),
+    ('\u{2b55}', '\u{2b55}'),
+    ('\u{3030}', '\u{3030}'),
+    ('\u{303d}', '\u{303d}'),
+    ('\u{3297}', '\u{3297}'),
+    ('\u{3299}', '\u{3299}'),
+    ('\u{1f004}', '\u{1f004}'),
+    ('\u{1f02c}', '\u{1f02f}'),
+    ('\u{1f094}', '\u{1f09f}'),
+    ('\u{1f0af}', '\u{1f0b0}'),
+    ('\u{1f0c0}', '\u{1f0c0}'),
+    ('\u{1f0cf}', '\u{1f0d0}'),
+    ('\u{1f0f6}', '\u{1f0ff}'),
+    ('\u{1f170}', '\u{1f171}'),
+    ('\u{1f17e}', '\u{1f17f}'),
+    ('\u{1f18e}', '\u{1f18e}'),
+    ('\u{1f191}', '\u{1f19a}'),
+    ('\u{1f1ae}', '\u{1f1e5}'),
+    ('\u{1f201}', '\u{1f20f}'),
+    ('\u{1f21a}', '\u{1f21a}'),
+    ('\u{1f22f}', '\u{1f22f}'),
+    ('\u{1f232}', '\u{1f23a}'),
+    ('\u{1f23c}', '\u{1f23f}'),
+    ('\u{1f249}', '\u{1f25f}'),
+    ('\u{1f266}', '\u{1f321}'),
+    ('\u{1f324}', '\u{1f393}'),
+    ('\u{1f396}', '\u{1f397}'),
+    ('\u{1f399}', '\u{1f39b}'),
+    ('\u{1f39e}', '\u{1f3f0}'),
+    ('\u{1f3f3}', '\u{1f3f5}'),
+    ('\u{1f3f7}', '\u{1f3fa}'),
+    ('\u{1f400}', '\u{1f4fd}'),
+    ('\u{1f4ff}', '\u{1f53d}'),
+    ('\u{1f549}', '\u{1f54e}'),
+    ('\u{1f550}', '\u{1f567}'),
+    ('\u{1f56f}', '\u{1f570}'),
+    ('\u{1f573}', '\u{1f57a}'),
+    ('\u{1f587}', '\u{1f587}'),
+    ('\u{1f58a}', '\u{1f58d}'),
+    ('\u{1f590}', '\u{1f590}'),
+    ('\u{1f595}', '\u{1f596}'),
+    ('\u{1f5a4}', '\u{1f5a5}'),
+    ('\u{1f5a8}', '\u{1f5a8}'),
+    ('\u{1f5b1}', '\u{1f5b2}'),
+    ('\u{1f5bc}', '\u{1f5bc}'),
+    ('\u{1f5c2}', '\u{1f5c4}'),
+    ('\u{1f5d1}', '\u{1f5d3}'),
+    ('\u{1f5dc}', '\u{1f5de}'),
+    ('\u{1f5e1}', '\u{1f5e1}'),
+    ('\u{1f5e3}', '\u{1f5e3}'),
+    ('\u{1f5e8}', '\u{1f5e8}'),
+    ('\u{1f5ef}', '\u{1f5ef}'),
+    ('\u{1f5f3}', '\u{1f5f3}'),
+    ('\u{1f5fa}', '\u{1f64f}'),
+    ('\u{1f680}', '\u{1f6c5}'),
+    ('\u{1f6cb}', '\u{1f6d2}'),
+    ('\u{1f6d5}', '\u{1f6e5}'),
+    ('\u{1f6e9}', '\u{1f6e9}'),
+    ('\u{1f6eb}', '\u{1f6f0}'),
+    ('\u{1f6f3}', '\u{1f6ff}'),
+    ('\u{1f7da}', '\u{1f7ff}'),
+    ('\u{1f80c}', '\u{1f80f}'),
+    ('\u{1f848}', '\u{1f84f}'),
+    ('\u{1f85a}', '\u{1f85f}'),
+    ('\u{1f888}', '\u{1f88f}'),
+    ('\u{1f8ae}', '\u{1f8af}'),
+    ('\u{1f8bc}', '\u{1f8bf}'),
+    ('\u{1f8c2}', '\u{1f8cf}'),
+    ('\u{1f8d9}', '\u{1f8ff}'),
+    ('\u{1f90c}', '\u{1f93a}'),
+    ('\u{1f93c}', '\u{1f945}'),
+    ('\u{1f947}', '\u{1f9ff}'),
+    ('\u{1fa58}', '\u{1fa5f}'),
+    ('\u{1fa6e}', '\u{1faff}'),
+    ('\u{1fc00}', '\u{1fffd}'),
+];
+
+pub const CONJUNCT_CONSONANT: &[(char, char)] = &[
+    ('\u{915}', '\u{939}'),
+    ('\u{958}', '\u{95f}'),
+    ('\u{978}', '\u{97f}'),
+    ('\u{995}', '\u{9a8}'),
+    ('\u{9aa}', '\u{9b0}'),
+    ('\u{9b2}', '\u{9b2}'),
+    ('\u{9b6}', '\u{9b9}'),
+    ('\u{9dc}', '\u{9dd}'),
+    ('\u{9df}', '\u{9df}'),
+    ('\u{9f0}', '\u{9f1}'),
+    ('\u{a95}', '\u{aa8}'),
+    ('\u{aaa}', '\u{ab0}'),
+    ('\u{ab2}', '\u{ab3}'),
+    ('\u{ab5}', '\u{ab9}'),
+    ('\u{af9}', '\u{af9}'),
+    ('\u{b15}', '\u{b28}'),
+    ('\u{b2a}', '\u{b30}'),
+    ('\u{b32}', '\u{b33}'),
+    ('\u{b35}', '\u{b39}'),
+    ('\u{b5c}', '\u{b5d}'),
+    ('\u{b5f}', '\u{b5f}'),
+    ('\u{b71}', '\u{b71}'),
+    ('\u{c15}', '\u{c28}'),
+    ('\u{c2a}', '\u{c39}'),
+    ('\u{c58}', '\u{c5a}'),
+    ('\u{d15}', '\u{d3a}'),
+    ('\u{1000}', '\u{102a}'),
+    ('\u{103f}', '\u{103f}'),
+    ('\u{1050}', '\u{1055}'),
+    ('\u{105a}', '\u{105d}'),
+    ('\u{1061}', '\u{1061}'),
+    ('\u{1065}', '\u{1066}'),
+    ('\u{106e}', '\u{1070}'),
+    ('\u{1075}', '\u{1081}'),
+    ('\u{108e}', '\u{108e}'),
+    ('\u{1780}', '\u{17b3}'),
+    ('\u{1a20}', '\u{1a54}'),
+    ('\u{1b0b}', '\u{1b0c}'),
+    ('\u{1b13}', '\u{1b33}'),
+    ('\u{1b45}', '\u{1b4c}'),
+    ('\u{1b83}', '\u{1ba0}'),
+    ('\u{1bae}', '\u{1baf}'),
+    ('\u{1bbb}', '\u{1bbd}'),
+    ('\u{a989}', '\u{a98b}'),
+    ('\u{a98f}', '\u{a9b2}'),
+    ('\u{a9e0}', '\u{a9e4}'),
+    ('\u{a9e7}', '\u{a9ef}'),
+    ('\u{a9fa}', '\u{a9fe}'),
+    ('\u{aa60}', '\u{aa6f}'),
+    ('\u{aa71}', '\u{aa73}'),
+    ('\u{aa7a}', '\u{aa7a}'),
+    ('\u{aa7e}', '\u{aa7f}'),
+    ('\u{aae0}', '\u{aaea}'),
+    ('\u{abc0}', '\u{abda}'),
+    ('\u{10a00}', '\u{10a00}'),
+    ('\u{10a10}', '\u{10a13}'),
+    ('\u{10a15}', '\u{10a17}'),
+    ('\u{10a19}', '\u{10a35}'),
+    ('\u{11103}', '\u{11126}'),
+    ('\u{11144}', '\u{11144}'),
+    ('\u{11147}', '\u{11147}'),
+    ('\u{11380}', '\u{11389}'),
+    ('\u{1138b}', '\u{1138b}'),
+    ('\u{1138e}', '\u{1138e}'),
+    ('\u{11390}', '\u{113b5}'),
+    ('\u{11900}', '\u{11906}'),
+    ('\u{11909}', '\u{11909}'),
+    ('\u{1190c}', '\u{11913}'),
+    ('\u{11915}', '\u{11916}'),
+    ('\u{11918}', '\u{1192f}'),
+    ('\u{11a00}', '\u{11a00}'),
+    ('\u{11a0b}', '\u{11a32}'),
+    ('\u{11a50}', '\u{11a50}'),
+    ('\u{11a5c}', '\u{11a83}'),
+    ('\u{11f04}', '\u{11f10}'),
+    ('\u{11f12}', '\u{11f33}'),
+];
+
+pub const CONJUNCT_EXTEND: &[(char, char)] = &[
+    ('\u{300}', '\u{36f}'),
+    ('\u{483}', '\u{489}'),
+    ('\u{591}', '\u{5bd}'),
+    ('\u{5bf}', '\u{5bf}'),
+    ('\u{5c1}', '\u{5c2}'),
+    ('\u{5c4}', '\u{5c5}'),
+    ('\u{5c7}', '\u{5c7}'),
+    ('\u{610}', '\u{61a}'),
+    ('\u{64b}', '\u{65f}'),
+    ('\u{670}', '\u{670}'),
+    ('\u{6d6}', '\u{6dc}'),
+    ('\u{6df}', '\u{6e4}'),
+    ('\u{6e7}', '\u{6e8}'),
+    ('\u{6ea}', '\u{6ed}'),
+    ('\u{711}', '\u{711}'),
+    ('\u{730}', '\u{74a}'),
+    ('\u{7a6}', '\u{7b0}'),
+    ('\u{7eb}', '\u{7f3}'),
+    ('\u{7fd}', '\u{7fd}'),
+    ('\u{816}', '\u{819}'),
+    ('\u{81b}', '\u{823}'),
+    ('\u{825}', '\u{827}'),
+    ('\u{829}', '\u{82d}'),
+    ('\u{859}', '\u{85b}'),
+    ('\u{897}', '\u{89f}'),
+    ('\u{8ca}', '\u{8e1}'),
+    ('\u{8e3}', '\u{902}'),
+    ('\u{93a}', '\u{93a}'),
+    ('\u{93c}', '\u{93c}'),
+    ('\u{941}', '\u{948}'),
+    ('\u{951}', '\u{957}'),
+    ('\u{962}', '\u{963}'),
+    ('\u{981}', '\u{981}'),
+    ('\u{9bc}', '\u{9bc}'),
+    ('\u{9be}', '\u{9be}'),
+    ('\u{9c1}', '\u{9c4}'),
+    ('\u{9d7}', '\u{9d7}'),
+    ('\u{9e2}', '\u{9e3}'),
+    ('\u{9fe}', '\u{9fe}'),
+    ('\u{a01}', '\u{a02}'),
+    ('\u{a3c}', '\u{a3c}'),
+    ('\u{a41}', '\u{a42}'),
+    ('\u{a47}', '\u{a48}'),
+    ('\u{a4b}', '\u{a4d}'),
+    ('\u{a51}', '\u{a51}'),
+    ('\u{a70}', '\u{a71}'),
+    ('\u{a75}', '\u{a75}'),
+    ('\u{a81}', '\u{a82}'),
+    ('\u{abc}', '\u{abc}'),
+    ('\u{ac1}', '\u{ac5}'),
+    ('\u{ac7}', '\u{ac8}'),
+    ('\u{ae2}', '\u{ae3}'),
+    ('\u{afa}', '\u{aff}'),
+    ('\u{b01}', '\u{b01}'),
+    ('\u{b3c}', '\u{b3c}'),
+    ('\u{b3e}', '\u{b3f}'),
+    ('\u{b41}', '\u{b44}'),
+    ('\u{b55}', '\u{b57}'),
+    ('\u{b62}', '\u{b63}'),
+    ('\u{b82}', '\u{b82}'),
+    ('\u{bbe}', '\u{bbe}'),
+    ('\u{bc0}', '\u{bc0}'),
+    ('\u{bcd}', '\u{bcd}'),
+    ('\u{bd7}', '\u{bd7}'),
+    ('\u{c00}', '\u{c00}'),
+    ('\u{c04}', '\u{c04}'),
+    ('\u{c3c}', '\u{c3c}'),
+    ('\u{c3e}', '\u{c40}'),
+    ('\u{c46}', '\u{c48}'),
+    ('\u{c4a}', '\u{c4c}'),
+    ('\u{c55}', '\u{c56}'),
+    ('\u{c62}', '\u{c63}'),
+    ('\u{c81}', '\u{c81}'),
+    ('\u{cbc}', '\u{cbc}'),
+    ('\u{cbf}', '\u{cc0}'),
+    ('\u{cc2}', '\u{cc2}'),
+    ('\u{cc6}', '\u{cc8}'),
+    ('\u{cca}', '\u{ccd}'),
+    ('\u{cd5}', '\u{cd6}'),
+    ('\u{ce2}', '\u{ce3}'),
+    ('\u{d00}', '\u{d01}'),
+    ('\u{d3b}', '\u{d3c}'),
+    ('\u{d3e}', '\u{d3e}'),
+    ('\u{d41}', '\u{d44}'),
+    ('\u{d57}', '\u{d57}'),
+    ('\u{d62}', '\u{d63}'),
+    ('\u{d81}', '\u{d81}'),
+    ('\u{dca}', '\u{dca}'),
+    ('\u{dcf}', '\u{dcf}'),
+    ('\u{dd2}', '\u{dd4}'),
+    ('\u{dd6}', '\u{dd6}'),
+    ('\u{ddf}', '\u{ddf}'),
+    ('\u{e31}', '\u{e31}'),
+    ('\u{e34}', '\u{e3a}'),
+    ('\u{e47}', '\u{e4e}'),
+    ('\u{eb1}', '\u{eb1}'),
+    ('\u{eb4}', '\u{ebc}'),
+    ('\u{ec8}', '\u{ece}'),
+    ('\u{f18}', '\u{f19}'),
+    ('\u{f35}', '\u{f35}'),
+    ('\u{f37}', '\u{f37}'),
+    ('\u{f39}', '\u{f39}'),
+    ('\u{f71}', '\u{f7e}'),
+    ('\u{f80}', '\u{f84}'),
+    ('\u{f86}', '\u{f87}'),
+    ('\u{f8d}', '\u{f97}'),
+    ('\u{f99}', '\u{fbc}'),
+    ('\u{fc6}', '\u{fc6}'),
+    ('\u{102d}', '\u{1030}'),
+    ('\u{1032}', '\u{1037}'),
+    ('\u{103a}', '\u{103a}'),
+    ('\u{103d}', '\u{103e}'),
+    ('\u{1058}', '\u{1059}'),
+    ('\u{105e}', '\u{1060}'),
+    ('\u{1071}', '\u{1074}'),
+    ('\u{1082}', '\u{1082}'),
+    ('\u{1085}', '\u{1086}'),
+    ('\u{108d}', '\u{108d}'),
+    ('\u{109d}', '\u{109d}'),
+    ('\u{135d}', '\u{135f}'),
+    ('\u{1712}', '\u{1715}'),
+    ('\u{1732}', '\u{1734}'),
+    ('\u{1752}', '\u{1753}'),
+    ('\u{1772}', '\u{1773}'),
+    ('\u{17b4}', '\u{17b5}'),
+    ('\u{17b7}', '\u{17bd}'),
+    ('\u{17c6}', '\u{17c6}'),
+    ('\u{17c9}', '\u{17d1}'),
+    ('\u{17d3}', '\u{17d3}'),
+    ('\u{17dd}', '\u{17dd}'),
+    ('\u{180b}', '\u{180d}'),
+    ('\u{180f}', '\u{180f}'),
+    ('\u{1885}', '\u{1886}'),
+    ('\u{18a9}', '\u{18a9}'),
+    ('\u{1920}', '\u{1922}'),
+    ('\u{1927}', '\u{1928}'),
+    ('\u{1932}', '\u{1932}'),
+    ('\u{1939}', '\u{193b}'),
+    ('\u{1a17}', '\u{1a18}'),
+    ('\u{1a1b}', '\u{1a1b}'),
+    ('\u{1a56}', '\u{1a56}'),
+    ('\u{1a58}', '\u{1a5e}'),
+    ('\u{1a62}', '\u{1a62}'),
+    ('\u{1a65}', '\u{1a6c}'),
+    ('\u{1a73}', '\u{1a7c}'),
+    ('\u{1a7f}', '\u{1a7f}'),
+    ('\u{1ab0}', '\u{1add}'),
+    ('\u{1ae0}', '\u{1aeb}'),
+    ('\u{1b00}', '\u{1b03}'),
+    ('\u{1b34}', '\u{1b3d}'),
+    ('\u{1b42}', '\u{1b43}'),
+    ('\u{1b6b}', '\u{1b73}'),
+    ('\u{1b80}', '\u{1b81}'),
+    ('\u{1ba2}', '\u{1ba5}'),
+    ('\u{1ba8}', '\u{1baa}'),
+    ('\u{1bac}', '\u{1bad}'),
+    ('\u{1be6}', '\u{1be6}'),
+    ('\u{1be8}', '\u{1be9}'),
+    ('\u{1bed}', '\u{1bed}'),
+    ('\u{1bef}', '\u{1bf3}'),
+    ('\u{1c2c}', '\u{1c33}'),
+    ('\u{1c36}', '\u{1c37}'),
+    ('\u{1cd0}', '\u{1cd2}'),
+    ('\u{1cd4}', '\u{1ce0}'),
+    ('\u{1ce2}', '\u{1ce8}'),
+    ('\u{1ced}', '\u{1ced}'),
+    ('\u{1cf4}', '\u{1cf4}'),
+    ('\u{1cf8}', '\u{1cf9}'),
+    ('\u{1dc0}', '\u{1dff}'),
+    ('\u{200d}', '\u{200d}'),
+    ('\u{20d0}', '\u{20f0}'),
+    ('\u{2cef}', '\u{2cf1}'),
+    ('\u{2d7f}', '\u{2d7f}'),
+    ('\u{2de0}', '\u{2dff}'),
+    ('\u{302a}', '\u{302f}'),
+    ('\u{3099}', '\u{309a}'),
+    ('\u{a66f}', '\u{a672}'),
+    ('\u{a674}', '\u{a67d}'),
+    ('\u{a69e}', '\u{a69f}'),
+    ('\u{a6f0}', '\u{a6f1}'),
+    ('\u{a802}', '\u{a802}'),
+    ('\u{a806}', '\u{a806}'),
+    ('\u{a80b}', '\u{a80b}'),
+    ('\u{a825}', '\u{a826}'),
+    ('\u{a82c}', '\u{a82c}'),
+    ('\u{a8c4}', '\u{a8c5}'),
+    ('\u{a8e0}', '\u{a8f1}'),
+    ('\u{a8ff}', '\u{a8ff}'),
+    ('\u{a926}', '\u{a92d}'),
+    ('\u{a947}', '\u{a951}'),
+    ('\u{a953}', '\u{a953}'),
+    ('\u{a980}', '\u{a982}'),
+    ('\u{a9b3}', '\u{a9b3}'),
+    ('\u{a9b6}', '\u{a9b9}'),
+    ('\u{a9bc}', '\u{a9bd}'),
+    ('\u{a9e5}', '\u{a9e5}'),
+    ('\u{aa29}', '\u{aa2e}'),
+    ('\u{aa31}', '\u{aa32}'),
+    ('\u{aa35}', '\u{aa36}'),
+    ('\u{aa43}', '\u{aa43}'),
+    ('\u{aa4c}', '\u{aa4c}'),
+    ('\u{aa7c}', '\u{aa7c}'),
+    ('\u{aab0}', '\u{aab0}'),
+    ('\u{aab2}', '\u{aab4}'),
+    ('\u{aab7}', '\u{aab8}'),
+    ('\u{aabe}', '\u{aabf}'),
+    ('\u{aac1}', '\u{aac1}'),
+    ('\u{aaec}', '\u{aaed}'),
+    ('\u{abe5}', '\u{abe5}'),
+    ('\u{abe8}', '\u{abe8}'),
+    ('\u{abed}', '\u{abed}'),
+    ('\u{fb1e}', '\u{fb1e}'),
+    ('\u{fe00}', '\u{fe0f}'),
+    ('\u{fe20}', '\u{fe2f}'),
+    ('\u{ff9e}', '\u{ff9f}'),
+    ('\u{101fd}', '\u{101fd}'),
+    ('\u{102e0}', '\u{102e0}'),
+    ('\u{10376}', '\u{1037a}'),
+    ('\u{10a01}', '\u{10a03}'),
+    ('\u{10a05}', '\u{10a06}'),
+    ('\u{10a0c}', '\u{10a0f}'),
+    ('\u{10a38}', '\u{10a3a}'),
+    ('\u{10ae5}', '\u{10ae6}'),
+    ('\u{10d24}', '\u{10d27}'),
+    ('\u{10d69}', '\u{10d6d}'),
+    ('\u{10eab}', '\u{10eac}'),
+    ('\u{10efa}', '\u{10eff}'),
+    ('\u{10f46}', '\u{10f50}'),
+    ('\u{10f82}', '\u{10f85}'),
+    ('\u{11001}', '\u{11001}'),
+    ('\u{11038}', '\u{11046}'),
+    ('\u{11070}', '\u{11070}'),
+    ('\u{11073}', '\u{11074}'),
+    ('\u{1107f}', '\u{11081}'),
+    ('\u{110b3}', '\u{110b6}'),
+    ('\u{110b9}', '\u{110ba}'),
+    ('\u{110c2}', '\u{110c2}'),
+    ('\u{11100}', '\u{11102}'),
+    ('\u{11127}', '\u{1112b}'),
+    ('\u{1112d}', '\u{11132}'),
+    ('\u{11134}', '\u{11134}'),
+    ('\u{11173}', '\u{11173}'),
+    ('\u{11180}', '\u{11181}'),
+    ('\u{111b6}', '\u{111be}'),
+    ('\u{111c0}', '\u{111c0}'),
+    ('\u{111c9}', '\u{111cc}'),
+    ('\u{111cf}', '\u{111cf}'),
+    ('\u{1122f}', '\u{11231}'),
+    ('\u{11234}', '\u{11237}'),
+    ('\u{1123e}', '\u{1123e}'),
+    ('\u{11241}', '\u{11241}'),
+    ('\u{112df}', '\u{112df}'),
+    ('\u{112e3}', '\u{112ea}'),
+    ('\u{11300}', '\u{11301}'),
+    ('\u{1133b}', '\u{1133c}'),
+    ('\u{1133e}', '\u{1133e}'),
+    ('\u{11340}', '\u{11340}'),
+    ('\u{1134d}', '\u{1134d}'),
+    ('\u{11357}', '\u{11357}'),
+    ('\u{11366}', '\u{1136c}'),
+    ('\u{11370}', '\u{11374}'),
+    ('\u{113b8}', '\u{113b8}'),
+    ('\u{113bb}', '\u{113c0}'),
+    ('\u{113c2}', '\u{113c2}'),
+    ('\u{113c5}', '\u{113c5}'),
+    ('\u{113c7}', '\u{113c9}'),
+    ('\u{113ce}', '\u{113cf}'),
+    ('\u{113d2}', '\u{113d2}'),
+    ('\u{113e1}', '\u{113e2}'),
+    ('\u{11438}', '\u{1143f}'),
+    ('\u{11442}', '\u{11444}'),
+    ('\u{11446}', '\u{11446}'),
+    ('\u{1145e}', '\u{1145e}'),
+    ('\u{114b0}', '\u{114b0}'),
+    ('\u{114b3}', '\u{114b8}'),
+    ('\u{114ba}', '\u{114ba}'),
+    ('\u{114bd}', '\u{114bd}'),
+    ('\u{114bf}', '\u{114c0}'),
+    ('\u{114c2}', '\u{114c3}'),
+    ('\u{115af}', '\u{115af}'),
+    ('\u{115b2}', '\u{115b5}'),
+    ('\u{115bc}', '\u{115bd}'),
+    ('\u{115bf}', '\u{115c0}'),
+    ('\u{115dc}', '\u{115dd}'),
+    ('\u{11633}', '\u{1163a}'),
+    ('\u{1163d}', '\u{1163d}'),
+    ('\u{1163f}', '\u{11640}'),
+    ('\u{116ab}', '\u{116ab}'),
+    ('\u{116ad}', '\u{116ad}'),
+    ('\u{116b0}', '\u{116b7}'),
+    ('\u{1171d}', '\u{1171d}'),
+    ('\u{1171f}', '\u{1171f}'),
+    ('\u{11722}', '\u{11725}'),
+    ('\u{11727}', '\u{1172b}'),
+    ('\u{1182f}', '\u{11837}'),
+    ('\u{11839}', '\u{1183a}'),
+    ('\u{11930}', '\u{11930}'),
+    ('\u{1193b}', '\u{1193d}'),
+    ('\u{11943}', '\u{11943}'),
+    ('\u{119d4}', '\u{119d7}'),
+    ('\u{119da}', '\u{119db}'),
+    ('\u{119e0}', '\u{119e0}'),
+    ('\u{11a01}', '\u{11a0a}'),
+    ('\u{11a33}', '\u{11a38}'),
+    ('\u{11a3b}', '\u{11a3e}'),
+    ('\u{11a51}', '\u{11a56}'),
+    ('\u{11a59}', '\u{11a5b}'),
+    ('\u{11a8a}', '\u{11a96}'),
+    ('\u{11a98}', '\u{11a98}'),
+    ('\u{11b60}', '\u{11b60}'),
+    ('\u{11b62}', '\u{11b64}'),
+    ('\u{11b66}', '\u{11b66}'),
+    ('\u{11c30}', '\u{11c36}'),
+    ('\u{11c38}', '\u{11c3d}'),
+    ('\u{11c3f}', '\u{11c3f}'),
+    ('\u{11c92}', '\u{11ca7}'),
+    ('\u{11caa}', '\u{11cb0}'),
+    ('\u{11cb2}', '\u{11cb3}'),
+    ('\u{11cb5}', '\u{11cb6}'),
+    ('\u{11d31}', '\u{11d36}'),
+    ('\u{11d3a}', '\u{11d3a}'),
+    ('\u{11d3c}', '\u{11d3d}'),
+    ('\u{11d3f}', '\u{11d45}'),
+    ('\u{11d47}', '\u{11d47}'),
+    ('\u{11d90}', '\u{11d91}'),
+    ('\u{11d95}', '\u{11d95}'),
+    ('\u{11d97}', '\u{11d97}'),
+    ('\u{11ef3}', '\u{11ef4}'),
+    ('\u{11f00}', '\u{11f01}'),
+    ('\u{11f36}', '\u{11f3a}'),
+    ('\u{11f40}', '\u{11f41}'),
+    ('\u{11f5a}', '\u{11f5a}'),
+    ('\u{13440}', '\u{13440}'),
+    ('\u{13447}', '\u{13455}'),
+    ('\u{1611e}', '\u{16129}'),
+    ('\u{1612d}', '\u{1612f}'),
+    ('\u{16af0}', '\u{16af4}'),
+    ('\u{16b30}', '\u{16b36}'),
+    ('\u{16f4f}', '\u{16f4f}'),
+    ('\u{16f8f}', '\u{16f92}'),
+    ('\u{16fe4}', '\u{16fe4}'),
+    ('\u{16ff0}', '\u{16ff1}'),
+    ('\u{1bc9d}', '\u{1bc9e}'),
+    ('\u{1cf00}', '\u{1cf2d}'),
+    ('\u{1cf30}', '\u{1cf46}'),
+    ('\u{1d165}', '\u{1d169}'),
+    ('\u{1d16d}', '\u{1d172}'),
+    ('\u{1d17b}', '\u{1d182}'),
+    ('\u{1d185}', '\u{1d18b}'),
+    ('\u{1d1aa}', '\u{1d1ad}'),
+    ('\u{1d242}', '\u{1d244}'),
+    ('\u{1da00}', '\u{1da36}'),
+    ('\u{1da3b}', '\u{1da6c}'),
+    ('\u{1da75}', '\u{1da75}'),
+    ('\u{1da84}', '\u{1da84}'),
+    ('\u{1da9b}', '\u{1da9f}'),
+    ('\u{1daa1}', '\u{1daaf}'),
+    ('\u{1e000}', '\u{1e006}'),
+    ('\u{1e008}', '\u{1e018}'),
+    ('\u{1e01b}', '\u{1e021}'),
+    ('\u{1e023}', '\u{1e024}'),
+    ('\u{1e026}', '\u{1e02a}'),
+    ('\u{1e08f}', '\u{1e08f}'),
+    ('\u{1e130}', '\u{1e136}'),
+    ('\u{1e2ae}', '\u{1e2ae}'),
+    ('\u{1e2ec}', '\u{1e2ef}'),
+    ('\u{1e4ec}', '\u{1e4ef}'),
+    ('\u{1e5ee}', '\u{1e5ef}'),
+    ('\u{1e6e3}', '\u{1e6e3}'),
+    ('\u{1e6e6}', '\u{1e6e6}'),
+    ('\u{1e6ee}', '\u{1e6ef}'),
+    ('\u{1e6f5}', '\u{1e6f5}'),
+    ('\u{1e8d0}', '\u{1e8d6}'),
+    ('\u{1e944}', '\u{1e94a}'),
+    ('\u{1f3fb}', '\u{1f3ff}'),
+    ('\u{e0020}', '\u{e007f}'),
+    ('\u{e0100}', '\u{e01ef}'),
+];
+
+pub const CONJUNCT_LINKER: &[(char, char)] = &[
+    ('\u{94d}', '\u{94d}'),
+    ('\u{9cd}', '\u{9cd}'),
+    ('\u{acd}', '\u{acd}'),
+    ('\u{b4d}', '\u{b4d}'),
+    ('\u{c4d}', '\u{c4d}'),
+    ('\u{d4d}', '\u{d4d}'),
+    ('\u{1039}', '\u{1039}'),
+    ('\u{17d2}', '\u{17d2}'),
+    ('\u{1a60}', '\u{1a60}'),
+    ('\u{1b44}', '\u{1b44}'),
+    ('\u{1bab}', '\u{1bab}'),
+    ('\u{a9c0}', '\u{a9c0}'),
+    ('\u{aaf6}', '\u{aaf6}'),
+    ('\u{10a3f}', '\u{10a3f}'),
+    ('\u{11133}', '\u{11133}'),
+    ('\u{113d0}', '\u{113d0}'),
+    ('\u{1193e}', '\u{1193e}'),
+    ('\u{11a47}', '\u{11a47}'),
+    ('\u{11a99}', '\u{11a99}'),
+    ('\u{11f42}', '\u{11f42}'),
+];
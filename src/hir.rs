@@ -0,0 +1,766 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+use crate::charset::CharSet;
+use crate::fsm::Look;
+use crate::grapheme;
+use crate::parser::{
+    CharacterClass, Element, Flag, Quantifier, Sign, SpecialSequence, Term, Token,
+};
+use crate::perl;
+use crate::ErrorKind;
+
+// What patterns are translated to on their way from the syntax tree to the
+// automaton: the flags have been applied, and are gone, classes and special
+// sequences are sets of characters or assertions, and every quantifier is a
+// repetition with bounds. Groups that don't capture are left out, and
+// capture groups are numbered.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hir {
+    // Matches the empty string.
+    Empty,
+    // Matches a single character from the set.
+    Class(CharSet),
+    // Matches the empty string where the assertion holds.
+    Look(Look),
+    // Matches the parts one after the other.
+    Concat(Vec<Hir>),
+    // Matches any of the alternatives, preferring those that come first.
+    Alternation(Vec<Hir>),
+    // Matches the part at least `min` and at most `max` times, preferring
+    // more repetitions if greedy, and fewer otherwise. The element of the
+    // syntax tree it was written as, if any, is numbered in the order
+    // `ast::walk` enters elements.
+    Repeat {
+        sub: Box<Hir>,
+        min: usize,
+        max: Option<usize>,
+        greedy: bool,
+        element: Option<usize>,
+    },
+    // Matches the part, recording where in the capture group with this
+    // number.
+    Capture(usize, Box<Hir>),
+    // Matches the empty string if the part matches (or, if negative,
+    // doesn't match) at the current position.
+    Lookahead(Sign, Box<Hir>),
+    // Matches what the part matches first, and never anything else.
+    Atomic(Box<Hir>),
+    // Matches the text last captured by the group with this number.
+    Backreference(usize),
+    // Matches the first part if the group with this number has captured
+    // anything, and the second one otherwise.
+    Conditional(usize, Box<Hir>, Box<Hir>),
+}
+
+impl Hir {
+    // Translates `terms` with the given initial flags, failing if a counted
+    // repetition exceeds `repetitions`. Errors come with the number of the
+    // element they were found at, in the order `ast::walk` enters elements.
+    pub fn translate(
+        terms: &[Term],
+        flags: Flags,
+        repetitions: usize,
+    ) -> Result<Self, (ErrorKind, usize)> {
+        let mut translator = Translator {
+            group: 1,
+            element: 0,
+            repetitions,
+        };
+        translator.alternatives(terms, flags)
+    }
+    // The number of capture groups, not counting the whole match.
+    pub fn groups(&self) -> usize {
+        match self {
+            Hir::Empty | Hir::Class(_) | Hir::Look(_) | Hir::Backreference(_) => 0,
+            Hir::Concat(parts) | Hir::Alternation(parts) => parts.iter().map(Hir::groups).sum(),
+            Hir::Repeat { sub, .. } | Hir::Lookahead(_, sub) | Hir::Atomic(sub) => sub.groups(),
+            Hir::Capture(_, sub) => 1 + sub.groups(),
+            Hir::Conditional(_, yes, no) => yes.groups() + no.groups(),
+        }
+    }
+    // Whether the part may match the empty string. Backreferences may, if
+    // the group captured nothing.
+    pub fn can_be_empty(&self) -> bool {
+        match self {
+            Hir::Empty | Hir::Look(_) | Hir::Lookahead(..) | Hir::Backreference(_) => true,
+            Hir::Class(_) => false,
+            Hir::Concat(parts) => parts.iter().all(Hir::can_be_empty),
+            Hir::Alternation(branches) => branches.iter().any(Hir::can_be_empty),
+            Hir::Repeat { sub, min, .. } => *min == 0 || sub.can_be_empty(),
+            Hir::Capture(_, sub) | Hir::Atomic(sub) => sub.can_be_empty(),
+            Hir::Conditional(_, yes, no) => yes.can_be_empty() || no.can_be_empty(),
+        }
+    }
+    // Whether every match starts at the beginning of the haystack.
+    pub fn is_anchored(&self) -> bool {
+        match self {
+            Hir::Look(Look::Start) => true,
+            Hir::Concat(parts) => parts.first().is_some_and(Hir::is_anchored),
+            Hir::Capture(_, sub) => sub.is_anchored(),
+            _ => false,
+        }
+    }
+    // The longest literal that every match has after something else, with
+    // the part before it, like `@example.com` and `\w+` in
+    // `\w+@example\.com/\d+`. That part may have no assertions and must
+    // never consume the first character of the literal, so that it can't
+    // reach past an occurrence of it.
+    pub fn inner_literal(&self) -> Option<(Hir, String)> {
+        let Hir::Concat(parts) = self else {
+            return None;
+        };
+        let char_of = |part: &Hir| match part {
+            Hir::Class(set) => match set.ranges() {
+                &[(from, to)] if from == to => Some(from),
+                _ => None,
+            },
+            _ => None,
+        };
+        let mut best: Option<(usize, String)> = None;
+        for start in 1..parts.len() {
+            let literal: String = parts[start..].iter().map_while(char_of).collect();
+            let Some(first) = literal.chars().next() else {
+                continue;
+            };
+            let longer = best
+                .as_ref()
+                .is_none_or(|(_, best)| literal.len() > best.len());
+            if longer && parts[..start].iter().all(|part| part.avoids(first)) {
+                best = Some((start, literal));
+            }
+        }
+        let (start, literal) = best?;
+        Some((Hir::concat(parts[..start].to_vec()), literal))
+    }
+    // Whether the part never consumes `c` and has no assertions.
+    fn avoids(&self, c: char) -> bool {
+        match self {
+            Hir::Empty => true,
+            Hir::Class(set) => !set.contains(c),
+            Hir::Concat(parts) | Hir::Alternation(parts) => parts.iter().all(|part| part.avoids(c)),
+            Hir::Repeat { sub, .. } | Hir::Capture(_, sub) => sub.avoids(c),
+            Hir::Look(_)
+            | Hir::Lookahead(..)
+            | Hir::Atomic(_)
+            | Hir::Backreference(_)
+            | Hir::Conditional(..) => false,
+        }
+    }
+    fn concat(mut parts: Vec<Hir>) -> Self {
+        match parts.len() {
+            0 => Hir::Empty,
+            1 => parts.pop().unwrap(),
+            _ => Hir::Concat(parts),
+        }
+    }
+    fn alternation(mut branches: Vec<Hir>) -> Self {
+        match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Hir::Alternation(branches),
+        }
+    }
+    fn repeat(sub: Hir, min: usize, max: Option<usize>) -> Self {
+        Hir::Repeat {
+            sub: Box::new(sub),
+            min,
+            max,
+            greedy: true,
+            element: None,
+        }
+    }
+}
+
+// Numbers capture groups by their opening parenthesis while translating,
+// and elements in the order they come up.
+struct Translator {
+    group: usize,
+    element: usize,
+    repetitions: usize,
+}
+
+impl Translator {
+    // Inline flags in an alternative carry over to the ones after it.
+    fn alternatives(&mut self, terms: &[Term], flags: Flags) -> Result<Hir, (ErrorKind, usize)> {
+        let mut flags = flags;
+        let mut branches = Vec::with_capacity(terms.len());
+        for term in terms {
+            branches.push(self.term(term, flags)?);
+            flags = flags.after(term);
+        }
+        Ok(Hir::alternation(branches))
+    }
+    fn term(&mut self, term: &Term, flags: Flags) -> Result<Hir, (ErrorKind, usize)> {
+        let mut parts = Vec::with_capacity(term.elements.len() + 2);
+        if term.left_anchored {
+            parts.push(Hir::Look(flags.at_start(term).start_anchor()));
+        }
+        let mut current = flags;
+        for element in &term.elements {
+            match element {
+                Element::Flags(changes) => {
+                    self.element += 1;
+                    current = current.apply(changes);
+                }
+                element => parts.push(self.element(element, current)?),
+            }
+        }
+        if term.right_anchored {
+            parts.push(Hir::Look(current.end_anchor()));
+        }
+        Ok(Hir::concat(parts))
+    }
+    fn element(&mut self, element: &Element, flags: Flags) -> Result<Hir, (ErrorKind, usize)> {
+        let number = self.element;
+        self.element += 1;
+        let (atom, quantifier) = match element {
+            Element::Class(class) => (Hir::Class(class_set(class, flags)), class.quantifier),
+            Element::Sequence(seq, quantifier) => (special_sequence(*seq, flags), *quantifier),
+            Element::CaptureGroup(_, terms, quantifier) => {
+                let group = self.group;
+                self.group += 1;
+                let sub = self.alternatives(terms, flags)?;
+                (Hir::Capture(group, Box::new(sub)), *quantifier)
+            }
+            Element::Group(changes, terms, quantifier) => {
+                (self.alternatives(terms, flags.apply(changes))?, *quantifier)
+            }
+            Element::Flags(_) => (Hir::Empty, Quantifier::Once),
+            Element::Lookahead(sign, terms) => {
+                let sub = self.alternatives(terms, flags)?;
+                (Hir::Lookahead(*sign, Box::new(sub)), Quantifier::Once)
+            }
+            Element::Atomic(terms, quantifier) => (
+                Hir::Atomic(Box::new(self.alternatives(terms, flags)?)),
+                *quantifier,
+            ),
+            // The branches are numbered and take flags like alternatives.
+            Element::Conditional(group, terms, quantifier) => {
+                let (yes, no) = match terms.as_slice() {
+                    [] => (Hir::Empty, Hir::Empty),
+                    [yes] => (self.term(yes, flags)?, Hir::Empty),
+                    [yes, no, ..] => (self.term(yes, flags)?, self.term(no, flags.after(yes))?),
+                };
+                (
+                    Hir::Conditional(*group, Box::new(yes), Box::new(no)),
+                    *quantifier,
+                )
+            }
+            Element::Backreference(group, quantifier) => (Hir::Backreference(*group), *quantifier),
+        };
+        self.quantified(atom, quantifier, number)
+    }
+    // Possessive quantifiers repeat greedily and never give back what they
+    // matched, like an atomic group.
+    fn quantified(
+        &self,
+        atom: Hir,
+        quantifier: Quantifier,
+        element: usize,
+    ) -> Result<Hir, (ErrorKind, usize)> {
+        let (min, max, greedy) = match quantifier {
+            Quantifier::Once => return Ok(atom),
+            Quantifier::Maybe | Quantifier::PossessiveMaybe => (0, Some(1), true),
+            Quantifier::ZeroOrMore | Quantifier::PossessiveZeroOrMore => (0, None, true),
+            Quantifier::OneOrMore | Quantifier::PossessiveOneOrMore => (1, None, true),
+            Quantifier::LazyMaybe => (0, Some(1), false),
+            Quantifier::LazyZeroOrMore => (0, None, false),
+            Quantifier::LazyOneOrMore => (1, None, false),
+            Quantifier::Exactly(n)
+            | Quantifier::AtLeast(n)
+            | Quantifier::Between(_, n)
+            | Quantifier::LazyExactly(n)
+            | Quantifier::LazyAtLeast(n)
+            | Quantifier::LazyBetween(_, n)
+            | Quantifier::PossessiveExactly(n)
+            | Quantifier::PossessiveAtLeast(n)
+            | Quantifier::PossessiveBetween(_, n)
+                if n > self.repetitions =>
+            {
+                return Err((
+                    ErrorKind::RepetitionLimitExceeded(self.repetitions),
+                    element,
+                ))
+            }
+            Quantifier::Exactly(n) | Quantifier::PossessiveExactly(n) => (n, Some(n), true),
+            Quantifier::AtLeast(n) | Quantifier::PossessiveAtLeast(n) => (n, None, true),
+            Quantifier::Between(n, m) | Quantifier::PossessiveBetween(n, m) => (n, Some(m), true),
+            Quantifier::LazyExactly(n) => (n, Some(n), false),
+            Quantifier::LazyAtLeast(n) => (n, None, false),
+            Quantifier::LazyBetween(n, m) => (n, Some(m), false),
+        };
+        let repeat = Hir::Repeat {
+            sub: Box::new(atom),
+            min,
+            max,
+            greedy,
+            element: Some(element),
+        };
+        let possessive = matches!(
+            quantifier,
+            Quantifier::PossessiveMaybe
+                | Quantifier::PossessiveZeroOrMore
+                | Quantifier::PossessiveOneOrMore
+                | Quantifier::PossessiveExactly(_)
+                | Quantifier::PossessiveAtLeast(_)
+                | Quantifier::PossessiveBetween(..)
+        );
+        Ok(if possessive {
+            Hir::Atomic(Box::new(repeat))
+        } else {
+            repeat
+        })
+    }
+}
+
+fn special_sequence(seq: SpecialSequence, flags: Flags) -> Hir {
+    let class = |ranges: &[(char, char)]| Hir::Class(CharSet::new(ranges.iter().copied()));
+    match seq {
+        SpecialSequence::AnyCharacter if flags.dot_all => Hir::Class(CharSet::full()),
+        SpecialSequence::AnyCharacter if flags.crlf => {
+            Hir::Class(CharSet::new([('\n', '\n'), ('\r', '\r')]).complement())
+        }
+        SpecialSequence::AnyCharacter => Hir::Class(CharSet::new([('\n', '\n')]).complement()),
+        // `\r\n` is tried before a single `\r`.
+        SpecialSequence::LineBreak => Hir::Alternation(vec![
+            Hir::Concat(vec![class(&[('\r', '\r')]), class(&[('\n', '\n')])]),
+            class(&[('\n', '\r'), ('\u{85}', '\u{85}'), ('\u{2028}', '\u{2029}')]),
+        ]),
+        SpecialSequence::Grapheme => grapheme(),
+        SpecialSequence::Digit
+        | SpecialSequence::NotDigit
+        | SpecialSequence::Whitespace
+        | SpecialSequence::NotWhitespace
+        | SpecialSequence::WordCharacter
+        | SpecialSequence::NotWordCharacter => Hir::Class(perl_set(&seq, flags)),
+        SpecialSequence::WordBoundary => Hir::Look(Look::WordBoundary(flags.ascii)),
+        SpecialSequence::WithinWord => Hir::Look(Look::NotWordBoundary(flags.ascii)),
+        SpecialSequence::Start => Hir::Look(Look::Start),
+        SpecialSequence::End => Hir::Look(Look::EndBeforeNewline),
+        SpecialSequence::AbsoluteEnd => Hir::Look(Look::End),
+        SpecialSequence::SearchStart => Hir::Look(Look::SearchStart),
+        SpecialSequence::LineStart => Hir::Look(flags.start_anchor()),
+        SpecialSequence::LineEnd => Hir::Look(flags.end_anchor()),
+    }
+}
+
+// An extended grapheme cluster, following the regular expression given for
+// it in Unicode Standard Annex #29:
+//
+//   crlf | Control | precore* core postcore*
+//
+// where the core is a Hangul syllable sequence, a pair of regional
+// indicators, an emoji ZWJ sequence, an Indic conjunct or any other
+// character but a control.
+fn grapheme() -> Hir {
+    const ZWJ: &[(char, char)] = &[('\u{200D}', '\u{200D}')];
+    const NEWLINES: &[(char, char)] = &[('\n', '\n'), ('\r', '\r')];
+    let set = |tables: &[&[(char, char)]]| CharSet::new(tables.concat());
+    let class = |tables: &[&[(char, char)]]| Hir::Class(set(tables));
+    let star = |tables: &[&[(char, char)]]| Hir::repeat(class(tables), 0, None);
+    let plus = |tables: &[&[(char, char)]]| Hir::repeat(class(tables), 1, None);
+    // L* (V+ | LV V* | LVT) T* | L+ | T+
+    let syllable = Hir::Concat(vec![
+        star(&[grapheme::L]),
+        Hir::Alternation(vec![
+            Hir::Concat(vec![
+                class(&[grapheme::V, hangul_syllables(false).ranges()]),
+                star(&[grapheme::V]),
+            ]),
+            Hir::Class(hangul_syllables(true)),
+        ]),
+        star(&[grapheme::T]),
+    ]);
+    // RI RI
+    let flag = Hir::repeat(class(&[grapheme::REGIONAL_INDICATOR]), 2, Some(2));
+    // ExtPict (Extend* ZWJ ExtPict)*
+    let joined = Hir::Concat(vec![
+        star(&[grapheme::EXTEND]),
+        class(&[ZWJ]),
+        class(&[grapheme::EXTENDED_PICTOGRAPHIC]),
+    ]);
+    let emoji = Hir::Concat(vec![
+        class(&[grapheme::EXTENDED_PICTOGRAPHIC]),
+        Hir::repeat(joined, 0, None),
+    ]);
+    // Consonant ([Extend Linker]* Linker [Extend Linker]* Consonant)*
+    let linked: &[&[(char, char)]] = &[grapheme::CONJUNCT_EXTEND, grapheme::CONJUNCT_LINKER];
+    let link = Hir::Concat(vec![
+        star(linked),
+        class(&[grapheme::CONJUNCT_LINKER]),
+        star(linked),
+        class(&[grapheme::CONJUNCT_CONSONANT]),
+    ]);
+    let conjunct = Hir::Concat(vec![
+        class(&[grapheme::CONJUNCT_CONSONANT]),
+        Hir::repeat(link, 0, None),
+    ]);
+    let controls = set(&[grapheme::CONTROL, NEWLINES]);
+    let core = Hir::Alternation(vec![
+        syllable,
+        plus(&[grapheme::L]),
+        plus(&[grapheme::T]),
+        flag,
+        emoji,
+        conjunct,
+        Hir::Class(controls.complement()),
+    ]);
+    Hir::Alternation(vec![
+        Hir::Concat(vec![class(&[&[('\r', '\r')]]), class(&[&[('\n', '\n')]])]),
+        Hir::Class(controls),
+        Hir::Concat(vec![
+            star(&[grapheme::PREPEND]),
+            core,
+            star(&[grapheme::EXTEND, grapheme::SPACING_MARK, ZWJ]),
+        ]),
+    ])
+}
+
+// The flags in effect while translating part of a pattern.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Flags {
+    case_insensitive: bool,
+    multi_line: bool,
+    dot_all: bool,
+    // Restricts perl classes and case folding to ASCII, with `(?-u)`.
+    ascii: bool,
+    // Treats `\r` as a line terminator as well, with `(?R)`.
+    crlf: bool,
+}
+
+impl Flags {
+    pub fn new(changes: &[(Flag, bool)]) -> Self {
+        Self::default().apply(changes)
+    }
+    // Verbose mode is taken care of before parsing.
+    pub fn apply(mut self, changes: &[(Flag, bool)]) -> Self {
+        for &(flag, enabled) in changes {
+            match flag {
+                Flag::CaseInsensitive => self.case_insensitive = enabled,
+                Flag::MultiLine => self.multi_line = enabled,
+                Flag::DotAll => self.dot_all = enabled,
+                Flag::Unicode => self.ascii = !enabled,
+                Flag::Crlf => self.crlf = enabled,
+                Flag::Verbose => {}
+            }
+        }
+        self
+    }
+    // The flags in effect at the `^` of `term`. Inline flags at the start of
+    // the term apply to it, so that `(?m)^` is a multi-line anchor.
+    pub fn at_start(self, term: &Term) -> Self {
+        term.elements
+            .iter()
+            .map_while(|element| match element {
+                Element::Flags(changes) => Some(changes),
+                _ => None,
+            })
+            .fold(self, |flags, changes| flags.apply(changes))
+    }
+    // The assertions `^` and `$` compile to.
+    pub fn start_anchor(self) -> Look {
+        match (self.multi_line, self.crlf) {
+            (false, _) => Look::Start,
+            (true, false) => Look::StartLine,
+            (true, true) => Look::StartCrlfLine,
+        }
+    }
+    pub fn end_anchor(self) -> Look {
+        match (self.multi_line, self.crlf) {
+            (false, _) => Look::End,
+            (true, false) => Look::EndLine,
+            (true, true) => Look::EndCrlfLine,
+        }
+    }
+    // The flags in effect after `term`, which inline flags like `(?i)`
+    // change up to the end of the enclosing group.
+    pub fn after(self, term: &Term) -> Self {
+        term.elements
+            .iter()
+            .fold(self, |flags, element| match element {
+                Element::Flags(changes) => flags.apply(changes),
+                _ => flags,
+            })
+    }
+}
+
+// The characters in `class`. When ignoring case, the items of the class are
+// folded before it is negated, so that `(?i)[^a]` matches neither `a` nor `A`.
+// Perl classes are left as they are, as folding doesn't lead out of them.
+fn class_set(class: &CharacterClass, flags: Flags) -> CharSet {
+    items_set(class.sign, &class.items, flags)
+}
+
+// Nested classes are worked out on their own and then added, like perl
+// classes.
+fn items_set(sign: Sign, items: &[Token], flags: Flags) -> CharSet {
+    let mut ranges = Vec::new();
+    let mut perl = Vec::new();
+    for token in items {
+        match token {
+            Token::Literal(c) => ranges.push((*c, *c)),
+            Token::Range(from, to) => ranges.push((*from, *to)),
+            Token::Perl(seq) => perl.extend_from_slice(perl_set(seq, flags).ranges()),
+            Token::Class(sign, items) => {
+                perl.extend_from_slice(items_set(*sign, items, flags).ranges())
+            }
+            Token::Named(_) => unreachable!("named classes are resolved by the builder"),
+        }
+    }
+    let items = CharSet::new(ranges);
+    let folded = if flags.case_insensitive && flags.ascii {
+        items.close_over(|c| [c.to_ascii_lowercase(), c.to_ascii_uppercase()])
+    } else if flags.case_insensitive {
+        items.close_over(case_variants)
+    } else {
+        items
+    };
+    let found = CharSet::new(folded.ranges().iter().copied().chain(perl));
+    match sign {
+        Sign::Inclusive => found,
+        Sign::Exclusive => found.complement(),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Perl {
+    Digit,
+    Space,
+    Word,
+}
+
+// The characters matched by `\d`, `\D`, `\s`, `\S`, `\w` or `\W`.
+fn perl_set(seq: &SpecialSequence, flags: Flags) -> CharSet {
+    let (class, negated) = match seq {
+        SpecialSequence::Digit => (Perl::Digit, false),
+        SpecialSequence::NotDigit => (Perl::Digit, true),
+        SpecialSequence::Whitespace => (Perl::Space, false),
+        SpecialSequence::NotWhitespace => (Perl::Space, true),
+        SpecialSequence::WordCharacter => (Perl::Word, false),
+        SpecialSequence::NotWordCharacter => (Perl::Word, true),
+        _ => unreachable!("not a perl class"),
+    };
+    let set = perl_class(class, flags);
+    if negated {
+        set.complement()
+    } else {
+        set
+    }
+}
+
+// The Hangul syllables of type LVT, or LV if `lvt` isn't set. Syllables are
+// laid out by leading consonant and vowel, with the 27 trailing consonants
+// after the syllable without one, which is of type LV.
+fn hangul_syllables(lvt: bool) -> CharSet {
+    let syllables = (0xAC00..=0xD7A3).step_by(28).map(|lv: u32| {
+        let lv = char::from_u32(lv).unwrap();
+        if lvt {
+            (
+                char::from_u32(lv as u32 + 1).unwrap(),
+                char::from_u32(lv as u32 + 27).unwrap(),
+            )
+        } else {
+            (lv, lv)
+        }
+    });
+    CharSet::new(syllables)
+}
+
+// The set of characters a perl class like `\d` stands for. The Unicode
+// versions are worked out by trying every character, once if the standard
+// library is there to keep them.
+fn perl_class(class: Perl, flags: Flags) -> CharSet {
+    let members = |class: Perl| {
+        let is_member = [is_digit, is_space, is_word_character][class as usize];
+        CharSet::from_predicate(|c| is_member(c, flags.ascii))
+    };
+    #[cfg(feature = "std")]
+    {
+        static UNICODE: OnceLock<[CharSet; 3]> = OnceLock::new();
+        static ASCII: OnceLock<[CharSet; 3]> = OnceLock::new();
+        let sets = if flags.ascii { &ASCII } else { &UNICODE };
+        let sets = sets.get_or_init(|| [Perl::Digit, Perl::Space, Perl::Word].map(members));
+        sets[class as usize].clone()
+    }
+    #[cfg(not(feature = "std"))]
+    members(class)
+}
+
+// Characters that simple case folding maps to the same character as their
+// key, but which the standard library's case mappings don't lead to.
+const FOLD_EXTRAS: [(char, &[char]); 15] = [
+    ('k', &['\u{212A}']),
+    ('s', &['\u{17F}']),
+    ('ß', &['\u{1E9E}']),
+    ('å', &['\u{212B}']),
+    ('β', &['\u{3D0}']),
+    ('ε', &['\u{3F5}']),
+    ('θ', &['\u{3D1}', '\u{3F4}']),
+    ('ι', &['\u{345}', '\u{1FBE}']),
+    ('κ', &['\u{3F0}']),
+    ('μ', &['\u{B5}']),
+    ('π', &['\u{3D6}']),
+    ('ρ', &['\u{3F1}']),
+    ('σ', &['\u{3C2}']),
+    ('φ', &['\u{3D5}']),
+    ('ω', &['\u{2126}']),
+];
+
+// All characters that are equal to `c` under simple Unicode case folding,
+// including `c` itself. Mappings to more than one character, like `ß` to
+// `SS`, are not simple and are left out.
+pub fn case_variants(c: char) -> impl Iterator<Item = char> {
+    fn simple<I: ExactSizeIterator<Item = char>>(mut mapped: I) -> Option<char> {
+        if mapped.len() == 1 {
+            mapped.next()
+        } else {
+            None
+        }
+    }
+    let folded = simple(c.to_uppercase())
+        .and_then(|upper| simple(upper.to_lowercase()))
+        .or_else(|| simple(c.to_lowercase()))
+        .unwrap_or(c);
+    let extras = FOLD_EXTRAS
+        .iter()
+        .find(|(key, _)| *key == folded)
+        .map_or(&[][..], |(_, extras)| extras);
+    [c, folded]
+        .into_iter()
+        .chain(simple(folded.to_uppercase()))
+        .chain(extras.iter().copied())
+}
+
+// The perl classes `\d`, `\s` and `\w`.
+fn is_digit(c: char, ascii: bool) -> bool {
+    if ascii {
+        c.is_ascii_digit()
+    } else {
+        in_table(perl::DECIMAL_NUMBER, c)
+    }
+}
+
+fn is_space(c: char, ascii: bool) -> bool {
+    if ascii {
+        matches!(c, '\t'..='\r' | ' ')
+    } else {
+        c.is_whitespace()
+    }
+}
+
+pub fn is_word_character(c: char, ascii: bool) -> bool {
+    if ascii {
+        c.is_ascii_alphanumeric() || c == '_'
+    } else {
+        in_table(perl::WORD, c)
+    }
+}
+
+fn in_table(table: &[(char, char)], c: char) -> bool {
+    table
+        .binary_search_by(|&(from, to)| match (from > c, to < c) {
+            (true, _) => Ordering::Greater,
+            (_, true) => Ordering::Less,
+            _ => Ordering::Equal,
+        })
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_regex;
+
+    fn translate(pattern: &str) -> Result<Hir, (ErrorKind, usize)> {
+        Hir::translate(&parse_regex(pattern).unwrap(), Flags::default(), 100)
+    }
+
+    #[test]
+    fn applies_flags_and_resolves_classes() {
+        let set = |ranges: &[(char, char)]| Hir::Class(CharSet::new(ranges.iter().copied()));
+        assert_eq!(translate("[c-ea-b]").unwrap(), set(&[('a', 'e')]));
+        let expected = Hir::Concat(vec![set(&[('a', 'a')]), set(&[('B', 'B'), ('b', 'b')])]);
+        assert_eq!(translate("a(?i)b").unwrap(), expected);
+        // Flags carry over to the alternatives after them, but not out of
+        // the group.
+        let expected = Hir::Concat(vec![
+            Hir::Alternation(vec![Hir::Empty, set(&[('C', 'C'), ('c', 'c')])]),
+            set(&[('d', 'd')]),
+        ]);
+        assert_eq!(translate("(?:(?i)|c)d").unwrap(), expected);
+        let anchors = Hir::Concat(vec![Hir::Look(Look::StartLine), Hir::Look(Look::EndLine)]);
+        assert_eq!(translate("(?m)^$").unwrap(), anchors);
+        let Hir::Alternation(branches) = translate(r"^a|\Ab|(?m)^c").unwrap() else {
+            panic!()
+        };
+        let anchored: Vec<bool> = branches.iter().map(Hir::is_anchored).collect();
+        assert_eq!(anchored, [true, true, false]);
+    }
+
+    #[test]
+    fn desugars_quantifiers() {
+        let a = || Box::new(Hir::Class(CharSet::new([('a', 'a')])));
+        let repeat = |min, max, greedy| Hir::Repeat {
+            sub: a(),
+            min,
+            max,
+            greedy,
+            element: Some(0),
+        };
+        assert_eq!(translate("a{2,5}").unwrap(), repeat(2, Some(5), true));
+        assert_eq!(translate("a{,5}").unwrap(), repeat(0, Some(5), true));
+        assert_eq!(translate("a*?").unwrap(), repeat(0, None, false));
+        assert_eq!(
+            translate("a?+").unwrap(),
+            Hir::Atomic(Box::new(repeat(0, Some(1), true)))
+        );
+        assert_eq!(
+            translate("a{101}"),
+            Err((ErrorKind::RepetitionLimitExceeded(100), 0))
+        );
+        assert_eq!(
+            translate("(?i)(b|a{101})"),
+            Err((ErrorKind::RepetitionLimitExceeded(100), 3))
+        );
+        let Hir::Concat(parts) = translate("(a)(?:(b)|(c))").unwrap() else {
+            panic!()
+        };
+        assert!(matches!(parts[0], Hir::Capture(1, _)));
+        assert!(
+            matches!(&parts[1], Hir::Alternation(branches) if matches!(branches[1], Hir::Capture(3, _)))
+        );
+        assert_eq!(Hir::Concat(parts).groups(), 3);
+    }
+
+    #[test]
+    fn finds_inner_literals() {
+        let literal = |pattern| {
+            translate(pattern)
+                .unwrap()
+                .inner_literal()
+                .map(|(before, literal)| (before.groups(), literal))
+        };
+        assert_eq!(
+            literal(r"\w+@example\.com/(\d+)"),
+            Some((0, "@example.com/".into()))
+        );
+        assert_eq!(literal(r"(\d)-(\d)+x:\d+"), Some((2, "x:".into())));
+        assert_eq!(literal(r"[a-z]+\d*\.rs:\d+ ok"), Some((0, ".rs:".into())));
+        // The part before may not reach past the literal, nor look around.
+        for pattern in [
+            r"(?:#@bb)?@b\w*",
+            r"\s\w+ \w+",
+            r"\b\w+@x",
+            "^a+bc",
+            "a+a",
+            "a|b+c",
+        ] {
+            assert_eq!(literal(pattern), None, "{pattern}");
+        }
+    }
+}
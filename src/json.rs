@@ -0,0 +1,752 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Display;
+
+use crate::error::JsonError;
+use crate::fsm::{self, Look, NFA};
+use crate::parser::Sign;
+
+// The version of the schema that `Automaton::to_json` writes.
+const VERSION: usize = 1;
+
+// The names of the assertions.
+const LOOKS: [(Look, &str); 12] = [
+    (Look::Start, "start"),
+    (Look::End, "end"),
+    (Look::StartLine, "start-line"),
+    (Look::EndLine, "end-line"),
+    (Look::StartCrlfLine, "start-crlf-line"),
+    (Look::EndCrlfLine, "end-crlf-line"),
+    (Look::EndBeforeNewline, "end-before-newline"),
+    (Look::SearchStart, "search-start"),
+    (Look::WordBoundary(false), "word-boundary"),
+    (Look::WordBoundary(true), "ascii-word-boundary"),
+    (Look::NotWordBoundary(false), "not-word-boundary"),
+    (Look::NotWordBoundary(true), "ascii-not-word-boundary"),
+];
+
+/// The automaton a [`Regex`](crate::Regex) is compiled to, as returned by
+/// [`Regex::automaton`](crate::Regex::automaton), for tools that analyze or
+/// visualize it.
+///
+/// It converts to and from JSON in a stable schema:
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "start": 0,
+///   "restart": 3,
+///   "accepting": [2],
+///   "states": [
+///     {"kind": "consume", "ranges": [[97, 122]], "next": 1},
+///     ...
+///   ]
+/// }
+/// ```
+///
+/// States are numbered by their position in `states`. Searches start in
+/// `start`, and begin new match attempts after the start of the haystack
+/// in `restart`, which is `null` if the pattern is anchored. The pattern has
+/// matched once a search reaches one of the `accepting` states. Every state
+/// has a `kind`:
+///
+/// - `consume`: consumes a character in one of the `ranges`, given as
+///   pairs of code points, and moves to `next`.
+/// - `split`: moves to all of its `targets` without consuming anything,
+///   preferring earlier ones.
+/// - `save`: records the position in capture `slot`, two per group, and
+///   moves to `next`.
+/// - `assert`: moves to `next` if the assertion `look` holds, one of
+///   `start`, `end`, `start-line`, `end-line`, `start-crlf-line`,
+///   `end-crlf-line`, `end-before-newline`, `search-start`,
+///   `word-boundary` and `not-word-boundary`, the last two also prefixed
+///   with `ascii-`.
+/// - `lookahead`: moves to `next` if its nested `automaton` matches at the
+///   position, or doesn't if it is `negated`.
+/// - `backreference`: consumes what `group` captured and moves to `next`.
+/// - `atomic`: consumes the first match of its nested `automaton` and moves
+///   to `next`.
+/// - `conditional`: moves to `yes` if `group` has captured anything, and
+///   to `no` otherwise.
+/// - `match`: the pattern has matched.
+///
+/// Nested automata have the same fields, except for `version`.
+///
+/// ```
+/// use regexrs::{Automaton, Regex};
+///
+/// let automaton = Regex::new("a+").unwrap().automaton();
+/// let json = automaton.to_json();
+/// assert!(json.contains(r#"{"kind": "consume", "ranges": [[97, 97]], "next": 1}"#));
+/// assert_eq!(Automaton::from_json(&json).unwrap(), automaton);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Automaton {
+    states: Vec<Node>,
+    start: usize,
+    restart: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Consume(Vec<(char, char)>, usize),
+    Split(Vec<usize>),
+    Save(usize, usize),
+    Assert(Look, usize),
+    Lookahead(bool, Automaton, usize),
+    Backref(usize, usize),
+    Atomic(Automaton, usize),
+    Conditional(usize, usize, usize),
+    Match,
+}
+
+impl Automaton {
+    pub(crate) fn new(nfa: &NFA) -> Self {
+        let states = nfa
+            .graph()
+            .iter()
+            .map(|node| match node {
+                fsm::Node::Consume(set, next) => Node::Consume(set.ranges().to_vec(), *next),
+                fsm::Node::Epsilon(targets) => Node::Split(targets.clone()),
+                fsm::Node::Save(slot, next) => Node::Save(*slot, *next),
+                fsm::Node::Assert(look, next) => Node::Assert(*look, *next),
+                fsm::Node::Lookahead(sign, sub, next) => {
+                    Node::Lookahead(*sign == Sign::Exclusive, Self::new(sub), *next)
+                }
+                fsm::Node::Backref(group, next) => Node::Backref(*group, *next),
+                fsm::Node::Atomic(sub, next) => Node::Atomic(Self::new(sub), *next),
+                fsm::Node::Conditional(group, yes, no) => Node::Conditional(*group, *yes, *no),
+                fsm::Node::Match => Node::Match,
+            })
+            .collect();
+        let (start, restart) = nfa.entries();
+        Self {
+            states,
+            start,
+            restart,
+        }
+    }
+    /// The number of states.
+    pub fn len(&self) -> usize {
+        self.states.len()
+    }
+    /// Always false, since every automaton has a state in which the pattern
+    /// has matched.
+    pub fn is_empty(&self) -> bool {
+        self.states.is_empty()
+    }
+    /// The state searches start in.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    /// The states in which the pattern has matched.
+    pub fn accepting(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.states.len()).filter(|&state| self.states[state] == Node::Match)
+    }
+    /// Writes the automaton as JSON, with a state per line.
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![format!("\"version\": {VERSION}")];
+        fields.extend(self.fields());
+        let states: Vec<String> = self.states.iter().map(Node::to_json).collect();
+        fields.push(format!(
+            "\"states\": [\n    {}\n  ]",
+            states.join(",\n    ")
+        ));
+        format!("{{\n  {}\n}}\n", fields.join(",\n  "))
+    }
+    /// Reads an automaton written by [`Automaton::to_json`], or by other
+    /// tools in the same schema.
+    pub fn from_json(json: &str) -> Result<Self, JsonError> {
+        let mut parser = Parser { json, position: 0 };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.position < json.len() {
+            return Err(JsonError::Syntax(parser.position));
+        }
+        let version = value.get("version")?.number()?;
+        if version != VERSION {
+            return Err(JsonError::UnsupportedVersion(version));
+        }
+        Self::from_value(&value)
+    }
+    fn fields(&self) -> Vec<String> {
+        let restart = self
+            .restart
+            .map_or("null".to_string(), |restart| restart.to_string());
+        let accepting: Vec<String> = self.accepting().map(|state| state.to_string()).collect();
+        vec![
+            format!("\"start\": {}", self.start),
+            format!("\"restart\": {restart}"),
+            format!("\"accepting\": [{}]", accepting.join(", ")),
+        ]
+    }
+    // On a single line, as nested automata are written.
+    fn to_compact_json(&self) -> String {
+        let mut fields = self.fields();
+        let states: Vec<String> = self.states.iter().map(Node::to_json).collect();
+        fields.push(format!("\"states\": [{}]", states.join(", ")));
+        format!("{{{}}}", fields.join(", "))
+    }
+    fn from_value(value: &Value) -> Result<Self, JsonError> {
+        let states = value
+            .get("states")?
+            .array()?
+            .iter()
+            .map(Node::from_value)
+            .collect::<Result<Vec<_>, _>>()?;
+        let restart = match value.get("restart")? {
+            Value::Null => None,
+            restart => Some(restart.number()?),
+        };
+        let automaton = Self {
+            states,
+            start: value.get("start")?.number()?,
+            restart,
+        };
+        let accepting = value
+            .get("accepting")?
+            .array()?
+            .iter()
+            .map(Value::number)
+            .collect::<Result<Vec<_>, _>>()?;
+        if !automaton.accepting().eq(accepting) {
+            return Err(invalid("the accepting states are not the `match` states"));
+        }
+        let len = automaton.len();
+        let mut targets = automaton
+            .states
+            .iter()
+            .flat_map(Node::targets)
+            .chain([automaton.start]);
+        if targets.any(|target| target >= len)
+            || automaton.restart.is_some_and(|restart| restart >= len)
+        {
+            return Err(invalid("a transition leads to a state that doesn't exist"));
+        }
+        Ok(automaton)
+    }
+}
+
+/// Shows the automaton as a table with a row per state, like
+///
+/// ```text
+/// start 4, restart 3
+///    0  match
+///    1  split → 2, 0
+///    2  a-z → 1
+/// ```
+///
+/// Nested automata are shown indented below their state.
+impl Display for Automaton {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "start {}", self.start)?;
+        if let Some(restart) = self.restart {
+            write!(f, ", restart {restart}")?;
+        }
+        for (idx, node) in self.states.iter().enumerate() {
+            write!(f, "\n{idx:>4}  ")?;
+            let nested = match node {
+                Node::Consume(ranges, next) => {
+                    let class: String = ranges.iter().map(|&(from, to)| label(from, to)).collect();
+                    match ranges.as_slice() {
+                        [(from, to)] if from == to => write!(f, "{class} → {next}")?,
+                        _ => write!(f, "[{class}] → {next}")?,
+                    }
+                    None
+                }
+                Node::Split(targets) => {
+                    let targets: Vec<String> =
+                        targets.iter().map(|target| target.to_string()).collect();
+                    write!(f, "split → {}", targets.join(", "))?;
+                    None
+                }
+                Node::Save(slot, next) => {
+                    write!(f, "save {slot} → {next}")?;
+                    None
+                }
+                Node::Assert(look, next) => {
+                    write!(f, "assert {} → {next}", look_name(*look))?;
+                    None
+                }
+                Node::Lookahead(negated, sub, next) => {
+                    let kind = if *negated {
+                        "negative lookahead"
+                    } else {
+                        "lookahead"
+                    };
+                    write!(f, "{kind} → {next}")?;
+                    Some(sub)
+                }
+                Node::Backref(group, next) => {
+                    write!(f, "backreference {group} → {next}")?;
+                    None
+                }
+                Node::Atomic(sub, next) => {
+                    write!(f, "atomic → {next}")?;
+                    Some(sub)
+                }
+                Node::Conditional(group, yes, no) => {
+                    write!(f, "if group {group} → {yes}, else → {no}")?;
+                    None
+                }
+                Node::Match => {
+                    write!(f, "match")?;
+                    None
+                }
+            };
+            if let Some(sub) = nested {
+                for line in sub.to_string().lines() {
+                    write!(f, "\n        {line}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Node {
+    fn to_json(&self) -> String {
+        match self {
+            Self::Consume(ranges, next) => {
+                let ranges: Vec<String> = ranges
+                    .iter()
+                    .map(|&(from, to)| format!("[{}, {}]", from as u32, to as u32))
+                    .collect();
+                format!("{{\"kind\": \"consume\", \"ranges\": [{}], \"next\": {next}}}", ranges.join(", "))
+            }
+            Self::Split(targets) => {
+                let targets: Vec<String> = targets.iter().map(|target| target.to_string()).collect();
+                format!("{{\"kind\": \"split\", \"targets\": [{}]}}", targets.join(", "))
+            }
+            Self::Save(slot, next) => format!("{{\"kind\": \"save\", \"slot\": {slot}, \"next\": {next}}}"),
+            Self::Assert(look, next) => {
+                format!("{{\"kind\": \"assert\", \"look\": \"{}\", \"next\": {next}}}", look_name(*look))
+            }
+            Self::Lookahead(negated, sub, next) => format!(
+                "{{\"kind\": \"lookahead\", \"negated\": {negated}, \"automaton\": {}, \"next\": {next}}}",
+                sub.to_compact_json()
+            ),
+            Self::Backref(group, next) => {
+                format!("{{\"kind\": \"backreference\", \"group\": {group}, \"next\": {next}}}")
+            }
+            Self::Atomic(sub, next) => format!(
+                "{{\"kind\": \"atomic\", \"automaton\": {}, \"next\": {next}}}",
+                sub.to_compact_json()
+            ),
+            Self::Conditional(group, yes, no) => {
+                format!("{{\"kind\": \"conditional\", \"group\": {group}, \"yes\": {yes}, \"no\": {no}}}")
+            }
+            Self::Match => "{\"kind\": \"match\"}".to_string(),
+        }
+    }
+    fn from_value(value: &Value) -> Result<Self, JsonError> {
+        let number = |key| value.get(key)?.number();
+        let node = match value.get("kind")?.string()? {
+            "consume" => {
+                let ranges = value
+                    .get("ranges")?
+                    .array()?
+                    .iter()
+                    .map(range)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Self::Consume(ranges, number("next")?)
+            }
+            "split" => {
+                let targets = value.get("targets")?.array()?.iter().map(Value::number);
+                Self::Split(targets.collect::<Result<Vec<_>, _>>()?)
+            }
+            "save" => Self::Save(number("slot")?, number("next")?),
+            "assert" => {
+                let name = value.get("look")?.string()?;
+                let (look, _) = LOOKS
+                    .iter()
+                    .find(|(_, known)| *known == name)
+                    .ok_or_else(|| invalid(&format!("unknown assertion `{name}`")))?;
+                Self::Assert(*look, number("next")?)
+            }
+            "lookahead" => {
+                let sub = Automaton::from_value(value.get("automaton")?)?;
+                Self::Lookahead(value.get("negated")?.boolean()?, sub, number("next")?)
+            }
+            "backreference" => Self::Backref(number("group")?, number("next")?),
+            "atomic" => Self::Atomic(
+                Automaton::from_value(value.get("automaton")?)?,
+                number("next")?,
+            ),
+            "conditional" => Self::Conditional(number("group")?, number("yes")?, number("no")?),
+            "match" => Self::Match,
+            kind => return Err(invalid(&format!("unknown kind of state `{kind}`"))),
+        };
+        Ok(node)
+    }
+    // The states this one moves to.
+    fn targets(&self) -> Vec<usize> {
+        match self {
+            Self::Split(targets) => targets.clone(),
+            Self::Conditional(_, yes, no) => vec![*yes, *no],
+            Self::Consume(_, next)
+            | Self::Save(_, next)
+            | Self::Assert(_, next)
+            | Self::Lookahead(_, _, next)
+            | Self::Backref(_, next)
+            | Self::Atomic(_, next) => vec![*next],
+            Self::Match => vec![],
+        }
+    }
+}
+
+fn look_name(look: Look) -> &'static str {
+    let (_, name) = LOOKS
+        .iter()
+        .find(|(known, _)| *known == look)
+        .expect("all assertions are named");
+    name
+}
+
+// A range of characters as shown in a class, with the characters that
+// have a meaning there escaped.
+fn label(from: char, to: char) -> String {
+    let escape = |c: char| match c {
+        '-' | '[' | ']' | '^' | '\\' => format!("\\{c}"),
+        _ => c.escape_debug().to_string(),
+    };
+    match from == to {
+        true => escape(from),
+        false => format!("{}-{}", escape(from), escape(to)),
+    }
+}
+
+// A range of characters, as a pair of code points.
+fn range(value: &Value) -> Result<(char, char), JsonError> {
+    let char = |value: &Value| {
+        let code = u32::try_from(value.number()?).ok();
+        code.and_then(char::from_u32)
+            .ok_or_else(|| invalid("a range has an invalid code point"))
+    };
+    match value.array()? {
+        [from, to] if char(from)? <= char(to)? => Ok((char(from)?, char(to)?)),
+        _ => Err(invalid("a range isn't a pair of ascending code points")),
+    }
+}
+
+fn invalid(message: &str) -> JsonError {
+    JsonError::Invalid(message.to_string())
+}
+
+// A parsed JSON value. Only numbers that are indices are of interest.
+enum Value {
+    Null,
+    Bool(bool),
+    Number(Option<usize>),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    fn get(&self, key: &str) -> Result<&Value, JsonError> {
+        let Self::Object(fields) = self else {
+            return Err(invalid("expected an object"));
+        };
+        match fields.iter().find(|(name, _)| name == key) {
+            Some((_, value)) => Ok(value),
+            None => Err(invalid(&format!("missing field `{key}`"))),
+        }
+    }
+    fn number(&self) -> Result<usize, JsonError> {
+        match self {
+            Self::Number(Some(number)) => Ok(*number),
+            _ => Err(invalid("expected an index")),
+        }
+    }
+    fn boolean(&self) -> Result<bool, JsonError> {
+        match self {
+            Self::Bool(value) => Ok(*value),
+            _ => Err(invalid("expected a boolean")),
+        }
+    }
+    fn string(&self) -> Result<&str, JsonError> {
+        match self {
+            Self::String(value) => Ok(value),
+            _ => Err(invalid("expected a string")),
+        }
+    }
+    fn array(&self) -> Result<&[Value], JsonError> {
+        match self {
+            Self::Array(values) => Ok(values),
+            _ => Err(invalid("expected an array")),
+        }
+    }
+}
+
+// Parses JSON, failing with the byte offset at which it isn't valid.
+struct Parser<'a> {
+    json: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn value(&mut self) -> Result<Value, JsonError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            _ if self.keyword("null") => Ok(Value::Null),
+            _ if self.keyword("true") => Ok(Value::Bool(true)),
+            _ if self.keyword("false") => Ok(Value::Bool(false)),
+            _ => Err(JsonError::Syntax(self.position)),
+        }
+    }
+    fn object(&mut self) -> Result<Value, JsonError> {
+        self.expect(b'{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Value::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            fields.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b'}') => {
+                    self.position += 1;
+                    return Ok(Value::Object(fields));
+                }
+                _ => return Err(JsonError::Syntax(self.position)),
+            }
+        }
+    }
+    fn array(&mut self) -> Result<Value, JsonError> {
+        self.expect(b'[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.position += 1,
+                Some(b']') => {
+                    self.position += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(JsonError::Syntax(self.position)),
+            }
+        }
+    }
+    fn string(&mut self) -> Result<String, JsonError> {
+        self.expect(b'"')?;
+        let mut value = String::new();
+        loop {
+            let start = self.position;
+            let c = self.json[start..]
+                .chars()
+                .next()
+                .ok_or(JsonError::Syntax(start))?;
+            self.position += c.len_utf8();
+            match c {
+                '"' => return Ok(value),
+                '\\' => value.push(self.escape()?),
+                '\0'..='\x1F' => return Err(JsonError::Syntax(start)),
+                c => value.push(c),
+            }
+        }
+    }
+    // The character escaped after a backslash.
+    fn escape(&mut self) -> Result<char, JsonError> {
+        let start = self.position;
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\x08',
+            Some(b'f') => '\x0C',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.position += 1;
+                let high = self.hex()?;
+                // Characters outside the basic plane are escaped as a
+                // surrogate pair.
+                let code = match high {
+                    0xD800..=0xDBFF if self.json[self.position..].starts_with("\\u") => {
+                        self.position += 2;
+                        let low = self.hex()?;
+                        if !(0xDC00..=0xDFFF).contains(&low) {
+                            return Err(JsonError::Syntax(start));
+                        }
+                        0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                    }
+                    code => code,
+                };
+                return char::from_u32(code).ok_or(JsonError::Syntax(start));
+            }
+            _ => return Err(JsonError::Syntax(start)),
+        };
+        self.position += 1;
+        Ok(c)
+    }
+    fn hex(&mut self) -> Result<u32, JsonError> {
+        let digits = self.json.get(self.position..self.position + 4);
+        let code = digits
+            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()))
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or(JsonError::Syntax(self.position))?;
+        self.position += 4;
+        Ok(code)
+    }
+    fn number(&mut self) -> Result<Value, JsonError> {
+        let start = self.position;
+        let rest = &self.json.as_bytes()[start..];
+        let len = rest
+            .iter()
+            .position(|byte| !matches!(byte, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+            .unwrap_or(rest.len());
+        let text = &self.json[start..start + len];
+        // Anything else than digits has to be a valid number, but can't be
+        // an index.
+        let digits = text.bytes().all(|byte| byte.is_ascii_digit());
+        if !digits && !valid_number(text) || digits && text.len() > 1 && text.starts_with('0') {
+            return Err(JsonError::Syntax(start));
+        }
+        self.position += len;
+        Ok(Value::Number(text.parse().ok().filter(|_| digits)))
+    }
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.json[self.position..].starts_with(keyword);
+        if found {
+            self.position += keyword.len();
+        }
+        found
+    }
+    fn expect(&mut self, byte: u8) -> Result<(), JsonError> {
+        if self.peek() != Some(byte) {
+            return Err(JsonError::Syntax(self.position));
+        }
+        self.position += 1;
+        Ok(())
+    }
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.position).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+}
+
+// Whether `text` is a JSON number: an optional minus, an integer without
+// leading zeros, an optional fraction and an optional exponent.
+fn valid_number(text: &str) -> bool {
+    let text = text.strip_prefix('-').unwrap_or(text);
+    let (mantissa, exponent) = match text.find(['e', 'E']) {
+        Some(idx) => (&text[..idx], Some(&text[idx + 1..])),
+        None => (text, None),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((integer, fraction)) => (integer, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit());
+    let exponent = exponent.map(|exponent| exponent.strip_prefix(['+', '-']).unwrap_or(exponent));
+    digits(integer)
+        && (integer == "0" || !integer.starts_with('0'))
+        && fraction.is_none_or(digits)
+        && exponent.is_none_or(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Regex;
+
+    #[test]
+    fn round_trips_through_json() {
+        for pattern in [
+            r"(?m)^(\w+)\b$",
+            "a|b*",
+            r"(?=x)(?!y)\d",
+            r"(a)\1",
+            "(?>a+)b",
+            "(a)?(?(1)b|c)",
+            "[^é]",
+            "",
+        ] {
+            let automaton = Regex::new(pattern).unwrap().automaton();
+            let json = automaton.to_json();
+            assert_eq!(Automaton::from_json(&json).unwrap(), automaton, "{pattern}");
+        }
+        let json = Regex::new("^ab").unwrap().automaton().to_json();
+        assert!(json.starts_with("{\n  \"version\": 1,\n  \"start\": "));
+        assert!(json.contains("\n  \"restart\": null,\n"));
+        assert!(json.contains("{\"kind\": \"assert\", \"look\": \"start\", \"next\": "));
+        assert!(json.ends_with("}\n  ]\n}\n"));
+    }
+
+    #[test]
+    fn displays_a_row_per_state() {
+        let table = Regex::new(r"(?=ab)[a-z\-]+\b|x")
+            .unwrap()
+            .automaton()
+            .to_string();
+        assert!(
+            table.starts_with("start 7, restart 6\n   0  match\n   1  assert word-boundary → 0\n")
+        );
+        assert!(table.contains("\n   3  [\\-a-z] → 2\n   4  lookahead → 3\n        start 2\n"));
+        assert!(table.contains("\n   5  x → 0\n"));
+    }
+
+    #[test]
+    fn reads_json_from_elsewhere() {
+        let json = r#" { "states" : [ {"next": 1, "ranges": [[9731, 9731]], "kind": "consume"},
+            {"kind": "match", "note": [1.5e3, -2, {}, "😀"]} ], "restart": null,
+            "accepting": [1], "start": 0, "version": 1 } "#;
+        let automaton = Automaton::from_json(json).unwrap();
+        assert_eq!(automaton.states[0], Node::Consume(vec![('☃', '☃')], 1));
+        assert_eq!(automaton.accepting().collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        let json = Regex::new("ab").unwrap().automaton().to_json();
+        assert_eq!(
+            Automaton::from_json("{\"version\": 1,}"),
+            Err(JsonError::Syntax(14))
+        );
+        assert_eq!(Automaton::from_json("[01]"), Err(JsonError::Syntax(1)));
+        assert_eq!(
+            Automaton::from_json(&json.replace("\"version\": 1", "\"version\": 2")),
+            Err(JsonError::UnsupportedVersion(2))
+        );
+        let missing = json.replace("\"start\"", "\"begin\"");
+        assert_eq!(
+            Automaton::from_json(&missing),
+            Err(JsonError::Invalid("missing field `start`".into()))
+        );
+        let astray = json.replace("\"next\": 1", "\"next\": 99");
+        assert!(matches!(
+            Automaton::from_json(&astray),
+            Err(JsonError::Invalid(_))
+        ));
+        let accepting = json.replace("\"accepting\": [", "\"accepting\": [0, ");
+        assert!(matches!(
+            Automaton::from_json(&accepting),
+            Err(JsonError::Invalid(_))
+        ));
+    }
+}
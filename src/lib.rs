@@ -0,0 +1,3268 @@
+//! regexrs -- The regex library no one asked for
+//!
+//! Parses regexes in Python syntax, converts them into finite state machines
+//! and executes them for simple pattern matching.
+//!
+//! Without the default `std` feature, the library only needs `alloc`. It
+//! then has no deadlines for searches, and searches don't share caches.
+//!
+//! Without the default `unicode-names` feature, the library leaves out the
+//! table of Unicode character names, and `\N{...}` escapes only know the
+//! names made up from code points, like `CJK UNIFIED IDEOGRAPH-4E00`.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+use alloc::borrow::Cow;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::AsRef;
+use core::fmt::Debug;
+use core::hash::{Hash, Hasher};
+use core::ops::Range;
+use core::str::FromStr;
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+mod aho_corasick;
+mod analysis;
+pub mod ast;
+mod builder;
+mod bytes;
+mod cache;
+mod captures;
+mod charset;
+mod collections;
+mod dense;
+#[cfg(test)]
+mod derivative;
+mod dfa;
+mod enumerate;
+mod error;
+mod fixed;
+mod fsm;
+mod generate;
+mod grapheme;
+mod hir;
+mod json;
+mod matcher;
+mod names;
+mod onepass;
+mod parser;
+mod perl;
+mod scanner;
+#[cfg(feature = "serde")]
+mod serialize;
+mod set;
+mod simplify;
+mod stream;
+mod trace;
+mod utf8;
+
+pub use analysis::{analyze, CompileWarnings, Report, Risk, Warning};
+pub use builder::{InvalidBytes, MatchKind, RegexBuilder};
+pub use cache::Cache;
+pub use captures::{CaptureLocations, Captures};
+pub use dense::Dfa;
+pub use enumerate::Strings;
+pub use error::{DeserializeError, Error, ErrorKind, JsonError, MatchError};
+pub use fixed::{FixedCaptures, FixedRegex};
+pub use fsm::State;
+pub use generate::Rng;
+pub use json::Automaton;
+pub use matcher::{Engine, Matcher};
+pub use scanner::{Lexeme, Location, Scanner};
+pub use set::{RegexSet, SetMatches, Tokens};
+pub use stream::{Status, StreamMatcher};
+pub use trace::{MatchTrace, Outcome, TraceStep, Tracer};
+
+use aho_corasick::AhoCorasick;
+use cache::Pool;
+use dfa::{equivalent, LazyDFA, Search, DFA};
+use fsm::{Budget, ReverseSuffix, Slots, NFA};
+use matcher::EngineMatcher;
+use onepass::OnePass;
+use utf8::ByteFSM;
+
+/// A compiled regular expression.
+///
+/// # Untrusted patterns and haystacks
+///
+/// Searches take time linear in the length of the haystack for patterns
+/// without lookaheads, backreferences, atomic groups and conditionals,
+/// whichever pattern and haystack it is. Lookaheads are tried at every
+/// position the search gets to, which may make it quadratic.
+///
+/// Backreferences, atomic groups and conditionals need backtracking, which
+/// may take far longer. A search then gives up after a limited number of
+/// steps, see [`RegexBuilder::backtrack_limit`]. To bound the time a
+/// search takes regardless, use [`Regex::find_with_deadline`].
+///
+/// # Sharing
+///
+/// A `Regex` can be used from several threads at once, and cloning it is
+/// cheap, since clones share the compiled pattern. Searches keep the states
+/// the lazy DFAs build in a [`Cache`], of which every thread usually gets
+/// its own.
+#[derive(Clone)]
+pub struct Regex {
+    inner: Arc<Inner>,
+}
+
+// The compiled pattern, which clones of a `Regex` share.
+struct Inner {
+    pattern: String,
+    ast: Vec<ast::Term>,
+    warnings: CompileWarnings,
+    nfa: NFA,
+    // The literal every match starts with, which searches look for first.
+    prefix: Option<String>,
+    // The ways of writing the prefix if it has letters that match in any
+    // case, for patterns without a literal one.
+    prefixes: Option<AhoCorasick>,
+    // The literal every match ends with, without which there is no match.
+    suffix: Option<String>,
+    // Whether searches without a prefix to skip ahead to find matches back
+    // from where the suffix occurs.
+    reverse_suffix: Option<ReverseSuffix>,
+    // Finds the matches of patterns that are literals or alternations of
+    // them without any of the other engines.
+    literals: Option<AhoCorasick>,
+    // The same automaton working on bytes, unless the pattern needs
+    // features only the character-based engines have.
+    bytes: Option<ByteFSM>,
+    // Built from the byte automaton if asked for and small enough, and built
+    // lazily otherwise.
+    dfa: Option<DFA>,
+    lazy: Option<LazyDFA>,
+    // Finds where matches start and end without the Pike VM, unless the
+    // pattern prefers the longest match.
+    search: Option<Search>,
+    // Extracts captures without the Pike VM, if the pattern allows it.
+    onepass: Option<OnePass>,
+    names: Arc<[Option<String>]>,
+    // How many steps backtracking may take in a single search.
+    backtrack_limit: usize,
+    // What searches through bytes do at bytes that aren't part of valid
+    // UTF-8, and at the quit bytes, one bit for each ASCII byte.
+    invalid_bytes: InvalidBytes,
+    quit_bytes: u128,
+    // How many bytes DFAs built for other purposes may take up.
+    dfa_size_limit: usize,
+    tracer: Option<Arc<dyn Tracer>>,
+    pool: Pool<Cache>,
+}
+
+impl Debug for Regex {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_tuple("Regex").field(&self.inner.pattern).finish()
+    }
+}
+
+/// Regexes are compared and hashed by their patterns, regardless of how
+/// they were configured.
+impl PartialEq for Regex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Regex {}
+
+impl Hash for Regex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+/// Compiles a pattern with [`Regex::new`].
+///
+/// ```
+/// use regexrs::Regex;
+///
+/// let regex: Regex = r"\d+".parse().unwrap();
+/// assert!(regex.is_match("42"));
+/// ```
+impl FromStr for Regex {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Error> {
+        Self::new(pattern)
+    }
+}
+
+impl TryFrom<&str> for Regex {
+    type Error = Error;
+
+    fn try_from(pattern: &str) -> Result<Self, Error> {
+        Self::new(pattern)
+    }
+}
+
+impl TryFrom<String> for Regex {
+    type Error = Error;
+
+    fn try_from(pattern: String) -> Result<Self, Error> {
+        Self::new(pattern)
+    }
+}
+
+impl Regex {
+    /// Compiles `regex` into a matcher, failing if it isn't a valid pattern.
+    /// Things that are probably mistakes don't keep it from compiling, but
+    /// come up in [`Regex::warnings`].
+    ///
+    /// Use a [`RegexBuilder`] to set flags or limits.
+    pub fn new<S: AsRef<str>>(regex: S) -> Result<Self, Error> {
+        RegexBuilder::new(regex).build()
+    }
+    /// Escapes all metacharacters in `text`, so that the result is a
+    /// pattern matching `text` literally.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new(&format!("^{}$", Regex::escape("1+1=2?"))).unwrap();
+    /// assert!(regex.is_match("1+1=2?"));
+    /// ```
+    pub fn escape(text: &str) -> String {
+        parser::escape(text)
+    }
+    /// Returns the pattern the regex was compiled from.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// assert_eq!(Regex::new(r"\w+").unwrap().as_str(), r"\w+");
+    /// ```
+    pub fn as_str(&self) -> &str {
+        &self.inner.pattern
+    }
+    /// Compiles a syntax tree with the default settings, see
+    /// [`RegexBuilder::from_ast`].
+    ///
+    /// ```
+    /// use regexrs::{ast, Regex};
+    ///
+    /// let mut terms = ast::parse("colou?r").unwrap();
+    /// terms.extend(ast::parse("hue").unwrap());
+    /// let regex = Regex::from_ast(terms).unwrap();
+    /// assert_eq!(regex.as_str(), "colou?r|hue");
+    /// assert!(regex.is_match("hue"));
+    /// ```
+    pub fn from_ast(terms: Vec<ast::Term>) -> Result<Self, Error> {
+        RegexBuilder::from_ast(terms).build()
+    }
+    /// The syntax tree the regex was compiled from, after
+    /// [`RegexBuilder::simplify`] if that is enabled.
+    ///
+    /// ```
+    /// use regexrs::{ast, Regex};
+    ///
+    /// let regex = Regex::new("a|b").unwrap();
+    /// assert_eq!(regex.ast().len(), 2);
+    /// assert_eq!(ast::to_pattern(regex.ast()), "a|b");
+    /// ```
+    pub fn ast(&self) -> &[ast::Term] {
+        &self.inner.ast
+    }
+    /// Things about the pattern that are probably mistakes, like
+    /// alternatives or anchors that never match, see [`CompileWarnings`].
+    pub fn warnings(&self) -> &CompileWarnings {
+        &self.inner.warnings
+    }
+    /// Returns true if the pattern matches anywhere in `string`.
+    pub fn is_match<S: AsRef<str>>(&self, string: S) -> bool {
+        self.with_cache(|cache| self.is_match_within(string.as_ref(), &self.budget(), cache))
+    }
+    /// Like [`Regex::is_match`], but fails if the search gives up, as
+    /// described in [`RegexBuilder::backtrack_limit`].
+    pub fn try_is_match(&self, string: &str) -> Result<bool, MatchError> {
+        let budget = self.budget();
+        let matched = self.with_cache(|cache| self.is_match_within(string, &budget, cache));
+        self.check(&budget)?;
+        Ok(matched)
+    }
+    fn is_match_within(&self, string: &str, budget: &Budget, cache: &mut Cache) -> bool {
+        if self.inner.tracer.is_some() && !self.inner.nfa.backtracks() {
+            return self.is_match_chars(string.char_indices(), string, budget);
+        }
+        if let Some(literals) = &self.inner.literals {
+            return literals.find_at(string, 0, false).is_some();
+        }
+        let mut required = [&self.inner.prefix, &self.inner.suffix]
+            .into_iter()
+            .flatten();
+        if required.any(|literal| !string.contains(literal.as_str())) {
+            return false;
+        }
+        if self
+            .inner
+            .prefixes
+            .as_ref()
+            .is_some_and(|prefixes| prefixes.find_at(string, 0, false).is_none())
+        {
+            return false;
+        }
+        if let Some(matched) = self.is_match_by_literal(string, cache) {
+            return matched;
+        }
+        if let Some(dfa) = &self.inner.dfa {
+            return dfa.is_match(string.as_bytes());
+        }
+        if let Some(bytes) = &self.inner.bytes {
+            let Some((lazy, cache)) = self.inner.lazy.as_ref().zip(cache.lazy.as_mut()) else {
+                return bytes.is_match(string.as_bytes());
+            };
+            return match lazy.is_match(bytes, cache, string.as_bytes()) {
+                Ok(matched) => matched,
+                // The simulation goes on from where the lazy DFA gave up.
+                Err(abandoned) => {
+                    let nodes = bytes.char_nodes(&self.inner.nfa, &abandoned.nodes);
+                    let states = self
+                        .inner
+                        .nfa
+                        .resume(&nodes, string, abandoned.position, budget);
+                    let chars = string[abandoned.position..].char_indices();
+                    let chars = chars.map(|(idx, c)| (abandoned.position + idx, c));
+                    self.is_match_from(states, chars, string, budget)
+                }
+            };
+        }
+        if self.inner.nfa.backtracks() {
+            return self
+                .captures_at(string, 0, 2, Anchored::No, budget, cache)
+                .is_some();
+        }
+        self.is_match_chars(string.char_indices(), string, budget)
+    }
+    /// Like [`Regex::is_match`], but walks an arbitrary character iterator.
+    ///
+    /// Patterns with lookaheads or backreferences need to see more of the
+    /// input than the current character, so for them the characters are
+    /// collected into a string first.
+    pub fn is_match_iter<I: Iterator<Item = char>>(&self, chars: I) -> bool {
+        if self.inner.nfa.looks_ahead() || self.inner.nfa.backtracks() {
+            return self.is_match(chars.collect::<String>());
+        }
+        let positions = chars.scan(0, |position, c| {
+            let idx = *position;
+            *position += c.len_utf8();
+            Some((idx, c))
+        });
+        self.is_match_chars(positions, "", &self.budget())
+    }
+    /// Like [`Regex::find_iter`], but walks an arbitrary character iterator,
+    /// e.g. one decoding UTF-16. Matches are ranges of indices into the
+    /// sequence of characters instead of byte offsets.
+    ///
+    /// The characters are collected into a string first.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let utf16: Vec<u16> = "né 42, 7".encode_utf16().collect();
+    /// let chars = char::decode_utf16(utf16).map(Result::unwrap);
+    /// let numbers: Vec<_> = Regex::new(r"\d+").unwrap().find_iter_chars(chars).collect();
+    /// assert_eq!(numbers, [3..5, 7..8]);
+    /// ```
+    pub fn find_iter_chars<I: IntoIterator<Item = char>>(&self, chars: I) -> CharMatches<'_> {
+        CharMatches {
+            regex: self,
+            haystack: chars.into_iter().collect(),
+            position: 0,
+            last_end: None,
+            counted: (0, 0),
+        }
+    }
+    /// Returns the DFA built for [`Regex::is_match`], to be saved with
+    /// [`Dfa::to_bytes`].
+    ///
+    /// This returns `None` unless the regex was built with
+    /// [`RegexBuilder::prefer_dfa`] and the pattern could have a DFA.
+    pub fn to_dfa(&self) -> Option<Dfa> {
+        let bytes = self.inner.dfa.as_ref()?.to_bytes()?;
+        Dfa::from_bytes(bytes).ok()
+    }
+    /// Returns true if both regexes match exactly the same haystacks in
+    /// full, as with [`Regex::is_full_match`], regardless of what they
+    /// capture. This is decided by building DFAs for both, which may take
+    /// time and memory exponential in the length of the patterns; when that
+    /// would take up more than the larger of their
+    /// [DFA size limits](RegexBuilder::dfa_size_limit), this returns false.
+    ///
+    /// So does it for regexes with features the DFAs don't support, like
+    /// lookaheads, backreferences and Unicode word boundaries, unless they
+    /// are the same regex.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new("colou?r|hue").unwrap();
+    /// assert!(regex.is_equivalent(&Regex::new("hue|colo(?:u|)r").unwrap()));
+    /// assert!(!regex.is_equivalent(&Regex::new("colou*r|hue").unwrap()));
+    /// ```
+    pub fn is_equivalent(&self, other: &Regex) -> bool {
+        if Arc::ptr_eq(&self.inner, &other.inner) {
+            return true;
+        }
+        let size_limit = self.inner.dfa_size_limit.max(other.inner.dfa_size_limit);
+        match (&self.inner.bytes, &other.inner.bytes) {
+            (Some(bytes), Some(other)) => equivalent(bytes, other, size_limit).unwrap_or(false),
+            _ => false,
+        }
+    }
+    /// Returns the automaton the pattern is compiled to, which can be
+    /// written as JSON with [`Automaton::to_json`].
+    pub fn automaton(&self) -> Automaton {
+        Automaton::new(&self.inner.nfa)
+    }
+    /// Searches `haystack` like [`Regex::is_match`], recording the states
+    /// of the automaton at every position and why the search ended, to
+    /// find out why a pattern does or doesn't match. See [`MatchTrace`].
+    ///
+    /// The search always steps through the automaton a character at a
+    /// time, so it is slow, but matches the same as the faster engines.
+    pub fn debug_match<S: AsRef<str>>(&self, haystack: S) -> MatchTrace {
+        let haystack = haystack.as_ref();
+        let budget = self.budget();
+        if self.inner.nfa.backtracks() {
+            let matched = self.with_cache(|cache| self.is_match_within(haystack, &budget, cache));
+            let outcome = match budget.exhausted() {
+                true => Outcome::GaveUp,
+                false => Outcome::Backtracked(matched),
+            };
+            return MatchTrace::new(vec![], outcome);
+        }
+        let mut states = self.inner.nfa.start(haystack, &budget);
+        let mut steps = vec![TraceStep::new(0, None, states.clone())];
+        let mut outcome = Outcome::EndOfHaystack;
+        for (idx, c) in haystack.char_indices() {
+            if states.contains(&State::Success) {
+                outcome = Outcome::Matched(idx);
+                break;
+            }
+            let position = idx + c.len_utf8();
+            states = self
+                .inner
+                .nfa
+                .next_unanchored(&states, c, haystack, position, &budget);
+            steps.push(TraceStep::new(position, Some(c), states.clone()));
+            if states.is_empty() {
+                outcome = Outcome::DeadEnd(idx);
+                break;
+            }
+        }
+        if outcome == Outcome::EndOfHaystack && states.contains(&State::Success) {
+            outcome = Outcome::Matched(haystack.len());
+        }
+        if budget.exhausted() {
+            outcome = Outcome::GaveUp;
+        }
+        MatchTrace::new(steps, outcome)
+    }
+    /// Returns a [`StreamMatcher`] for input that arrives in chunks.
+    ///
+    /// The matcher needs a DFA, so this returns `None` for the patterns
+    /// that can't have one, as described in [`RegexBuilder::prefer_dfa`].
+    pub fn stream_matcher(&self) -> Option<StreamMatcher<'_>> {
+        StreamMatcher::new(self)
+    }
+    /// Returns one of the engines the regex is made of on its own, as a
+    /// [`Matcher`], or `None` if the regex doesn't have it. Which engines a
+    /// regex has depends on the pattern and on the size limits set in
+    /// [`RegexBuilder`].
+    ///
+    /// ```
+    /// use regexrs::{Anchored, Engine, Matcher, Regex};
+    ///
+    /// let regex = Regex::new(r"\w+@\w+").unwrap();
+    /// let dfa = regex.engine(Engine::Dfa).unwrap();
+    /// assert_eq!(dfa.find_at("mail me@home", 0, Anchored::No).unwrap().as_str(), "me@home");
+    /// assert!(Regex::new(r"(\w)\1").unwrap().engine(Engine::Dfa).is_none());
+    /// ```
+    pub fn engine(&self, engine: Engine) -> Option<impl Matcher + '_> {
+        EngineMatcher::new(self, engine)
+    }
+    // Runs the state-set simulation over `chars`, which are taken from
+    // `haystack` unless the pattern doesn't look ahead.
+    fn is_match_chars<I: Iterator<Item = (usize, char)>>(
+        &self,
+        chars: I,
+        haystack: &str,
+        budget: &Budget,
+    ) -> bool {
+        let states = self.inner.nfa.start(haystack, budget);
+        self.is_match_from(states, chars, haystack, budget)
+    }
+    // Like `is_match_chars`, but starting out in `states` instead.
+    fn is_match_from<I: Iterator<Item = (usize, char)>>(
+        &self,
+        mut states: Vec<State>,
+        chars: I,
+        haystack: &str,
+        budget: &Budget,
+    ) -> bool {
+        for (idx, c) in chars {
+            if states.contains(&State::Success) {
+                return true;
+            }
+            if budget.expired() {
+                return false;
+            }
+            let position = idx + c.len_utf8();
+            let next = self
+                .inner
+                .nfa
+                .next_unanchored(&states, c, haystack, position, budget);
+            if let Some(tracer) = &self.inner.tracer {
+                tracer.transition(c, position, &states, &next);
+            }
+            states = next;
+            // Matches may start later even if no thread is left, unless the
+            // pattern is anchored.
+            if states.is_empty() && self.inner.nfa.entries().1.is_none() {
+                return false;
+            }
+        }
+        states.contains(&State::Success)
+    }
+    /// Like [`Regex::is_match`], but only looks for matches starting at or
+    /// after byte offset `start`, or right at it if `anchored` is
+    /// [`Anchored::Yes`].
+    ///
+    /// The text before `start` still counts for assertions, so that `^`
+    /// doesn't match at `start` unless a line starts there, and `\b` looks
+    /// at the character before it. `\G` matches at `start`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    pub fn is_match_at(&self, haystack: &str, start: usize, anchored: Anchored) -> bool {
+        if start == 0 && anchored == Anchored::No {
+            return self.is_match(haystack);
+        }
+        self.find_at(haystack, start, anchored).is_some()
+    }
+    /// Returns the leftmost match in `haystack`, if there is one.
+    pub fn find<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_at(haystack, 0, Anchored::No)
+    }
+    /// Returns the leftmost match in `haystack` that starts at or after
+    /// byte offset `start`, or right at it if `anchored` is
+    /// [`Anchored::Yes`], as described in [`Regex::is_match_at`].
+    ///
+    /// Unlike searching a slice of the haystack, this lets a tokenizer
+    /// resume where it left off without losing the context before it.
+    ///
+    /// ```
+    /// use regexrs::{Anchored, Regex};
+    ///
+    /// let regex = Regex::new(r"\b\w+").unwrap();
+    /// assert_eq!(regex.find_at("foo bar", 1, Anchored::No).unwrap().as_str(), "bar");
+    /// assert!(regex.find_at("foo bar", 1, Anchored::Yes).is_none());
+    /// assert!(regex.find_at("foo bar", 4, Anchored::Yes).is_some());
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    pub fn find_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let slots = self.with_cache(|cache| {
+            self.captures_at(haystack, start, 2, anchored, &self.budget(), cache)
+        })?;
+        Some(Match {
+            haystack,
+            start: slots[0]?,
+            end: slots[1]?,
+        })
+    }
+    /// Like [`Regex::find`], but fails if the search gives up, as described
+    /// in [`RegexBuilder::backtrack_limit`].
+    ///
+    /// ```
+    /// use regexrs::{MatchError, RegexBuilder};
+    ///
+    /// let regex = RegexBuilder::new(r"(a*)*\1b|c").backtrack_limit(10_000).build().unwrap();
+    /// let haystack = "a".repeat(30);
+    /// assert_eq!(regex.try_find(&haystack), Err(MatchError::BacktrackLimitExceeded(10_000)));
+    /// assert_eq!(regex.try_find("aab").unwrap().unwrap().as_str(), "aab");
+    /// ```
+    pub fn try_find<'h>(&self, haystack: &'h str) -> Result<Option<Match<'h>>, MatchError> {
+        let budget = self.budget();
+        let slots =
+            self.with_cache(|cache| self.captures_at(haystack, 0, 2, Anchored::No, &budget, cache));
+        self.check(&budget)?;
+        Ok(slots.and_then(|slots| {
+            Some(Match {
+                haystack,
+                start: slots[0]?,
+                end: slots[1]?,
+            })
+        }))
+    }
+    /// Like [`Regex::try_find`], but also gives up once the search has run
+    /// for longer than `timeout`, for services that match untrusted
+    /// patterns against untrusted haystacks.
+    ///
+    /// The time is checked as the search goes along, so it may overrun the
+    /// timeout slightly. Searches with a literal or a DFA, which take time
+    /// linear in the length of the haystack, aren't interrupted.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new(r"(\w+) \1").unwrap();
+    /// let found = regex.find_with_deadline("say hello hello", Duration::from_secs(1));
+    /// assert_eq!(found.unwrap().unwrap().as_str(), "hello hello");
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn find_with_deadline<'h>(
+        &self,
+        haystack: &'h str,
+        timeout: Duration,
+    ) -> Result<Option<Match<'h>>, MatchError> {
+        let budget = self.budget().with_timeout(timeout);
+        let slots =
+            self.with_cache(|cache| self.captures_at(haystack, 0, 2, Anchored::No, &budget, cache));
+        self.check(&budget)?;
+        Ok(slots.and_then(|slots| {
+            Some(Match {
+                haystack,
+                start: slots[0]?,
+                end: slots[1]?,
+            })
+        }))
+    }
+    /// Returns an iterator over all non-overlapping matches in `haystack`,
+    /// from left to right.
+    ///
+    /// An empty match right at the end of the previous match is skipped, so
+    /// the iterator always makes progress. Empty matches never split a
+    /// character: `a*` finds empty matches at 0, 1 and 3 in `"bé"`.
+    pub fn find_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h> {
+        Matches(Searcher::new(self, haystack))
+    }
+    /// Returns the capture groups of the leftmost match in `haystack`, if
+    /// there is one.
+    pub fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        let slots = 2 * self.captures_len();
+        let slots = self.with_cache(|cache| {
+            self.captures_at(haystack, 0, slots, Anchored::No, &self.budget(), cache)
+        })?;
+        Some(Captures::new(haystack, slots, self.inner.names.clone()))
+    }
+    /// Like [`Regex::captures`], but fails if the search gives up, as
+    /// described in [`RegexBuilder::backtrack_limit`].
+    pub fn try_captures<'h>(&self, haystack: &'h str) -> Result<Option<Captures<'h>>, MatchError> {
+        let budget = self.budget();
+        let slots = 2 * self.captures_len();
+        let slots = self
+            .with_cache(|cache| self.captures_at(haystack, 0, slots, Anchored::No, &budget, cache));
+        self.check(&budget)?;
+        Ok(slots.map(|slots| Captures::new(haystack, slots, self.inner.names.clone())))
+    }
+    /// Returns a new [`Cache`] for the searches of this regex and its clones.
+    pub fn create_cache(&self) -> Cache {
+        let bytes = self.inner.bytes.as_ref();
+        Cache {
+            owner: Arc::as_ptr(&self.inner) as usize,
+            lazy: self
+                .inner
+                .lazy
+                .as_ref()
+                .zip(bytes)
+                .map(|(lazy, bytes)| lazy.cache(bytes)),
+            search: self
+                .inner
+                .search
+                .as_ref()
+                .zip(bytes)
+                .map(|(search, bytes)| search.cache(bytes)),
+        }
+    }
+    /// Like [`Regex::is_match`], but keeps the states of the search in
+    /// `cache` rather than one from the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cache` wasn't created by this regex or one of its clones.
+    pub fn is_match_with(&self, cache: &mut Cache, haystack: &str) -> bool {
+        self.claim(cache);
+        self.is_match_within(haystack, &self.budget(), cache)
+    }
+    /// Like [`Regex::find`], but keeps the states of the search in `cache`
+    /// rather than one from the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cache` wasn't created by this regex or one of its clones.
+    pub fn find_with<'h>(&self, cache: &mut Cache, haystack: &'h str) -> Option<Match<'h>> {
+        self.claim(cache);
+        let slots = self.captures_at(haystack, 0, 2, Anchored::No, &self.budget(), cache)?;
+        Some(Match {
+            haystack,
+            start: slots[0]?,
+            end: slots[1]?,
+        })
+    }
+    /// Like [`Regex::captures`], but keeps the states of the search in
+    /// `cache` rather than one from the pool.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cache` wasn't created by this regex or one of its clones.
+    pub fn captures_with<'h>(&self, cache: &mut Cache, haystack: &'h str) -> Option<Captures<'h>> {
+        self.claim(cache);
+        let slots = 2 * self.captures_len();
+        let slots = self.captures_at(haystack, 0, slots, Anchored::No, &self.budget(), cache)?;
+        Some(Captures::new(haystack, slots, self.inner.names.clone()))
+    }
+    /// Returns a buffer for the offsets of the capture groups, to be filled
+    /// in by [`Regex::captures_read`].
+    pub fn capture_locations(&self) -> CaptureLocations {
+        CaptureLocations(vec![None; 2 * self.captures_len()])
+    }
+    /// Like [`Regex::captures`], but stores the offsets of the groups in
+    /// `locations`, so that searching in a loop doesn't allocate
+    /// [`Captures`] over and over. Returns the whole match.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new(r"(\w+)=(\d+)").unwrap();
+    /// let mut locations = regex.capture_locations();
+    /// let m = regex.captures_read(&mut locations, "x=1, yy=22").unwrap();
+    /// assert_eq!(m.as_str(), "x=1");
+    /// assert_eq!(locations.get(2), Some((2, 3)));
+    /// ```
+    pub fn captures_read<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+    ) -> Option<Match<'h>> {
+        self.captures_read_at(locations, haystack, 0, Anchored::No)
+    }
+    /// Like [`Regex::captures_read`], but searches from byte offset `start`
+    /// as described in [`Regex::find_at`]. If there is no match, all groups
+    /// in `locations` are cleared.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    pub fn captures_read_at<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let slots = locations.0.len().max(2);
+        let captured = self.with_cache(|cache| {
+            self.captures_at(haystack, start, slots, anchored, &self.budget(), cache)
+        });
+        locations.read(haystack, captured)
+    }
+    /// Returns an iterator over the capture groups of all non-overlapping
+    /// matches in `haystack`, following the same rules as
+    /// [`Regex::find_iter`].
+    pub fn captures_iter<'r, 'h>(&'r self, haystack: &'h str) -> CaptureMatches<'r, 'h> {
+        CaptureMatches(Searcher::new(self, haystack))
+    }
+    /// Returns an iterator over the pieces of `haystack` up to and including
+    /// each match of the regex, followed by the rest of the haystack unless
+    /// it is empty. Matches are found as by [`Regex::find_iter`].
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new(r"\r?\n").unwrap();
+    /// let lines: Vec<&str> = regex.split_inclusive("a\r\nb\nc").collect();
+    /// assert_eq!(lines, ["a\r\n", "b\n", "c"]);
+    /// ```
+    pub fn split_inclusive<'r, 'h>(&'r self, haystack: &'h str) -> SplitInclusive<'r, 'h> {
+        SplitInclusive {
+            matches: self.find_iter(haystack),
+            last: Some(0),
+        }
+    }
+    /// Returns an iterator over the gaps between the matches of the regex in
+    /// `haystack` and the matches themselves, in the order they appear.
+    ///
+    /// Gaps and delimiters alternate, starting and ending with a gap, which
+    /// is empty where a match is at the start or end of the haystack, or
+    /// right after another match. Matches are found as by
+    /// [`Regex::find_iter`].
+    ///
+    /// ```
+    /// use regexrs::{Piece, Regex};
+    ///
+    /// let regex = Regex::new(r"\s*,\s*").unwrap();
+    /// let pieces: Vec<Piece> = regex.split_with_delimiters("a , b,").collect();
+    /// assert_eq!(pieces.len(), 5);
+    /// assert!(matches!(pieces[1], Piece::Delimiter(m) if m.as_str() == " , "));
+    /// assert!(matches!(pieces[4], Piece::Gap(m) if m.is_empty()));
+    /// ```
+    pub fn split_with_delimiters<'r, 'h>(
+        &'r self,
+        haystack: &'h str,
+    ) -> SplitWithDelimiters<'r, 'h> {
+        SplitWithDelimiters {
+            matches: self.find_iter(haystack),
+            last: Some(0),
+            delimiter: None,
+        }
+    }
+    /// Replaces the leftmost match in `haystack` with `replacement`, which
+    /// may refer to capture groups as described in [`Captures::expand`].
+    ///
+    /// The haystack is borrowed unchanged if there is no match.
+    pub fn replace<'h>(&self, haystack: &'h str, replacement: &str) -> Cow<'h, str> {
+        self.replacen(haystack, 1, replacement)
+    }
+    /// Replaces all non-overlapping matches in `haystack` with
+    /// `replacement`, like [`Regex::replace`].
+    pub fn replace_all<'h>(&self, haystack: &'h str, replacement: &str) -> Cow<'h, str> {
+        self.replacen(haystack, 0, replacement)
+    }
+    /// Replaces at most `limit` non-overlapping matches in `haystack` with
+    /// `replacement`, or all of them if `limit` is 0.
+    pub fn replacen<'h>(&self, haystack: &'h str, limit: usize, replacement: &str) -> Cow<'h, str> {
+        let mut replaced: Option<String> = None;
+        let mut last_end = 0;
+        for (count, caps) in self.captures_iter(haystack).enumerate() {
+            if limit > 0 && count == limit {
+                break;
+            }
+            let m = caps.get(0).unwrap();
+            let result = replaced.get_or_insert_with(String::new);
+            result.push_str(&haystack[last_end..m.start()]);
+            caps.expand(replacement, result);
+            last_end = m.end();
+        }
+        match replaced {
+            Some(mut result) => {
+                result.push_str(&haystack[last_end..]);
+                Cow::Owned(result)
+            }
+            None => Cow::Borrowed(haystack),
+        }
+    }
+    /// The number of capture groups, including the implicit group 0 for
+    /// the whole match.
+    pub fn captures_len(&self) -> usize {
+        self.inner.names.len()
+    }
+    /// Returns an iterator over the names of all capture groups in order,
+    /// yielding `None` for unnamed groups, starting with group 0.
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> + '_ {
+        self.inner.names.iter().map(|name| name.as_deref())
+    }
+    /// Returns true only if the pattern consumes all of `string`, as if it
+    /// were wrapped in `\A(?:...)\z`.
+    ///
+    /// Unlike with [`Regex::is_match`], no alternative is preferred over
+    /// another: `a|ab` fully matches `ab`. A trailing newline is part of the
+    /// string like any other character, so `a$` doesn't fully match `"a\n"`.
+    pub fn is_full_match<S: AsRef<str>>(&self, string: S) -> bool {
+        let string = string.as_ref();
+        let budget = self.budget();
+        if self.inner.nfa.backtracks() {
+            return self
+                .inner
+                .nfa
+                .backtrack(string, 0, 0, true, true, &budget)
+                .is_some();
+        }
+        let mut states = self.inner.nfa.start(string, &budget);
+        for (idx, c) in string.char_indices() {
+            states = self
+                .inner
+                .nfa
+                .next(&states, c, string, idx + c.len_utf8(), &budget);
+            if states.is_empty() {
+                return false;
+            }
+        }
+        states.contains(&State::Success)
+    }
+    /// Returns a random string of at most `max_len` characters that the
+    /// pattern fully matches, as with [`Regex::is_full_match`], for fuzzing
+    /// and property-based tests. Strings are built along random paths
+    /// through the automaton the pattern is compiled to, and those failing
+    /// lookaheads or assertions are thrown away; this returns `None` if no
+    /// string came out right after a number of tries, as when no string
+    /// short enough matches.
+    ///
+    /// Characters are drawn uniformly from the ones allowed at each point,
+    /// so `.` mostly gives characters from the far end of Unicode.
+    pub fn generate<R: Rng + ?Sized>(&self, rng: &mut R, max_len: usize) -> Option<String> {
+        (0..generate::ATTEMPTS)
+            .filter_map(|_| generate::walk(&self.inner.nfa, rng, max_len))
+            .find(|generated| self.is_full_match(generated))
+    }
+    /// Returns an iterator over all strings of at most `max_len` characters
+    /// that the pattern fully matches, as with [`Regex::is_full_match`],
+    /// shortest first, for exhaustive tests of small grammars.
+    ///
+    /// This works on a DFA built for the purpose, and returns `None` for
+    /// patterns with features it doesn't support, like lookaheads,
+    /// backreferences and Unicode word boundaries, and if it would take up
+    /// more than the [DFA size limit](RegexBuilder::dfa_size_limit).
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let regex = Regex::new("(?:ab)*c?").unwrap();
+    /// let strings: Vec<String> = regex.strings(3).unwrap().collect();
+    /// assert_eq!(strings, ["", "c", "ab", "abc"]);
+    /// ```
+    pub fn strings(&self, max_len: usize) -> Option<Strings> {
+        Strings::new(
+            self.inner.bytes.as_ref()?,
+            max_len,
+            self.inner.dfa_size_limit,
+        )
+    }
+    // The captures of the leftmost-first match at or after byte offset
+    // `start`, or right at it if the search is anchored. If the pattern is
+    // one-pass, the Pike VM only has to find where
+    // the match starts, or nothing at all if it is anchored.
+    fn captures_at(
+        &self,
+        haystack: &str,
+        start: usize,
+        slots: usize,
+        anchored: Anchored,
+        budget: &Budget,
+        cache: &mut Cache,
+    ) -> Option<Slots> {
+        let anchored = anchored == Anchored::Yes;
+        if let Some(literals) = &self.inner.literals {
+            let (from, to) = literals.find_at(haystack, start, anchored)?;
+            let mut captured = vec![None; slots.max(2)];
+            captured[0] = Some(from);
+            captured[1] = Some(to);
+            return Some(captured);
+        }
+        // No match can start before the next occurrence of the prefix.
+        let start = match &self.inner.prefix {
+            Some(prefix) if anchored => {
+                if !haystack[start..].starts_with(prefix.as_str()) {
+                    return None;
+                }
+                start
+            }
+            Some(prefix) => start + haystack[start..].find(prefix.as_str())?,
+            None => match &self.inner.prefixes {
+                Some(prefixes) => prefixes.find_at(haystack, start, anchored)?.0,
+                None => start,
+            },
+        };
+        if self
+            .inner
+            .suffix
+            .as_ref()
+            .is_some_and(|suffix| !haystack[start..].contains(suffix.as_str()))
+        {
+            return None;
+        }
+        // Once the DFAs have found the match, captures only take a search
+        // anchored at its start.
+        let search = self.inner.search.as_ref().zip(cache.search.as_mut());
+        let (start, anchored) = match (search, &self.inner.bytes) {
+            (Some((search, cache)), Some(bytes)) if !anchored => {
+                let found = match (&self.inner.suffix, self.inner.reverse_suffix) {
+                    (Some(suffix), Some(kind)) => {
+                        search.find_by_suffix(bytes, cache, haystack, start, suffix, kind)
+                    }
+                    _ => search
+                        .find_by_inner_literal(bytes, cache, haystack, start)
+                        .or_else(|| search.find(bytes, cache, haystack.as_bytes(), start)),
+                };
+                match found {
+                    Some(Some((from, to))) if slots <= 2 => {
+                        return Some(vec![Some(from), Some(to)])
+                    }
+                    Some(Some((from, _))) => (from, true),
+                    Some(None) => return None,
+                    None => (start, false),
+                }
+            }
+            _ => (start, anchored),
+        };
+        match &self.inner.onepass {
+            Some(onepass) if anchored || onepass.is_anchored() => {
+                onepass.captures(haystack, start, start, slots)
+            }
+            Some(onepass) if slots > 2 => {
+                let found = self
+                    .inner
+                    .nfa
+                    .captures_at(haystack, start, 2, false, budget)?;
+                onepass.captures(haystack, start, found[0]?, slots)
+            }
+            _ => self
+                .inner
+                .nfa
+                .captures_at(haystack, start, slots, anchored, budget),
+        }
+    }
+    // Whether the pattern matches, found back from where its suffix or its
+    // inner literal occurs, unless that doesn't work for the pattern or had
+    // to be abandoned.
+    fn is_match_by_literal(&self, haystack: &str, cache: &mut Cache) -> Option<bool> {
+        let (search, cache) = self.inner.search.as_ref().zip(cache.search.as_mut())?;
+        if let (Some(suffix), Some(kind)) = (&self.inner.suffix, self.inner.reverse_suffix) {
+            return Some(
+                search
+                    .start_by_suffix(cache, haystack, 0, suffix, kind)?
+                    .is_some(),
+            );
+        }
+        // Only the part before the inner literal has matched up to there.
+        match search.start_by_inner_literal(cache, haystack, 0)? {
+            Some(from) => {
+                let bytes = self.inner.bytes.as_ref()?;
+                Some(
+                    search
+                        .find(bytes, cache, haystack.as_bytes(), from)?
+                        .is_some(),
+                )
+            }
+            None => Some(false),
+        }
+    }
+    // Makes sure `cache` holds the states of this regex.
+    fn claim(&self, cache: &Cache) {
+        assert_eq!(
+            cache.owner,
+            Arc::as_ptr(&self.inner) as usize,
+            "cache created by a different regex"
+        );
+    }
+    // Runs `search` with a cache from the pool.
+    fn with_cache<R, F: FnOnce(&mut Cache) -> R>(&self, search: F) -> R {
+        self.inner.pool.with(|| self.create_cache(), search)
+    }
+    // The steps backtracking may take in a new search.
+    fn budget(&self) -> Budget {
+        Budget::new(self.inner.backtrack_limit)
+    }
+    // Fails if a search has run out of `budget`, so that whatever it found
+    // can't be trusted.
+    fn check(&self, budget: &Budget) -> Result<(), MatchError> {
+        if let Some(timeout) = budget.timed_out() {
+            return Err(MatchError::DeadlineExceeded(timeout));
+        }
+        match budget.exhausted() {
+            true => Err(MatchError::BacktrackLimitExceeded(
+                self.inner.backtrack_limit,
+            )),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Whether a search starting at an offset may find a match anywhere after
+/// it, or only one that starts right there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchored {
+    /// Matches may start anywhere at or after the offset.
+    #[default]
+    No,
+    /// Matches have to start at the offset.
+    Yes,
+}
+
+/// A single match of a pattern in a haystack.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Match<'h> {
+    haystack: &'h str,
+    start: usize,
+    end: usize,
+}
+
+impl<'h> Match<'h> {
+    /// The byte offset at which the match starts.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+    /// The byte offset right after the end of the match.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+    /// The byte range of the match.
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+    /// The matched text.
+    pub fn as_str(&self) -> &'h str {
+        &self.haystack[self.range()]
+    }
+    /// Returns true if the match is empty.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+    /// The length of the match in bytes.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+    /// The range of the match counted in extended grapheme clusters instead
+    /// of bytes, for column models based on what a reader perceives as
+    /// characters. Clusters the match covers only in part are included.
+    ///
+    /// ```
+    /// use regexrs::Regex;
+    ///
+    /// let m = Regex::new("b+").unwrap().find("e\u{301}🇺🇸bb").unwrap();
+    /// assert_eq!(m.range(), 11..13);
+    /// assert_eq!(m.grapheme_range(), 2..4);
+    /// ```
+    pub fn grapheme_range(&self) -> Range<usize> {
+        let (mut start, mut end) = (0, 0);
+        for cluster in clusters().find_iter(self.haystack) {
+            if cluster.start() >= self.end {
+                break;
+            }
+            if cluster.end() <= self.start {
+                start += 1;
+            }
+            if cluster.start() < self.end {
+                end += 1;
+            }
+        }
+        start..end
+    }
+}
+
+// Segments text into extended grapheme clusters.
+fn clusters() -> Regex {
+    let compile = || Regex::new(r"\X").expect("grapheme clusters compile");
+    #[cfg(feature = "std")]
+    {
+        static CLUSTERS: std::sync::OnceLock<Regex> = std::sync::OnceLock::new();
+        CLUSTERS.get_or_init(compile).clone()
+    }
+    #[cfg(not(feature = "std"))]
+    compile()
+}
+
+// Walks through the successive non-overlapping matches in a haystack.
+struct Searcher<'r, 'h> {
+    regex: &'r Regex,
+    haystack: &'h str,
+    position: usize,
+    last_end: Option<usize>,
+}
+
+impl<'r, 'h> Searcher<'r, 'h> {
+    fn new(regex: &'r Regex, haystack: &'h str) -> Self {
+        Self {
+            regex,
+            haystack,
+            position: 0,
+            last_end: None,
+        }
+    }
+    // Finds the next match, filling in the first `slots` capture slots. An
+    // empty match right at the end of the previous one is skipped.
+    fn next(&mut self, slots: usize) -> Option<Slots> {
+        loop {
+            if self.position > self.haystack.len() {
+                return None;
+            }
+            let captured = self.regex.with_cache(|cache| {
+                self.regex.captures_at(
+                    self.haystack,
+                    self.position,
+                    slots,
+                    Anchored::No,
+                    &self.regex.budget(),
+                    cache,
+                )
+            })?;
+            let (start, end) = (captured[0]?, captured[1]?);
+            if start == end && self.last_end == Some(end) {
+                self.position = end
+                    + self.haystack[end..]
+                        .chars()
+                        .next()
+                        .map_or(1, char::len_utf8);
+                continue;
+            }
+            self.position = end;
+            self.last_end = Some(end);
+            return Some(captured);
+        }
+    }
+}
+
+/// An iterator over the successive non-overlapping matches in a haystack.
+///
+/// Created by [`Regex::find_iter`].
+pub struct Matches<'r, 'h>(Searcher<'r, 'h>);
+
+impl<'r, 'h> Iterator for Matches<'r, 'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let captured = self.0.next(2)?;
+        Some(Match {
+            haystack: self.0.haystack,
+            start: captured[0]?,
+            end: captured[1]?,
+        })
+    }
+}
+
+/// An iterator over the successive non-overlapping matches in a sequence of
+/// characters, as ranges of indices into it.
+///
+/// Created by [`Regex::find_iter_chars`].
+pub struct CharMatches<'r> {
+    regex: &'r Regex,
+    haystack: String,
+    position: usize,
+    last_end: Option<usize>,
+    // A byte offset into the haystack, and the number of characters before it.
+    counted: (usize, usize),
+}
+
+impl CharMatches<'_> {
+    // The number of characters before the byte offset `at`, which is never
+    // before the offset asked about last.
+    fn index(&mut self, at: usize) -> usize {
+        let (offset, before) = self.counted;
+        let before = before + self.haystack[offset..at].chars().count();
+        self.counted = (at, before);
+        before
+    }
+}
+
+impl Iterator for CharMatches<'_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut searcher = Searcher {
+            regex: self.regex,
+            haystack: &self.haystack,
+            position: self.position,
+            last_end: self.last_end,
+        };
+        let captured = searcher.next(2);
+        (self.position, self.last_end) = (searcher.position, searcher.last_end);
+        let captured = captured?;
+        let (start, end) = (captured[0]?, captured[1]?);
+        Some(self.index(start)..self.index(end))
+    }
+}
+
+/// An iterator over the capture groups of successive non-overlapping
+/// matches in a haystack.
+///
+/// Created by [`Regex::captures_iter`].
+pub struct CaptureMatches<'r, 'h>(Searcher<'r, 'h>);
+
+impl<'r, 'h> Iterator for CaptureMatches<'r, 'h> {
+    type Item = Captures<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let captured = self.0.next(2 * self.0.regex.captures_len())?;
+        Some(Captures::new(
+            self.0.haystack,
+            captured,
+            self.0.regex.inner.names.clone(),
+        ))
+    }
+}
+
+/// An iterator over the pieces of a haystack that end with a match.
+///
+/// Created by [`Regex::split_inclusive`].
+pub struct SplitInclusive<'r, 'h> {
+    matches: Matches<'r, 'h>,
+    // Where the next piece starts, or None once the rest has been yielded.
+    last: Option<usize>,
+}
+
+impl<'r, 'h> Iterator for SplitInclusive<'r, 'h> {
+    type Item = &'h str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let haystack = self.matches.0.haystack;
+        let last = self.last?;
+        match self.matches.next() {
+            Some(m) => {
+                self.last = Some(m.end);
+                Some(&haystack[last..m.end])
+            }
+            None => {
+                self.last = None;
+                Some(&haystack[last..]).filter(|rest| !rest.is_empty())
+            }
+        }
+    }
+}
+
+/// A piece of a haystack split by [`Regex::split_with_delimiters`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Piece<'h> {
+    /// The text between two matches, or before the first or after the last.
+    Gap(Match<'h>),
+    /// A match of the regex.
+    Delimiter(Match<'h>),
+}
+
+impl<'h> Piece<'h> {
+    /// The piece as a span of the haystack.
+    pub fn span(&self) -> Match<'h> {
+        match self {
+            Self::Gap(span) | Self::Delimiter(span) => *span,
+        }
+    }
+    /// The text of the piece.
+    pub fn as_str(&self) -> &'h str {
+        self.span().as_str()
+    }
+}
+
+/// An iterator over the gaps between the matches in a haystack, and the
+/// matches themselves.
+///
+/// Created by [`Regex::split_with_delimiters`].
+pub struct SplitWithDelimiters<'r, 'h> {
+    matches: Matches<'r, 'h>,
+    // Where the next gap starts, or None once the last one has been yielded.
+    last: Option<usize>,
+    // The match ending the gap yielded last.
+    delimiter: Option<Match<'h>>,
+}
+
+impl<'r, 'h> Iterator for SplitWithDelimiters<'r, 'h> {
+    type Item = Piece<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(delimiter) = self.delimiter.take() {
+            return Some(Piece::Delimiter(delimiter));
+        }
+        let haystack = self.matches.0.haystack;
+        let start = self.last?;
+        let end = match self.matches.next() {
+            Some(m) => {
+                self.last = Some(m.end);
+                self.delimiter = Some(m);
+                m.start
+            }
+            None => {
+                self.last = None;
+                haystack.len()
+            }
+        };
+        Some(Piece::Gap(Match {
+            haystack,
+            start,
+            end,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Whether each pattern matches somewhere in the haystack.
+    const TEST_CASES: [(&str, &str, bool); 30] = [
+        (r"a", "a", true),
+        (r"cat", "Cat", false),
+        (r"[aeiou]", "apple", true),
+        (r"[^0-9]", "Hello World!", true),
+        (r"ab*c", "ac", true),
+        (r"ab+c", "abbc", true),
+        (r"(ab)+c", "ababc", true),
+        (r"apple|banana", "banana", true),
+        (r"^Hello$", "Hello", true),
+        (r"\d{3,5}", "12345", true),
+        (r"\bword\b", "This is a word.", true),
+        (r"a(?=b)", "abc", true),
+        (r"a(?!b)", "axc", true),
+        (r"[A-Za-z]", "Hello World", true),
+        (r"a.*?b", "aabb", true),
+        (r"a", "b", false),
+        (r"cat", "dog", false),
+        (r"[aeiou]", "xyz", false),
+        (r"[^0-9]", "12345", false),
+        (r"ab*c", "adc", false),
+        (r"ab+c", "ac", false),
+        (r"(ab)+c", "abcabc", true),
+        (r"apple|banana", "cherry", false),
+        (r"^Hello$", "Hello, World!", false),
+        (r"\d{3,5}", "12", false),
+        (r"\bword\b", "wording", false),
+        (r"a(?=b)", "axb", false),
+        (r"a(?!b)", "abc", false),
+        (r"[A-Za-z]", "123", false),
+        (r"a.*?b", "acb", true),
+    ];
+
+    use super::*;
+
+    #[test]
+    fn passes_test_cases() {
+        for (pattern, haystack, expected) in TEST_CASES {
+            let regex = Regex::new(pattern).unwrap();
+            assert_eq!(
+                regex.is_match(haystack),
+                expected,
+                "{pattern} on {haystack:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn full_match_requires_whole_input() {
+        let regex = Regex::new("cat").unwrap();
+        assert!(regex.is_full_match("cat"));
+        assert!(!regex.is_full_match("cats"));
+        assert!(!regex.is_full_match("ca"));
+        assert!(regex.is_match("cats"));
+    }
+
+    #[test]
+    fn searches_for_substrings() {
+        let regex = Regex::new("cat").unwrap();
+        assert!(regex.is_match("the cat sat"));
+        assert!(regex.is_match("concatenate"));
+        assert!(!regex.is_match("the dog sat"));
+        let regex = Regex::new("ab+c").unwrap();
+        assert!(regex.is_match("aabbbcc"));
+        let regex = Regex::new("^cat|dog").unwrap();
+        assert!(regex.is_match("cat flap"));
+        assert!(!regex.is_match("the cat"));
+        assert!(regex.is_match("the dog"));
+    }
+
+    #[test]
+    fn finds_leftmost_match() {
+        let regex = Regex::new("ab+").unwrap();
+        let m = regex.find("xxabbbyab").unwrap();
+        assert_eq!((m.start(), m.end()), (2, 6));
+        assert_eq!(m.as_str(), "abbb");
+        assert!(regex.find("xyz").is_none());
+        let regex = Regex::new("fé").unwrap();
+        assert_eq!(regex.find("café au lait").unwrap().range(), 2..5);
+        let regex = Regex::new("b|abc").unwrap();
+        assert_eq!(regex.find("abc").unwrap().as_str(), "abc");
+        let regex = Regex::new("x*").unwrap();
+        assert_eq!(regex.find("abc").unwrap().range(), 0..0);
+    }
+
+    #[test]
+    fn iterates_over_matches() {
+        let regex = Regex::new(r"\d+").unwrap();
+        let found: Vec<&str> = regex.find_iter("a1 b22 c333").map(|m| m.as_str()).collect();
+        assert_eq!(found, ["1", "22", "333"]);
+        let regex = Regex::new("a*").unwrap();
+        let spans: Vec<_> = regex.find_iter("baaa").map(|m| m.range()).collect();
+        assert_eq!(spans, [0..0, 1..4]);
+        let spans: Vec<_> = regex.find_iter("bé").map(|m| m.range()).collect();
+        assert_eq!(spans, [0..0, 1..1, 3..3]);
+        let regex = Regex::new("^a").unwrap();
+        assert_eq!(regex.find_iter("aaa").count(), 1);
+    }
+
+    #[test]
+    fn extracts_capture_groups() {
+        let regex = Regex::new(r"(\d+)-(\d+)(x)?").unwrap();
+        assert_eq!(regex.captures_len(), 4);
+        let caps = regex.captures("on 2023-10 we").unwrap();
+        assert_eq!(&caps[0], "2023-10");
+        assert_eq!(&caps[1], "2023");
+        assert_eq!(caps.get(2).unwrap().range(), 8..10);
+        assert!(caps.get(3).is_none());
+        assert!(caps.get(4).is_none());
+        let regex = Regex::new("(a(b)?)+").unwrap();
+        let caps = regex.captures("abaab").unwrap();
+        assert_eq!(&caps[0], "abaab");
+        assert_eq!(&caps[1], "ab");
+        assert_eq!(caps.get(2).unwrap().range(), 4..5);
+        let regex = Regex::new("(x)|(y)").unwrap();
+        let groups: Vec<_> = regex
+            .captures("y")
+            .unwrap()
+            .iter()
+            .map(|m| m.is_some())
+            .collect();
+        assert_eq!(groups, [true, false, true]);
+    }
+
+    #[test]
+    fn iterates_over_captures() {
+        let regex = Regex::new(r"(\d+)-(\w+)").unwrap();
+        let log = "1-INFO started\n2-WARN disk\n3-ERROR crashed";
+        let fields: Vec<(String, String)> = regex
+            .captures_iter(log)
+            .map(|caps| (caps[1].to_owned(), caps[2].to_owned()))
+            .collect();
+        assert_eq!(
+            fields,
+            [("1", "INFO"), ("2", "WARN"), ("3", "ERROR")]
+                .map(|(a, b)| (a.to_owned(), b.to_owned()))
+        );
+        let regex = Regex::new("(a)*").unwrap();
+        assert_eq!(regex.captures_iter("baa").count(), 2);
+    }
+
+    #[test]
+    fn replaces_matches() {
+        let regex = Regex::new(r"(\d+)-(\d+)").unwrap();
+        assert_eq!(regex.replace("1-2 3-4", "$2-$1"), "2-1 3-4");
+        assert_eq!(regex.replace_all("1-2 3-4", "$2-$1"), "2-1 4-3");
+        assert_eq!(regex.replacen("1-2 3-4 5-6", 2, "${1}x"), "1x 3x 5-6");
+        assert_eq!(regex.replace_all("1-2", "$$1 $3 $ ${}"), "$1  $ ${}");
+        assert!(matches!(
+            regex.replace_all("none", "x"),
+            Cow::Borrowed("none")
+        ));
+        let regex = Regex::new("a*").unwrap();
+        assert_eq!(regex.replace_all("baaac", "-"), "-b-c-");
+    }
+
+    #[test]
+    fn indexes_captures_by_name() {
+        let regex = Regex::new(r"(?P<key>\w+)=(?<value>\w+)?").unwrap();
+        let names: Vec<_> = regex.capture_names().collect();
+        assert_eq!(names, [None, Some("key"), Some("value")]);
+        let caps = regex.captures("x color=red").unwrap();
+        assert_eq!(&caps["key"], "color");
+        assert_eq!(caps.name("value").unwrap().range(), 8..11);
+        assert!(caps.name("other").is_none());
+        assert_eq!(regex.replace("size=", "${key}: $value!"), "size: !");
+        assert_eq!(regex.replace("size=big", "$key is $value"), "size is big");
+        assert!(Regex::new("(?P<a>x)|(?P<a>y)").is_err());
+    }
+
+    #[test]
+    fn matches_nested_alternation() {
+        let regex = Regex::new("a(b|c(d|e))f").unwrap();
+        assert!(regex.is_full_match("abf"));
+        assert!(regex.is_full_match("acef"));
+        assert!(!regex.is_match("acf"));
+        let caps = regex.captures("xacdf").unwrap();
+        assert_eq!(&caps[1], "cd");
+        assert_eq!(&caps[2], "d");
+        let regex = Regex::new("(ab|cd)e").unwrap();
+        assert_eq!(regex.find("xcde").unwrap().as_str(), "cde");
+    }
+
+    #[test]
+    fn matches_anchors_inside_terms() {
+        let regex = Regex::new("(?m)a$\n^b").unwrap();
+        assert!(regex.is_match("xa\nby"));
+        assert!(regex.is_match("a\nb"));
+        assert!(!regex.is_match("a\n\nb"));
+        assert_eq!(Regex::new("x(?m)^y").unwrap().find("x\nxy"), None);
+        assert!(Regex::new("(?m)x\n(?m)^y").unwrap().is_match("x\ny"));
+        let empty_lines: Vec<_> = Regex::new("(?m)$^")
+            .unwrap()
+            .find_iter("a\n\nb\n")
+            .map(|m| m.start())
+            .collect();
+        assert_eq!(empty_lines, [2, 5]);
+        assert!(!Regex::new("a^").unwrap().is_match("aa"));
+        assert!(Regex::new("(?:^|,)x").unwrap().is_match("y,x"));
+        assert_eq!(ast::to_pattern(&ast::parse("a$|b^c").unwrap()), "a$|b^c");
+    }
+
+    #[test]
+    fn finds_matches_in_char_iterators() {
+        let ranges = |pattern: &str, haystack: &str| {
+            let regex = Regex::new(pattern).unwrap();
+            regex.find_iter_chars(haystack.chars()).collect::<Vec<_>>()
+        };
+        assert_eq!(ranges("é+", "aéé bé"), [1..3, 5..6]);
+        assert_eq!(ranges("x*", "äx"), [0..0, 1..2]);
+        assert_eq!(ranges(r"(\w)\1(?=!)", "ßß! öö"), vec![0..2]);
+        assert!(ranges("z", "äöü").is_empty());
+    }
+
+    #[test]
+    fn checks_lookaheads() {
+        let regex = Regex::new("a(?=b)").unwrap();
+        assert!(regex.is_match("abc"));
+        assert!(!regex.is_match("axb"));
+        assert_eq!(regex.find("aab").unwrap().range(), 1..2);
+        assert!(regex.is_match_iter("cab".chars()));
+        let regex = Regex::new("a(?!b)").unwrap();
+        assert!(regex.is_match("axc"));
+        assert!(!regex.is_match("abc"));
+        assert_eq!(regex.find("aba").unwrap().range(), 2..3);
+        let regex = Regex::new(r"(\w+)(?=(\d))").unwrap();
+        let caps = regex.captures("ab1").unwrap();
+        assert_eq!((&caps[0], &caps[1], &caps[2]), ("ab", "ab", "1"));
+        let regex = Regex::new("x(?=y|z)(?!zz)").unwrap();
+        assert!(!regex.is_full_match("x"));
+        assert!(regex.is_match("xz!"));
+        assert!(!regex.is_match("xzz"));
+    }
+
+    #[test]
+    fn matches_backreferences() {
+        let regex = Regex::new(r"(\w+) \1").unwrap();
+        assert!(regex.is_match("it is is here"));
+        assert!(!regex.is_match("it is here"));
+        assert_eq!(regex.find("so it is is").unwrap().range(), 6..11);
+        assert!(regex.is_match_iter("a b b".chars()));
+        let regex = Regex::new(r"(a|b)\1*c").unwrap();
+        assert!(regex.is_full_match("aaac"));
+        assert!(!regex.is_full_match("abac"));
+        let caps = regex.captures("xbbc").unwrap();
+        assert_eq!((&caps[0], &caps[1]), ("bbc", "b"));
+        let regex = Regex::new(r"(')\w*\1").unwrap();
+        assert_eq!(regex.replace_all("say 'hi' 'x", "Q"), "say Q 'x");
+        let regex = Regex::new(r"(a*)*\1b").unwrap();
+        assert!(regex.is_match("aab"));
+        assert!(Regex::new(r"(a)\2").is_err());
+        let regex = Regex::new(r#"(?P<quote>['"])\w*(?P=quote) (?<q>')\k<q>"#).unwrap();
+        assert!(regex.is_match("say \"hi\" ''"));
+        assert!(!regex.is_match("say \"hi' ''"));
+    }
+
+    #[test]
+    fn applies_inline_flags() {
+        let regex = Regex::new("(?i)hello").unwrap();
+        assert!(regex.is_match("HeLLo"));
+        let regex = Regex::new("a(?i)b|c").unwrap();
+        assert!(regex.is_full_match("aB"));
+        assert!(regex.is_full_match("C"));
+        assert!(!regex.is_match("AB"));
+        let regex = Regex::new("((?i)a)b").unwrap();
+        assert!(regex.is_full_match("Ab"));
+        assert!(!regex.is_full_match("AB"));
+        let regex = Regex::new("(?i:[a-c]+)(?-i:x)").unwrap();
+        assert_eq!(regex.find("zzAbCx").unwrap().range(), 2..6);
+        assert!(!regex.is_match("abcX"));
+        let regex = Regex::new("(?:ab)+c").unwrap();
+        assert_eq!(regex.captures_len(), 1);
+        assert!(regex.is_full_match("ababc"));
+    }
+
+    #[test]
+    fn anchors_at_text_or_line_boundaries() {
+        let regex = Regex::new("^b").unwrap();
+        assert!(!regex.is_match("a\nb"));
+        let regex = Regex::new("a$").unwrap();
+        assert!(!regex.is_match("a\nb"));
+        assert!(regex.is_match("b\na"));
+        assert!(!Regex::new("[^0-9]$").unwrap().is_match("123"));
+        let regex = Regex::new("(?m)^b").unwrap();
+        assert_eq!(regex.find("a\nb").unwrap().range(), 2..3);
+        let regex = RegexBuilder::new("^\\w+$")
+            .multi_line(true)
+            .build()
+            .unwrap();
+        let lines: Vec<&str> = regex
+            .find_iter("one\ntwo\nthree")
+            .map(|m| m.as_str())
+            .collect();
+        assert_eq!(lines, ["one", "two", "three"]);
+        let regex = Regex::new("x|(^a)").unwrap();
+        assert_eq!(regex.find("ba").map(|m| m.range()), None);
+    }
+
+    #[test]
+    fn matches_newlines_with_dot_all() {
+        let regex = Regex::new("a.b").unwrap();
+        assert!(!regex.is_match("a\nb"));
+        assert!(regex.is_match("a\rb"));
+        assert!(Regex::new("(?s)a.b").unwrap().is_match("a\nb"));
+        assert!(Regex::new("a(?s:.)b").unwrap().is_match("a\nb"));
+        let regex = RegexBuilder::new("a.b")
+            .dot_matches_new_line(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_match("a\nb"));
+    }
+
+    #[test]
+    fn restricts_perl_classes_to_ascii() {
+        let regex = Regex::new("\\w+").unwrap();
+        assert_eq!(regex.find("-über-").unwrap().as_str(), "über");
+        let regex = Regex::new("(?-u)\\w+").unwrap();
+        assert_eq!(regex.find("-über-").unwrap().as_str(), "ber");
+        let regex = RegexBuilder::new("\\s\\d").build().unwrap();
+        assert!(regex.is_match("\u{a0}\u{663}"));
+        let regex = RegexBuilder::new("\\s\\d").unicode(false).build().unwrap();
+        assert!(!regex.is_match("\u{a0}\u{b2}"));
+        assert!(regex.is_match("\u{b}7"));
+        let regex = Regex::new("(?i-u)é").unwrap();
+        assert!(!regex.is_match("É"));
+    }
+
+    #[test]
+    fn matches_beyond_latin1() {
+        let regex = Regex::new("é|λ|😀").unwrap();
+        let found: Vec<&str> = regex.find_iter("a😀bλcé").map(|m| m.as_str()).collect();
+        assert_eq!(found, ["😀", "λ", "é"]);
+        let regex = Regex::new("[α-ω]+").unwrap();
+        assert_eq!(regex.find("abc λογος").unwrap().as_str(), "λογος");
+        assert!(Regex::new("^\\d\\w$").unwrap().is_match("٣ж"));
+        assert!(Regex::new("(?i)λ").unwrap().is_match("Λ"));
+        assert!(Regex::new("^.$").unwrap().is_match("\u{1F600}"));
+    }
+
+    #[test]
+    fn matches_unicode_perl_classes() {
+        // `\d` is general category Nd, which leaves out other numbers.
+        let digit = Regex::new(r"^\d$").unwrap();
+        assert!(digit.is_match("٣") && digit.is_match("𝟘"));
+        assert!(!digit.is_match("½") && !digit.is_match("Ⅷ") && !digit.is_match("²"));
+        // `\w` adds marks, connector punctuation and joiners to letters.
+        let word = Regex::new(r"^\w+$").unwrap();
+        assert!(word.is_match("e\u{301}") && word.is_match("a‿b") && word.is_match("क\u{200d}"));
+        assert!(!word.is_match("½") && !word.is_match("-"));
+        let boundaries: Vec<usize> = Regex::new(r"\b")
+            .unwrap()
+            .find_iter("ab\u{301} ²")
+            .map(|m| m.start())
+            .collect();
+        assert_eq!(boundaries, [0, 4]);
+    }
+
+    #[test]
+    fn matches_bytes_like_characters() {
+        let patterns = [
+            "[^a]",
+            "\\w+😀$",
+            "(?m)^λ",
+            "[α-ω]{2}",
+            "(?i)straße",
+            ".b",
+            "\\d",
+        ];
+        let haystacks = ["a", "😀", "ab😀", "x\nλ", "λa", "STRAẞE", "😀b", "٣", ""];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(regex.inner.bytes.is_some());
+            for haystack in haystacks {
+                assert_eq!(
+                    regex.is_match(haystack),
+                    regex.find(haystack).is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_with_dfa() {
+        let patterns = [
+            "[^a]",
+            "\\w+😀$",
+            "(?m)^λ",
+            "(?m)a$",
+            "^ab|c$",
+            "[α-ω]{2}",
+            "x*",
+            "(?m)(a$)[\n](^b)",
+        ];
+        let haystacks = [
+            "a", "😀", "ab😀", "x\nλ", "λa", "ba\nc", "ab", "c\n", "a\nb", "",
+        ];
+        for pattern in patterns {
+            let regex = RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap();
+            let lazy = Regex::new(pattern).unwrap();
+            assert!(regex.inner.dfa.is_some());
+            assert!(lazy.inner.lazy.is_some());
+            for haystack in haystacks {
+                let expected = regex.find(haystack).is_some();
+                assert_eq!(
+                    regex.is_match(haystack),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    lazy.is_match(haystack),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+            }
+        }
+        let mut builder = RegexBuilder::new("[01]*1[01]{20}");
+        let regex = builder
+            .prefer_dfa(true)
+            .dfa_size_limit(1 << 16)
+            .build()
+            .unwrap();
+        assert!(regex.inner.dfa.is_none());
+        // The lazy DFA runs out of room as well, and falls back.
+        let haystack: String = (0..300)
+            .map(|i| if i % 7 < 3 { '1' } else { '0' })
+            .collect();
+        assert!(regex.is_match(&haystack));
+        assert!(!regex.is_match(&haystack[..20]));
+        assert!(regex.is_match(&haystack));
+    }
+
+    #[test]
+    fn captures_in_one_pass() {
+        let patterns = [
+            r"^(\d+)-(\d+)$",
+            r"(\w+)@(\w+)\.com",
+            r"(a*)(b)?c",
+            r"(?m)^(x)$|(y)",
+            r"(ab)+(c|d)*$",
+            r"x?$|(a)",
+        ];
+        let haystacks = [
+            "12-345",
+            "mail me@example.com",
+            "aac",
+            "bc",
+            "x\nx",
+            "zy",
+            "ababdc",
+            "",
+            "a",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(regex.inner.onepass.is_some(), "{pattern}");
+            for haystack in haystacks {
+                let slots = 2 * regex.captures_len();
+                let expected =
+                    regex
+                        .inner
+                        .nfa
+                        .captures_at(haystack, 0, slots, false, &regex.budget());
+                let found = regex.captures_at(
+                    haystack,
+                    0,
+                    slots,
+                    Anchored::No,
+                    &regex.budget(),
+                    &mut regex.create_cache(),
+                );
+                assert_eq!(found, expected, "{pattern} on {haystack:?}");
+            }
+        }
+        for pattern in [r"(a|ab)(c|bcd)", r"(a*)*", r"(\w+)\s?(\w+)"] {
+            assert!(
+                Regex::new(pattern).unwrap().inner.onepass.is_none(),
+                "{pattern}"
+            );
+        }
+        let regex = Regex::new(r"(\w+)=(\d+)").unwrap();
+        let pairs: Vec<&str> = regex
+            .captures_iter("a=1, bc=23")
+            .map(|caps| caps.get(2).unwrap().as_str())
+            .collect();
+        assert_eq!(pairs, ["1", "23"]);
+    }
+
+    #[test]
+    fn hands_out_its_engines_as_matchers() {
+        let haystack = "ab 12-345 x=1 aab\né";
+        for pattern in [
+            r"(\d+)-(\d+)",
+            r"(\w)=(\d)",
+            "(a|ab)(b?)",
+            r"(?m)^(é)$",
+            r"\G(a)",
+            r"(a)\1",
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            let mut expected = regex.capture_locations();
+            for engine in [Engine::Nfa, Engine::OnePass, Engine::Dfa] {
+                let Some(matcher) = regex.engine(engine) else {
+                    continue;
+                };
+                let mut locations = matcher.capture_locations();
+                // The DFA only knows where matches start and end.
+                let groups = if engine == Engine::Dfa {
+                    1
+                } else {
+                    regex.captures_len()
+                };
+                for start in (0..=haystack.len()).filter(|&start| haystack.is_char_boundary(start))
+                {
+                    for anchored in [Anchored::No, Anchored::Yes] {
+                        let found = matcher.find_at(haystack, start, anchored);
+                        assert_eq!(
+                            found,
+                            regex.find_at(haystack, start, anchored),
+                            "{pattern} with {engine:?} at {start}"
+                        );
+                        assert_eq!(
+                            matcher.is_match_at(haystack, start, anchored),
+                            found.is_some()
+                        );
+                        let found = matcher.captures_at(&mut locations, haystack, start, anchored);
+                        assert_eq!(
+                            found,
+                            regex.captures_read_at(&mut expected, haystack, start, anchored)
+                        );
+                        for group in 0..groups {
+                            assert_eq!(
+                                locations.get(group),
+                                expected.get(group),
+                                "{pattern} with {engine:?} at {start}"
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        let regex = Regex::new(r"(\d+)-(\d+)").unwrap();
+        assert!([Engine::Nfa, Engine::OnePass, Engine::Dfa]
+            .iter()
+            .all(|&engine| regex.engine(engine).is_some()));
+        let regex = Regex::new(r"(a)\1").unwrap();
+        assert!(regex.engine(Engine::OnePass).is_none() && regex.engine(Engine::Dfa).is_none());
+    }
+
+    #[test]
+    fn anchors_at_text_boundaries() {
+        let regex = Regex::new(r"(?m)\Aa$").unwrap();
+        assert!(regex.is_match("a\nb"));
+        assert!(!regex.is_match("b\na"));
+        let regex = Regex::new(r"a\Z").unwrap();
+        assert!(regex.is_match("ba") && regex.is_match("ba\n"));
+        assert!(!regex.is_match("ba\n\n") && !regex.is_match("ab"));
+        let regex = Regex::new(r"a\z").unwrap();
+        assert!(regex.is_match("ba"));
+        assert!(!regex.is_match("ba\n"));
+        let regex = Regex::new(r"\G(\w)").unwrap();
+        let letters: Vec<&str> = regex.find_iter("abc de").map(|m| m.as_str()).collect();
+        assert_eq!(letters, ["a", "b", "c"]);
+        assert_eq!(regex.replace_all("ab cd", "$1$1"), "aabb cd");
+        let regex = Regex::new(r"\Ga|b").unwrap();
+        let found: Vec<&str> = regex.find_iter("aaxab").map(|m| m.as_str()).collect();
+        assert_eq!(found, ["a", "a", "b"]);
+        for pattern in [r"\Aa", r"a\z", r"\Gb"] {
+            let regex = RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap();
+            assert!(regex.inner.dfa.is_some());
+            for haystack in ["a", "ba", "ab", "b", "a\n"] {
+                assert_eq!(
+                    regex.is_match(haystack),
+                    regex.find(haystack).is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn matches_escapes() {
+        let regex = Regex::new(r"a\tb\r?\n").unwrap();
+        assert!(regex.is_match("a\tb\r\n"));
+        assert!(!regex.is_match("a b\n"));
+        let regex = Regex::new(r"^[\t\v\f ]+[^\n\0]$").unwrap();
+        assert!(regex.is_full_match("\t\x0B\x0C x"));
+        assert!(!regex.is_match(" \0"));
+        assert!(Regex::new(r"[\b]").unwrap().is_match("\x08"));
+        let regex = Regex::new(r"\x41[\u{3B1}-\u{3C9}]+\u{1F600}").unwrap();
+        assert!(regex.is_full_match("Aλογος😀"));
+        assert!(!regex.is_full_match("Aλόγος😀"));
+    }
+
+    #[test]
+    fn matches_perl_classes_in_classes() {
+        let regex = Regex::new(r"^[\d\s,]+$").unwrap();
+        assert!(regex.is_match("1, 2,\t3"));
+        assert!(!regex.is_match("1,a"));
+        let regex = Regex::new(r"^[\w.]+@").unwrap();
+        assert!(regex.is_match("first.last@"));
+        let regex = Regex::new(r"[^\d\s]").unwrap();
+        assert_eq!(regex.find("1 2 x").unwrap().start(), 4);
+        let regex = Regex::new(r"(?i)^[\Wk]+$").unwrap();
+        assert!(regex.is_match("K-\u{212A}!"));
+        assert!(!regex.is_match("a"));
+        assert!(!Regex::new(r"(?-u)[\w]").unwrap().is_match("é"));
+    }
+
+    #[test]
+    fn matches_class_edge_cases() {
+        let regex = Regex::new(r"^[]a-]+$").unwrap();
+        assert!(regex.is_match("a]-"));
+        assert!(!regex.is_match("b"));
+        let regex = Regex::new(r"^[a\-z]+$").unwrap();
+        assert!(regex.is_match("z-a"));
+        assert!(!regex.is_match("b"));
+        assert!(Regex::new(r"^[\w.-]+$").unwrap().is_match("a.b-c"));
+        assert!(Regex::new(r"[\\]").unwrap().is_full_match("\\"));
+    }
+
+    #[test]
+    fn never_backtracks_into_atomic_groups() {
+        assert!(!Regex::new("a++a").unwrap().is_match("aaaa"));
+        assert!(Regex::new("a+a").unwrap().is_match("aaaa"));
+        assert_eq!(
+            Regex::new(r"\d*+\.")
+                .unwrap()
+                .find("x12.5")
+                .unwrap()
+                .as_str(),
+            "12."
+        );
+        assert!(!Regex::new("^a?+a$").unwrap().is_match("a"));
+        assert!(!Regex::new("^(?>ab|a)b$").unwrap().is_match("ab"));
+        assert!(Regex::new("^(?>ab|a)c$").unwrap().is_match("abc"));
+        let captures = Regex::new(r"(?>(\w+)-)+(\d)")
+            .unwrap()
+            .captures("a-bc-1")
+            .unwrap();
+        assert_eq!(&captures[1], "bc");
+        assert_eq!(&captures[2], "1");
+        assert!(Regex::new(r"^(a)(?>\1+)$").unwrap().is_match("aaa"));
+        assert!(Regex::new(r#""[^"]*+""#)
+            .unwrap()
+            .is_full_match(r#""quoted""#));
+    }
+
+    #[test]
+    fn matches_conditionals() {
+        let regex = Regex::new(r"^(<)?\w+(?(1)>)$").unwrap();
+        assert!(regex.is_match("<a>"));
+        assert!(regex.is_match("a"));
+        assert!(!regex.is_match("<a"));
+        assert!(!regex.is_match("a>"));
+        let regex = Regex::new(r"^(a)?(?(1)b|(c))$").unwrap();
+        assert!(regex.is_match("ab"));
+        assert!(!regex.is_match("ac"));
+        assert_eq!(&regex.captures("c").unwrap()[2], "c");
+        assert!(Regex::new(r"^(?:(a)|b)(?(1)x|y)+$")
+            .unwrap()
+            .is_match("byy"));
+    }
+
+    #[test]
+    fn matches_line_breaks() {
+        let regex = Regex::new(r"^a\Rb$").unwrap();
+        for text in ["a\r\nb", "a\nb", "a\rb", "a\u{2028}b"] {
+            assert!(regex.is_match(text), "{text:?}");
+        }
+        assert!(!regex.is_match("a\n\rb"));
+        assert_eq!(
+            Regex::new(r"\R").unwrap().find("x\r\ny").unwrap().as_str(),
+            "\r\n"
+        );
+        assert!(Regex::new(r"^\R+$").unwrap().is_match("\n\r\n\r"));
+    }
+
+    #[test]
+    fn anchors_at_crlf_line_boundaries() {
+        let regex = RegexBuilder::new("(?m)^\\w+$").crlf(true).build().unwrap();
+        let lines: Vec<&str> = regex
+            .find_iter("one\r\ntwo\rthree\n")
+            .map(|m| m.as_str())
+            .collect();
+        assert_eq!(lines, ["one", "two", "three"]);
+        let regex = RegexBuilder::new("(?m)$").crlf(true).build().unwrap();
+        let ends: Vec<usize> = regex.find_iter("a\r\nb").map(|m| m.start()).collect();
+        assert_eq!(ends, [1, 4]);
+        assert!(!Regex::new("(?R)^.$").unwrap().is_match("\r"));
+        assert!(Regex::new("(?m)^.$").unwrap().is_match("\r"));
+        let regex = RegexBuilder::new("(?m)a$")
+            .crlf(true)
+            .prefer_dfa(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_match("a\r\n"));
+        assert!(!regex.is_match("ab"));
+    }
+
+    #[test]
+    fn matches_grapheme_clusters() {
+        let regex = Regex::new(r"\X").unwrap();
+        let clusters = |text: &str| -> Vec<String> {
+            regex
+                .find_iter(text)
+                .map(|m| m.as_str().to_owned())
+                .collect()
+        };
+        assert_eq!(clusters("e\u{301}x"), ["e\u{301}", "x"]);
+        assert_eq!(clusters("\r\n\n"), ["\r\n", "\n"]);
+        assert_eq!(clusters("🇺🇸🇩🇪🇫"), ["🇺🇸", "🇩🇪", "🇫"]);
+        assert_eq!(
+            clusters("👨\u{200D}👩\u{200D}👧👍🏽"),
+            ["👨\u{200D}👩\u{200D}👧", "👍🏽"]
+        );
+        assert_eq!(
+            clusters("\u{1100}\u{1161}\u{11A8}각"),
+            ["\u{1100}\u{1161}\u{11A8}", "각"]
+        );
+        assert_eq!(clusters("क्षि"), ["क्षि"]);
+        assert!(Regex::new(r"^\X{3}$").unwrap().is_match("a🇺🇸e\u{301}"));
+    }
+
+    #[test]
+    fn splits_keeping_delimiters() {
+        let regex = Regex::new(",").unwrap();
+        let pieces = |haystack| regex.split_inclusive(haystack).collect::<Vec<_>>();
+        assert_eq!(pieces("a,b,,c"), ["a,", "b,", ",", "c"]);
+        assert_eq!(pieces(",a,"), [",", "a,"]);
+        assert!(pieces("").is_empty());
+        let pieces = |haystack| {
+            regex
+                .split_with_delimiters(haystack)
+                .map(|piece| match piece {
+                    Piece::Gap(gap) => format!("<{}>", gap.as_str()),
+                    Piece::Delimiter(_) => "|".to_owned(),
+                })
+                .collect::<String>()
+        };
+        assert_eq!(pieces("a,b,,c"), "<a>|<b>|<>|<c>");
+        assert_eq!(pieces(",a,"), "<>|<a>|<>");
+        assert_eq!(pieces(""), "<>");
+        let empty = Regex::new("x*").unwrap();
+        assert_eq!(
+            empty.split_inclusive("ab").collect::<Vec<_>>(),
+            ["", "a", "b"]
+        );
+        let spans: Vec<_> = empty
+            .split_with_delimiters("axb")
+            .map(|piece| piece.span().range())
+            .collect();
+        assert_eq!(spans, [0..0, 0..0, 0..1, 1..2, 2..3, 3..3, 3..3]);
+    }
+
+    #[test]
+    fn reports_grapheme_ranges() {
+        let haystack = "a\u{1100}\u{1161}e\u{301}x\r\n";
+        let range = |pattern: &str| {
+            Regex::new(pattern)
+                .unwrap()
+                .find(haystack)
+                .unwrap()
+                .grapheme_range()
+        };
+        assert_eq!(range("x"), 3..4);
+        assert_eq!(range("e\u{301}x"), 2..4);
+        assert_eq!(range("\u{301}"), 2..3);
+        assert_eq!(range("\n"), 4..5);
+        assert_eq!(range("$"), 5..5);
+        assert_eq!(range("^"), 0..0);
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-names")]
+    fn matches_named_characters() {
+        assert!(Regex::new(r"^\N{GREEK SMALL LETTER ALPHA}+$")
+            .unwrap()
+            .is_match("ααα"));
+        assert!(Regex::new(r"[\N{SNOWMAN}\N{COMET}]")
+            .unwrap()
+            .is_full_match("☄"));
+        assert!(Regex::new(r"(?x) \N{HANGUL SYLLABLE HAN} ")
+            .unwrap()
+            .is_full_match("한"));
+    }
+
+    #[test]
+    fn escapes_metacharacters() {
+        assert_eq!(Regex::escape(r"a.b*(c)"), r"a\.b\*\(c\)");
+        assert_eq!(Regex::escape("plain é"), r"plain\ é");
+        let text = r"^.$*+?{}[]\|() #";
+        assert!(Regex::new(Regex::escape(text)).unwrap().is_full_match(text));
+        let verbose = format!("(?x){}", Regex::escape(text));
+        assert!(Regex::new(verbose).unwrap().is_full_match(text));
+    }
+
+    #[test]
+    fn ignores_whitespace_in_verbose_mode() {
+        let regex = Regex::new(
+            r"(?x)
+            ^ (\d{4}) - (\d{2})  # year and month
+              [ ] \# (\d+) $     # a space, then a number
+            ",
+        )
+        .unwrap();
+        let captures = regex.captures("2024-05 #12").unwrap();
+        assert_eq!(&captures[1], "2024");
+        assert_eq!(&captures[3], "12");
+        assert!(Regex::new("a(?x: b c )d e")
+            .unwrap()
+            .is_full_match("abcd e"));
+        let regex = RegexBuilder::new("a b (?-x) c")
+            .ignore_whitespace(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_full_match("ab c"));
+        assert!(Regex::new("a # b").unwrap().is_full_match("a # b"));
+        let error = RegexBuilder::new("a # (\n )")
+            .ignore_whitespace(true)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), &ErrorKind::UnbalancedParenthesis);
+        assert_eq!(error.position(), 7);
+    }
+
+    #[test]
+    fn matches_nested_classes() {
+        let regex = Regex::new(r"^[[a-z][0-9]]+$").unwrap();
+        assert!(regex.is_match("abc123"));
+        assert!(!regex.is_match("ABC"));
+        let regex = Regex::new(r"^[^[a-z][0-9]]+$").unwrap();
+        assert!(regex.is_match("ABC"));
+        assert!(!regex.is_match("A1"));
+        let regex = Regex::new(r"^[_[^\W\d]]+$").unwrap();
+        assert!(regex.is_match("a_b"));
+        assert!(!regex.is_match("a1"));
+        assert!(Regex::new(r"(?i)[[a-c]]").unwrap().is_full_match("B"));
+    }
+
+    #[test]
+    fn matches_classes_defined_by_name() {
+        let mut builder = RegexBuilder::new(r"^\p{vowel}+[^[:vowel:]\d]\P{vowel}$");
+        builder.define_class("vowel", ['a', 'e', 'i', 'o', 'u']);
+        let regex = builder.build().unwrap();
+        assert!(regex.is_match("aux1"));
+        assert!(!regex.is_match("au1x"));
+        assert!(!regex.is_match("auxo"));
+        assert!(builder
+            .case_insensitive(true)
+            .build()
+            .unwrap()
+            .is_match("AUXY"));
+        let regex = builder
+            .define_class("vowel", [ast::Token::Perl(ast::SpecialSequence::Digit)])
+            .build()
+            .unwrap();
+        assert!(regex.is_match("12xy"));
+        let err = RegexBuilder::new(r"a\p{vowel}").build().err().unwrap();
+        assert_eq!(
+            (err.kind(), err.position()),
+            (&ErrorKind::UnknownClassName("vowel".to_owned()), 1)
+        );
+        let mut terms = ast::parse("xy").unwrap();
+        terms[0].elements[1] = ast::Element::Class(ast::CharacterClass {
+            sign: ast::Sign::Exclusive,
+            items: vec![ast::Token::Named("vowel".to_owned())],
+            quantifier: ast::Quantifier::Once,
+        });
+        let builder = RegexBuilder::from_ast(terms);
+        assert_eq!(builder.build().err().unwrap().position(), 1);
+        assert!(builder
+            .clone()
+            .define_class("vowel", ['a'])
+            .build()
+            .unwrap()
+            .is_match("xb"));
+    }
+
+    #[test]
+    fn checks_word_boundaries() {
+        let regex = Regex::new(r"\bword\b").unwrap();
+        assert!(regex.is_match("a word."));
+        assert!(regex.is_match("word"));
+        assert!(!regex.is_match("wording"));
+        assert!(!regex.is_match("swordfish"));
+        let regex = Regex::new(r"py\B").unwrap();
+        assert_eq!(regex.find("py py3 python").unwrap().start(), 3);
+        assert!(!regex.is_match("py! py."));
+        let regex = Regex::new(r"\bé").unwrap();
+        assert!(!regex.is_match("caé"));
+        assert!(regex.is_match("à é"));
+        let regex = Regex::new(r"(?-u)\bé").unwrap();
+        assert!(regex.is_match("caé"));
+        for pattern in [r"(?-u)\bab\b", r"(?-u)\Bb\B", r"(?m-u)^\bb$"] {
+            let regex = RegexBuilder::new(pattern).prefer_dfa(true).build().unwrap();
+            let lazy = Regex::new(pattern).unwrap();
+            assert!(regex.inner.dfa.is_some() && lazy.inner.lazy.is_some());
+            for haystack in ["ab", "cab", "a ab.", "abc", "\nb\n", "bbb", "é ab", "éab"] {
+                let expected = regex.find(haystack).is_some();
+                assert_eq!(
+                    regex.is_match(haystack),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+                assert_eq!(
+                    lazy.is_match(haystack),
+                    expected,
+                    "{pattern} on {haystack:?}"
+                );
+            }
+        }
+        assert!(Regex::new(r"\bab").unwrap().inner.lazy.is_none());
+    }
+
+    #[test]
+    fn folds_case() {
+        let regex = Regex::new("(?i)[a-z]+").unwrap();
+        assert!(regex.is_full_match("QuiZ"));
+        let regex = Regex::new("(?i)[^a]").unwrap();
+        assert_eq!(regex.find("aAb").unwrap().start(), 2);
+        let regex = Regex::new("(?i)straße").unwrap();
+        assert!(regex.is_full_match("STRAßE"));
+        assert!(!regex.is_match("STRASSE"));
+        let regex = Regex::new("(?i)\u{17F}\u{212A}\u{3BC}\u{1E9E}").unwrap();
+        assert!(regex.is_full_match("sKµß"));
+        let regex = Regex::new("(?i)s").unwrap();
+        assert!(!regex.is_match("ß"));
+    }
+
+    #[test]
+    fn builds_with_options() {
+        let regex = RegexBuilder::new("hello")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_match("HeLLo"));
+        let regex = RegexBuilder::new("a(?-i)b")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert!(regex.is_full_match("Ab"));
+        assert!(!regex.is_full_match("AB"));
+        let err = RegexBuilder::new("a{100}")
+            .size_limit(1000)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::CompiledTooBig(1000));
+        let err = RegexBuilder::new("(?=a{100})")
+            .size_limit(2000)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::CompiledTooBig(2000));
+        let err = RegexBuilder::new("(a|aa){1,40}")
+            .size_limit(1000)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::CompiledTooBig(1000));
+        let err = RegexBuilder::new("((a|aa){1,1000}){1,1000}")
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::CompiledTooBig(10 * (1 << 20)));
+        let err = RegexBuilder::new("((a)[(])(((b)))")
+            .nest_limit(2)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::NestLimitExceeded(2));
+        assert_eq!(err.position(), 10);
+        assert!(RegexBuilder::new("((a))").nest_limit(2).build().is_ok());
+        for pattern in ["[]a)]", r"[\]a)]", "([]a)])", "[[a](]"] {
+            assert!(Regex::new(pattern).is_ok(), "{pattern}");
+        }
+        let err = RegexBuilder::new("([)](()))")
+            .nest_limit(2)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.position(), 5);
+    }
+
+    #[test]
+    fn prefers_longest_match() {
+        let longest = |pattern: &str| {
+            let mut builder = RegexBuilder::new(pattern);
+            builder
+                .match_kind(MatchKind::LeftmostLongest)
+                .build()
+                .unwrap()
+        };
+        assert_eq!(
+            Regex::new("foo|foobar")
+                .unwrap()
+                .find("xfoobar")
+                .unwrap()
+                .as_str(),
+            "foo"
+        );
+        assert_eq!(
+            longest("foo|foobar").find("xfoobar").unwrap().as_str(),
+            "foobar"
+        );
+        assert_eq!(longest("a+?").find("baaa").unwrap().range(), 1..4);
+        assert_eq!(longest("bcd|abc|b").find("abcd").unwrap().as_str(), "abc");
+        let caps = longest("(a|ab)(c|bcd)").captures("abcd").unwrap();
+        assert_eq!(
+            (caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str()),
+            ("a", "bcd")
+        );
+        let caps = longest("(a|ab)(c|bcd)?").captures("abc").unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "abc");
+        let regex = longest(r"(a|ab)\1?");
+        assert_eq!(regex.find("abab").unwrap().as_str(), "abab");
+        let words: Vec<&str> = longest("ab|abcd|c")
+            .find_iter("abcdc")
+            .map(|m| m.as_str())
+            .collect();
+        assert_eq!(words, ["abcd", "c"]);
+    }
+
+    #[test]
+    fn match_over_char_iterator() {
+        let regex = Regex::new("cat").unwrap();
+        let utf16: Vec<u16> = "cats".encode_utf16().collect();
+        let chars = char::decode_utf16(utf16).map(|c| c.unwrap());
+        assert!(regex.is_match_iter(chars));
+        assert!(!regex.is_match_iter("dog".chars()));
+    }
+
+    #[test]
+    fn compiles_quantifiers() {
+        let regex = Regex::new("ab*c").unwrap();
+        assert!(regex.is_full_match("ac"));
+        assert!(regex.is_full_match("abbbc"));
+        assert!(!regex.is_match("adc"));
+        let regex = Regex::new("(ab)+c").unwrap();
+        assert!(regex.is_full_match("ababc"));
+        assert!(!regex.is_match("ac"));
+        let regex = Regex::new(r"\d{3,5}").unwrap();
+        assert!(regex.is_full_match("1234"));
+        assert!(!regex.is_full_match("123456"));
+        assert!(!regex.is_match("12"));
+        let regex = Regex::new("a.*?b").unwrap();
+        assert_eq!(regex.find("aabb").unwrap().as_str(), "aab");
+        let regex = Regex::new("<(.+?)>(x??)").unwrap();
+        let caps = regex.captures("<a><b>x").unwrap();
+        assert_eq!((&caps[1], &caps[2]), ("a", ""));
+        assert!(Regex::new("a+?$").unwrap().is_full_match("aaa"));
+        let regex = Regex::new("^a{3}$").unwrap();
+        assert!(regex.is_match("aaa"));
+        assert!(!regex.is_match("aaaa"));
+        let regex = Regex::new("^(ab){2,}$").unwrap();
+        assert!(regex.is_match("ababab"));
+        assert!(!regex.is_match("ab"));
+        let regex = Regex::new("^x{,2}$").unwrap();
+        assert!(regex.is_match("") && regex.is_match("xx"));
+        assert!(!regex.is_match("xxx"));
+        let find = |pattern, haystack| Regex::new(pattern).unwrap().find(haystack).unwrap().range();
+        assert_eq!(find("a{2,3}?", "aaaa"), 0..2);
+        assert_eq!(find("a{2,}?", "aaaa"), 0..2);
+        assert_eq!(find("(?:ab){1}?c", "abc"), 0..3);
+        assert_eq!(find("<.{1,}?>", "<a><b>"), 0..3);
+        let caps = Regex::new("(a{1,3}?)(a*)")
+            .unwrap()
+            .captures("aaa")
+            .unwrap();
+        assert_eq!((&caps[1], &caps[2]), ("a", "aa"));
+        assert!(!Regex::new("^a{1,3}+a$").unwrap().is_match("aaa"));
+        assert!(!Regex::new("^a{2,}+a$").unwrap().is_match("aaa"));
+        assert!(Regex::new("^a{2}+a$").unwrap().is_match("aaa"));
+        let err = RegexBuilder::new("a{1001}").build().err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::RepetitionLimitExceeded(1000));
+        let err = RegexBuilder::new("a{1001}?").build().err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::RepetitionLimitExceeded(1000));
+        let err = Regex::new("ba{99999999999999999999}").err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::RepetitionLimitExceeded(usize::MAX));
+        assert_eq!(err.position(), 3);
+        assert!(Regex::new(r"(a)\99999999999999999999").is_err());
+        assert!(Regex::new("(a)(?(99999999999999999999)b)").is_err());
+        let err = RegexBuilder::new("(?=a{1,5})")
+            .repetition_limit(4)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), &ErrorKind::RepetitionLimitExceeded(4));
+    }
+
+    #[test]
+    fn compiles_classes_and_alternation() {
+        let regex = Regex::new("[^0-9][A-Za-z]?").unwrap();
+        assert!(regex.is_full_match("xY"));
+        assert!(regex.is_full_match("!"));
+        assert!(!regex.is_full_match("1a"));
+        let regex = Regex::new("apple|banana").unwrap();
+        assert!(regex.is_match("banana"));
+        assert!(!regex.is_match("cherry"));
+        let regex = Regex::new("^Hello$").unwrap();
+        assert!(regex.is_match("Hello"));
+        assert!(!regex.is_match("Hello, World!"));
+    }
+
+    #[test]
+    fn invalid_patterns_fail_to_compile() {
+        let err = Regex::new("(ab").err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::UnbalancedParenthesis);
+        assert_eq!(err.pattern(), "(ab");
+        let err = Regex::new("a+*").err().unwrap();
+        assert_eq!(err.kind(), &ErrorKind::NothingToRepeat);
+        assert_eq!(err.position(), 2);
+    }
+
+    #[test]
+    fn points_at_elements_failing_to_compile() {
+        let position = |builder: &RegexBuilder| builder.build().err().unwrap().position();
+        assert_eq!(position(&RegexBuilder::new("(?i)a|(?:b|(c{2000}))")), 12);
+        assert_eq!(
+            position(RegexBuilder::new("(?x) a (?: b {2000})").repetition_limit(5)),
+            11
+        );
+        assert_eq!(
+            position(RegexBuilder::new("x(?:a{100}){100}").size_limit(1000)),
+            1
+        );
+        assert_eq!(
+            position(RegexBuilder::new("x(?=a{100})").size_limit(2000)),
+            4
+        );
+        let terms = ast::parse("x(?:a{5000}|b)").unwrap();
+        assert_eq!(position(&RegexBuilder::from_ast(terms)), 4);
+        let fixed = |pattern| {
+            RegexBuilder::new(pattern)
+                .build_fixed::<64, 4>()
+                .err()
+                .unwrap()
+                .position()
+        };
+        assert_eq!(fixed("ab(?=c)"), 2);
+        assert_eq!(fixed("x(a)(b)"), 4);
+        let err = RegexBuilder::new("ab{5}")
+            .repetition_limit(4)
+            .build()
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "regex parse error:\n    ab{5}\n     ^\nerror: counted repetition exceeds the limit of 4"
+        );
+    }
+
+    #[test]
+    fn resumes_searches_the_cache_gave_up_on() {
+        let patterns = [
+            "[01]*1[01]{8}$",
+            r"(?-u)\b[aé]{2}[01]{6}\b",
+            "(?m)^é[01]*1[01]{6}$",
+        ];
+        let haystacks = [
+            "0110100111010010110",
+            "é0110100111010010110\n",
+            "0101 éa011011 01 aa010110",
+            "é10000000\né1000000",
+            "00000000000",
+        ];
+        for pattern in patterns {
+            for limit in [1, 500, 2000] {
+                let regex = RegexBuilder::new(pattern)
+                    .dfa_size_limit(limit)
+                    .build()
+                    .unwrap();
+                // Later searches start out with the cache given up on.
+                for _ in 0..5 {
+                    for haystack in haystacks {
+                        let expected = regex.find(haystack).is_some();
+                        assert_eq!(
+                            regex.is_match(haystack),
+                            expected,
+                            "{pattern} on {haystack:?}"
+                        );
+                    }
+                }
+            }
+        }
+        // The simulation goes on past positions where no match can start.
+        assert!(Regex::new(r"\bcat\b(?=\s)")
+            .unwrap()
+            .is_match("the cat sat"));
+    }
+
+    #[test]
+    fn matches_input_fed_in_chunks() {
+        let patterns = [
+            r"(?-u)\bcat\b",
+            "^é+$",
+            "(?m)^b$",
+            r"a\d{3}",
+            "[01]*1[01]{5}",
+            "x?",
+        ];
+        let haystacks = [
+            "the cat sat",
+            "concatenate",
+            "éé",
+            "aéé",
+            "a\nb\nc",
+            "xa123",
+            "11000000",
+            "",
+        ];
+        for pattern in patterns {
+            let builders = [
+                RegexBuilder::new(pattern).clone(),
+                RegexBuilder::new(pattern).prefer_dfa(true).clone(),
+                RegexBuilder::new(pattern).dfa_size_limit(1).clone(),
+            ];
+            for builder in builders {
+                let regex = builder.build().unwrap();
+                for haystack in haystacks {
+                    for size in 1..4 {
+                        let mut matcher = regex.stream_matcher().unwrap();
+                        for chunk in haystack.as_bytes().chunks(size) {
+                            matcher.feed(chunk);
+                        }
+                        assert_eq!(
+                            matcher.finish(),
+                            regex.is_match(haystack),
+                            "{pattern} on {haystack:?}"
+                        );
+                    }
+                }
+            }
+        }
+        let regex = Regex::new("^ab").unwrap();
+        let mut matcher = regex.stream_matcher().unwrap();
+        assert_eq!(matcher.feed(b"a"), Status::Undecided);
+        assert_eq!(matcher.feed(b"c"), Status::Rejected);
+        assert_eq!(matcher.feed(b"ab"), Status::Rejected);
+        let regex = Regex::new("b").unwrap();
+        let mut matcher = regex.stream_matcher().unwrap();
+        assert_eq!(matcher.feed(b"ab"), Status::Matched);
+        assert!(Regex::new("a(?=b)").unwrap().stream_matcher().is_none());
+    }
+
+    #[test]
+    fn searches_from_an_offset() {
+        let regex = Regex::new(r"\d+").unwrap();
+        assert_eq!(
+            regex.find_at("a1 22", 2, Anchored::No).unwrap().range(),
+            3..5
+        );
+        assert!(regex.find_at("a1 22", 2, Anchored::Yes).is_none());
+        assert_eq!(
+            regex.find_at("a1 22", 3, Anchored::Yes).unwrap().range(),
+            3..5
+        );
+        assert!(regex.is_match_at("a1 22", 4, Anchored::Yes));
+        assert!(!regex.is_match_at("a1 22", 5, Anchored::No));
+        // The text before the offset is still seen by assertions.
+        let regex = Regex::new(r"^x|\bx").unwrap();
+        assert!(!regex.is_match_at("ax", 1, Anchored::Yes));
+        assert!(regex.is_match_at(" x", 1, Anchored::Yes));
+        assert!(Regex::new(r"\Gx")
+            .unwrap()
+            .is_match_at("ax", 1, Anchored::Yes));
+        // Every engine honours the anchor.
+        for pattern in [r"(a)(b)", r"(a)\1?b", "ab", "(?=a)ab"] {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(
+                regex.find_at("xab", 0, Anchored::Yes).is_none(),
+                "{pattern}"
+            );
+            assert_eq!(
+                regex.find_at("xab", 1, Anchored::Yes).unwrap().range(),
+                1..3,
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn reads_captures_into_a_buffer() {
+        let regex = Regex::new(r"(\w)(\d)?").unwrap();
+        let mut locations = regex.capture_locations();
+        assert_eq!(locations.len(), 3);
+        let mut found = Vec::new();
+        let mut start = 0;
+        while let Some(m) = regex.captures_read_at(&mut locations, "a1 b", start, Anchored::No) {
+            found.push((locations.get(1), locations.get(2)));
+            start = m.end();
+        }
+        assert_eq!(found, [(Some((0, 1)), Some((1, 2))), (Some((3, 4)), None)]);
+        assert_eq!(locations.get(0), None);
+        assert!(regex
+            .captures_read_at(&mut locations, "a1 b", 2, Anchored::Yes)
+            .is_none());
+        let regex = Regex::new(r"(a)\1").unwrap();
+        let mut locations = regex.capture_locations();
+        assert_eq!(
+            regex.captures_read(&mut locations, "baa").unwrap().range(),
+            1..3
+        );
+        assert_eq!(locations.get(1), Some((1, 2)));
+    }
+
+    #[test]
+    fn decides_equivalence() {
+        let equivalent = |a: &str, b: &str| {
+            Regex::new(a)
+                .unwrap()
+                .is_equivalent(&Regex::new(b).unwrap())
+        };
+        assert!(equivalent("a+", "aa*"));
+        assert!(equivalent("(a|b)*", "[ab]*"));
+        assert!(equivalent("a|ab", "ab?"));
+        assert!(equivalent("^a$", "a"));
+        assert!(equivalent("(?i)k", "[kK\u{212A}]"));
+        assert!(equivalent("(?-u)\\bx\\b", "x"));
+        assert!(!equivalent("x*", "x+"));
+        assert!(!equivalent("\\d", "[0-9]"));
+        assert!(!equivalent("é", "e"));
+        assert!(!equivalent("a(?=b)|a", "a"));
+        let regex = Regex::new("(a)\\1").unwrap();
+        assert!(regex.is_equivalent(&regex.clone()));
+        assert!(!regex.is_equivalent(&Regex::new("(a)\\1").unwrap()));
+        let tiny = RegexBuilder::new("[01]*1[01]{12}")
+            .dfa_size_limit(1000)
+            .build()
+            .unwrap();
+        assert!(!tiny.is_equivalent(
+            &RegexBuilder::new("[01]*1[01]{12}")
+                .dfa_size_limit(1000)
+                .build()
+                .unwrap()
+        ));
+        assert!(tiny.is_equivalent(&Regex::new("[01]*1[01]{12}").unwrap()));
+    }
+
+    #[test]
+    fn full_matches_are_anchored_at_both_ends() {
+        let full = |pattern: &str, text: &str| Regex::new(pattern).unwrap().is_full_match(text);
+        assert!(full("a|ab", "ab"));
+        assert!(full("a*?", "aaa"));
+        assert!(full("x*", ""));
+        assert!(!full("a$", "a\n"));
+        assert!(!full(r"a\Z", "a\n"));
+        assert!(full(r"(a)\1|aab", "aab"));
+        assert!(!full("(?>a|ab)c?", "abc"));
+        assert!(full("(?=a)a|b", "a"));
+        assert!(!full("b", "abc"));
+    }
+
+    #[test]
+    fn matches_the_empty_string() {
+        let spans = |regex: &Regex, haystack| {
+            regex
+                .find_iter(haystack)
+                .map(|m| m.range())
+                .collect::<Vec<_>>()
+        };
+        for pattern in ["", "|a", "()", "(?:)", "(?=)", "(|a)+"] {
+            for dfa in [false, true] {
+                let regex = RegexBuilder::new(pattern).prefer_dfa(dfa).build().unwrap();
+                assert!(regex.is_match("") && regex.is_full_match(""), "{pattern}");
+                assert_eq!(spans(&regex, "ab"), [0..0, 1..1, 2..2], "{pattern}");
+            }
+        }
+        // Empty matches next to a real match are skipped, but not those
+        // after a character that wasn't matched.
+        let regex = Regex::new("a|").unwrap();
+        assert_eq!(spans(&regex, "aab"), [0..1, 1..2, 3..3]);
+        assert_eq!(regex.replace_all("aab", "-"), "--b-");
+        let regex = Regex::new("a*").unwrap();
+        assert_eq!(spans(&regex, "bbb"), [0..0, 1..1, 2..2, 3..3]);
+        assert_eq!(regex.replace_all("abaa", "-"), "-b-");
+        assert_eq!(Regex::new("^$").unwrap().find_iter("").count(), 1);
+        assert!(spans(&Regex::new("^$").unwrap(), "a").is_empty());
+        let regex = Regex::new("(a)?(?(1)|b)").unwrap();
+        assert_eq!(spans(&regex, "ab"), [0..1, 1..2]);
+    }
+
+    #[test]
+    fn leaves_loops_after_iterations_matching_nothing() {
+        for (pattern, haystack) in [
+            ("(?:a*?)*", "a"),
+            ("(?:b*?){2,}", "b"),
+            ("((?:c*?)*)", "c"),
+            ("(?:a*?)+", "a"),
+        ] {
+            for dfa in [false, true] {
+                let regex = RegexBuilder::new(pattern).prefer_dfa(dfa).build().unwrap();
+                assert_eq!(
+                    regex.find(haystack).map(|m| m.range()),
+                    Some(0..0),
+                    "{pattern}"
+                );
+            }
+        }
+        let regex = Regex::new(r"(?:a*?)*()\1").unwrap();
+        assert_eq!(regex.find("a").map(|m| m.range()), Some(0..0));
+    }
+
+    #[test]
+    fn skips_ahead_to_literal_prefixes() {
+        let prefix = |pattern: &str| Regex::new(pattern).unwrap().inner.prefix.clone();
+        assert_eq!(prefix(r"ERROR: \d+").as_deref(), Some("ERROR: "));
+        assert_eq!(prefix(r"\b(ab)c|abd").as_deref(), None);
+        assert_eq!(prefix(r"\b(ab)c").as_deref(), Some("abc"));
+        assert_eq!(prefix("(?:ab)+").as_deref(), Some("ab"));
+        for pattern in ["^ab", "(?i)ab", r"\Gab", "a?b", "(?>a)b|c"] {
+            assert_eq!(prefix(pattern), None, "{pattern}");
+        }
+        let haystack = "ERROR ERROR: x ERROR: 42 aERROR: 7";
+        for pattern in [r"ERROR: \d+", r"\bERROR: (\d)+", r"ERROR: (\d)\1?"] {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(regex.is_match(haystack));
+            assert!(!regex.is_match("ERROR 42"));
+            let spans: Vec<_> = regex.find_iter(haystack).map(|m| m.range()).collect();
+            let mut expected = Vec::new();
+            let mut start = 0;
+            while let Some((from, to)) = regex.inner.nfa.find_at(haystack, start) {
+                expected.push(from..to);
+                start = to;
+            }
+            assert_eq!(spans, expected, "{pattern}");
+            assert!(
+                regex.find_at(haystack, 15, Anchored::Yes).is_some(),
+                "{pattern}"
+            );
+            assert!(
+                regex.find_at(haystack, 14, Anchored::Yes).is_none(),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn skips_ahead_to_prefixes_in_any_case() {
+        let variants = |pattern: &str| {
+            let regex = Regex::new(pattern).unwrap();
+            regex
+                .inner
+                .nfa
+                .prefix_variants(64)
+                .filter(|_| regex.inner.prefixes.is_some())
+        };
+        assert_eq!(variants(r"(?i)ab\d").unwrap(), ["AB", "Ab", "aB", "ab"]);
+        assert_eq!(variants("(?i)needle").unwrap().len(), 64);
+        assert_eq!(variants("(?i)k").unwrap(), ["K", "k", "\u{212A}"]);
+        assert_eq!(
+            variants("(?i)needles").unwrap(),
+            variants("(?i)needle").unwrap()
+        );
+        for pattern in ["ab", "(?i)[ab]c", "(?i)^ab", "(?i-u)é"] {
+            assert_eq!(variants(pattern), None, "{pattern}");
+        }
+        let haystack = "GET /a get /b gEt/c Get /d";
+        for pattern in [r"(?i)get /(\w)", r"(?i)\bget\b /\w", "(?i:g)et /."] {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(regex.inner.prefixes.is_some(), "{pattern}");
+            assert!(!regex.is_match("gte /a"));
+            let spans: Vec<_> = regex.find_iter(haystack).map(|m| m.range()).collect();
+            let mut expected = Vec::new();
+            let mut start = 0;
+            while let Some((from, to)) = regex.inner.nfa.find_at(haystack, start) {
+                expected.push(from..to);
+                start = to;
+            }
+            assert_eq!(spans, expected, "{pattern}");
+            assert!(
+                regex.find_at(haystack, 20, Anchored::Yes).is_some(),
+                "{pattern}"
+            );
+            assert!(
+                regex.find_at(haystack, 19, Anchored::Yes).is_none(),
+                "{pattern}"
+            );
+        }
+        let regex = RegexBuilder::new("get")
+            .case_insensitive(true)
+            .build()
+            .unwrap();
+        assert_eq!(regex.find("the GeT").map(|m| m.start()), Some(4));
+    }
+
+    #[test]
+    fn finds_literal_alternations_without_the_automaton() {
+        let haystack = "she sells seashells by the seashore, éh?";
+        let patterns = ["sea|seashell|shore|she|hell", "é|éh|h", "x|y", "s|e|l"];
+        for pattern in patterns {
+            for kind in [MatchKind::LeftmostFirst, MatchKind::LeftmostLongest] {
+                let regex = RegexBuilder::new(pattern).match_kind(kind).build().unwrap();
+                assert!(regex.inner.literals.is_some(), "{pattern}");
+                let spans: Vec<_> = regex.find_iter(haystack).map(|m| m.range()).collect();
+                let mut expected = Vec::new();
+                let mut start = 0;
+                while let Some((from, to)) = regex.inner.nfa.find_at(haystack, start) {
+                    expected.push(from..to);
+                    start = to;
+                }
+                assert_eq!(spans, expected, "{pattern} {kind:?}");
+                assert_eq!(regex.is_match(haystack), !expected.is_empty());
+            }
+        }
+        assert!(Regex::new("(?i)ab|cd").unwrap().inner.literals.is_none());
+        assert!(RegexBuilder::new("ab|cd")
+            .case_insensitive(true)
+            .build()
+            .unwrap()
+            .inner
+            .literals
+            .is_none());
+    }
+
+    #[test]
+    fn rejects_haystacks_without_the_literal_suffix() {
+        let suffix = |pattern: &str| Regex::new(pattern).unwrap().inner.suffix.clone();
+        assert_eq!(suffix(r".*\.rs$").as_deref(), Some(".rs"));
+        assert_eq!(
+            suffix(r"(\w+)@example\.com\b").as_deref(),
+            Some("@example.com")
+        );
+        assert_eq!(suffix("(?:ab)+").as_deref(), Some("ab"));
+        for pattern in ["a|b", "ab*", "(?i)ab", "a(?=b)", r"(a)\1", "a(?>b)"] {
+            assert_eq!(suffix(pattern), None, "{pattern}");
+        }
+        let regex = Regex::new(r"(?m)^.*\.rs$").unwrap();
+        assert!(!regex.is_match("main.c\nlib.r"));
+        assert_eq!(regex.find("main.c\nlib.rs\n").unwrap().as_str(), "lib.rs");
+        let found: Vec<_> = regex
+            .find_iter("a.rs\nb.c\nc.rs")
+            .map(|m| m.as_str())
+            .collect();
+        assert_eq!(found, ["a.rs", "c.rs"]);
+    }
+
+    #[test]
+    fn finds_matches_back_from_the_literal_suffix() {
+        let kind = |pattern: &str| Regex::new(pattern).unwrap().inner.reverse_suffix;
+        assert_eq!(kind(r".*\.rs$"), Some(ReverseSuffix::AtEnd));
+        assert_eq!(kind(r"\d+-\d+ms"), Some(ReverseSuffix::FirstEnding));
+        assert_eq!(kind(r"(?:\s|x)+é"), Some(ReverseSuffix::FirstEnding));
+        // Matches may contain the suffix further left, go round it again, or
+        // are found by their prefix.
+        for pattern in [r"\w.*xb|\db", "(?:ab)+", r"(?m)^.*\.rs$", "ab+c", r"a\d*ms"] {
+            assert_eq!(kind(pattern), None, "{pattern}");
+        }
+        let haystacks = [
+            "main.rs",
+            "lib.rs\nmain.rs\n",
+            "12-34ms 5-6ms",
+            "ms12-ms1-2ms",
+            " xé xxéé é",
+            "a 1b xb",
+            "",
+        ];
+        for pattern in [
+            r".*\.rs$",
+            r"\d+-\d+ms",
+            r"(?:\s|x)+é",
+            r"(\d)-(\d+)ms",
+            r"\w.*xb|\db",
+        ] {
+            let regex = Regex::new(pattern).unwrap();
+            for haystack in haystacks {
+                assert_eq!(
+                    regex.is_match(haystack),
+                    regex.inner.nfa.find_at(haystack, 0).is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+                for start in (0..=haystack.len()).filter(|&start| haystack.is_char_boundary(start))
+                {
+                    let found = regex
+                        .find_at(haystack, start, Anchored::No)
+                        .map(|m| (m.start(), m.end()));
+                    assert_eq!(
+                        found,
+                        regex.inner.nfa.find_at(haystack, start),
+                        "{pattern} on {haystack:?} at {start}"
+                    );
+                }
+            }
+        }
+        let regex = Regex::new(r"(\d)-(\d+)ms").unwrap();
+        let caps = regex.captures("took 12-345ms").unwrap();
+        assert_eq!((&caps[0], &caps[1], &caps[2]), ("2-345ms", "2", "345"));
+    }
+
+    #[test]
+    fn finds_matches_around_inner_literals() {
+        let haystacks = [
+            "mail bob@example.com/12 or ann@example.com/7",
+            "@example.com/1 x@example.com/ y@example.com/2",
+            "1-2-3x:4 5-6x:7",
+            "a.rs:1 b.rs:x c1.rs:22 ok, d.rs:3 ok",
+            "",
+        ];
+        let patterns = [
+            r"\w+@example\.com/(\d+)",
+            r"(\d)-(\d)+x:\d+",
+            r"[a-z]+\d*\.rs:\d+",
+            r"\w*@example\.com/\d",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(
+                regex.inner.prefix.is_none() && regex.inner.reverse_suffix.is_none(),
+                "{pattern}"
+            );
+            for haystack in haystacks {
+                assert_eq!(
+                    regex.is_match(haystack),
+                    regex.inner.nfa.find_at(haystack, 0).is_some(),
+                    "{pattern} on {haystack:?}"
+                );
+                for start in (0..=haystack.len()).filter(|&start| haystack.is_char_boundary(start))
+                {
+                    let found = regex
+                        .find_at(haystack, start, Anchored::No)
+                        .map(|m| (m.start(), m.end()));
+                    assert_eq!(
+                        found,
+                        regex.inner.nfa.find_at(haystack, start),
+                        "{pattern} on {haystack:?} at {start}"
+                    );
+                }
+            }
+        }
+        let regex = Regex::new(r"(\d)-(\d)+x:\d+").unwrap();
+        let caps = regex.captures("1-2-3x:4").unwrap();
+        assert_eq!((&caps[0], &caps[1], &caps[2]), ("2-3x:4", "2", "3"));
+    }
+
+    #[test]
+    fn finds_match_starts_with_the_reversed_dfa() {
+        let haystack = "aab abcd xaby foo\nbb\ncategory é éé h 1234 foobar\n";
+        let patterns = [
+            "a+b",
+            "(a|ab)(c|bcd)",
+            "x*",
+            r"(?-u)\bfoo\b",
+            "(?m)^b+$",
+            "$",
+            "a|",
+            "[a-c]+?c",
+            "(é)+|h",
+            r"(\d{2,3})",
+            "(?s).b",
+            "b*a*",
+            "(?m)o$",
+            "cat|category",
+            r"(?-u)\b",
+            "^a|y",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            assert!(regex.inner.search.is_some(), "{pattern}");
+            let mut locations = regex.capture_locations();
+            for start in (0..=haystack.len()).filter(|&start| haystack.is_char_boundary(start)) {
+                let found = regex
+                    .find_at(haystack, start, Anchored::No)
+                    .map(|m| (m.start(), m.end()));
+                assert_eq!(
+                    found,
+                    regex.inner.nfa.find_at(haystack, start),
+                    "{pattern} at {start}"
+                );
+                regex.captures_read_at(&mut locations, haystack, start, Anchored::No);
+                let expected = regex.inner.nfa.captures_at(
+                    haystack,
+                    start,
+                    2 * regex.captures_len(),
+                    false,
+                    &regex.budget(),
+                );
+                let groups: Vec<_> = (0..regex.captures_len())
+                    .map(|idx| locations.get(idx))
+                    .collect();
+                let expected: Vec<_> = (0..regex.captures_len())
+                    .map(|idx| {
+                        expected
+                            .as_ref()
+                            .and_then(|slots| Some((slots[2 * idx]?, slots[2 * idx + 1]?)))
+                    })
+                    .collect();
+                assert_eq!(groups, expected, "{pattern} at {start}");
+            }
+        }
+        for pattern in [r"\Gab", r"(?-u)a\B", r"\bword\b", "(a)\\1"] {
+            assert!(
+                Regex::new(pattern).unwrap().inner.search.is_none(),
+                "{pattern}"
+            );
+        }
+        let longest = RegexBuilder::new("a|ab")
+            .match_kind(MatchKind::LeftmostLongest)
+            .build()
+            .unwrap();
+        assert!(longest.inner.search.is_none());
+    }
+
+    #[test]
+    fn searches_for_exact_literals_as_substrings() {
+        let regex = Regex::new("needle").unwrap();
+        assert!(regex.inner.literals.is_some());
+        let haystack = "haystack with a needle, and another needle";
+        assert!(regex.is_match(haystack));
+        assert!(!regex.is_match("needl"));
+        let found: Vec<_> = regex.find_iter(haystack).map(|m| m.range()).collect();
+        assert_eq!(found, [16..22, 36..42]);
+        assert_eq!(
+            regex.find_at(haystack, 16, Anchored::Yes).map(|m| m.end()),
+            Some(22)
+        );
+        assert!(regex.find_at(haystack, 15, Anchored::Yes).is_none());
+        let caps = regex.captures(haystack).unwrap();
+        assert_eq!(caps.get(0).unwrap().as_str(), "needle");
+        assert!(Regex::new(r"a\.b").unwrap().is_match("xa.b"));
+        for pattern in [
+            "(?i)needle",
+            "^needle",
+            "needle$",
+            "(needle)",
+            "need+le",
+            "",
+        ] {
+            assert!(
+                Regex::new(pattern).unwrap().inner.literals.is_none(),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn gives_up_on_backtracking_past_the_limit() {
+        let haystack = "a".repeat(30);
+        for pattern in [r"(a*)*\1b|c", r"x|(?=(a*)*\1b)"] {
+            let regex = RegexBuilder::new(pattern)
+                .backtrack_limit(1000)
+                .build()
+                .unwrap();
+            let exceeded = MatchError::BacktrackLimitExceeded(1000);
+            assert_eq!(
+                regex.try_is_match(&haystack),
+                Err(exceeded.clone()),
+                "{pattern}"
+            );
+            assert_eq!(regex.try_find(&haystack), Err(exceeded), "{pattern}");
+            assert!(regex.try_captures(&haystack).is_err(), "{pattern}");
+            assert!(!regex.is_match(&haystack), "{pattern}");
+            assert!(regex.find(&haystack).is_none(), "{pattern}");
+            assert_eq!(
+                regex.try_is_match("c"),
+                Ok(!pattern.starts_with('x')),
+                "{pattern}"
+            );
+            let unlimited = RegexBuilder::new(pattern)
+                .backtrack_limit(usize::MAX)
+                .build()
+                .unwrap();
+            assert_eq!(unlimited.try_is_match(&haystack), Ok(false), "{pattern}");
+        }
+        let regex = Regex::new(r"(\w+) \1").unwrap();
+        let caps = regex.try_captures("say hello hello").unwrap().unwrap();
+        #[cfg(feature = "std")]
+        {
+            let found = regex.find_with_deadline("say hello hello", Duration::from_secs(60));
+            assert_eq!(found.unwrap().unwrap().as_str(), "hello hello");
+            for pattern in [r"(a*)*\1b|c", "(?=a*b)"] {
+                let regex = RegexBuilder::new(pattern)
+                    .backtrack_limit(usize::MAX)
+                    .build()
+                    .unwrap();
+                let found = regex.find_with_deadline(&haystack, Duration::ZERO);
+                assert_eq!(
+                    found,
+                    Err(MatchError::DeadlineExceeded(Duration::ZERO)),
+                    "{pattern}"
+                );
+            }
+        }
+        assert_eq!(caps.get(1).unwrap().as_str(), "hello");
+        assert_eq!(
+            MatchError::BacktrackLimitExceeded(5).to_string(),
+            "backtracking exceeded the limit of 5 steps"
+        );
+        assert_eq!(
+            MatchError::DeadlineExceeded(core::time::Duration::from_millis(5)).to_string(),
+            "the search took longer than 5ms"
+        );
+    }
+
+    #[test]
+    fn shares_the_compiled_pattern_between_threads() {
+        fn shareable<T: Clone + Send + Sync + Debug>() {}
+        shareable::<Regex>();
+        let regex = Regex::new(r"(\w+)@(\w+)").unwrap();
+        let clone = regex.clone();
+        assert!(Arc::ptr_eq(&regex.inner, &clone.inner));
+        assert_eq!(format!("{regex:?}"), r#"Regex("(\\w+)@(\\w+)")"#);
+        let parsed: Regex = regex.as_str().parse().unwrap();
+        assert_eq!(parsed, regex);
+        assert_eq!(Regex::try_from(String::from(r"(\w+)@(\w+)")), Ok(parsed));
+        assert_ne!(Regex::try_from("a").unwrap(), regex);
+        assert!(Regex::try_from("(").is_err());
+        let hash = |regex: &Regex| {
+            let mut hasher = std::hash::DefaultHasher::new();
+            regex.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(&regex), hash(&clone));
+        let hosts: Vec<String> = std::thread::scope(|scope| {
+            let workers: Vec<_> = ["a@b", "c@d"]
+                .into_iter()
+                .map(|haystack| {
+                    let regex = &regex;
+                    scope.spawn(move || regex.captures(haystack).unwrap()[2].to_owned())
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+        assert_eq!(hosts, ["b", "d"]);
+    }
+
+    #[test]
+    fn searches_with_an_explicit_cache() {
+        let regex = Regex::new(r"(\w+)@(\w+)\.com").unwrap();
+        let clone = regex.clone();
+        let mut cache = regex.create_cache();
+        assert!(cache.search.is_some());
+        for haystack in ["mail x@y.com", "x@y.org", "@.com a@b.com"] {
+            assert_eq!(
+                clone.is_match_with(&mut cache, haystack),
+                regex.is_match(haystack)
+            );
+            assert_eq!(clone.find_with(&mut cache, haystack), regex.find(haystack));
+            assert_eq!(
+                clone.captures_with(&mut cache, haystack),
+                regex.captures(haystack)
+            );
+        }
+        let other = Regex::new(r"(\w+)@(\w+)\.com").unwrap();
+        let result =
+            std::panic::catch_unwind(|| other.find_with(&mut regex.create_cache(), "a@b.com"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn compiles_syntax_trees() {
+        // Swaps the alternatives of every group.
+        let terms = ast::fold(ast::parse("(a|b)+c").unwrap(), |element| match element {
+            ast::Element::CaptureGroup(name, mut terms, quantifier) => {
+                terms.reverse();
+                ast::Element::CaptureGroup(name, terms, quantifier)
+            }
+            element => element,
+        });
+        let regex = Regex::from_ast(terms.clone()).unwrap();
+        assert_eq!(regex.as_str(), "(b|a)+c");
+        assert_eq!(regex.ast(), terms);
+        assert_eq!(regex.captures("abc").unwrap().get(1).unwrap().as_str(), "b");
+        let verbose = RegexBuilder::new("a b")
+            .ignore_whitespace(true)
+            .build()
+            .unwrap();
+        assert_eq!(ast::to_pattern(verbose.ast()), "ab");
+        let spaced = RegexBuilder::from_ast(ast::parse("a b").unwrap())
+            .ignore_whitespace(true)
+            .build()
+            .unwrap();
+        assert!(spaced.is_match("a b"));
+        let mut invalid = ast::parse("a").unwrap();
+        invalid[0]
+            .elements
+            .push(ast::Element::Backreference(1, ast::Quantifier::Once));
+        assert_eq!(
+            Regex::from_ast(invalid).unwrap_err().kind(),
+            &ErrorKind::InvalidBackreference(1)
+        );
+    }
+}
@@ -0,0 +1,202 @@
+use alloc::vec;
+
+use crate::captures::CaptureLocations;
+use crate::fsm::Slots;
+use crate::{Anchored, Match, Regex};
+
+/// Searches for a pattern from an offset into a haystack, the way
+/// [`Regex::find_at`] does. Implemented by [`Regex`], [`FixedRegex`] and
+/// the engines a regex is made of, see [`Regex::engine`], so that code can
+/// work with any of them, or with matchers of its own, behind a
+/// `dyn Matcher`.
+///
+/// ```
+/// use regexrs::{Anchored, Engine, Matcher, Regex};
+///
+/// let regex = Regex::new(r"(\d+)-(\d+)").unwrap();
+/// let nfa = regex.engine(Engine::Nfa).unwrap();
+/// let matchers: [&dyn Matcher; 2] = [&regex, &nfa];
+/// for matcher in matchers {
+///     let mut locations = matcher.capture_locations();
+///     let m = matcher.captures_at(&mut locations, "pages 10-12", 0, Anchored::No).unwrap();
+///     assert_eq!(m.as_str(), "10-12");
+///     assert_eq!(locations.get(2), Some((9, 11)));
+///     assert!(matcher.is_match_at("pages 10-12", 6, Anchored::Yes));
+///     assert!(!matcher.is_match_at("pages 10-12", 5, Anchored::Yes));
+/// }
+/// ```
+pub trait Matcher {
+    /// Returns true if the pattern matches at or after byte offset `start`,
+    /// or right at it if `anchored` is [`Anchored::Yes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    fn is_match_at(&self, haystack: &str, start: usize, anchored: Anchored) -> bool {
+        self.find_at(haystack, start, anchored).is_some()
+    }
+    /// Returns the leftmost match at or after byte offset `start`, or right
+    /// at it if `anchored` is [`Anchored::Yes`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    fn find_at<'h>(&self, haystack: &'h str, start: usize, anchored: Anchored)
+        -> Option<Match<'h>>;
+    /// Like [`Matcher::find_at`], but also stores the offsets of the groups
+    /// in `locations`, which are all cleared if there is no match.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `start` is not at a character boundary of `haystack`.
+    fn captures_at<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>>;
+    /// Returns a buffer for the offsets of the groups, to be filled in by
+    /// [`Matcher::captures_at`].
+    fn capture_locations(&self) -> CaptureLocations;
+}
+
+/// The engines a [`Regex`] is made of, which [`Regex::engine`] hands out
+/// on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Engine {
+    /// Simulates the automaton the pattern is compiled to, or backtracks
+    /// through it if the pattern needs that. Every pattern has this one.
+    Nfa,
+    /// Follows the only way a pattern can go on at every character, for
+    /// patterns where there is only one. Unanchored searches try every
+    /// start in turn.
+    OnePass,
+    /// Runs a lazy DFA forward to where the match ends and the one of the
+    /// reversed pattern back to where it starts. It doesn't know about
+    /// groups, so only the whole match is recorded.
+    Dfa,
+}
+
+// One of the engines of a regex on its own.
+pub(crate) struct EngineMatcher<'r> {
+    regex: &'r Regex,
+    engine: Engine,
+}
+
+impl<'r> EngineMatcher<'r> {
+    pub(crate) fn new(regex: &'r Regex, engine: Engine) -> Option<Self> {
+        let inner = &regex.inner;
+        let available = match engine {
+            Engine::Nfa => true,
+            Engine::OnePass => inner.onepass.is_some(),
+            Engine::Dfa => inner.search.is_some() && inner.bytes.is_some(),
+        };
+        available.then_some(Self { regex, engine })
+    }
+    // The first `slots` capture slots of the match, like `Regex::captures_at`
+    // finds them.
+    fn slots(
+        &self,
+        haystack: &str,
+        start: usize,
+        slots: usize,
+        anchored: Anchored,
+    ) -> Option<Slots> {
+        let inner = &self.regex.inner;
+        let anchored = anchored == Anchored::Yes;
+        let budget = self.regex.budget();
+        match (self.engine, &inner.onepass, &inner.search, &inner.bytes) {
+            (Engine::OnePass, Some(onepass), _, _) if anchored || onepass.is_anchored() => {
+                onepass.captures(haystack, start, start, slots)
+            }
+            (Engine::OnePass, Some(onepass), _, _) => {
+                let mut starts = haystack[start..]
+                    .char_indices()
+                    .map(|(idx, _)| start + idx)
+                    .chain([haystack.len()]);
+                starts.find_map(|from| onepass.captures(haystack, start, from, slots))
+            }
+            (Engine::Dfa, _, Some(search), Some(bytes)) => {
+                let found = self.regex.with_cache(|cache| {
+                    let cache = cache.search.as_mut()?;
+                    search.find(bytes, cache, haystack.as_bytes(), start)
+                });
+                // The simulation takes over if the caches keep filling up.
+                let (from, to) = match found {
+                    Some(found) => found?,
+                    None => {
+                        return inner
+                            .nfa
+                            .captures_at(haystack, start, slots, anchored, &budget)
+                    }
+                };
+                if anchored && from != start {
+                    return None;
+                }
+                let mut captured = vec![None; slots];
+                captured[0] = Some(from);
+                captured[1] = Some(to);
+                Some(captured)
+            }
+            _ => inner
+                .nfa
+                .captures_at(haystack, start, slots, anchored, &budget),
+        }
+    }
+}
+
+impl Matcher for EngineMatcher<'_> {
+    fn find_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let slots = self.slots(haystack, start, 2, anchored)?;
+        Some(Match {
+            haystack,
+            start: slots[0]?,
+            end: slots[1]?,
+        })
+    }
+    fn captures_at<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        let slots = locations.0.len().max(2);
+        locations.read(haystack, self.slots(haystack, start, slots, anchored))
+    }
+    fn capture_locations(&self) -> CaptureLocations {
+        self.regex.capture_locations()
+    }
+}
+
+impl Matcher for Regex {
+    fn is_match_at(&self, haystack: &str, start: usize, anchored: Anchored) -> bool {
+        Regex::is_match_at(self, haystack, start, anchored)
+    }
+    fn find_at<'h>(
+        &self,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        Regex::find_at(self, haystack, start, anchored)
+    }
+    fn captures_at<'h>(
+        &self,
+        locations: &mut CaptureLocations,
+        haystack: &'h str,
+        start: usize,
+        anchored: Anchored,
+    ) -> Option<Match<'h>> {
+        self.captures_read_at(locations, haystack, start, anchored)
+    }
+    fn capture_locations(&self) -> CaptureLocations {
+        Regex::capture_locations(self)
+    }
+}
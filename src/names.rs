@@ -0,0 +1,206 @@
+use alloc::string::String;
+#[cfg(all(feature = "unicode-names", feature = "std"))]
+use alloc::{boxed::Box, vec::Vec};
+#[cfg(all(feature = "unicode-names", feature = "std"))]
+use std::sync::OnceLock;
+
+// Resolves the Unicode character names used in `\N{...}` escapes, as given
+// by the Unicode Character Database, version 14.0. Without the
+// `unicode-names` feature, only the names made up from code points are
+// known.
+
+// One line per named character, by code point: the distance to the previous
+// code point in hex, a space, the number of leading characters the name
+// shares with the previous one as a base 36 digit, and the rest of the
+// name. Characters whose names are made up from their code points are left
+// out and handled by `numbered` and `hangul`.
+#[cfg(feature = "unicode-names")]
+const NAMES: &str = include_str!("names.txt");
+
+// Names ending in the code point, and the ranges they are given to.
+const NUMBERED: [(&str, &[(u32, u32)]); 5] = [
+    (
+        "CJKUNIFIEDIDEOGRAPH",
+        &[
+            (0x3400, 0x4DBF),
+            (0x4E00, 0x9FFF),
+            (0x20000, 0x2A6DF),
+            (0x2A700, 0x2B738),
+            (0x2B740, 0x2B81D),
+            (0x2B820, 0x2CEA1),
+            (0x2CEB0, 0x2EBE0),
+            (0x30000, 0x3134A),
+        ],
+    ),
+    (
+        "CJKCOMPATIBILITYIDEOGRAPH",
+        &[(0xF900, 0xFA6D), (0xFA70, 0xFAD9), (0x2F800, 0x2FA1D)],
+    ),
+    ("TANGUTIDEOGRAPH", &[(0x17000, 0x187F7), (0x18D00, 0x18D08)]),
+    ("KHITANSMALLSCRIPTCHARACTER", &[(0x18B00, 0x18CD5)]),
+    ("NUSHUCHARACTER", &[(0x1B170, 0x1B2FB)]),
+];
+
+// The parts of Hangul syllable names, for the leading consonant, the vowel
+// and the trailing consonant.
+const LEADING: [&str; 19] = [
+    "G", "GG", "N", "D", "DD", "R", "M", "B", "BB", "S", "SS", "", "J", "JJ", "C", "K", "T", "P",
+    "H",
+];
+const VOWELS: [&str; 21] = [
+    "A", "AE", "YA", "YAE", "EO", "E", "YEO", "YE", "O", "WA", "WAE", "OE", "YO", "U", "WEO", "WE",
+    "WI", "YU", "EU", "YI", "I",
+];
+const TRAILING: [&str; 28] = [
+    "", "G", "GG", "GS", "N", "NJ", "NH", "D", "L", "LG", "LM", "LB", "LS", "LT", "LP", "LH", "M",
+    "B", "BS", "S", "SS", "NG", "J", "C", "K", "T", "P", "H",
+];
+
+// The character called `name`. Like in the Unicode Standard's loose
+// matching, case, spaces, underscores and hyphens don't matter, except
+// that an exact match takes precedence, which tells apart the two names
+// that only differ in a hyphen.
+pub fn lookup(name: &str) -> Option<char> {
+    let exact = name.to_ascii_uppercase();
+    let loose: String = exact.chars().filter(|&c| !is_ignored(c)).collect();
+    numbered(&loose)
+        .or_else(|| hangul(&loose))
+        .or_else(|| listed(&exact, &loose))
+}
+
+// Looks a name up in `NAMES`, going by an index of them sorted by their
+// loose forms, which is built the first time it is needed.
+#[cfg(all(feature = "unicode-names", feature = "std"))]
+fn listed(exact: &str, loose: &str) -> Option<char> {
+    static INDEX: OnceLock<Vec<(Box<str>, char)>> = OnceLock::new();
+    let index = INDEX.get_or_init(|| {
+        let mut index: Vec<(Box<str>, char)> = Vec::new();
+        each_name(|name, c| {
+            index.push((Box::from(name), c));
+            false
+        });
+        // The sort is stable, so names with the same loose form stay in
+        // the order of their code points.
+        index.sort_by(|(a, _), (b, _)| loosely(a).cmp(loosely(b)));
+        index
+    });
+    let start = index.partition_point(|(name, _)| loosely(name).lt(loose.chars()));
+    let mut same = index[start..]
+        .iter()
+        .take_while(|(name, _)| loosely(name).eq(loose.chars()));
+    let first = same.clone().next().map(|&(_, c)| c);
+    same.find(|(name, _)| **name == *exact)
+        .map(|&(_, c)| c)
+        .or(first)
+}
+
+// Without the standard library to keep an index in, walks through all of
+// `NAMES`.
+#[cfg(all(feature = "unicode-names", not(feature = "std")))]
+fn listed(exact: &str, loose: &str) -> Option<char> {
+    let mut found = None;
+    let mut first = None;
+    each_name(|name, c| {
+        if name == exact {
+            found = Some(c);
+            return true;
+        }
+        if first.is_none() && loosely(name).eq(loose.chars()) {
+            first = Some(c);
+        }
+        false
+    });
+    found.or(first)
+}
+
+#[cfg(not(feature = "unicode-names"))]
+fn listed(_exact: &str, _loose: &str) -> Option<char> {
+    None
+}
+
+// Calls `f` with each name in `NAMES` and its character, by code point,
+// until it returns true.
+#[cfg(feature = "unicode-names")]
+fn each_name<F: FnMut(&str, char) -> bool>(mut f: F) {
+    let mut code = 0;
+    let mut current = String::new();
+    for line in NAMES.lines() {
+        let Some((distance, rest)) = line.split_once(' ') else {
+            return;
+        };
+        let mut chars = rest.chars();
+        let (Ok(distance), Some(shared)) = (
+            u32::from_str_radix(distance, 16),
+            chars.next().and_then(|c| c.to_digit(36)),
+        ) else {
+            return;
+        };
+        code += distance;
+        current.truncate(shared as usize);
+        current.push_str(chars.as_str());
+        if char::from_u32(code).is_some_and(|c| f(&current, c)) {
+            return;
+        }
+    }
+}
+
+#[cfg(feature = "unicode-names")]
+fn loosely(name: &str) -> impl Iterator<Item = char> + Clone + '_ {
+    name.chars().filter(|&c| !is_ignored(c))
+}
+
+fn is_ignored(c: char) -> bool {
+    matches!(c, ' ' | '_' | '-')
+}
+
+fn numbered(name: &str) -> Option<char> {
+    NUMBERED.iter().find_map(|(prefix, ranges)| {
+        let digits = name.strip_prefix(prefix)?;
+        if !(4..=5).contains(&digits.len()) {
+            return None;
+        }
+        let code = u32::from_str_radix(digits, 16).ok()?;
+        let named = ranges.iter().any(|&(from, to)| (from..=to).contains(&code));
+        char::from_u32(code).filter(|_| named)
+    })
+}
+
+fn hangul(name: &str) -> Option<char> {
+    let syllable = name.strip_prefix("HANGULSYLLABLE")?;
+    (0..LEADING.len() * VOWELS.len() * TRAILING.len())
+        .find(|&idx| {
+            let leading = LEADING[idx / (VOWELS.len() * TRAILING.len())];
+            let vowel = VOWELS[idx / TRAILING.len() % VOWELS.len()];
+            let trailing = TRAILING[idx % TRAILING.len()];
+            [leading, vowel, trailing].concat() == syllable
+        })
+        .and_then(|idx| char::from_u32(0xAC00 + idx as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "unicode-names")]
+    fn looks_up_names() {
+        assert_eq!(lookup("LATIN SMALL LETTER A"), Some('a'));
+        assert_eq!(lookup("latin_small_letter_a"), Some('a'));
+        assert_eq!(lookup("SPACE"), Some(' '));
+        assert_eq!(lookup("GRINNING FACE"), Some('😀'));
+        assert_eq!(lookup("HANGUL JUNGSEONG OE"), Some('\u{116C}'));
+        assert_eq!(lookup("HANGUL JUNGSEONG O-E"), Some('\u{1180}'));
+        assert_eq!(lookup("hangul jungseong o_e"), Some('\u{116C}'));
+        assert_eq!(lookup("ZERO WIDTH SPACE"), Some('\u{200B}'));
+        assert_eq!(lookup("NO SUCH CHARACTER"), None);
+        assert_eq!(lookup(""), None);
+    }
+
+    #[test]
+    fn makes_up_names_from_code_points() {
+        assert_eq!(lookup("CJK UNIFIED IDEOGRAPH-4E00"), Some('一'));
+        assert_eq!(lookup("CJK UNIFIED IDEOGRAPH-0041"), None);
+        assert_eq!(lookup("HANGUL SYLLABLE GAG"), Some('각'));
+        assert_eq!(lookup("HANGUL SYLLABLE HIH"), Some('힣'));
+    }
+}
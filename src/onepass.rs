@@ -0,0 +1,224 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::charset::CharSet;
+use crate::collections::{Map, Set};
+use crate::fsm::{Look, Node, Slots, NFA};
+
+// An automaton for patterns in which every match takes exactly one path
+// through the `NFA`: at each position, the next character decides which node
+// consumes it, so capture positions can be recorded by a single thread
+// instead of one per node as in the Pike VM.
+//
+// Every state stands for a node that the `NFA` moves to after consuming a
+// character, together with all nodes reachable from it without consuming
+// anything. Whether a pattern qualifies is worked out conservatively: the
+// character sets leaving a state must not overlap, and no node may be
+// reachable from a state in more than one way.
+pub struct OnePass {
+    states: Vec<State>,
+    start: usize,
+    restart: Option<usize>,
+}
+
+struct State {
+    // The character ranges leaving the state, sorted, and the index of the
+    // transition each belongs to.
+    ranges: Vec<(char, char, usize)>,
+    transitions: Vec<Transition>,
+    // How the state reaches the end of the pattern, if it does.
+    accept: Option<Path>,
+}
+
+struct Transition {
+    path: Path,
+    target: usize,
+    // Whether the path has lower priority than the one to the end of the
+    // pattern, so that it is only taken if the latter's assertions fail.
+    // Searches for the longest match take it regardless.
+    after_match: bool,
+}
+
+// The capture slots and assertions along the nodes that don't consume
+// anything, which are all at the same position.
+#[derive(Clone, Default)]
+struct Path {
+    saves: Vec<usize>,
+    looks: Vec<Look>,
+}
+
+impl Path {
+    fn holds(&self, haystack: &[u8], start: usize, position: usize) -> bool {
+        self.looks
+            .iter()
+            .all(|look| look.holds(haystack, start, position))
+    }
+    fn apply(&self, captured: &mut Slots, position: usize) {
+        for &slot in &self.saves {
+            if let Some(value) = captured.get_mut(slot) {
+                *value = Some(position);
+            }
+        }
+    }
+}
+
+impl OnePass {
+    // Builds the automaton, giving up if the pattern doesn't qualify or the
+    // result would take up more than `size_limit` bytes.
+    pub fn new(nfa: &NFA, size_limit: usize) -> Option<Self> {
+        let (start, restart) = nfa.entries();
+        let mut builder = Builder::default();
+        let start = builder.state(start);
+        let restart = restart.map(|restart| builder.state(restart));
+        let mut states = Vec::new();
+        let mut size = 0;
+        while let Some(&root) = builder.roots.get(states.len()) {
+            let state = builder.closure(nfa, root)?;
+            size += core::mem::size_of::<State>()
+                + core::mem::size_of_val(state.ranges.as_slice())
+                + core::mem::size_of_val(state.transitions.as_slice());
+            if size > size_limit {
+                return None;
+            }
+            states.push(state);
+        }
+        Some(Self {
+            states,
+            start,
+            restart,
+        })
+    }
+    // Whether every match has to start at the beginning of the haystack.
+    pub fn is_anchored(&self) -> bool {
+        self.restart.is_none()
+    }
+    // The captures of the match starting exactly at byte offset `from`, in a
+    // search that began at `start`, filling in the first `slots` capture
+    // slots. Like in the Pike VM, only branches not anchored with `^` are
+    // tried after the beginning of the haystack.
+    pub fn captures(
+        &self,
+        haystack: &str,
+        start: usize,
+        from: usize,
+        slots: usize,
+    ) -> Option<Slots> {
+        let mut state = if from == 0 { self.start } else { self.restart? };
+        let mut captured = vec![None; slots];
+        if let Some(first) = captured.first_mut() {
+            *first = Some(from);
+        }
+        let bytes = haystack.as_bytes();
+        let mut matched = None;
+        let mut position = from;
+        let mut chars = haystack[from..].chars();
+        loop {
+            let current = &self.states[state];
+            let accepted = current
+                .accept
+                .as_ref()
+                .filter(|path| path.holds(bytes, start, position))
+                .map(|path| {
+                    let mut found = captured.clone();
+                    path.apply(&mut found, position);
+                    if let Some(end) = found.get_mut(1) {
+                        *end = Some(position);
+                    }
+                    matched = Some(found);
+                })
+                .is_some();
+            let Some(c) = chars.next() else { break };
+            let Some(transition) = current.transition(c) else {
+                break;
+            };
+            if accepted && transition.after_match || !transition.path.holds(bytes, start, position)
+            {
+                break;
+            }
+            transition.path.apply(&mut captured, position);
+            position += c.len_utf8();
+            state = transition.target;
+        }
+        matched
+    }
+}
+
+impl State {
+    fn transition(&self, c: char) -> Option<&Transition> {
+        let idx = self.ranges.partition_point(|range| range.1 < c);
+        match self.ranges.get(idx) {
+            Some(&(from, _, transition)) if from <= c => Some(&self.transitions[transition]),
+            _ => None,
+        }
+    }
+}
+
+// Numbers the nodes that states start from in the order they are found.
+#[derive(Default)]
+struct Builder {
+    roots: Vec<usize>,
+    states: Map<usize, usize>,
+}
+
+impl Builder {
+    fn state(&mut self, root: usize) -> usize {
+        *self.states.entry(root).or_insert_with(|| {
+            self.roots.push(root);
+            self.roots.len() - 1
+        })
+    }
+    // Follows the moves from `root` that don't consume anything in priority
+    // order, or returns `None` if the state disqualifies the pattern.
+    fn closure(&mut self, nfa: &NFA, root: usize) -> Option<State> {
+        let mut visited = Set::new();
+        let mut stack = vec![(root, Path::default())];
+        let mut sets: Vec<&CharSet> = Vec::new();
+        let mut transitions = Vec::new();
+        let mut accept = None;
+        while let Some((idx, mut path)) = stack.pop() {
+            if !visited.insert(idx) {
+                return None;
+            }
+            match &nfa.graph()[idx] {
+                Node::Consume(set, next) => {
+                    sets.push(set);
+                    transitions.push(Transition {
+                        path,
+                        target: self.state(*next),
+                        after_match: accept.is_some() && !nfa.longest(),
+                    });
+                }
+                Node::Epsilon(targets) => {
+                    stack.extend(targets.iter().rev().map(|&target| (target, path.clone())))
+                }
+                Node::Save(slot, next) => {
+                    path.saves.push(*slot);
+                    stack.push((*next, path));
+                }
+                Node::Assert(look, next) => {
+                    path.looks.push(*look);
+                    stack.push((*next, path));
+                }
+                Node::Match => accept = Some(path),
+                Node::Lookahead(..)
+                | Node::Backref(..)
+                | Node::Atomic(..)
+                | Node::Conditional(..) => return None,
+            }
+        }
+        let mut ranges: Vec<(char, char, usize)> = sets
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, set)| set.ranges().iter().map(move |&(from, to)| (from, to, idx)))
+            .collect();
+        ranges.sort_unstable();
+        if ranges.windows(2).any(|pair| pair[0].1 >= pair[1].0) {
+            return None;
+        }
+        Some(State {
+            ranges,
+            transitions,
+            accept,
+        })
+    }
+}
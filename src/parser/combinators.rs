@@ -1,6 +1,80 @@
-use std::fmt::Debug;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
 
-pub(super) type ParseResult<'a, Output> = Result<(Output, &'a str), ()>;
+use crate::ErrorKind;
+
+pub(super) type ParseResult<'a, Output> = Result<(Output, &'a str), Failure<'a>>;
+
+// Why a parser gave up, and the input that was left at that point.
+#[derive(Debug, PartialEq)]
+pub(super) struct Failure<'a> {
+    pub(super) rest: &'a str,
+    pub(super) reason: Reason,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) enum Reason {
+    // The input didn't fit, but another alternative might. Holds what each
+    // alternative expected, once.
+    Expected(Vec<String>),
+    // The input is definitely invalid, so no alternatives are tried.
+    Fatal(ErrorKind),
+}
+
+impl<'a> Failure<'a> {
+    pub(super) fn expected<S: Into<String>>(rest: &'a str, what: S) -> Self {
+        Self {
+            rest,
+            reason: Reason::Expected(vec![what.into()]),
+        }
+    }
+    pub(super) fn fatal(rest: &'a str, kind: ErrorKind) -> Self {
+        Self {
+            rest,
+            reason: Reason::Fatal(kind),
+        }
+    }
+    pub(super) fn is_fatal(&self) -> bool {
+        matches!(self.reason, Reason::Fatal(_))
+    }
+    // Keeps a fatal failure, or else whichever failure got further into the
+    // input, merging the expectations of failures at the same position.
+    fn furthest(self, other: Self) -> Self {
+        if self.is_fatal() {
+            return self;
+        }
+        if other.is_fatal() {
+            return other;
+        }
+        if self.rest.len() < other.rest.len() {
+            return self;
+        }
+        if other.rest.len() < self.rest.len() {
+            return other;
+        }
+        match (self.reason, other.reason) {
+            (Reason::Expected(mut expected), Reason::Expected(more)) => {
+                for what in more {
+                    if !expected.contains(&what) {
+                        expected.push(what);
+                    }
+                }
+                Self {
+                    rest: self.rest,
+                    reason: Reason::Expected(expected),
+                }
+            }
+            (reason, _) => Self {
+                rest: self.rest,
+                reason,
+            },
+        }
+    }
+}
 
 pub(super) trait Parser<'a, Output> {
     fn parse(&self, input: &'a str) -> ParseResult<'a, Output>;
@@ -26,9 +100,21 @@ pub(super) trait Parser<'a, Output> {
         Self: Sized + 'a,
         Output: 'a,
     {
-        let alternative = move |input| self.parse(input).or_else(|_| parser.parse(input));
+        let alternative = move |input| match self.parse(input) {
+            Err(failure) if !failure.is_fatal() => {
+                parser.parse(input).map_err(|other| failure.furthest(other))
+            }
+            result => result,
+        };
         BoxedParser::new(alternative)
     }
+    fn label(self, what: &'static str) -> BoxedParser<'a, Output>
+    where
+        Self: Sized + 'a,
+        Output: 'a,
+    {
+        BoxedParser::new(label(self, what))
+    }
 }
 
 impl<'a, F, Output> Parser<'a, Output> for F
@@ -104,30 +190,43 @@ where
     F: Fn(&A) -> bool,
 {
     move |input| {
-        if let Ok((result, rest)) = parser.parse(input) {
-            if predicate(&result) {
-                return Ok((result, rest));
-            }
+        let (result, rest) = parser.parse(input)?;
+        if predicate(&result) {
+            Ok((result, rest))
+        } else {
+            Err(Failure::expected(input, "a different character"))
         }
-        Err(())
+    }
+}
+
+// Describes what `parser` expects, unless it failed further into the input.
+pub(super) fn label<'a, A>(parser: impl Parser<'a, A>, what: &'static str) -> impl Parser<'a, A> {
+    move |input| {
+        parser.parse(input).map_err(|failure| {
+            if failure.is_fatal() || failure.rest.len() < input.len() {
+                failure
+            } else {
+                Failure::expected(input, what)
+            }
+        })
     }
 }
 
 pub(super) fn one_or_more<'a, R>(parser: impl Parser<'a, R>) -> impl Parser<'a, Vec<R>> {
     move |input| {
-        let mut result = Vec::new();
-        let mut tmp_input;
-        if let Ok((first, rest)) = parser.parse(input) {
-            tmp_input = rest;
-            result.push(first);
-        } else {
-            return Err(());
-        }
-        while let Ok((next, rest)) = parser.parse(tmp_input) {
-            tmp_input = rest;
-            result.push(next);
+        let (first, rest) = parser.parse(input)?;
+        let mut result = vec![first];
+        let mut tmp_input = rest;
+        loop {
+            match parser.parse(tmp_input) {
+                Ok((next, rest)) => {
+                    tmp_input = rest;
+                    result.push(next);
+                }
+                Err(failure) if failure.is_fatal() => return Err(failure),
+                Err(_) => return Ok((result, tmp_input)),
+            }
         }
-        return Ok((result, tmp_input));
     }
 }
 
@@ -135,42 +234,42 @@ pub(super) fn zero_or_more<'a, R>(parser: impl Parser<'a, R>) -> impl Parser<'a,
     move |input| {
         let mut result = Vec::new();
         let mut tmp_input = input;
-        while let Ok((next, rest)) = parser.parse(tmp_input) {
-            tmp_input = rest;
-            result.push(next);
+        loop {
+            match parser.parse(tmp_input) {
+                Ok((next, rest)) => {
+                    tmp_input = rest;
+                    result.push(next);
+                }
+                Err(failure) if failure.is_fatal() => return Err(failure),
+                Err(_) => return Ok((result, tmp_input)),
+            }
         }
-        return Ok((result, tmp_input));
     }
 }
 
 pub(super) fn maybe<'a, R>(parser: impl Parser<'a, R>) -> impl Parser<'a, Option<R>> {
     move |input| match parser.parse(input) {
         Ok((value, rest)) => Ok((Some(value), rest)),
-        Err(()) => Ok((None, input)),
+        Err(failure) if failure.is_fatal() => Err(failure),
+        Err(_) => Ok((None, input)),
     }
 }
 
-pub(super) fn any_char(input: &str) -> ParseResult<char> {
+pub(super) fn any_char(input: &str) -> ParseResult<'_, char> {
     match input.chars().next() {
         Some(next) => Ok((next, &input[next.len_utf8()..])),
-        _ => Err(()),
+        _ => Err(Failure::expected(input, "a character")),
     }
 }
 
-pub(super) fn match_literal(expected: &'static str) -> impl Fn(&str) -> Result<((), &str), ()> {
-    move |input| match input.split_once(expected) {
-        Some((before, rest)) => {
-            if before == "" {
-                Ok(((), rest))
-            } else {
-                Err(())
-            }
-        }
-        None => Err(()),
+pub(super) fn match_literal(expected: &'static str) -> impl Fn(&str) -> ParseResult<'_, ()> {
+    move |input| match input.strip_prefix(expected) {
+        Some(rest) => Ok(((), rest)),
+        None => Err(Failure::expected(input, format!("`{expected}`"))),
     }
 }
 
-pub(super) fn whitespace(input: &str) -> ParseResult<()> {
+pub(super) fn whitespace(input: &str) -> ParseResult<'_, ()> {
     zero_or_more(any_char.pred(|c| c.is_whitespace()))
         .map(|_| ())
         .parse(input)
@@ -185,20 +284,21 @@ pub(super) fn sep_by<'a, R: Debug>(
     sep: &'static str,
 ) -> impl Parser<'a, Vec<R>> {
     move |input| {
-        if let Ok((first, rest)) = parser.parse(input) {
-            let mut result = Vec::new();
-            let mut tmp_input = rest;
-            result.push(first);
-            while let Ok((next, rest)) = whitespace_surrounded_sep(sep)
+        let (first, rest) = parser.parse(input)?;
+        let mut result = vec![first];
+        let mut tmp_input = rest;
+        loop {
+            match whitespace_surrounded_sep(sep)
                 .parse(tmp_input)
                 .and_then(|(_, s)| parser.parse(s))
             {
-                tmp_input = rest;
-                result.push(next)
+                Ok((next, rest)) => {
+                    tmp_input = rest;
+                    result.push(next)
+                }
+                Err(failure) if failure.is_fatal() => return Err(failure),
+                Err(_) => return Ok((result, tmp_input)),
             }
-            return Ok((result, tmp_input));
-        } else {
-            return Err(());
         }
     }
 }
@@ -1,103 +1,236 @@
 mod combinators;
+mod print;
+mod verbose;
+
+use alloc::borrow::ToOwned;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
 
 use combinators::*;
 
+pub(crate) use print::Written;
+pub(crate) use verbose::Stripped;
+
+use crate::ast::{fold, walk, Visitor};
+use crate::{names, Error, ErrorKind};
+
 const SPECIAL_CHARS: [char; 14] = [
     '.', '^', '$', '*', '+', '?', '{', '}', '[', ']', '\\', '|', '(', ')',
 ];
 
-const ESCAPES: [char; 7] = ['a', 'b', 'f', 'n', 'r', 't', 'v'];
+// Escapes for control characters, and what they stand for. Inside classes,
+// `\b` stands for a backspace as well.
+const ESCAPES: [(char, char); 7] = [
+    ('a', '\x07'),
+    ('f', '\x0C'),
+    ('n', '\n'),
+    ('r', '\r'),
+    ('t', '\t'),
+    ('v', '\x0B'),
+    ('0', '\0'),
+];
 
-const SEQ_CHARS: [char; 10] = ['A', 'b', 'B', 'd', 'D', 's', 'S', 'w', 'W', 'Z'];
+const SEQ_CHARS: [char; 14] = [
+    'A', 'b', 'B', 'd', 'D', 'G', 'R', 's', 'S', 'w', 'W', 'X', 'Z', 'z',
+];
 
-#[derive(PartialEq, Debug)]
-pub(crate) enum Quantifier {
+/// How often an element repeats, and how eagerly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Quantifier {
+    /// `*`
     ZeroOrMore,
+    /// `+`
     OneOrMore,
+    /// `?`
     Maybe,
+    /// `*?`
     LazyZeroOrMore,
+    /// `+?`
     LazyOneOrMore,
+    /// `??`
     LazyMaybe,
+    /// `*+`
+    PossessiveZeroOrMore,
+    /// `++`
+    PossessiveOneOrMore,
+    /// `?+`
+    PossessiveMaybe,
+    /// No quantifier.
     Once,
+    /// `{n}`
+    Exactly(usize),
+    /// `{n,}`
     AtLeast(usize),
+    /// `{n,m}`
     Between(usize, usize),
+    /// `{n}?`
+    LazyExactly(usize),
+    /// `{n,}?`
+    LazyAtLeast(usize),
+    /// `{n,m}?`
+    LazyBetween(usize, usize),
+    /// `{n}+`
+    PossessiveExactly(usize),
+    /// `{n,}+`
+    PossessiveAtLeast(usize),
+    /// `{n,m}+`
+    PossessiveBetween(usize, usize),
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum Sign {
+/// Whether a class or lookahead matches what it contains, or everything
+/// else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sign {
+    /// `[...]` or `(?=...)`.
     Inclusive,
+    /// `[^...]` or `(?!...)`.
     Exclusive,
 }
 
-#[derive(PartialEq, Debug)]
-pub(crate) enum Token {
+/// An item of a character class.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Token {
+    /// A range like `a-z`.
     Range(char, char),
+    /// A single character.
     Literal(char),
+    /// `\d`, `\s`, `\w` or one of their negations.
+    Perl(SpecialSequence),
+    /// A class nested in another one, like `[a-z]` in `[[a-z][0-9]]`.
+    Class(Sign, Vec<Token>),
+    /// A class defined with
+    /// [`RegexBuilder::define_class`](crate::RegexBuilder::define_class),
+    /// referred to by its name as `[:name:]`, or as `\p{name}` outside of
+    /// classes. The builder replaces it with the class it stands for.
+    Named(String),
+}
+
+/// A flag that can be changed inside a pattern, like `i` in `(?i)`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Flag {
+    /// `i`
+    CaseInsensitive,
+    /// `m`
+    MultiLine,
+    /// `s`
+    DotAll,
+    /// `x`
+    Verbose,
+    /// `u`
+    Unicode,
+    /// `R`
+    Crlf,
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct CharacterClass {
-    sign: Sign,
-    items: Vec<Token>,
-    quantifier: Quantifier,
+/// A character class, like `[a-z]`, with its quantifier. A literal
+/// character is a class with a single item.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CharacterClass {
+    /// Whether the class is negated.
+    pub sign: Sign,
+    /// The items of the class.
+    pub items: Vec<Token>,
+    /// How often the class repeats.
+    pub quantifier: Quantifier,
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum Element {
+/// A single element of a [`Term`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Element {
+    /// A character class or a literal character.
     Class(CharacterClass),
+    /// A special sequence, like `\d` or `.`.
     Sequence(SpecialSequence, Quantifier),
-    CaptureGroup(Term, Quantifier),
+    /// A capture group, with its name if it has one, and its alternatives,
+    /// separated by `|`.
+    CaptureGroup(Option<String>, Vec<Term>, Quantifier),
+    /// `(?flags:...)`, a group that doesn't capture, with flags that only
+    /// apply inside it. The flags enabled come first, e.g. `(?i-s:...)`.
+    Group(Vec<(Flag, bool)>, Vec<Term>, Quantifier),
+    /// `(?flags)`, changing flags up to the end of the enclosing group.
+    Flags(Vec<(Flag, bool)>),
+    /// `(?=...)` if inclusive, `(?!...)` if exclusive.
+    Lookahead(Sign, Vec<Term>),
+    /// `\1` to `\9`, matching the text last captured by that group. Named
+    /// backreferences, `(?P=name)` or `\k<name>`, are numbered like this
+    /// too once the pattern is parsed.
+    Backreference(usize, Quantifier),
+    /// `(?>...)`, a group that never gives back what it matched, even if
+    /// the rest of the pattern then fails.
+    Atomic(Vec<Term>, Quantifier),
+    /// `(?(1)then|else)`, matching the first alternative if the group has
+    /// taken part in the match so far, and the second one, if any,
+    /// otherwise.
+    Conditional(usize, Vec<Term>, Quantifier),
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) enum SpecialSequence {
-    // .
-    // Matches any character
+/// An escape sequence, `.`, `^` or `$`, standing for a class of characters
+/// or an assertion. Which characters the classes contain, and where the
+/// assertions hold, depends on the flags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SpecialSequence {
+    /// `.`, any character but a newline, unless `s` is set.
     AnyCharacter,
-    // \A
-    // Matches only at the start of the string.
+    /// `\A`, the start of the haystack.
     Start,
-    // \b
-    // Matches the empty string, but only at the beginning or end of a word. A word is defined as a sequence of word characters. Note that formally, \b is defined as the
-    //  boundary between a \w and a \W character (or vice versa), or between \w and the beginning/end of the string. This means that r'\bfoo\b' matches 'foo', 'foo.', '(foo)',
-    // 'bar foo baz' but not 'foobar' or 'foo3'.
+    /// `\b`, between a word character and something else.
     WordBoundary,
-    // \B
-    // Matches the empty string, but only when it is not at the beginning or end of a word. This means that r'py\B' matches 'python', 'py3', 'py2', but not 'py', 'py.', or
-    // 'py!'. \B is just the opposite of \b, so word characters in Unicode patterns are Unicode alphanumerics or the underscore, although this can be changed by using the ASCII
-    // flag. Word boundaries are determined by the current locale if the LOCALE flag is used.
+    /// `\B`, anywhere but at a word boundary.
     WithinWord,
-    // \d
-    // Matches any Unicode decimal digit (that is, any character in Unicode character category [Nd]). This includes [0-9], and also many other digit characters.
+    /// `\d`, a decimal digit.
     Digit,
-    // \D
-    // Matches any character which is not a decimal digit. This is the opposite of \d. If the ASCII flag is used this becomes the equivalent of [^0-9].
+    /// `\D`, anything but a decimal digit.
     NotDigit,
-    // \s
-    // Matches Unicode whitespace characters (which includes [ \t\n\r\f\v], and also many other characters,
+    /// `\s`, a whitespace character.
     Whitespace,
-    // \S
-    // Matches any character which is not a whitespace character. This is the opposite of \s..
+    /// `\S`, anything but a whitespace character.
     NotWhitespace,
-    // \w
-    // Matches Unicode word characters; this includes alphanumeric characters (as defined by str.isalnum()) as well as the underscore (_).
+    /// `\w`, a letter, digit or underscore.
     WordCharacter,
-    // \W
-    // Matches any character which is not a word character. This is the opposite of \w.
+    /// `\W`, anything but a word character.
     NotWordCharacter,
-    // \Z
-    // Matches only at the end of the string
+    /// `\Z`, the end of the haystack, or before a newline that ends it.
     End,
+    /// `\z`, the very end of the haystack.
+    AbsoluteEnd,
+    /// `\G`, where the search started.
+    SearchStart,
+    /// `\R`, a line break: `\r\n`, or any one of `\n`, `\x0B`, `\x0C`,
+    /// `\r`, `\x85`, `\u2028` and `\u2029`.
+    LineBreak,
+    /// `\X`, an extended grapheme cluster, i.e. what a reader perceives as
+    /// a single character, like an emoji with its modifiers.
+    Grapheme,
+    /// `^` anywhere but at the start of a term, where it anchors the term:
+    /// the start of the haystack, or of a line in multi-line mode.
+    LineStart,
+    /// `$` anywhere but at the end of a term, where it anchors the term:
+    /// the end of the haystack, or of a line in multi-line mode.
+    LineEnd,
 }
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct Term {
-    left_anchored: bool,
-    right_anchored: bool,
-    elements: Vec<Element>,
+/// One alternative of a pattern or group: a sequence of elements, which
+/// may be anchored with `^` at its start or `$` at its end. Anywhere else,
+/// `^` and `$` are elements of the term.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    /// Whether the term starts with `^`.
+    pub left_anchored: bool,
+    /// Whether the term ends with `$`.
+    pub right_anchored: bool,
+    /// The elements, in order.
+    pub elements: Vec<Element>,
 }
 
-fn character_class(input: &str) -> ParseResult<Element> {
+fn character_class(input: &str) -> ParseResult<'_, Element> {
     pair(
         right(
             match_literal("["),
@@ -110,45 +243,168 @@ fn character_class(input: &str) -> ParseResult<Element> {
         items,
         quantifier: quantifier.unwrap_or(Quantifier::Once),
     })
-    .map(|c| Element::Class(c))
+    .map(Element::Class)
     .parse(input)
 }
 
-fn parse_quantifier(input: &str) -> ParseResult<Quantifier> {
-    match_literal("+?").map(|_| Quantifier::LazyOneOrMore)
+fn parse_quantifier(input: &str) -> ParseResult<'_, Quantifier> {
+    match_literal("+?")
+        .map(|_| Quantifier::LazyOneOrMore)
         .or(match_literal("*?").map(|_| Quantifier::LazyZeroOrMore))
         .or(match_literal("??").map(|_| Quantifier::LazyMaybe))
+        .or(match_literal("++").map(|_| Quantifier::PossessiveOneOrMore))
+        .or(match_literal("*+").map(|_| Quantifier::PossessiveZeroOrMore))
+        .or(match_literal("?+").map(|_| Quantifier::PossessiveMaybe))
         .or(match_literal("+").map(|_| Quantifier::OneOrMore))
         .or(match_literal("*").map(|_| Quantifier::ZeroOrMore))
         .or(match_literal("?").map(|_| Quantifier::Maybe))
-        .or(left(
-            right(match_literal("{"), sep_by(parse_int, ",")),
-            match_literal("}"),
-        )
-        .map(|values| {
-            if values.len() == 1 {
-                Quantifier::AtLeast(values[0])
-            } else {
-                Quantifier::Between(values[0], values[1])
+        .or(counted_repetition)
+        .parse(input)
+}
+
+// `{n}`, `{n,}`, `{n,m}` or `{,m}`, followed by `?` to repeat lazily, or by
+// `+` to repeat possessively.
+fn counted_repetition(input: &str) -> ParseResult<'_, Quantifier> {
+    let bounds = pair(
+        maybe(repetition_count),
+        maybe(right(
+            whitespace_surrounded_sep(","),
+            maybe(repetition_count),
+        )),
+    );
+    let ((min, max), rest) =
+        left(right(match_literal("{"), bounds), match_literal("}")).parse(input)?;
+    let (greedy, lazy, possessive) = match (min, max) {
+        (Some(n), None) => (
+            Quantifier::Exactly(n),
+            Quantifier::LazyExactly(n),
+            Quantifier::PossessiveExactly(n),
+        ),
+        (Some(min), Some(None)) => (
+            Quantifier::AtLeast(min),
+            Quantifier::LazyAtLeast(min),
+            Quantifier::PossessiveAtLeast(min),
+        ),
+        (min, Some(Some(max))) => match min.unwrap_or(0) {
+            min if min > max => {
+                return Err(Failure::fatal(
+                    input,
+                    ErrorKind::InvalidRepetition(min, max),
+                ))
             }
-        }))
+            min => (
+                Quantifier::Between(min, max),
+                Quantifier::LazyBetween(min, max),
+                Quantifier::PossessiveBetween(min, max),
+            ),
+        },
+        (None, _) => return Err(Failure::expected(input, "a repetition count")),
+    };
+    match rest.chars().next() {
+        Some('?') => Ok((lazy, &rest[1..])),
+        Some('+') => Ok((possessive, &rest[1..])),
+        _ => Ok((greedy, rest)),
+    }
+}
+
+// A bound of a counted repetition, which fails for good if it doesn't fit
+// into a `usize`.
+fn repetition_count(input: &str) -> ParseResult<'_, usize> {
+    let (digits, rest) = parse_digits(input)?;
+    match digits.parse() {
+        Ok(count) => Ok((count, rest)),
+        Err(_) => Err(Failure::fatal(
+            input,
+            ErrorKind::RepetitionLimitExceeded(usize::MAX),
+        )),
+    }
+}
+
+// A group number. Numbers too large for a `usize` become `usize::MAX`, and
+// fail as references to a group that doesn't exist.
+fn parse_int(input: &str) -> ParseResult<'_, usize> {
+    parse_digits
+        .map(|digits| digits.parse().unwrap_or(usize::MAX))
         .parse(input)
 }
 
-fn parse_int(input: &str) -> ParseResult<usize> {
-    one_or_more(any_char.pred(|&c| c.is_digit(10)))
-        .map(|value| {
-            let value: String = value.iter().collect();
-            usize::from_str_radix(&value, 10).unwrap()
-        })
+fn parse_digits(input: &str) -> ParseResult<'_, String> {
+    one_or_more(any_char.pred(|&c| c.is_ascii_digit()))
+        .map(|digits| digits.into_iter().collect())
+        .parse(input)
+}
+
+// A `]` right at the start of a class is a literal, as is a `-` that doesn't
+// stand between the ends of a range.
+fn inside_character_class(input: &str) -> ParseResult<'_, (Sign, Vec<Token>)> {
+    let (sign, rest) = parse_sign(input)?;
+    if let Ok((_, rest)) = match_literal("]").parse(rest) {
+        let (mut items, rest) = zero_or_more(class_item).parse(rest)?;
+        items.insert(0, Token::Literal(']'));
+        return Ok(((sign, items), rest));
+    }
+    let (items, rest) = one_or_more(class_item).parse(rest)?;
+    Ok(((sign, items), rest))
+}
+
+fn class_item(input: &str) -> ParseResult<'_, Token> {
+    named_class
+        .or(nested_class)
+        .or(property_item)
+        .or(perl_item)
+        .or(character_range)
+        .or(single_item)
+        .parse(input)
+}
+
+// `[:name:]`, or `[:^name:]` for everything but the named class.
+fn named_class(input: &str) -> ParseResult<'_, Token> {
+    right(
+        match_literal("[:"),
+        left(
+            pair(parse_sign, name.label("a class name")),
+            match_literal(":]"),
+        ),
+    )
+    .map(|(sign, name)| named_item(sign, name))
+    .parse(input)
+}
+
+// `\p{name}` for a named class, or `\P{name}` for everything but it.
+fn property_class(input: &str) -> ParseResult<'_, (Sign, String)> {
+    pair(
+        match_literal("\\p{")
+            .map(|_| Sign::Inclusive)
+            .or(match_literal("\\P{").map(|_| Sign::Exclusive)),
+        left(name.label("a class name"), match_literal("}")),
+    )
+    .parse(input)
+}
+
+fn property_item(input: &str) -> ParseResult<'_, Token> {
+    property_class
+        .map(|(sign, name)| named_item(sign, name))
         .parse(input)
 }
 
-fn inside_character_class(input: &str) -> ParseResult<(Sign, Vec<Token>)> {
-    pair(parse_sign, one_or_more(character_range.or(single_item))).parse(input)
+fn named_item(sign: Sign, name: String) -> Token {
+    match sign {
+        Sign::Inclusive => Token::Named(name),
+        Sign::Exclusive => Token::Class(sign, vec![Token::Named(name)]),
+    }
 }
 
-fn parse_sign(input: &str) -> ParseResult<Sign> {
+// A `[` that doesn't open a nested class is a literal.
+fn nested_class(input: &str) -> ParseResult<'_, Token> {
+    right(
+        match_literal("["),
+        left(inside_character_class, match_literal("]")),
+    )
+    .map(|(sign, items)| Token::Class(sign, items))
+    .parse(input)
+}
+
+fn parse_sign(input: &str) -> ParseResult<'_, Sign> {
     maybe(match_literal("^"))
         .map(|s| match s {
             Some(_) => Sign::Exclusive,
@@ -157,70 +413,261 @@ fn parse_sign(input: &str) -> ParseResult<Sign> {
         .parse(input)
 }
 
-fn single_item(input: &str) -> ParseResult<Token> {
-    not_backslash
-        .pred(|&c| c != ']')
-        .map(|c| Token::Literal(c))
-        .parse(input)
+fn single_item(input: &str) -> ParseResult<'_, Token> {
+    class_character.map(Token::Literal).parse(input)
 }
 
-fn character_range(input: &str) -> ParseResult<Token> {
-    if let Ok((values, rest)) = sep_by(not_backslash.pred(|&c| c != '-'), "-").parse(input) {
-        if values.len() == 2 {
-            Ok((Token::Range(values[0], values[1]), rest))
-        } else {
-            Err(())
+fn perl_item(input: &str) -> ParseResult<'_, Token> {
+    right(
+        match_literal("\\"),
+        any_char
+            .pred(|c| "dDsSwW".contains(*c))
+            .label("a perl class"),
+    )
+    .map(|c| {
+        Token::Perl(match c {
+            'd' => SpecialSequence::Digit,
+            'D' => SpecialSequence::NotDigit,
+            's' => SpecialSequence::Whitespace,
+            'S' => SpecialSequence::NotWhitespace,
+            'w' => SpecialSequence::WordCharacter,
+            _ => SpecialSequence::NotWordCharacter,
+        })
+    })
+    .parse(input)
+}
+
+fn character_range(input: &str) -> ParseResult<'_, Token> {
+    // A perl class has no end to stand for, so it can't end a range.
+    if let Ok((_, end)) = left(class_character, match_literal("-")).parse(input) {
+        if perl_item.parse(end).is_ok() {
+            let expected = "expected a character to end the class range".to_owned();
+            return Err(Failure::fatal(end, ErrorKind::Syntax(expected)));
         }
-    } else {
-        Err(())
     }
+    let ((from, to), rest) = pair(left(class_character, match_literal("-")), class_character)
+        .label("a class range")
+        .parse(input)?;
+    if from > to {
+        return Err(Failure::fatal(input, ErrorKind::InvalidRange(from, to)));
+    }
+    Ok((Token::Range(from, to), rest))
 }
 
-fn regular_character(input: &str) -> ParseResult<char> {
+fn regular_character(input: &str) -> ParseResult<'_, char> {
     any_char.pred(|c| !SPECIAL_CHARS.contains(c)).parse(input)
 }
 
-fn not_backslash(input: &str) -> ParseResult<char> {
-    any_char.pred(|&c| c != '\\').parse(input)
+// A character in a class: an escape, or anything but `\` and the `]` that
+// closes the class.
+fn class_character(input: &str) -> ParseResult<'_, char> {
+    control_escape
+        .or(code_point_escape)
+        .or(named_escape)
+        .or(match_literal("\\b").map(|_| '\x08'))
+        .or(right(
+            match_literal("\\"),
+            any_char
+                .pred(|c| SPECIAL_CHARS.contains(c) || *c == '-')
+                .label("an escaped metacharacter"),
+        ))
+        .or(any_char.pred(|&c| c != '\\' && c != ']'))
+        .parse(input)
+}
+
+fn control_escape(input: &str) -> ParseResult<'_, char> {
+    right(
+        match_literal("\\"),
+        any_char
+            .pred(|c| ESCAPES.iter().any(|(name, _)| name == c))
+            .label("an escape sequence"),
+    )
+    .map(|name| {
+        ESCAPES
+            .iter()
+            .find(|(escape, _)| *escape == name)
+            .unwrap()
+            .1
+    })
+    .parse(input)
 }
 
-fn regex_term(input: &str) -> ParseResult<Term> {
-    pair(
-        maybe(match_literal("^")),
-        pair(
-            one_or_more(
-                special_sequence
-                    .or(character_class)
-                    .or(quantified_ordinary_character)
-                    .or(match_group),
-            ),
-            maybe(match_literal("$")),
+// `\xHH`, or `\u{H}` with up to six hex digits.
+fn code_point_escape(input: &str) -> ParseResult<'_, char> {
+    let (value, rest) = right(match_literal("\\x"), hex_digits(2, 2))
+        .or(right(
+            match_literal("\\u{"),
+            left(hex_digits(1, 6), match_literal("}")),
+        ))
+        .parse(input)?;
+    match char::from_u32(value) {
+        Some(c) => Ok((c, rest)),
+        None => Err(Failure::fatal(input, ErrorKind::InvalidCodePoint(value))),
+    }
+}
+
+// `\N{name}`, for the Unicode character with that name.
+fn named_escape(input: &str) -> ParseResult<'_, char> {
+    let (name, rest) = right(
+        match_literal("\\N{"),
+        left(
+            one_or_more(any_char.pred(|&c| c != '}')).label("a character name"),
+            match_literal("}"),
         ),
     )
-    .map(|(start, (elements, end))| Term {
-        left_anchored: start.is_some(),
-        right_anchored: end.is_some(),
-        elements,
+    .parse(input)?;
+    let name: String = name.into_iter().collect();
+    match names::lookup(&name) {
+        Some(c) => Ok((c, rest)),
+        None => Err(Failure::fatal(input, ErrorKind::UnknownCharacterName(name))),
+    }
+}
+
+fn hex_digits<'a>(min: usize, max: usize) -> impl Parser<'a, u32> {
+    move |input: &'a str| {
+        let len = input
+            .chars()
+            .take(max)
+            .take_while(char::is_ascii_hexdigit)
+            .count();
+        if len < min {
+            return Err(Failure::expected(&input[len..], "a hex digit"));
+        }
+        Ok((
+            u32::from_str_radix(&input[..len], 16).unwrap(),
+            &input[len..],
+        ))
+    }
+}
+
+// A term may be empty, like the alternatives in `a|` or `(|b)`, and then
+// matches the empty string.
+fn regex_term(input: &str) -> ParseResult<'_, Term> {
+    pair(
+        pair(zero_or_more(anchor_flags), maybe(match_literal("^"))),
+        zero_or_more(element),
+    )
+    .map(|((mut flags, start), mut elements)| {
+        // A `$` at the end anchors the term instead of being an element.
+        let end = elements.last()
+            == Some(&Element::Sequence(
+                SpecialSequence::LineEnd,
+                Quantifier::Once,
+            ));
+        if end {
+            elements.pop();
+        }
+        flags.extend(elements);
+        Term {
+            left_anchored: start.is_some(),
+            right_anchored: end,
+            elements: flags,
+        }
     })
     .parse(input)
 }
 
-pub(crate) fn parse_regex(input: &str) -> ParseResult<Vec<Term>> {
-    if let Ok((value, rest)) = sep_by(regex_term, "|").parse(input) {
-        if rest == "" {
-            return Ok((value, rest))
-        } else {
-            return Err(())
+fn element(input: &str) -> ParseResult<'_, Element> {
+    special_sequence
+        .or(anchor)
+        .or(property_element)
+        .or(backreference)
+        .or(named_backreference)
+        .or(character_class)
+        .or(quantified_ordinary_character)
+        .or(lookahead)
+        .or(atomic_group)
+        .or(conditional)
+        .or(flag_group)
+        .or(match_group)
+        .parse(input)
+}
+
+// Inline flags in front of a `^`, like in `(?m)^`, so that they can set the
+// mode the anchor is matched in.
+fn anchor_flags(input: &str) -> ParseResult<'_, Element> {
+    match flag_group(input) {
+        Ok((element @ Element::Flags(_), rest)) if rest.starts_with('^') => Ok((element, rest)),
+        _ => Err(Failure::expected(input, "inline flags")),
+    }
+}
+
+pub(crate) fn parse_regex(input: &str) -> Result<Vec<Term>, Error> {
+    let failure = match sep_by(regex_term, "|").parse(input) {
+        Ok((terms, "")) => return resolve_names(input, terms),
+        Ok((_, rest)) => diagnose(rest),
+        Err(failure) if failure.is_fatal() => failure,
+        Err(_) => diagnose(input),
+    };
+    let position = input.len() - failure.rest.len();
+    let kind = match failure.reason {
+        Reason::Fatal(kind) => kind,
+        Reason::Expected(what) if failure.rest.is_empty() => ErrorKind::Syntax(format!(
+            "unexpected end of pattern, expected {}",
+            what.join(" or ")
+        )),
+        Reason::Expected(what) => ErrorKind::Syntax(format!("expected {}", what.join(" or "))),
+    };
+    Err(Error::new(kind, input, position))
+}
+
+// Works out why parsing stopped at the start of `rest`.
+fn diagnose(rest: &str) -> Failure<'_> {
+    match rest.chars().next() {
+        Some(')') => Failure::fatal(rest, ErrorKind::UnbalancedParenthesis),
+        // The alternative after the bar is what failed to parse.
+        Some('|') => diagnose(&rest[1..]),
+        Some('*' | '+' | '?' | '{') => Failure::fatal(rest, ErrorKind::NothingToRepeat),
+        Some('(') if !closes(rest, '(', ')') => {
+            Failure::fatal(rest, ErrorKind::UnbalancedParenthesis)
+        }
+        Some('[') if !rest[1..].contains(']') => {
+            Failure::fatal(rest, ErrorKind::UnclosedCharacterClass)
+        }
+        // Parsing the remainder on its own tells what was expected there.
+        Some(c) => match element(rest) {
+            Ok(_) => Failure::expected(rest, "`|` or the end of the pattern"),
+            Err(failure) if failure.is_fatal() => failure,
+            // No element starts like this.
+            Err(failure) if failure.rest.len() == rest.len() => Failure::fatal(
+                rest,
+                ErrorKind::Syntax(format!("unexpected `{c}`, expected an element")),
+            ),
+            // Nothing that may follow a backslash does.
+            Err(failure) if c == '\\' && failure.rest.len() + 1 == rest.len() => {
+                Failure::expected(failure.rest, "an escape sequence")
+            }
+            Err(failure) => failure,
+        },
+        None => Failure::expected(rest, "an element"),
+    }
+}
+
+fn closes(input: &str, open: char, close: char) -> bool {
+    let mut depth = 0usize;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return true;
+            }
         }
     }
-    Err(())
+    false
 }
 
-fn special_sequence(input: &str) -> ParseResult<Element> {
+fn special_sequence(input: &str) -> ParseResult<'_, Element> {
     pair(
         match_literal(".").map(|_| '.').or(right(
             match_literal("\\"),
-            any_char.pred(|c| SEQ_CHARS.contains(c)),
+            any_char
+                .pred(|c| SEQ_CHARS.contains(c))
+                .label("a special sequence"),
         )),
         maybe(parse_quantifier),
     )
@@ -232,11 +679,15 @@ fn special_sequence(input: &str) -> ParseResult<Element> {
             'B' => SpecialSequence::WithinWord,
             'd' => SpecialSequence::Digit,
             'D' => SpecialSequence::NotDigit,
+            'G' => SpecialSequence::SearchStart,
+            'R' => SpecialSequence::LineBreak,
+            'X' => SpecialSequence::Grapheme,
             's' => SpecialSequence::Whitespace,
             'S' => SpecialSequence::NotWhitespace,
             'w' => SpecialSequence::WordCharacter,
             'W' => SpecialSequence::NotWordCharacter,
             'Z' => SpecialSequence::End,
+            'z' => SpecialSequence::AbsoluteEnd,
             _ => unreachable!(),
         };
         Element::Sequence(seq, q.unwrap_or(Quantifier::Once))
@@ -244,21 +695,525 @@ fn special_sequence(input: &str) -> ParseResult<Element> {
     .parse(input)
 }
 
-fn match_group(input: &str) -> ParseResult<Element> {
-    pair(left(right(match_literal("("), regex_term), match_literal(")")), maybe(parse_quantifier))
-        .map(|(t, q)| Element::CaptureGroup(t, q.unwrap_or(Quantifier::Once)))
+// `^` or `$`, which can't be repeated.
+fn anchor(input: &str) -> ParseResult<'_, Element> {
+    match_literal("^")
+        .map(|_| SpecialSequence::LineStart)
+        .or(match_literal("$").map(|_| SpecialSequence::LineEnd))
+        .map(|seq| Element::Sequence(seq, Quantifier::Once))
+        .parse(input)
+}
+
+fn property_element(input: &str) -> ParseResult<'_, Element> {
+    pair(property_class, maybe(parse_quantifier))
+        .map(|((sign, name), q)| {
+            Element::Class(CharacterClass {
+                sign,
+                items: vec![Token::Named(name)],
+                quantifier: q.unwrap_or(Quantifier::Once),
+            })
+        })
+        .parse(input)
+}
+
+fn match_group(input: &str) -> ParseResult<'_, Element> {
+    pair(
+        left(
+            right(
+                match_literal("("),
+                pair(maybe(group_name), sep_by(regex_term, "|")),
+            ),
+            match_literal(")"),
+        ),
+        maybe(parse_quantifier),
+    )
+    .map(|((name, terms), q)| Element::CaptureGroup(name, terms, q.unwrap_or(Quantifier::Once)))
+    .parse(input)
+}
+
+fn backreference(input: &str) -> ParseResult<'_, Element> {
+    pair(
+        right(
+            match_literal("\\"),
+            any_char
+                .pred(|c| ('1'..='9').contains(c))
+                .label("a group number"),
+        ),
+        maybe(parse_quantifier),
+    )
+    .map(|(digit, q)| {
+        let group = digit.to_digit(10).unwrap() as usize;
+        Element::Backreference(group, q.unwrap_or(Quantifier::Once))
+    })
+    .parse(input)
+}
+
+// Refers to group 0 until `resolve_names` has found the group by its name,
+// which is the only way to get a backreference to it.
+fn named_backreference(input: &str) -> ParseResult<'_, Element> {
+    pair(named_reference, maybe(parse_quantifier))
+        .map(|(_, q)| Element::Backreference(0, q.unwrap_or(Quantifier::Once)))
         .parse(input)
 }
 
-fn escaped_character(input: &str) -> ParseResult<char> {
+fn named_reference(input: &str) -> ParseResult<'_, String> {
+    right(match_literal("(?P="), left(name, match_literal(")")))
+        .or(right(match_literal("\\k<"), left(name, match_literal(">"))))
+        .parse(input)
+}
+
+fn lookahead(input: &str) -> ParseResult<'_, Element> {
+    pair(
+        match_literal("(?=")
+            .map(|_| Sign::Inclusive)
+            .or(match_literal("(?!").map(|_| Sign::Exclusive)),
+        left(sep_by(regex_term, "|"), match_literal(")")),
+    )
+    .map(|(sign, terms)| Element::Lookahead(sign, terms))
+    .parse(input)
+}
+
+fn atomic_group(input: &str) -> ParseResult<'_, Element> {
+    pair(
+        right(
+            match_literal("(?>"),
+            left(sep_by(regex_term, "|"), match_literal(")")),
+        ),
+        maybe(parse_quantifier),
+    )
+    .map(|(terms, q)| Element::Atomic(terms, q.unwrap_or(Quantifier::Once)))
+    .parse(input)
+}
+
+fn conditional(input: &str) -> ParseResult<'_, Element> {
+    let condition = left(
+        parse_int.pred(|&group| group > 0).label("a group number"),
+        match_literal(")"),
+    );
+    let ((group, branches), rest) = right(
+        match_literal("(?("),
+        pair(condition, left(sep_by(regex_term, "|"), match_literal(")"))),
+    )
+    .parse(input)?;
+    if branches.len() > 2 {
+        let message = "a conditional has at most two branches".to_owned();
+        return Err(Failure::fatal(input, ErrorKind::Syntax(message)));
+    }
+    let (q, rest) = maybe(parse_quantifier).parse(rest)?;
+    Ok((
+        Element::Conditional(group, branches, q.unwrap_or(Quantifier::Once)),
+        rest,
+    ))
+}
+
+fn flag_group(input: &str) -> ParseResult<'_, Element> {
+    let (changes, rest) = right(match_literal("(?"), flag_changes).parse(input)?;
+    if let Ok((_, rest)) = match_literal(")").parse(rest) {
+        if changes.is_empty() {
+            return Err(Failure::expected(&input[2..], "a flag"));
+        }
+        return Ok((Element::Flags(changes), rest));
+    }
+    let ((terms, q), rest) = pair(
+        left(
+            right(match_literal(":"), sep_by(regex_term, "|")),
+            match_literal(")"),
+        ),
+        maybe(parse_quantifier),
+    )
+    .parse(rest)?;
+    Ok((
+        Element::Group(changes, terms, q.unwrap_or(Quantifier::Once)),
+        rest,
+    ))
+}
+
+// Flags to enable, optionally followed by `-` and flags to disable.
+fn flag_changes(input: &str) -> ParseResult<'_, Vec<(Flag, bool)>> {
+    pair(
+        zero_or_more(flag),
+        maybe(right(match_literal("-"), one_or_more(flag))),
+    )
+    .map(|(enabled, disabled)| {
+        let enabled = enabled.into_iter().map(|f| (f, true));
+        let disabled = disabled.into_iter().flatten().map(|f| (f, false));
+        enabled.chain(disabled).collect()
+    })
+    .parse(input)
+}
+
+fn flag(input: &str) -> ParseResult<'_, Flag> {
+    any_char
+        .pred(|c| "imsxuR".contains(*c))
+        .label("a flag")
+        .map(|c| match c {
+            'i' => Flag::CaseInsensitive,
+            'm' => Flag::MultiLine,
+            's' => Flag::DotAll,
+            'x' => Flag::Verbose,
+            'u' => Flag::Unicode,
+            'R' => Flag::Crlf,
+            _ => unreachable!(),
+        })
+        .parse(input)
+}
+
+// The `?P<name>` or `?<name>` that starts a named group.
+fn group_name(input: &str) -> ParseResult<'_, String> {
+    let (_, name_start) = match_literal("?P<").or(match_literal("?<")).parse(input)?;
+    let (name, rest) = left(name, match_literal(">")).parse(name_start)?;
+    if name.starts_with(|c: char| c.is_numeric()) {
+        return Err(Failure::fatal(
+            name_start,
+            ErrorKind::InvalidGroupName(name),
+        ));
+    }
+    Ok((name, rest))
+}
+
+// Puts a backslash in front of every metacharacter in `text`, and of `#`
+// and whitespace, which verbose mode would strip.
+pub(crate) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if SPECIAL_CHARS.contains(&c) || c == '#' || c.is_whitespace() {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn name(input: &str) -> ParseResult<'_, String> {
+    one_or_more(any_char.pred(|&c| c.is_alphanumeric() || c == '_'))
+        .map(|name| name.into_iter().collect())
+        .label("a group name")
+        .parse(input)
+}
+
+// Lists the names of all groups in the order they are numbered, starting
+// with the unnamed group 0 for the whole match, after checking that names
+// are unique and backreferences point to existing groups.
+pub(crate) fn group_names(pattern: &str, terms: &[Term]) -> Result<Vec<Option<String>>, Error> {
+    let names = capture_names(terms);
+    check_backreferences(pattern, terms, names.len())?;
+    for (idx, name) in names.iter().enumerate() {
+        let Some(name) = name else { continue };
+        if names[..idx].contains(&Some(name.clone())) {
+            // Point at the opening parenthesis of the second definition.
+            let mut openings: Vec<usize> = [format!("?P<{name}>"), format!("?<{name}>")]
+                .iter()
+                .flat_map(|needle| pattern.match_indices(needle.as_str()))
+                .filter_map(|(at, _)| at.checked_sub(1))
+                .collect();
+            openings.sort_unstable();
+            let position = openings.get(1).copied().unwrap_or(0);
+            return Err(Error::new(
+                ErrorKind::DuplicateGroupName(name.clone()),
+                pattern,
+                position,
+            ));
+        }
+    }
+    Ok(names)
+}
+
+// The names of all groups in the order they are numbered, with `None` for
+// group 0 and the groups without one.
+fn capture_names(terms: &[Term]) -> Vec<Option<String>> {
+    fn collect(term: &Term, names: &mut Vec<Option<String>>) {
+        for element in term.elements.iter() {
+            match element {
+                Element::CaptureGroup(name, terms, _) => {
+                    names.push(name.clone());
+                    terms.iter().for_each(|term| collect(term, names));
+                }
+                Element::Group(_, terms, _)
+                | Element::Lookahead(_, terms)
+                | Element::Atomic(terms, _)
+                | Element::Conditional(_, terms, _) => {
+                    terms.iter().for_each(|term| collect(term, names))
+                }
+                _ => {}
+            }
+        }
+    }
+    let mut names = vec![None];
+    terms.iter().for_each(|term| collect(term, &mut names));
+    names
+}
+
+// Numbers the named backreferences in `terms`, parsed from `pattern`, by the
+// groups they refer to. They are found again where the elements start.
+fn resolve_names(pattern: &str, terms: Vec<Term>) -> Result<Vec<Term>, Error> {
+    struct References(Vec<bool>);
+    impl Visitor for References {
+        fn enter_element(&mut self, element: &Element) {
+            self.0.push(matches!(element, Element::Backreference(0, _)));
+        }
+    }
+    let mut references = References(Vec::new());
+    walk(&terms, &mut references);
+    if !references.0.contains(&true) {
+        return Ok(terms);
+    }
+    let names = capture_names(&terms);
+    let offsets = element_offsets(pattern, &terms)
+        .into_iter()
+        .zip(references.0);
+    let mut groups = Vec::new();
+    for (offset, _) in offsets.filter(|&(_, named)| named) {
+        let (name, _) =
+            named_reference(&pattern[offset..]).expect("parsed as a named backreference");
+        match names
+            .iter()
+            .position(|group| group.as_deref() == Some(name.as_str()))
+        {
+            Some(group) => groups.push(group),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::UnknownGroupName(name),
+                    pattern,
+                    offset,
+                ))
+            }
+        }
+    }
+    // Backreferences are never nested, so they are folded in the order
+    // they were walked in.
+    let mut groups = groups.into_iter();
+    Ok(fold(terms, |element| match element {
+        Element::Backreference(0, quantifier) => {
+            Element::Backreference(groups.next().unwrap(), quantifier)
+        }
+        element => element,
+    }))
+}
+
+// Replaces the named classes in `terms`, parsed from `pattern`, by the items
+// `classes` defines them with. A name that isn't defined, or that is used in
+// the definition of another one, is reported at the element it appears in.
+pub(crate) fn resolve_classes(
+    pattern: &str,
+    terms: Vec<Term>,
+    classes: &BTreeMap<String, Vec<Token>>,
+) -> Result<Vec<Term>, Error> {
+    fn unknown<'a>(
+        items: &'a [Token],
+        classes: &'a BTreeMap<String, Vec<Token>>,
+        defining: bool,
+    ) -> Option<&'a str> {
+        items.iter().find_map(|item| match item {
+            Token::Named(name) => match classes.get(name).filter(|_| !defining) {
+                Some(items) => unknown(items, classes, true),
+                None => Some(name.as_str()),
+            },
+            Token::Class(_, items) => unknown(items, classes, defining),
+            _ => None,
+        })
+    }
+    fn resolve(items: Vec<Token>, classes: &BTreeMap<String, Vec<Token>>) -> Vec<Token> {
+        items
+            .into_iter()
+            .map(|item| match item {
+                Token::Named(name) => Token::Class(Sign::Inclusive, classes[&name].clone()),
+                Token::Class(sign, items) => Token::Class(sign, resolve(items, classes)),
+                item => item,
+            })
+            .collect()
+    }
+    struct Unknown<'a>(&'a BTreeMap<String, Vec<Token>>, Vec<Option<String>>);
+    impl Visitor for Unknown<'_> {
+        fn enter_element(&mut self, element: &Element) {
+            let name = match element {
+                Element::Class(class) => unknown(&class.items, self.0, false),
+                _ => None,
+            };
+            self.1.push(name.map(ToOwned::to_owned));
+        }
+    }
+    let mut names = Unknown(classes, Vec::new());
+    walk(&terms, &mut names);
+    if let Some((element, name)) = names
+        .1
+        .into_iter()
+        .enumerate()
+        .find_map(|(idx, name)| Some((idx, name?)))
+    {
+        let position = element_offsets(pattern, &terms)
+            .get(element)
+            .copied()
+            .unwrap_or(0);
+        return Err(Error::new(
+            ErrorKind::UnknownClassName(name),
+            pattern,
+            position,
+        ));
+    }
+    Ok(fold(terms, |element| match element {
+        Element::Class(class) => Element::Class(CharacterClass {
+            items: resolve(class.items, classes),
+            ..class
+        }),
+        element => element,
+    }))
+}
+
+// Makes sure that every backreference and conditional refers to an existing
+// group.
+fn check_backreferences(pattern: &str, terms: &[Term], groups: usize) -> Result<(), Error> {
+    // The invalid group, and how it is referred to.
+    fn find_invalid(terms: &[Term], groups: usize) -> Option<(usize, String)> {
+        terms
+            .iter()
+            .flat_map(|term| term.elements.iter())
+            .find_map(|element| match element {
+                Element::Backreference(group, _) if *group >= groups => {
+                    Some((*group, format!("\\{group}")))
+                }
+                Element::Conditional(group, _, _) if *group >= groups => {
+                    Some((*group, format!("(?({group})")))
+                }
+                Element::CaptureGroup(_, terms, _)
+                | Element::Group(_, terms, _)
+                | Element::Lookahead(_, terms)
+                | Element::Atomic(terms, _)
+                | Element::Conditional(_, terms, _) => find_invalid(terms, groups),
+                _ => None,
+            })
+    }
+    match find_invalid(terms, groups) {
+        Some((group, reference)) => {
+            let position = pattern
+                .match_indices(&reference)
+                .map(|(at, _)| at)
+                .find(|&at| !pattern[..at].ends_with('\\'))
+                .unwrap_or(0);
+            Err(Error::new(
+                ErrorKind::InvalidBackreference(group),
+                pattern,
+                position,
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+// Makes sure that groups aren't nested more than `limit` levels deep in
+// `terms`, parsed from `pattern`, pointing at the first group that is.
+pub(crate) fn check_nesting(pattern: &str, terms: &[Term], limit: usize) -> Result<(), Error> {
+    struct Depth {
+        limit: usize,
+        current: usize,
+        entered: usize,
+        exceeded: Option<usize>,
+    }
+    impl Visitor for Depth {
+        fn enter_element(&mut self, element: &Element) {
+            if element.terms().is_some() {
+                self.current += 1;
+                if self.current > self.limit && self.exceeded.is_none() {
+                    self.exceeded = Some(self.entered);
+                }
+            }
+            self.entered += 1;
+        }
+        fn exit_element(&mut self, element: &Element) {
+            if element.terms().is_some() {
+                self.current -= 1;
+            }
+        }
+    }
+    let mut depth = Depth {
+        limit,
+        current: 0,
+        entered: 0,
+        exceeded: None,
+    };
+    walk(terms, &mut depth);
+    match depth.exceeded {
+        Some(element) => {
+            let position = element_offsets(pattern, terms)
+                .get(element)
+                .copied()
+                .unwrap_or(0);
+            Err(Error::new(
+                ErrorKind::NestLimitExceeded(limit),
+                pattern,
+                position,
+            ))
+        }
+        None => Ok(()),
+    }
+}
+
+// The byte offsets at which the elements of `terms` start in `pattern`,
+// which they were parsed from, in the order `ast::walk` enters them. The
+// pattern is known to be valid here, so the elements are parsed again one
+// after the other, and groups entered after their opening.
+pub(crate) fn element_offsets(pattern: &str, terms: &[Term]) -> Vec<usize> {
+    fn offsets(pattern: &str, mut input: &str, terms: &[Term], found: &mut Vec<usize>) {
+        for (idx, term) in terms.iter().enumerate() {
+            if idx > 0 {
+                input = input.strip_prefix('$').unwrap_or(input);
+                input = whitespace_surrounded_sep("|")
+                    .parse(input)
+                    .map_or(input, |(_, rest)| rest);
+            }
+            let mut anchor = term.left_anchored;
+            for element in &term.elements {
+                if anchor && input.starts_with('^') {
+                    input = &input[1..];
+                    anchor = false;
+                }
+                found.push(pattern.len() - input.len());
+                let Ok((_, rest)) = self::element(input) else {
+                    return;
+                };
+                if let Some(terms) = element.terms() {
+                    let opening = match element {
+                        Element::CaptureGroup(..) => right(match_literal("("), maybe(group_name))
+                            .map(|_| ())
+                            .parse(input),
+                        Element::Group(..) => {
+                            right(match_literal("(?"), left(flag_changes, match_literal(":")))
+                                .map(|_| ())
+                                .parse(input)
+                        }
+                        Element::Conditional(..) => {
+                            right(match_literal("(?("), left(parse_int, match_literal(")")))
+                                .map(|_| ())
+                                .parse(input)
+                        }
+                        _ => Ok(((), &input[3..])),
+                    };
+                    if let Ok((_, inside)) = opening {
+                        offsets(pattern, inside, terms, found);
+                    }
+                }
+                input = rest;
+            }
+        }
+    }
+    let mut found = Vec::new();
+    offsets(pattern, pattern, terms, &mut found);
+    found
+}
+
+fn escaped_character(input: &str) -> ParseResult<'_, char> {
     right(
         match_literal("\\"),
-        any_char.pred(|c| SPECIAL_CHARS.contains(c)),
+        any_char
+            .pred(|c| SPECIAL_CHARS.contains(c))
+            .label("an escaped metacharacter"),
     )
+    .or(control_escape)
+    .or(code_point_escape)
+    .or(named_escape)
     .parse(input)
 }
 
-fn quantified_ordinary_character(input: &str) -> ParseResult<Element> {
+fn quantified_ordinary_character(input: &str) -> ParseResult<'_, Element> {
     pair(
         regular_character.or(escaped_character),
         maybe(parse_quantifier),
@@ -294,29 +1249,526 @@ mod tests {
         );
     }
 
+    #[test]
+    fn parses_counted_repetition() {
+        assert_eq!(parse_quantifier("{3}x"), Ok((Quantifier::Exactly(3), "x")));
+        assert_eq!(parse_quantifier("{3,}"), Ok((Quantifier::AtLeast(3), "")));
+        assert_eq!(
+            parse_quantifier("{,5}"),
+            Ok((Quantifier::Between(0, 5), ""))
+        );
+        assert_eq!(
+            parse_quantifier("{3, 5}"),
+            Ok((Quantifier::Between(3, 5), ""))
+        );
+        assert_eq!(
+            parse_quantifier("{3}?x"),
+            Ok((Quantifier::LazyExactly(3), "x"))
+        );
+        assert_eq!(
+            parse_quantifier("{3,}?"),
+            Ok((Quantifier::LazyAtLeast(3), ""))
+        );
+        assert_eq!(
+            parse_quantifier("{,5}+"),
+            Ok((Quantifier::PossessiveBetween(0, 5), ""))
+        );
+        for pattern in ["a{2}?", "a{2,}+", "a{2,3}?b{4}+"] {
+            assert_eq!(
+                crate::ast::to_pattern(&parse_regex(pattern).unwrap()),
+                pattern
+            );
+        }
+        assert!(parse_quantifier("{}").is_err());
+        assert!(parse_quantifier("{,}").is_err());
+        assert_eq!(
+            parse_regex("a{,1}b{5,3}").unwrap_err().kind(),
+            &ErrorKind::InvalidRepetition(5, 3)
+        );
+    }
+
+    #[test]
+    fn parses_possessive_and_atomic() {
+        assert_eq!(
+            parse_quantifier("++"),
+            Ok((Quantifier::PossessiveOneOrMore, ""))
+        );
+        assert_eq!(
+            parse_quantifier("*+a"),
+            Ok((Quantifier::PossessiveZeroOrMore, "a"))
+        );
+        assert_eq!(
+            parse_quantifier("?+"),
+            Ok((Quantifier::PossessiveMaybe, ""))
+        );
+        let terms = parse_regex("(?>a|(b))*c").unwrap();
+        let Element::Atomic(alternatives, Quantifier::ZeroOrMore) = &terms[0].elements[0] else {
+            panic!("{terms:?}");
+        };
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!(
+            group_names("(?>(?<x>a))", &parse_regex("(?>(?<x>a))").unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn parses_conditionals() {
+        let terms = parse_regex("(a)?(?(1)b|c)+").unwrap();
+        let Element::Conditional(1, branches, Quantifier::OneOrMore) = &terms[0].elements[1] else {
+            panic!("{terms:?}");
+        };
+        assert_eq!(branches.len(), 2);
+        assert!(parse_regex("(a)(?(1)b)").is_ok());
+        assert_eq!(
+            parse_regex("(a)(?(1)b|c|d)").unwrap_err().kind(),
+            &ErrorKind::Syntax("a conditional has at most two branches".to_owned())
+        );
+        let error = group_names("(a)(?(2)b)", &parse_regex("(a)(?(2)b)").unwrap()).unwrap_err();
+        assert_eq!(
+            (error.kind(), error.position()),
+            (&ErrorKind::InvalidBackreference(2), 3)
+        );
+    }
+
+    #[test]
+    fn parses_empty_terms() {
+        assert_eq!(parse_regex("").unwrap()[0].elements, []);
+        assert_eq!(parse_regex("a|").unwrap().len(), 2);
+        let terms = parse_regex("(|b)").unwrap();
+        let Element::CaptureGroup(None, branches, Quantifier::Once) = &terms[0].elements[0] else {
+            panic!("{terms:?}");
+        };
+        assert!(branches[0].elements.is_empty());
+        let terms = parse_regex("^$").unwrap();
+        assert!(terms[0].left_anchored && terms[0].right_anchored);
+        assert!(parse_regex("(?:)(?=)(?>)").is_ok());
+        assert_eq!(
+            parse_regex("a|*").unwrap_err().kind(),
+            &ErrorKind::NothingToRepeat
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-names")]
+    fn parses_named_escapes() {
+        assert_eq!(
+            escaped_character("\\N{LATIN SMALL LETTER A}b"),
+            Ok(('a', "b"))
+        );
+        assert_eq!(
+            character_range("\\N{DIGIT ZERO}-\\N{DIGIT NINE}"),
+            Ok((Token::Range('0', '9'), ""))
+        );
+    }
+
+    #[test]
+    fn parses_escapes() {
+        assert_eq!(escaped_character("\\tx"), Ok(('\t', "x")));
+        assert_eq!(escaped_character("\\0"), Ok(('\0', "")));
+        assert_eq!(single_item("\\b"), Ok((Token::Literal('\x08'), "")));
+        assert_eq!(
+            character_range("\\0-\\n"),
+            Ok((Token::Range('\0', '\n'), ""))
+        );
+        assert!(escaped_character("\\q").is_err());
+        assert_eq!(escaped_character("\\x41B"), Ok(('A', "B")));
+        assert_eq!(escaped_character("\\u{1F600}"), Ok(('😀', "")));
+        assert_eq!(
+            escaped_character("\\N{CJK UNIFIED IDEOGRAPH-4E00}b"),
+            Ok(('一', "b"))
+        );
+        assert_eq!(
+            parse_regex("a\\N{NO SUCH CHARACTER}").unwrap_err().kind(),
+            &ErrorKind::UnknownCharacterName("NO SUCH CHARACTER".to_owned())
+        );
+        assert_eq!(
+            character_range("\\x00-\\u{10FFFF}"),
+            Ok((Token::Range('\0', char::MAX), ""))
+        );
+        assert!(escaped_character("\\x4").is_err());
+        assert!(escaped_character("\\u{1234567}").is_err());
+        let err = parse_regex("ab\\u{D800}").unwrap_err();
+        assert_eq!(
+            (err.kind(), err.position()),
+            (&ErrorKind::InvalidCodePoint(0xD800), 2)
+        );
+        let err = parse_regex("[\\u{110000}]").unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidCodePoint(0x110000));
+    }
+
+    #[test]
+    fn parses_perl_classes_in_classes() {
+        assert_eq!(
+            character_class("[\\d\\S,]"),
+            Ok((
+                Element::Class(CharacterClass {
+                    sign: Sign::Inclusive,
+                    items: vec![
+                        Token::Perl(SpecialSequence::Digit),
+                        Token::Perl(SpecialSequence::NotWhitespace),
+                        Token::Literal(','),
+                    ],
+                    quantifier: Quantifier::Once,
+                }),
+                ""
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_class_edge_cases() {
+        let items = |class: &str| match character_class(class) {
+            Ok((Element::Class(class), "")) => class.items,
+            other => panic!("{class}: {other:?}"),
+        };
+        assert_eq!(
+            items("[]a-]"),
+            [
+                Token::Literal(']'),
+                Token::Literal('a'),
+                Token::Literal('-')
+            ]
+        );
+        assert_eq!(items("[^]]"), [Token::Literal(']')]);
+        assert_eq!(
+            items("[a\\-z]"),
+            [
+                Token::Literal('a'),
+                Token::Literal('-'),
+                Token::Literal('z')
+            ]
+        );
+        assert_eq!(
+            items("[\\]\\\\\\[]"),
+            [
+                Token::Literal(']'),
+                Token::Literal('\\'),
+                Token::Literal('[')
+            ]
+        );
+        assert_eq!(items("[-a]"), [Token::Literal('-'), Token::Literal('a')]);
+        assert_eq!(items("[\\[-\\]]"), [Token::Range('[', ']')]);
+        assert_eq!(items("[ - ]"), [Token::Range(' ', ' ')]);
+        assert!(character_class("[]").is_err());
+    }
+
+    #[test]
+    fn parses_nested_classes() {
+        let items = |class: &str| match character_class(class) {
+            Ok((Element::Class(class), "")) => class.items,
+            other => panic!("{class}: {other:?}"),
+        };
+        assert_eq!(
+            items("[[a-z][0-9]]"),
+            [
+                Token::Class(Sign::Inclusive, vec![Token::Range('a', 'z')]),
+                Token::Class(Sign::Inclusive, vec![Token::Range('0', '9')]),
+            ]
+        );
+        assert_eq!(
+            items("[_[^\\d]]"),
+            [
+                Token::Literal('_'),
+                Token::Class(Sign::Exclusive, vec![Token::Perl(SpecialSequence::Digit)]),
+            ]
+        );
+        assert_eq!(items("[[]"), [Token::Literal('[')]);
+        assert_eq!(items("[a[]"), [Token::Literal('a'), Token::Literal('[')]);
+    }
+
+    #[test]
+    fn parses_named_classes() {
+        let named = |name: &str| Token::Named(name.to_owned());
+        let not_named = |name| Token::Class(Sign::Exclusive, vec![named(name)]);
+        let Ok((Element::Class(class), "")) = character_class("[[:hex:]\\P{id}[:^x_1:]]") else {
+            panic!();
+        };
+        assert_eq!(
+            class.items,
+            [named("hex"), not_named("id"), not_named("x_1")]
+        );
+        let terms = parse_regex("\\p{id}+\\P{id}").unwrap();
+        let class = |sign, quantifier| {
+            Element::Class(CharacterClass {
+                sign,
+                items: vec![named("id")],
+                quantifier,
+            })
+        };
+        assert_eq!(
+            terms[0].elements,
+            [
+                class(Sign::Inclusive, Quantifier::OneOrMore),
+                class(Sign::Exclusive, Quantifier::Once)
+            ]
+        );
+        assert_eq!(Written(&terms).to_string(), "\\p{id}+\\P{id}");
+        // Without the colons, it's a nested class.
+        let nested = Token::Class(
+            Sign::Inclusive,
+            vec![Token::Literal(':'), Token::Literal('a')],
+        );
+        assert!(
+            matches!(character_class("[[:a]]"), Ok((Element::Class(class), "")) if class.items == [nested])
+        );
+        assert!(parse_regex("\\p{}").is_err());
+
+        let mut classes = BTreeMap::new();
+        classes.insert(
+            "id".to_owned(),
+            vec![Token::Range('a', 'z'), Token::Literal('_')],
+        );
+        classes.insert("nested".to_owned(), vec![named("id")]);
+        let resolve = |pattern| resolve_classes(pattern, parse_regex(pattern).unwrap(), &classes);
+        let defined = Token::Class(
+            Sign::Inclusive,
+            vec![Token::Range('a', 'z'), Token::Literal('_')],
+        );
+        let Element::Class(class) = &resolve("[0[:^id:]]").unwrap()[0].elements[0] else {
+            panic!();
+        };
+        assert_eq!(
+            class.items,
+            [
+                Token::Literal('0'),
+                Token::Class(Sign::Exclusive, vec![defined])
+            ]
+        );
+        let error = |pattern| {
+            resolve(pattern)
+                .map(|_| ())
+                .map_err(|e| (e.kind().clone(), e.position()))
+        };
+        assert_eq!(
+            error("a(b\\p{no})"),
+            Err((ErrorKind::UnknownClassName("no".to_owned()), 3))
+        );
+        assert_eq!(
+            error("a[[:nested:]]"),
+            Err((ErrorKind::UnknownClassName("id".to_owned()), 1))
+        );
+    }
+
     #[test]
     fn sep_by_works() {
         assert_eq!(
             parse_regex("(ab)+c"),
-            Ok((vec![
-                Term { 
-                    left_anchored: false, 
-                    right_anchored: false, 
-                    elements: vec![
-                        Element::CaptureGroup(
-                            Term { 
-                                left_anchored: false, 
-                                right_anchored: false, 
-                                elements: vec![
-                                    Element::Class(CharacterClass { sign: Sign::Inclusive, items: vec![Token::Literal('a')], quantifier: Quantifier::Once }),
-                                    Element::Class(CharacterClass { sign: Sign::Inclusive, items: vec![Token::Literal('b')], quantifier: Quantifier::Once })
-                                ]
-                            }, Quantifier::OneOrMore
-                        ),
-                        Element::Class(CharacterClass { sign: Sign::Inclusive, items: vec![Token::Literal('c')], quantifier: Quantifier::Once })
-                    ] 
-                }
-            ],""))
+            Ok(vec![Term {
+                left_anchored: false,
+                right_anchored: false,
+                elements: vec![
+                    Element::CaptureGroup(
+                        None,
+                        vec![Term {
+                            left_anchored: false,
+                            right_anchored: false,
+                            elements: vec![
+                                Element::Class(CharacterClass {
+                                    sign: Sign::Inclusive,
+                                    items: vec![Token::Literal('a')],
+                                    quantifier: Quantifier::Once
+                                }),
+                                Element::Class(CharacterClass {
+                                    sign: Sign::Inclusive,
+                                    items: vec![Token::Literal('b')],
+                                    quantifier: Quantifier::Once
+                                })
+                            ]
+                        }],
+                        Quantifier::OneOrMore
+                    ),
+                    Element::Class(CharacterClass {
+                        sign: Sign::Inclusive,
+                        items: vec![Token::Literal('c')],
+                        quantifier: Quantifier::Once
+                    })
+                ]
+            }])
+        );
+    }
+
+    fn error(pattern: &str) -> (ErrorKind, usize) {
+        let err = parse_regex(pattern).unwrap_err();
+        (err.kind().clone(), err.position())
+    }
+
+    #[test]
+    fn reports_errors() {
+        assert_eq!(error("(ab"), (ErrorKind::UnbalancedParenthesis, 0));
+        assert_eq!(error("ab)"), (ErrorKind::UnbalancedParenthesis, 2));
+        assert_eq!(error("x[ab"), (ErrorKind::UnclosedCharacterClass, 1));
+        assert_eq!(error("[az-a]"), (ErrorKind::InvalidRange('z', 'a'), 2));
+        let expected = ErrorKind::Syntax("expected a character to end the class range".to_owned());
+        assert_eq!(error(r"x[a-\d]"), (expected, 4));
+        assert_eq!(error("a{5,3}"), (ErrorKind::InvalidRepetition(5, 3), 1));
+        assert_eq!(error("*a"), (ErrorKind::NothingToRepeat, 0));
+        assert_eq!(error("a|+"), (ErrorKind::NothingToRepeat, 2));
+        let syntax = |message: &str| ErrorKind::Syntax(message.to_owned());
+        assert_eq!(error(r"a\q"), (syntax("expected an escape sequence"), 2));
+        assert_eq!(
+            error("a\\"),
+            (
+                syntax("unexpected end of pattern, expected an escape sequence"),
+                2
+            )
+        );
+        assert_eq!(
+            error("ab]"),
+            (syntax("unexpected `]`, expected an element"), 2)
+        );
+        let either = match_literal("x")
+            .or(match_literal("y"))
+            .or(match_literal("x"));
+        assert_eq!(
+            either.parse("z").unwrap_err().reason,
+            Reason::Expected(vec!["`x`".to_owned(), "`y`".to_owned()])
+        );
+    }
+
+    #[test]
+    fn parses_anchors_anywhere() {
+        let anchor = |seq| Element::Sequence(seq, Quantifier::Once);
+        let terms = parse_regex("^a$b$").unwrap();
+        assert!(terms[0].left_anchored && terms[0].right_anchored);
+        assert_eq!(terms[0].elements[1], anchor(SpecialSequence::LineEnd));
+        let terms = parse_regex("x(?m)^y|$^").unwrap();
+        assert_eq!(terms[0].elements[2], anchor(SpecialSequence::LineStart));
+        assert!(!terms[1].left_anchored && !terms[1].right_anchored);
+        assert_eq!(
+            terms[1].elements,
+            [
+                anchor(SpecialSequence::LineEnd),
+                anchor(SpecialSequence::LineStart)
+            ]
+        );
+        assert_eq!(error("a^*"), (ErrorKind::NothingToRepeat, 2));
+    }
+
+    #[test]
+    fn parses_named_groups() {
+        let terms = parse_regex("(?P<year>\\d+)-(?<month>\\d+)(x)").unwrap();
+        assert_eq!(
+            group_names("", &terms),
+            Ok(vec![
+                None,
+                Some("year".to_owned()),
+                Some("month".to_owned()),
+                None
+            ])
+        );
+        assert_eq!(
+            error("(?P<1st>a)"),
+            (ErrorKind::InvalidGroupName("1st".to_owned()), 4)
+        );
+        let pattern = "(?P<a>x)(?<a>y)";
+        let err = group_names(pattern, &parse_regex(pattern).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::DuplicateGroupName("a".to_owned()));
+        assert_eq!(err.position(), 8);
+    }
+
+    #[test]
+    fn parses_nested_alternation() {
+        let terms = parse_regex("a(b|c(d|e))f").unwrap();
+        assert_eq!(terms.len(), 1);
+        let Element::CaptureGroup(None, outer, Quantifier::Once) = &terms[0].elements[1] else {
+            panic!("expected a group, got {:?}", terms[0].elements[1]);
+        };
+        assert_eq!(outer.len(), 2);
+        assert!(
+            matches!(&outer[1].elements[1], Element::CaptureGroup(None, inner, _) if inner.len() == 2)
+        );
+        assert_eq!(error("(a|b"), (ErrorKind::UnbalancedParenthesis, 0));
+    }
+
+    #[test]
+    fn parses_backreferences() {
+        let terms = parse_regex(r"(a)\1+").unwrap();
+        assert_eq!(
+            terms[0].elements[1],
+            Element::Backreference(1, Quantifier::OneOrMore)
+        );
+        let pattern = r"(a)\\2\2";
+        let err = group_names(pattern, &parse_regex(pattern).unwrap()).unwrap_err();
+        assert_eq!(err.kind(), &ErrorKind::InvalidBackreference(2));
+        assert_eq!(err.position(), 6);
+    }
+
+    #[test]
+    fn resolves_named_backreferences() {
+        let terms = parse_regex(r"(?P<word>a)(b)(?P=word)\k<word>+").unwrap();
+        assert_eq!(
+            terms[0].elements[2],
+            Element::Backreference(1, Quantifier::Once)
+        );
+        assert_eq!(
+            terms[0].elements[3],
+            Element::Backreference(1, Quantifier::OneOrMore)
+        );
+        let terms = parse_regex(r"(a)|(?:(?<x>b)|c(?P=x))").unwrap();
+        assert_eq!(terms, parse_regex(r"(a)|(?:(?<x>b)|c\2)").unwrap());
+        assert_eq!(
+            error("(?P<x>a)b(?P=y)"),
+            (ErrorKind::UnknownGroupName("y".to_owned()), 9)
+        );
+        assert_eq!(
+            error(r"(?P<x>a)\k<x"),
+            (
+                ErrorKind::Syntax("unexpected end of pattern, expected `>`".to_owned()),
+                12
+            )
+        );
+    }
+
+    #[test]
+    fn parses_flags() {
+        let terms = parse_regex("a(?i)b(?s-mx:c)").unwrap();
+        assert_eq!(
+            terms[0].elements[1],
+            Element::Flags(vec![(Flag::CaseInsensitive, true)])
+        );
+        let Element::Group(changes, _, Quantifier::Once) = &terms[0].elements[3] else {
+            panic!("expected a group, got {:?}", terms[0].elements[3]);
+        };
+        assert_eq!(
+            changes,
+            &[
+                (Flag::DotAll, true),
+                (Flag::MultiLine, false),
+                (Flag::Verbose, false)
+            ]
+        );
+        assert!(matches!(
+            parse_regex("(?:a|b)+").unwrap()[0].elements[0],
+            Element::Group(_, _, Quantifier::OneOrMore)
+        ));
+        assert_eq!(
+            error("(?)"),
+            (ErrorKind::Syntax("expected a flag".to_owned()), 2)
+        );
+        let terms = parse_regex("(?m)^a").unwrap();
+        assert!(terms[0].left_anchored);
+        assert_eq!(
+            terms[0].elements[0],
+            Element::Flags(vec![(Flag::MultiLine, true)])
+        );
+    }
+
+    #[test]
+    fn locates_elements() {
+        let pattern = "^a(b|(?P<x>c)d)$|(?m)^e(?(1)f)";
+        let offsets = element_offsets(pattern, &parse_regex(pattern).unwrap());
+        assert_eq!(offsets, [1, 2, 3, 5, 11, 13, 17, 22, 23, 28]);
+    }
+
+    #[test]
+    fn displays_caret_under_error() {
+        let err = parse_regex("ab{3,1}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "regex parse error:\n    ab{3,1}\n      ^\nerror: invalid repetition {3,1}: minimum exceeds maximum"
         );
     }
 }
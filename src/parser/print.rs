@@ -0,0 +1,195 @@
+use core::fmt::{Display, Formatter, Result};
+
+use super::{
+    CharacterClass, Element, Flag, Quantifier, Sign, SpecialSequence, Term, Token, ESCAPES,
+    SPECIAL_CHARS,
+};
+
+// Alternatives written back as a pattern, which parses to the same terms.
+// Characters are escaped where they would be special, and whitespace and `#`
+// outside classes as well, as verbose mode may be enabled inline.
+pub(crate) struct Written<'a>(pub(crate) &'a [Term]);
+
+impl Display for Written<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for (i, term) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "|")?;
+            }
+            let mut elements = term.elements.as_slice();
+            if term.left_anchored {
+                // Inline flags are only parsed in front of a `^`, not after it.
+                if let [flags @ Element::Flags(_), rest @ ..] = elements {
+                    element(f, flags)?;
+                    elements = rest;
+                }
+                write!(f, "^")?;
+            }
+            for e in elements {
+                element(f, e)?;
+            }
+            if term.right_anchored {
+                write!(f, "$")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn element(f: &mut Formatter<'_>, element: &Element) -> Result {
+    let quantifier = match element {
+        Element::Class(CharacterClass {
+            sign,
+            items,
+            quantifier,
+        }) => {
+            match (sign, items.as_slice()) {
+                (Sign::Inclusive, [Token::Literal(c)]) => character(f, *c, false)?,
+                (Sign::Inclusive, [Token::Named(name)]) => write!(f, "\\p{{{name}}}")?,
+                (Sign::Exclusive, [Token::Named(name)]) => write!(f, "\\P{{{name}}}")?,
+                _ => class(f, *sign, items)?,
+            }
+            quantifier
+        }
+        Element::Sequence(sequence, quantifier) => {
+            write!(f, "{}", special_sequence(*sequence))?;
+            quantifier
+        }
+        Element::CaptureGroup(name, terms, quantifier) => {
+            match name {
+                Some(name) => write!(f, "(?P<{name}>{})", Written(terms))?,
+                None => write!(f, "({})", Written(terms))?,
+            }
+            quantifier
+        }
+        Element::Group(changes, terms, quantifier) => {
+            write!(f, "(?")?;
+            flags(f, changes)?;
+            write!(f, ":{})", Written(terms))?;
+            quantifier
+        }
+        Element::Flags(changes) => {
+            write!(f, "(?")?;
+            flags(f, changes)?;
+            return write!(f, ")");
+        }
+        Element::Lookahead(Sign::Inclusive, terms) => return write!(f, "(?={})", Written(terms)),
+        Element::Lookahead(Sign::Exclusive, terms) => return write!(f, "(?!{})", Written(terms)),
+        Element::Backreference(group, quantifier) => {
+            write!(f, "\\{group}")?;
+            quantifier
+        }
+        Element::Atomic(terms, quantifier) => {
+            write!(f, "(?>{})", Written(terms))?;
+            quantifier
+        }
+        Element::Conditional(group, terms, quantifier) => {
+            write!(f, "(?({group}){})", Written(terms))?;
+            quantifier
+        }
+    };
+    let suffix = match quantifier {
+        Quantifier::ZeroOrMore => "*",
+        Quantifier::OneOrMore => "+",
+        Quantifier::Maybe => "?",
+        Quantifier::LazyZeroOrMore => "*?",
+        Quantifier::LazyOneOrMore => "+?",
+        Quantifier::LazyMaybe => "??",
+        Quantifier::PossessiveZeroOrMore => "*+",
+        Quantifier::PossessiveOneOrMore => "++",
+        Quantifier::PossessiveMaybe => "?+",
+        Quantifier::Once => "",
+        Quantifier::Exactly(n) => return write!(f, "{{{n}}}"),
+        Quantifier::AtLeast(n) => return write!(f, "{{{n},}}"),
+        Quantifier::Between(min, max) => return write!(f, "{{{min},{max}}}"),
+        Quantifier::LazyExactly(n) => return write!(f, "{{{n}}}?"),
+        Quantifier::LazyAtLeast(n) => return write!(f, "{{{n},}}?"),
+        Quantifier::LazyBetween(min, max) => return write!(f, "{{{min},{max}}}?"),
+        Quantifier::PossessiveExactly(n) => return write!(f, "{{{n}}}+"),
+        Quantifier::PossessiveAtLeast(n) => return write!(f, "{{{n},}}+"),
+        Quantifier::PossessiveBetween(min, max) => return write!(f, "{{{min},{max}}}+"),
+    };
+    write!(f, "{suffix}")
+}
+
+fn class(f: &mut Formatter<'_>, sign: Sign, items: &[Token]) -> Result {
+    write!(f, "[")?;
+    if sign == Sign::Exclusive {
+        write!(f, "^")?;
+    }
+    for item in items {
+        match item {
+            Token::Range(from, to) => {
+                character(f, *from, true)?;
+                write!(f, "-")?;
+                character(f, *to, true)?;
+            }
+            Token::Literal(c) => character(f, *c, true)?,
+            Token::Perl(sequence) => write!(f, "{}", special_sequence(*sequence))?,
+            Token::Class(sign, items) => class(f, *sign, items)?,
+            Token::Named(name) => write!(f, "[:{name}:]")?,
+        }
+    }
+    write!(f, "]")
+}
+
+fn character(f: &mut Formatter<'_>, c: char, in_class: bool) -> Result {
+    if let Some((name, _)) = ESCAPES.iter().find(|(_, escaped)| *escaped == c) {
+        return write!(f, "\\{name}");
+    }
+    let special = if in_class {
+        c == '-'
+    } else {
+        c == '#' || c.is_whitespace()
+    };
+    if SPECIAL_CHARS.contains(&c) || special {
+        write!(f, "\\{c}")
+    } else if c.is_control() {
+        write!(f, "\\u{{{:x}}}", c as u32)
+    } else {
+        write!(f, "{c}")
+    }
+}
+
+fn flags(f: &mut Formatter<'_>, changes: &[(Flag, bool)]) -> Result {
+    let name = |flag| match flag {
+        Flag::CaseInsensitive => 'i',
+        Flag::MultiLine => 'm',
+        Flag::DotAll => 's',
+        Flag::Verbose => 'x',
+        Flag::Unicode => 'u',
+        Flag::Crlf => 'R',
+    };
+    for (flag, _) in changes.iter().filter(|(_, enabled)| *enabled) {
+        write!(f, "{}", name(*flag))?;
+    }
+    if changes.iter().any(|(_, enabled)| !enabled) {
+        write!(f, "-")?;
+    }
+    for (flag, _) in changes.iter().filter(|(_, enabled)| !enabled) {
+        write!(f, "{}", name(*flag))?;
+    }
+    Ok(())
+}
+
+fn special_sequence(sequence: SpecialSequence) -> &'static str {
+    match sequence {
+        SpecialSequence::AnyCharacter => ".",
+        SpecialSequence::Start => "\\A",
+        SpecialSequence::WordBoundary => "\\b",
+        SpecialSequence::WithinWord => "\\B",
+        SpecialSequence::Digit => "\\d",
+        SpecialSequence::NotDigit => "\\D",
+        SpecialSequence::Whitespace => "\\s",
+        SpecialSequence::NotWhitespace => "\\S",
+        SpecialSequence::WordCharacter => "\\w",
+        SpecialSequence::NotWordCharacter => "\\W",
+        SpecialSequence::End => "\\Z",
+        SpecialSequence::AbsoluteEnd => "\\z",
+        SpecialSequence::SearchStart => "\\G",
+        SpecialSequence::LineBreak => "\\R",
+        SpecialSequence::Grapheme => "\\X",
+        SpecialSequence::LineStart => "^",
+        SpecialSequence::LineEnd => "$",
+    }
+}
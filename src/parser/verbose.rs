@@ -0,0 +1,136 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::combinators::Parser;
+use super::{flag_changes, Flag};
+use crate::Error;
+
+// A pattern with the whitespace and `#` comments removed from the parts in
+// which verbose mode is enabled, ready for the parser. Escaped whitespace
+// and `\#` are replaced by the bare characters, which then stand for
+// themselves. Classes are copied as they are.
+pub(crate) struct Stripped {
+    pub(crate) text: String,
+    // The offset in the pattern of every byte of `text`, and of its end.
+    offsets: Vec<usize>,
+}
+
+impl Stripped {
+    // Strips `pattern`, starting out in verbose mode if `verbose` is set.
+    // Inline flags like `(?x)` switch it on or off up to the end of the
+    // enclosing group.
+    pub(crate) fn new(pattern: &str, mut verbose: bool) -> Self {
+        let mut stripped = Self {
+            text: String::with_capacity(pattern.len()),
+            offsets: Vec::with_capacity(pattern.len() + 1),
+        };
+        // Whether verbose mode was enabled outside each open group.
+        let mut scopes = Vec::new();
+        let mut class = 0usize;
+        let mut chars = pattern.char_indices().peekable();
+        while let Some((at, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((at, next)) if next == '#' || next.is_whitespace() => {
+                        stripped.push(at, next)
+                    }
+                    Some((next_at, next)) => {
+                        stripped.push(at, c);
+                        stripped.push(next_at, next);
+                    }
+                    None => stripped.push(at, c),
+                },
+                '[' => {
+                    stripped.push(at, c);
+                    class += 1;
+                    // A leading `]` is a literal rather than the end.
+                    for opening in ['^', ']'] {
+                        if let Some(&(at, c)) = chars.peek().filter(|&&(_, c)| c == opening) {
+                            stripped.push(at, c);
+                            chars.next();
+                        }
+                    }
+                }
+                ']' if class > 0 => {
+                    stripped.push(at, c);
+                    class -= 1;
+                }
+                _ if class > 0 => stripped.push(at, c),
+                '#' if verbose => {
+                    while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+                    chars.next();
+                }
+                _ if verbose && c.is_whitespace() => {}
+                '(' => {
+                    stripped.push(at, c);
+                    let inline = pattern[at + 1..]
+                        .strip_prefix('?')
+                        .and_then(|flags| flag_changes.parse(flags).ok())
+                        .filter(|(_, rest)| rest.starts_with([')', ':']));
+                    let Some((changes, rest)) = inline else {
+                        scopes.push(verbose);
+                        continue;
+                    };
+                    // Copy the flags, up to the `)` or `:` after them.
+                    let end = pattern.len() - rest.len();
+                    while let Some((at, c)) = chars.next_if(|&(at, _)| at <= end) {
+                        stripped.push(at, c);
+                    }
+                    if rest.starts_with(':') {
+                        scopes.push(verbose);
+                    }
+                    for (flag, enabled) in changes {
+                        if flag == Flag::Verbose {
+                            verbose = enabled;
+                        }
+                    }
+                }
+                ')' => {
+                    stripped.push(at, c);
+                    if let Some(outer) = scopes.pop() {
+                        verbose = outer;
+                    }
+                }
+                _ => stripped.push(at, c),
+            }
+        }
+        stripped.offsets.push(pattern.len());
+        stripped
+    }
+    fn push(&mut self, at: usize, c: char) {
+        self.text.push(c);
+        self.offsets.extend((0..c.len_utf8()).map(|idx| at + idx));
+    }
+    // Points an error found in the stripped text at the original `pattern`.
+    pub(crate) fn locate(&self, pattern: &str, error: Error) -> Error {
+        let position = self.offsets[error.position()];
+        Error::new(error.kind().clone(), pattern, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorKind;
+
+    #[test]
+    fn strips_whitespace_and_comments() {
+        let strip = |pattern: &str, verbose: bool| Stripped::new(pattern, verbose).text;
+        assert_eq!(strip("a b # comment\n c", true), "abc");
+        assert_eq!(strip("a b", false), "a b");
+        assert_eq!(strip("a (?x) b ( c ) d", false), "a (?x)b(c)d");
+        assert_eq!(strip("(?x: a ) b", false), "(?x:a) b");
+        assert_eq!(strip("a (?-x) b", true), "a(?-x) b");
+        assert_eq!(strip("[ #] \\  \\#", true), "[ #] #");
+        assert_eq!(strip("[] ] \\[ ]", true), "[] ]\\[]");
+    }
+
+    #[test]
+    fn locates_errors_in_the_pattern() {
+        let pattern = "a # (\n )";
+        let stripped = Stripped::new(pattern, true);
+        assert_eq!(stripped.text, "a)");
+        let error = Error::new(ErrorKind::UnbalancedParenthesis, &stripped.text, 1);
+        assert_eq!(stripped.locate(pattern, error).position(), 7);
+    }
+}
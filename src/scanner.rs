@@ -0,0 +1,149 @@
+use crate::{Match, RegexSet, Tokens};
+
+/// A position in a haystack as a line and a column, both starting at 1.
+///
+/// Lines are separated by `\n`, and columns count characters rather than
+/// bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Location {
+    /// The line, starting at 1.
+    pub line: usize,
+    /// The column within the line, starting at 1.
+    pub column: usize,
+}
+
+impl Location {
+    fn advance(&mut self, text: &str) {
+        for c in text.chars() {
+            if c == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+    }
+}
+
+impl Default for Location {
+    fn default() -> Self {
+        Self { line: 1, column: 1 }
+    }
+}
+
+/// A token found by a [`Scanner`], along with where it starts and ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lexeme<'h> {
+    pattern: usize,
+    span: Match<'h>,
+    start: Location,
+    end: Location,
+}
+
+impl<'h> Lexeme<'h> {
+    /// The index of the pattern that matched the token.
+    pub fn pattern(&self) -> usize {
+        self.pattern
+    }
+    /// The token as a match in the haystack.
+    pub fn span(&self) -> Match<'h> {
+        self.span
+    }
+    /// The text of the token.
+    pub fn as_str(&self) -> &'h str {
+        self.span.as_str()
+    }
+    /// The location of the first character of the token.
+    pub fn start(&self) -> Location {
+        self.start
+    }
+    /// The location right after the last character of the token.
+    pub fn end(&self) -> Location {
+        self.end
+    }
+}
+
+/// Splits a haystack into tokens like [`RegexSet::tokens`], keeping track
+/// of the line and column at which each of them starts and ends, as needed
+/// for diagnostics.
+///
+/// ```
+/// use regexrs::{Location, RegexSet, Scanner};
+///
+/// let set = RegexSet::new([r"\s+", "[a-z]+"]).unwrap();
+/// let mut scanner = Scanner::new(&set, "one\ntwo 3");
+/// let words: Vec<_> = scanner.by_ref().filter(|token| token.pattern() == 1).collect();
+/// assert_eq!(words[1].as_str(), "two");
+/// assert_eq!(words[1].start(), Location { line: 2, column: 1 });
+/// assert_eq!(scanner.location(), Location { line: 2, column: 5 });
+/// ```
+#[derive(Clone)]
+pub struct Scanner<'s, 'h> {
+    tokens: Tokens<'s, 'h>,
+    location: Location,
+}
+
+impl<'s, 'h> Scanner<'s, 'h> {
+    /// Scans `haystack` for the patterns in `set`.
+    pub fn new(set: &'s RegexSet, haystack: &'h str) -> Self {
+        Self {
+            tokens: set.tokens(haystack),
+            location: Location::default(),
+        }
+    }
+    /// The byte offset at which the next token would start, see
+    /// [`Tokens::position`].
+    pub fn position(&self) -> usize {
+        self.tokens.position()
+    }
+    /// The location at which the next token would start. Once the scanner
+    /// is exhausted before the end of the haystack, this is where no pattern
+    /// matched.
+    pub fn location(&self) -> Location {
+        self.location
+    }
+}
+
+impl<'s, 'h> Iterator for Scanner<'s, 'h> {
+    type Item = Lexeme<'h>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (pattern, span) = self.tokens.next()?;
+        let start = self.location;
+        self.location.advance(span.as_str());
+        Some(Lexeme {
+            pattern,
+            span,
+            start,
+            end: self.location,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_lines_and_columns() {
+        let set = RegexSet::new([r"\s+", r"\w+", "=", "\"[^\"]*\""]).unwrap();
+        let mut scanner = Scanner::new(&set, "key = \"ä\nb\"\n\nnext ?");
+        let located: Vec<_> = scanner
+            .by_ref()
+            .filter(|token| token.pattern() != 0)
+            .map(|token| (token.as_str(), token.start(), token.end()))
+            .collect();
+        let at = |line, column| Location { line, column };
+        assert_eq!(
+            located,
+            [
+                ("key", at(1, 1), at(1, 4)),
+                ("=", at(1, 5), at(1, 6)),
+                ("\"ä\nb\"", at(1, 7), at(2, 3)),
+                ("next", at(4, 1), at(4, 5)),
+            ]
+        );
+        assert_eq!(scanner.location(), at(4, 6));
+        assert_eq!(scanner.position(), 19);
+    }
+}
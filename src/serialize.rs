@@ -0,0 +1,41 @@
+use alloc::string::String;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::Regex;
+
+/// Serializes the pattern the regex was compiled from. Options set with a
+/// [`RegexBuilder`](crate::RegexBuilder) are left out, unless the pattern
+/// sets them with inline flags.
+impl Serialize for Regex {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// Deserializes a pattern and compiles it with [`Regex::new`], so that an
+/// invalid pattern fails deserialization with the compiler's error.
+impl<'de> Deserialize<'de> for Regex {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(pattern).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::value::{Error, StrDeserializer};
+    use serde::de::IntoDeserializer;
+
+    use super::*;
+
+    #[test]
+    fn compiles_patterns_when_deserialized() {
+        let deserializer: StrDeserializer<Error> = r"(?i)\d+px".into_deserializer();
+        let regex = Regex::deserialize(deserializer).unwrap();
+        assert!(regex.is_match("12PX"));
+        let deserializer: StrDeserializer<Error> = "(a".into_deserializer();
+        let error = Regex::deserialize(deserializer).unwrap_err();
+        assert!(error.to_string().contains("(a"), "{error}");
+    }
+}
@@ -0,0 +1,444 @@
+use alloc::borrow::ToOwned;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::cache::Pool;
+use crate::fsm::{Budget, Node, NFA};
+use crate::parser::Sign;
+use crate::{Error, Match, RegexBuilder};
+
+/// Several patterns, matched against a haystack all at once.
+///
+/// The patterns are compiled into a single automaton, so that finding out
+/// which of them match takes one pass over the haystack, instead of one
+/// per pattern.
+///
+/// ```
+/// use regexrs::RegexSet;
+///
+/// let set = RegexSet::new([r"\d+", "error", "warn(ing)?"]).unwrap();
+/// let matches = set.matches("error 42");
+/// assert_eq!(matches.iter().collect::<Vec<_>>(), [0, 1]);
+/// ```
+///
+/// A set can also split a haystack into tokens, as a lexer does:
+///
+/// ```
+/// use regexrs::RegexSet;
+///
+/// let set = RegexSet::new([r"\s+", "let", "[a-z]+", "=", r"\d+"]).unwrap();
+/// let tokens: Vec<(usize, &str)> = set
+///     .tokens("let lettuce = 5")
+///     .map(|(pattern, token)| (pattern, token.as_str()))
+///     .collect();
+/// assert_eq!(tokens[..3], [(1, "let"), (0, " "), (2, "lettuce")]);
+/// ```
+///
+/// Each pattern that needs backtracking gives up after the default number
+/// of steps of [`RegexBuilder::backtrack_limit`], and then doesn't match.
+pub struct RegexSet {
+    nfa: NFA,
+    // The pattern each node of `nfa` ends, for its match nodes.
+    ends: Vec<Option<usize>>,
+    // Patterns that need backtracking can't be simulated along with the
+    // others, and are searched for one by one.
+    backtracking: Vec<(usize, NFA)>,
+    patterns: Vec<String>,
+    pool: Pool<Scratch>,
+}
+
+// What the simulation of the combined automaton works with, kept between
+// searches so that they don't allocate it anew.
+struct Scratch {
+    // When each node was last added, as the stamp of the position.
+    seen: Vec<usize>,
+    // Tells the positions of the searches apart.
+    stamp: usize,
+    // The nodes consuming the next character, and those consuming the one
+    // after it.
+    states: Vec<usize>,
+    next: Vec<usize>,
+    // The nodes `add` has yet to follow.
+    stack: Vec<usize>,
+    // The patterns that match at the current position.
+    matched: Vec<bool>,
+}
+
+impl Scratch {
+    fn new(nodes: usize, patterns: usize) -> Self {
+        Self {
+            seen: vec![0; nodes],
+            stamp: 0,
+            states: Vec::new(),
+            next: Vec::new(),
+            stack: Vec::new(),
+            matched: vec![false; patterns],
+        }
+    }
+    // Gets ready for a search, before the nodes at its start are added.
+    fn start(&mut self) {
+        self.states.clear();
+        self.next.clear();
+        self.stamp += 1;
+    }
+    // Moves on to the next position, once the nodes reached there are
+    // added.
+    fn advance(&mut self) {
+        core::mem::swap(&mut self.states, &mut self.next);
+        self.next.clear();
+        self.stamp += 1;
+    }
+}
+
+impl RegexSet {
+    /// Compiles all of `patterns`, failing on the first one that isn't
+    /// valid.
+    ///
+    /// Use a [`RegexBuilder`] to compile a single pattern with flags or
+    /// limits.
+    pub fn new<I, S>(patterns: I) -> Result<Self, Error>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let patterns: Vec<String> = patterns
+            .into_iter()
+            .map(|p| p.as_ref().to_owned())
+            .collect();
+        let mut simulated = Vec::new();
+        let mut owners = Vec::new();
+        let mut backtracking = Vec::new();
+        for (idx, pattern) in patterns.iter().enumerate() {
+            let mut nfa = RegexBuilder::new(pattern).compile()?.nfa;
+            if nfa.backtracks() {
+                // Tokens are as long as possible.
+                nfa.set_longest(true);
+                backtracking.push((idx, nfa));
+            } else {
+                simulated.push(nfa);
+                owners.push(idx);
+            }
+        }
+        let (nfa, match_nodes) = NFA::union(simulated);
+        let mut ends = vec![None; nfa.graph().len()];
+        for (node, owner) in match_nodes.into_iter().zip(owners) {
+            ends[node] = Some(owner);
+        }
+        Ok(Self {
+            nfa,
+            ends,
+            backtracking,
+            patterns,
+            pool: Pool::new(),
+        })
+    }
+    /// Returns true if any of the patterns matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.matches(haystack).matched_any()
+    }
+    /// Finds out which of the patterns match somewhere in `haystack`.
+    pub fn matches(&self, haystack: &str) -> SetMatches {
+        let mut matched = vec![false; self.patterns.len()];
+        self.with_scratch(|scratch| self.simulate(haystack, &mut matched, scratch));
+        for (idx, nfa) in &self.backtracking {
+            let budget = Budget::new(Budget::DEFAULT_STEPS);
+            matched[*idx] = nfa.captures_at(haystack, 0, 2, false, &budget).is_some();
+        }
+        SetMatches(matched)
+    }
+    /// Returns an iterator that splits `haystack` into tokens, each of them
+    /// the longest match of any pattern right where the previous token
+    /// ended, along with the index of the pattern. If several patterns
+    /// match the longest token, the one that comes first in the set wins.
+    ///
+    /// The iterator stops at the end of the haystack, or where no pattern
+    /// matches a non-empty token; [`Tokens::position`] tells which.
+    pub fn tokens<'s, 'h>(&'s self, haystack: &'h str) -> Tokens<'s, 'h> {
+        Tokens {
+            set: self,
+            haystack,
+            position: 0,
+        }
+    }
+    /// The number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.patterns.len()
+    }
+    /// Returns true if the set has no patterns, so that it never matches.
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+    /// The patterns, in the order they were given.
+    pub fn patterns(&self) -> &[String] {
+        &self.patterns
+    }
+    // Runs `search` with scratch space from the pool.
+    fn with_scratch<R, F: FnOnce(&mut Scratch) -> R>(&self, search: F) -> R {
+        let create = || Scratch::new(self.nfa.graph().len(), self.patterns.len());
+        self.pool.with(create, search)
+    }
+    // Runs the combined automaton over the whole haystack, like the
+    // state-set simulation of a single pattern. Reaching the end of a
+    // pattern doesn't stop the search, which only ends early once no
+    // thread is left.
+    fn simulate(&self, haystack: &str, matched: &mut [bool], scratch: &mut Scratch) {
+        let (start, restart) = self.nfa.entries();
+        let budget = Budget::new(Budget::DEFAULT_STEPS);
+        scratch.start();
+        self.add(start, haystack, 0, scratch, matched, &budget);
+        scratch.advance();
+        for (idx, c) in haystack.char_indices() {
+            if scratch.states.is_empty() && restart.is_none() {
+                return;
+            }
+            let position = idx + c.len_utf8();
+            for state in 0..scratch.states.len() {
+                if let Node::Consume(set, target) = &self.nfa.graph()[scratch.states[state]] {
+                    if set.contains(c) {
+                        self.add(*target, haystack, position, scratch, matched, &budget);
+                    }
+                }
+            }
+            if let Some(restart) = restart {
+                self.add(restart, haystack, position, scratch, matched, &budget);
+            }
+            scratch.advance();
+        }
+    }
+    // The longest match of any pattern starting right at byte offset `from`,
+    // as the pattern and the end of the match, preferring earlier patterns
+    // for matches of the same length.
+    fn longest_at(&self, haystack: &str, from: usize) -> Option<(usize, usize)> {
+        let mut longest = self.with_scratch(|scratch| self.simulate_at(haystack, from, scratch));
+        for (pattern, nfa) in &self.backtracking {
+            let budget = Budget::new(Budget::DEFAULT_STEPS);
+            let found = nfa.captures_at(haystack, from, 2, true, &budget);
+            let Some(end) = found.and_then(|slots| slots[1]) else {
+                continue;
+            };
+            match longest {
+                Some((other, longest_end))
+                    if longest_end > end || longest_end == end && other < *pattern => {}
+                _ => longest = Some((*pattern, end)),
+            }
+        }
+        longest
+    }
+    // Like `longest_at`, but only for the patterns in the combined
+    // automaton.
+    fn simulate_at(
+        &self,
+        haystack: &str,
+        from: usize,
+        scratch: &mut Scratch,
+    ) -> Option<(usize, usize)> {
+        let (start, _) = self.nfa.entries();
+        let budget = Budget::new(Budget::DEFAULT_STEPS);
+        let mut matched = core::mem::take(&mut scratch.matched);
+        matched.fill(false);
+        scratch.start();
+        self.add(start, haystack, from, scratch, &mut matched, &budget);
+        scratch.advance();
+        let mut longest = matched
+            .iter()
+            .position(|&m| m)
+            .map(|pattern| (pattern, from));
+        for (idx, c) in haystack[from..].char_indices() {
+            if scratch.states.is_empty() {
+                break;
+            }
+            let position = from + idx + c.len_utf8();
+            matched.fill(false);
+            for state in 0..scratch.states.len() {
+                if let Node::Consume(set, target) = &self.nfa.graph()[scratch.states[state]] {
+                    if set.contains(c) {
+                        self.add(*target, haystack, position, scratch, &mut matched, &budget);
+                    }
+                }
+            }
+            if let Some(pattern) = matched.iter().position(|&m| m) {
+                longest = Some((pattern, position));
+            }
+            scratch.advance();
+        }
+        scratch.matched = matched;
+        longest
+    }
+    // Follows the epsilon moves from `idx`, adding the nodes that consume a
+    // character to the next states and noting the patterns that match.
+    fn add(
+        &self,
+        idx: usize,
+        haystack: &str,
+        position: usize,
+        scratch: &mut Scratch,
+        matched: &mut [bool],
+        budget: &Budget,
+    ) {
+        let Scratch {
+            seen,
+            stamp,
+            next,
+            stack,
+            ..
+        } = scratch;
+        stack.push(idx);
+        while let Some(idx) = stack.pop() {
+            if seen[idx] == *stamp {
+                continue;
+            }
+            seen[idx] = *stamp;
+            match &self.nfa.graph()[idx] {
+                Node::Consume(..) => next.push(idx),
+                Node::Epsilon(targets) => stack.extend(targets.iter().rev()),
+                Node::Save(_, next) => stack.push(*next),
+                Node::Assert(look, next) => {
+                    if look.holds(haystack.as_bytes(), 0, position) {
+                        stack.push(*next);
+                    }
+                }
+                Node::Lookahead(sign, sub, next) => {
+                    let found = sub
+                        .captures_at(haystack, position, 0, true, budget)
+                        .is_some();
+                    if found == (*sign == Sign::Inclusive) {
+                        stack.push(*next);
+                    }
+                }
+                Node::Match => {
+                    if let Some(pattern) = self.ends[idx] {
+                        matched[pattern] = true;
+                    }
+                }
+                // Patterns with these are kept out of the combined automaton.
+                Node::Backref(..) | Node::Atomic(..) | Node::Conditional(..) => {}
+            }
+        }
+    }
+}
+
+/// An iterator over the tokens of a haystack, along with the index of the
+/// pattern that matched each of them.
+///
+/// This struct is created by [`RegexSet::tokens`].
+#[derive(Clone)]
+pub struct Tokens<'s, 'h> {
+    set: &'s RegexSet,
+    haystack: &'h str,
+    position: usize,
+}
+
+impl<'s, 'h> Tokens<'s, 'h> {
+    /// The byte offset at which the next token would start. Once the
+    /// iterator is exhausted, this is where no pattern matched, unless it
+    /// is the end of the haystack.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<'s, 'h> Iterator for Tokens<'s, 'h> {
+    type Item = (usize, Match<'h>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.position == self.haystack.len() {
+            return None;
+        }
+        let (pattern, end) = self.set.longest_at(self.haystack, self.position)?;
+        if end == self.position {
+            return None;
+        }
+        let token = Match {
+            haystack: self.haystack,
+            start: self.position,
+            end,
+        };
+        self.position = end;
+        Some((pattern, token))
+    }
+}
+
+/// Which patterns of a [`RegexSet`] matched a haystack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetMatches(Vec<bool>);
+
+impl SetMatches {
+    /// Returns true if pattern `idx` matched.
+    pub fn matched(&self, idx: usize) -> bool {
+        self.0.get(idx).copied().unwrap_or(false)
+    }
+    /// Returns true if any pattern matched.
+    pub fn matched_any(&self) -> bool {
+        self.0.contains(&true)
+    }
+    /// Returns an iterator over the indices of the patterns that matched,
+    /// in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0
+            .iter()
+            .enumerate()
+            .filter(|(_, &matched)| matched)
+            .map(|(idx, _)| idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_all_matching_patterns() {
+        let set = RegexSet::new(["^foo", "bar$", r"(\w)\1", "(?=z)", "x"]).unwrap();
+        assert_eq!(set.len(), 5);
+        let matched = |text: &str| set.matches(text).iter().collect::<Vec<_>>();
+        assert_eq!(matched("foobar"), [0, 1, 2]);
+        assert_eq!(matched("a fob bar!"), Vec::<usize>::new());
+        assert_eq!(matched("xyz"), [3, 4]);
+        assert!(!set.is_match("abc"));
+        let empty = RegexSet::new(Vec::<&str>::new()).unwrap();
+        assert!(empty.is_empty());
+        assert!(!empty.is_match("anything"));
+        assert!(RegexSet::new(["a", "("]).is_err());
+    }
+
+    #[test]
+    fn splits_into_longest_tokens() {
+        let set =
+            RegexSet::new([r"\s+", "if", r"[a-z]+", r"\d+", "<=?", r#"(['"]).*?\1"#]).unwrap();
+        let mut tokens = set.tokens("if iffy <= 10 'a\"b' <x");
+        let lexed: Vec<(usize, &str)> = tokens.by_ref().map(|(p, m)| (p, m.as_str())).collect();
+        assert_eq!(
+            lexed,
+            [
+                (1, "if"),
+                (0, " "),
+                (2, "iffy"),
+                (0, " "),
+                (4, "<="),
+                (0, " "),
+                (3, "10"),
+                (0, " "),
+                (5, "'a\"b'"),
+                (0, " "),
+                (4, "<"),
+                (2, "x"),
+            ]
+        );
+        assert_eq!(tokens.position(), 22);
+        let mut tokens = set.tokens("ab ?c");
+        assert_eq!(tokens.by_ref().count(), 2);
+        assert_eq!(tokens.position(), 3);
+    }
+
+    #[test]
+    fn gives_every_backtracking_pattern_its_own_budget() {
+        let set = RegexSet::new([r"(a*)*\1x|y", r"(b)\1", "b+"]).unwrap();
+        let haystack = format!("{} bb", "a".repeat(100));
+        assert_eq!(set.matches(&haystack).iter().collect::<Vec<_>>(), [1, 2]);
+        let tokens: Vec<usize> = set.tokens("bbb").map(|(pattern, _)| pattern).collect();
+        assert_eq!(tokens, [2]);
+        // Searches reuse the scratch space of earlier ones.
+        assert_eq!(set.matches("cbc").iter().collect::<Vec<_>>(), [2]);
+    }
+}
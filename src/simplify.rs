@@ -0,0 +1,360 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::ast::{fold, quantifier_mut, Element, Quantifier, SpecialSequence, Term};
+
+// How often the passes are run at most, should they keep finding something
+// to simplify.
+const ROUNDS: usize = 8;
+
+/// Simplifies a syntax tree without changing what it matches, or what its
+/// groups capture, for patterns generated by code rather than written by
+/// hand. Groups keep their numbers.
+///
+/// The passes, run until none of them changes anything:
+///
+/// - groups that don't capture or change flags are spliced into the term
+///   around them, like `(?:ab)c` into `abc`;
+/// - nested quantifiers are merged, like `(?:a*)+` into `a*`;
+/// - repetitions of the same character next to each other are merged, like
+///   `aa*` into `a+`;
+/// - alternatives that appear a second time are removed, like the second
+///   `a` in `a|b|a`;
+/// - alternatives next to each other that start the same way share that
+///   prefix, like `abc|abd` turning into `ab(?:c|d)`.
+///
+/// Alternatives are only removed or shared if they don't contain capture
+/// groups, and not at all in groups where inline flags carry over from one
+/// alternative to the next.
+///
+/// ```
+/// use regexrs::ast;
+///
+/// let terms = ast::simplify(ast::parse("(?:x*)*y|foo|foobar|foo").unwrap());
+/// assert_eq!(ast::to_pattern(&terms), "x*y|foo(?:|bar)");
+/// ```
+pub fn simplify(terms: Vec<Term>) -> Vec<Term> {
+    let mut terms = terms;
+    for _ in 0..ROUNDS {
+        let simplified = alternatives(fold(terms.clone(), element));
+        if simplified == terms {
+            break;
+        }
+        terms = simplified;
+    }
+    terms
+}
+
+// Simplifies the terms inside `element`, which are already simplified
+// themselves, and then the element.
+fn element(mut element: Element) -> Element {
+    match &mut element {
+        Element::Conditional(_, branches, _) => {
+            for branch in branches.iter_mut() {
+                branch.elements = merge_repetitions(splice_groups(mem::take(&mut branch.elements)));
+            }
+        }
+        element => {
+            if let Some(terms) = element.terms_mut() {
+                *terms = alternatives(mem::take(terms));
+            }
+        }
+    }
+    unnest(element)
+}
+
+fn alternatives(terms: Vec<Term>) -> Vec<Term> {
+    let terms: Vec<Term> = terms
+        .into_iter()
+        .map(|term| Term {
+            elements: merge_repetitions(splice_groups(term.elements)),
+            ..term
+        })
+        .collect();
+    // `(?i)` in an alternative applies to the ones after it as well.
+    if terms
+        .iter()
+        .any(|term| term.elements.iter().any(|e| matches!(e, Element::Flags(_))))
+    {
+        return terms;
+    }
+    share_prefixes(deduplicate(terms))
+}
+
+// Replaces groups that only hold a single term by its elements.
+fn splice_groups(elements: Vec<Element>) -> Vec<Element> {
+    let mut spliced = Vec::with_capacity(elements.len());
+    for element in elements {
+        match element {
+            Element::Group(changes, mut terms, Quantifier::Once)
+                if changes.is_empty() && terms.len() == 1 && is_plain(&terms[0]) =>
+            {
+                spliced.extend(terms.pop().unwrap().elements)
+            }
+            element => spliced.push(element),
+        }
+    }
+    spliced
+}
+
+// Removes a group around a single element, merging their quantifiers.
+fn unnest(element: Element) -> Element {
+    let Element::Group(changes, mut terms, outer) = element else {
+        return element;
+    };
+    let single = changes.is_empty()
+        && terms.len() == 1
+        && is_plain(&terms[0])
+        && terms[0].elements.len() == 1;
+    if !single {
+        return Element::Group(changes, terms, outer);
+    }
+    let mut inner = terms.pop().unwrap().elements.pop().unwrap();
+    let captures = captures(&inner);
+    let merged = match quantifier_mut(&mut inner).copied() {
+        Some(Quantifier::Once) => Some(outer),
+        _ if outer == Quantifier::Once => return inner,
+        Some(quantifier) if !captures => nested(outer, quantifier),
+        _ => None,
+    };
+    match merged {
+        Some(quantifier) => {
+            *quantifier_mut(&mut inner).unwrap() = quantifier;
+            inner
+        }
+        None => {
+            let term = Term {
+                left_anchored: false,
+                right_anchored: false,
+                elements: vec![inner],
+            };
+            Element::Group(changes, vec![term], outer)
+        }
+    }
+}
+
+// The quantifier that repeating a repetition amounts to, if there is one.
+fn nested(outer: Quantifier, inner: Quantifier) -> Option<Quantifier> {
+    use Quantifier::{Maybe, OneOrMore, ZeroOrMore};
+    match (outer, inner) {
+        (OneOrMore, OneOrMore) => Some(OneOrMore),
+        (Maybe, Maybe) => Some(Maybe),
+        (Maybe | ZeroOrMore | OneOrMore, Maybe | ZeroOrMore | OneOrMore) => Some(ZeroOrMore),
+        _ => None,
+    }
+}
+
+// Merges greedy repetitions of the same single character class next to each
+// other. Literals that aren't repeated are left alone.
+fn merge_repetitions(elements: Vec<Element>) -> Vec<Element> {
+    let mut merged: Vec<Element> = Vec::with_capacity(elements.len());
+    for element in elements {
+        if let Some(last) = merged.last_mut() {
+            if let Some(quantifier) = merge(last, &element) {
+                *quantifier_mut(last).unwrap() = quantifier;
+                continue;
+            }
+        }
+        merged.push(element);
+    }
+    merged
+}
+
+fn merge(first: &Element, second: &Element) -> Option<Quantifier> {
+    let (first_atom, first) = single_character(first)?;
+    let (second_atom, second) = single_character(second)?;
+    if first_atom != second_atom || first == Quantifier::Once && second == Quantifier::Once {
+        return None;
+    }
+    let (min, max) = bounds(first)?;
+    let (more, most) = bounds(second)?;
+    let max = max.zip(most).map(|(max, most)| max + most);
+    Some(match (min + more, max) {
+        (0, Some(1)) => Quantifier::Maybe,
+        (0, None) => Quantifier::ZeroOrMore,
+        (1, None) => Quantifier::OneOrMore,
+        (min, None) => Quantifier::AtLeast(min),
+        (min, Some(max)) if min == max => Quantifier::Exactly(min),
+        (min, Some(max)) => Quantifier::Between(min, max),
+    })
+}
+
+// An element that consumes a single character, without its quantifier.
+fn single_character(element: &Element) -> Option<(Element, Quantifier)> {
+    let mut atom = match element {
+        Element::Class(_) => element.clone(),
+        Element::Sequence(
+            SpecialSequence::AnyCharacter
+            | SpecialSequence::Digit
+            | SpecialSequence::NotDigit
+            | SpecialSequence::Whitespace
+            | SpecialSequence::NotWhitespace
+            | SpecialSequence::WordCharacter
+            | SpecialSequence::NotWordCharacter,
+            _,
+        ) => element.clone(),
+        _ => return None,
+    };
+    let quantifier = mem::replace(quantifier_mut(&mut atom)?, Quantifier::Once);
+    Some((atom, quantifier))
+}
+
+// The bounds of the quantifiers that may be merged. Only small ones are, so
+// that merging doesn't exceed the repetition limit.
+fn bounds(quantifier: Quantifier) -> Option<(usize, Option<usize>)> {
+    match quantifier {
+        Quantifier::Once => Some((1, Some(1))),
+        Quantifier::Maybe => Some((0, Some(1))),
+        Quantifier::ZeroOrMore => Some((0, None)),
+        Quantifier::OneOrMore => Some((1, None)),
+        _ => None,
+    }
+}
+
+// Removes alternatives without capture groups that came up before, and so
+// never match where an earlier one wouldn't.
+fn deduplicate(terms: Vec<Term>) -> Vec<Term> {
+    let mut kept: Vec<Term> = Vec::with_capacity(terms.len());
+    for term in terms {
+        if !kept.contains(&term) || term.elements.iter().any(captures) {
+            kept.push(term);
+        }
+    }
+    kept
+}
+
+// Moves the elements that alternatives next to each other start with in
+// front of a group of what is left of them, which keeps the order in which
+// they are tried.
+fn share_prefixes(terms: Vec<Term>) -> Vec<Term> {
+    let mut shared: Vec<Term> = Vec::with_capacity(terms.len());
+    let mut terms = terms.into_iter().peekable();
+    while let Some(first) = terms.next() {
+        let mut run = vec![first];
+        while let Some(next) = terms.next_if(|next| common_prefix(&run[0], next) > 0) {
+            run.push(next);
+        }
+        if run.len() == 1 {
+            shared.extend(run);
+            continue;
+        }
+        let len = run
+            .iter()
+            .map(|term| common_prefix(&run[0], term))
+            .min()
+            .unwrap();
+        let left_anchored = run[0].left_anchored;
+        let prefix: Vec<Element> = run[0].elements[..len].to_vec();
+        let rest = run
+            .into_iter()
+            .map(|term| Term {
+                left_anchored: false,
+                right_anchored: term.right_anchored,
+                elements: term.elements[len..].to_vec(),
+            })
+            .collect();
+        let mut elements = prefix;
+        elements.push(Element::Group(vec![], alternatives(rest), Quantifier::Once));
+        shared.push(Term {
+            left_anchored,
+            right_anchored: false,
+            elements: merge_repetitions(splice_groups(elements)),
+        });
+    }
+    shared
+}
+
+// The number of elements both terms start with, not counting capture groups.
+fn common_prefix(first: &Term, second: &Term) -> usize {
+    if first.left_anchored != second.left_anchored {
+        return 0;
+    }
+    first
+        .elements
+        .iter()
+        .zip(&second.elements)
+        .take_while(|(first, second)| first == second && !captures(first))
+        .count()
+}
+
+// Whether a term can stand in for a group around it: not when anchored.
+fn is_plain(term: &Term) -> bool {
+    !term.left_anchored
+        && !term.right_anchored
+        && !term.elements.iter().any(|e| matches!(e, Element::Flags(_)))
+}
+
+fn captures(element: &Element) -> bool {
+    matches!(element, Element::CaptureGroup(..))
+        || element
+            .terms()
+            .is_some_and(|terms| terms.iter().any(|term| term.elements.iter().any(captures)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::{parse, to_pattern};
+    use crate::{Regex, RegexBuilder};
+
+    #[test]
+    fn simplifies_patterns() {
+        let cases = [
+            ("(?:ab)c(?:d|e)", "abc(?:d|e)"),
+            ("(?:a*)*(?:b+)+(?:c?)?(?:d+)?(?:(e))*", "a*b+c?d*(e)*"),
+            ("(?:a*?)*", "(?:a*?)*"),
+            ("aa*bb+c?c*.*.+", "a+b{2,}c*.+"),
+            ("a|b|a|(c)|(c)", "a|b|(c)|(c)"),
+            ("abc|abd|ae|f|fg", "a(?:b(?:c|d)|e)|f(?:|g)"),
+            ("^ab|^ac|ad$|ad", "^a(?:b|c)|ad(?:$|)"),
+            ("(?i)a|a", "(?i)a|a"),
+            ("(?:(?i)a)b|(?:^a)", "(?:(?i)a)b|(?:^a)"),
+            ("(a)b|(a)c", "(a)b|(a)c"),
+            ("(a)?(?(1)(?:ab)|ac)", "(a)?(?(1)ab|ac)"),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(
+                to_pattern(&simplify(parse(pattern).unwrap())),
+                expected,
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_what_patterns_match() {
+        let patterns = [
+            "(?:ab|ac)+d",
+            "x(?:a|ab)(?:c|bcd)",
+            "(?:a?)*?b",
+            "(a|b)(?:a|b)\\1",
+            "(?:a+)?b|a",
+        ];
+        let haystacks = [
+            "abacd", "xabcd", "xacd", "aab", "bbb", "aabab", "ab", "b", "x",
+        ];
+        for pattern in patterns {
+            let regex = Regex::new(pattern).unwrap();
+            let simplified = Regex::from_ast(simplify(parse(pattern).unwrap())).unwrap();
+            for haystack in haystacks {
+                assert_eq!(
+                    simplified.captures(haystack),
+                    regex.captures(haystack),
+                    "{pattern} {haystack}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_before_compiling() {
+        let regex = RegexBuilder::new("(?:ab|ac)(?:a+)*")
+            .simplify(true)
+            .build()
+            .unwrap();
+        assert_eq!(to_pattern(regex.ast()), "a(?:b|c)a*");
+        assert_eq!(regex.as_str(), "(?:ab|ac)(?:a+)*");
+        assert_eq!(regex.find("xacaa").map(|m| m.range()), Some(1..5));
+    }
+}
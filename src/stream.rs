@@ -0,0 +1,78 @@
+use crate::dfa::Stream;
+use crate::Regex;
+
+/// What a [`StreamMatcher`] knows after the input it has been fed so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// The pattern matches, whatever comes next.
+    Matched,
+    /// The pattern can't match, whatever comes next.
+    Rejected,
+    /// It depends on what comes next. If nothing does, ask
+    /// [`StreamMatcher::finish`].
+    Undecided,
+}
+
+/// Finds out whether a pattern matches somewhere in input that arrives in
+/// chunks, like [`Regex::is_match`] would on all of it, but without holding
+/// on to any of the chunks.
+///
+/// Chunks are bytes, and may split UTF-8 encoded characters.
+///
+/// Created by [`Regex::stream_matcher`].
+///
+/// ```
+/// use regexrs::{Regex, Status};
+///
+/// let regex = Regex::new(r"\d+ bytes$").unwrap();
+/// let mut matcher = regex.stream_matcher().unwrap();
+/// assert_eq!(matcher.feed(b"received 25"), Status::Undecided);
+/// assert_eq!(matcher.feed(b"6 bytes"), Status::Undecided);
+/// assert!(matcher.finish());
+/// ```
+pub struct StreamMatcher<'r> {
+    stream: Stream<'r>,
+    status: Status,
+}
+
+impl<'r> StreamMatcher<'r> {
+    pub(crate) fn new(regex: &'r Regex) -> Option<Self> {
+        let stream = match (&regex.inner.dfa, &regex.inner.lazy, &regex.inner.bytes) {
+            (Some(dfa), _, _) => Stream::full(dfa),
+            (None, Some(lazy), Some(bytes)) => Stream::lazy(bytes, lazy.capacity()),
+            _ => return None,
+        };
+        let mut matcher = Self {
+            stream,
+            status: Status::Undecided,
+        };
+        matcher.feed(&[]);
+        Some(matcher)
+    }
+    /// Moves on over the next `chunk` of the input. Once the result is
+    /// decided, further chunks don't change it.
+    pub fn feed(&mut self, chunk: &[u8]) -> Status {
+        if self.status == Status::Undecided {
+            self.status = match self.stream.feed(chunk) {
+                Some(true) => Status::Matched,
+                Some(false) => Status::Rejected,
+                None => Status::Undecided,
+            };
+        }
+        self.status
+    }
+    /// What the matcher knows so far, as last returned by
+    /// [`StreamMatcher::feed`].
+    pub fn status(&self) -> Status {
+        self.status
+    }
+    /// Returns true if the pattern matches the input, taking it to end
+    /// after the chunks fed so far.
+    pub fn finish(&self) -> bool {
+        match self.status {
+            Status::Matched => true,
+            Status::Rejected => false,
+            Status::Undecided => self.stream.accepts_at_end(),
+        }
+    }
+}
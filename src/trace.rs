@@ -0,0 +1,248 @@
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{Debug, Display};
+use core::panic::RefUnwindSafe;
+
+use crate::ast::Term;
+use crate::fsm::State;
+
+/// Observes how a [`Regex`](crate::Regex) is compiled and matched, for
+/// debugging. Set one with
+/// [`RegexBuilder::tracer`](crate::RegexBuilder::tracer); both methods do
+/// nothing by default.
+///
+/// Searches of a traced regex don't use the DFAs, which have no states to
+/// report, but simulate the automaton a character at a time, so that every
+/// transition is seen. Tracers have to be [`RefUnwindSafe`], like a
+/// `Mutex` is, so that regexes stay unwind safe.
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+/// use regexrs::{RegexBuilder, State, Tracer};
+///
+/// #[derive(Default)]
+/// struct Steps(Mutex<Vec<(char, usize)>>);
+///
+/// impl Tracer for Steps {
+///     fn transition(&self, c: char, _position: usize, _from: &[State], to: &[State]) {
+///         self.0.lock().unwrap().push((c, to.len()));
+///     }
+/// }
+///
+/// let steps = Arc::new(Steps::default());
+/// let regex = RegexBuilder::new("ab").tracer(steps.clone()).build().unwrap();
+/// assert!(regex.is_match("xab"));
+/// assert_eq!(steps.0.lock().unwrap()[0].0, 'x');
+/// ```
+pub trait Tracer: Send + Sync + RefUnwindSafe {
+    /// Called with the syntax tree of the pattern once it has been parsed,
+    /// as returned by [`RegexBuilder::parse`](crate::RegexBuilder::parse).
+    fn parsed(&self, _ast: &[Term]) {}
+    /// Called whenever a search consumes the character `c`, which ends at
+    /// byte offset `position`, with the states of the automaton before and
+    /// after. No states after means the search has failed.
+    fn transition(&self, _c: char, _position: usize, _from: &[State], _to: &[State]) {}
+}
+
+// The tracer of a builder, which can't derive `Debug` otherwise.
+#[derive(Clone, Default)]
+pub(crate) struct Tracing(pub(crate) Option<Arc<dyn Tracer>>);
+
+impl Debug for Tracing {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.0 {
+            Some(_) => write!(f, "Some(..)"),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+/// A record of how [`Regex::debug_match`](crate::Regex::debug_match) went
+/// through a haystack: the states of the automaton at every position, and
+/// why the search ended where it did.
+///
+/// It displays as one line per step, with the byte offset, the character
+/// just consumed and the states, followed by the outcome.
+///
+/// ```
+/// use regexrs::{Outcome, Regex};
+///
+/// let trace = Regex::new("^ab").unwrap().debug_match("ac");
+/// assert_eq!(trace.outcome(), Outcome::DeadEnd(1));
+/// assert_eq!(trace.steps().len(), 3);
+/// assert!(trace.steps()[2].states().is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchTrace {
+    steps: Vec<TraceStep>,
+    outcome: Outcome,
+}
+
+/// The states of the automaton at one position of a [`MatchTrace`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    position: usize,
+    char: Option<char>,
+    states: Vec<State>,
+}
+
+/// Why the search of a [`MatchTrace`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// A state matched once the haystack had been consumed up to this byte
+    /// offset.
+    Matched(usize),
+    /// None of the states could consume the character at this byte offset,
+    /// and no match could start after it.
+    DeadEnd(usize),
+    /// The haystack ended before any of the states matched.
+    EndOfHaystack,
+    /// The pattern has backreferences, atomic groups or conditionals, so it
+    /// is matched by backtracking instead of stepping through states, and
+    /// there are no steps. The flag tells whether it matched.
+    Backtracked(bool),
+    /// The search gave up, as described in
+    /// [`RegexBuilder::backtrack_limit`](crate::RegexBuilder::backtrack_limit).
+    GaveUp,
+}
+
+impl MatchTrace {
+    pub(crate) fn new(steps: Vec<TraceStep>, outcome: Outcome) -> Self {
+        Self { steps, outcome }
+    }
+    /// The states before the first character, and after every character
+    /// consumed.
+    pub fn steps(&self) -> &[TraceStep] {
+        &self.steps
+    }
+    /// Why the search ended.
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+    /// Returns true if the pattern matched.
+    pub fn is_match(&self) -> bool {
+        matches!(
+            self.outcome,
+            Outcome::Matched(_) | Outcome::Backtracked(true)
+        )
+    }
+}
+
+impl Display for MatchTrace {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for step in &self.steps {
+            match step.char {
+                Some(c) => write!(f, "{:>4} {:<8}", step.position, format!("{c:?}"))?,
+                None => write!(f, "{:>4} {:<8}", step.position, "")?,
+            }
+            for state in &step.states {
+                write!(f, "{state}")?;
+            }
+            writeln!(f)?;
+        }
+        match self.outcome {
+            Outcome::Matched(position) => write!(f, "matched at {position}"),
+            Outcome::DeadEnd(position) => {
+                write!(f, "no state consumes the character at {position}")
+            }
+            Outcome::EndOfHaystack => write!(f, "no state matched before the end of the haystack"),
+            Outcome::Backtracked(true) => write!(f, "matched by backtracking"),
+            Outcome::Backtracked(false) => write!(f, "no match found by backtracking"),
+            Outcome::GaveUp => write!(f, "gave up"),
+        }
+    }
+}
+
+impl TraceStep {
+    pub(crate) fn new(position: usize, char: Option<char>, states: Vec<State>) -> Self {
+        Self {
+            position,
+            char,
+            states,
+        }
+    }
+    /// The byte offset in the haystack.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+    /// The character consumed to get here, or `None` at the start.
+    pub fn char(&self) -> Option<char> {
+        self.char
+    }
+    /// The states of the automaton, in the order of their priority.
+    pub fn states(&self) -> &[State] {
+        &self.states
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::RegexBuilder;
+
+    #[derive(Default)]
+    struct Recorder {
+        parsed: Mutex<usize>,
+        steps: Mutex<Vec<(char, usize, Vec<State>)>>,
+    }
+
+    impl Tracer for Recorder {
+        fn parsed(&self, _ast: &[Term]) {
+            *self.parsed.lock().unwrap() += 1;
+        }
+        fn transition(&self, c: char, position: usize, _from: &[State], to: &[State]) {
+            self.steps.lock().unwrap().push((c, position, to.to_vec()));
+        }
+    }
+
+    #[test]
+    fn reports_parses_and_transitions() {
+        let recorder = Arc::new(Recorder::default());
+        // Literals and DFAs would otherwise skip the simulation.
+        let regex = RegexBuilder::new("ab|cd")
+            .prefer_dfa(true)
+            .tracer(recorder.clone())
+            .build()
+            .unwrap();
+        assert_eq!(*recorder.parsed.lock().unwrap(), 1);
+        assert!(regex.is_match("xaby"));
+        let steps = recorder.steps.lock().unwrap();
+        let chars: Vec<(char, usize)> = steps.iter().map(|step| (step.0, step.1)).collect();
+        assert_eq!(chars, [('x', 1), ('a', 2), ('b', 3)]);
+        assert!(steps[2].2.contains(&State::Success));
+        assert_eq!(State::Success.to_string(), "(S)");
+    }
+
+    #[test]
+    fn records_why_searches_end() {
+        let trace = |pattern, haystack| crate::Regex::new(pattern).unwrap().debug_match(haystack);
+        let found = trace("b+", "abbc");
+        assert_eq!(found.outcome(), Outcome::Matched(2));
+        assert!(found.is_match());
+        let positions: Vec<usize> = found.steps().iter().map(TraceStep::position).collect();
+        assert_eq!(positions, [0, 1, 2]);
+        assert_eq!(found.steps()[1].char(), Some('a'));
+        assert_eq!(trace("b$", "ab\n").outcome(), Outcome::EndOfHaystack);
+        assert_eq!(trace("^ab", "ac").outcome(), Outcome::DeadEnd(1));
+        assert_eq!(trace(r"(a)\1", "xaa").outcome(), Outcome::Backtracked(true));
+        assert!(trace("^ab", "ac")
+            .to_string()
+            .ends_with("   2 'c'     \nno state consumes the character at 1"));
+        for (pattern, haystack) in [
+            ("a|ab", "xab"),
+            (r"\bcat\b", "a cat"),
+            ("(?=ab)a", "aab"),
+            ("", ""),
+        ] {
+            let regex = crate::Regex::new(pattern).unwrap();
+            assert_eq!(
+                regex.debug_match(haystack).is_match(),
+                regex.is_match(haystack),
+                "{pattern}"
+            );
+        }
+    }
+}
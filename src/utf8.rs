@@ -0,0 +1,319 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::charset::CharSet;
+use crate::collections::Map;
+use crate::fsm::{Look, Node, NFA};
+
+// The UTF-8 encodings of the characters from `from` to `to`, as sequences of
+// byte ranges. Every combination of bytes from the ranges of a sequence
+// encodes one of the characters, so that a sequence can be matched one byte
+// at a time.
+pub fn utf8_sequences(from: char, to: char) -> Vec<Vec<(u8, u8)>> {
+    let mut sequences = Vec::new();
+    let mut pending = vec![(from as u32, to as u32)];
+    'ranges: while let Some((start, end)) = pending.pop() {
+        // The lower half is pushed last, so that sequences come out sorted.
+        if start <= 0xD7FF && end >= 0xE000 {
+            pending.push((0xE000, end));
+            pending.push((start, 0xD7FF));
+            continue;
+        }
+        // Split where the encoding gets longer...
+        for max in [0x7F, 0x7FF, 0xFFFF] {
+            if start <= max && max < end {
+                pending.push((max + 1, end));
+                pending.push((start, max));
+                continue 'ranges;
+            }
+        }
+        // ...and where a continuation byte wraps around, so that the bytes
+        // of both ends only differ in whole ranges.
+        for i in 1..4 {
+            let mask = (1u32 << (6 * i)) - 1;
+            if start & !mask != end & !mask {
+                if start & mask != 0 {
+                    pending.push(((start | mask) + 1, end));
+                    pending.push((start, start | mask));
+                    continue 'ranges;
+                }
+                if end & mask != mask {
+                    pending.push((end & !mask, end));
+                    pending.push((start, (end & !mask) - 1));
+                    continue 'ranges;
+                }
+            }
+        }
+        let (mut low, mut high) = ([0; 4], [0; 4]);
+        let low = encode(start, &mut low);
+        let high = encode(end, &mut high);
+        sequences.push(low.iter().copied().zip(high.iter().copied()).collect());
+    }
+    sequences
+}
+
+fn encode(value: u32, buffer: &mut [u8; 4]) -> &[u8] {
+    char::from_u32(value)
+        .unwrap()
+        .encode_utf8(buffer)
+        .as_bytes()
+}
+
+pub enum ByteNode {
+    // Consumes a byte in the range, then moves on.
+    Range(u8, u8, usize),
+    Epsilon(Vec<usize>),
+    Assert(Look, usize),
+    Match,
+}
+
+// An automaton that works on the UTF-8 encoding of the haystack one byte at
+// a time, so that it never has to decode characters. It is derived from an
+// `NFA` by replacing every character set with the byte sequences encoding
+// it; each node of the `NFA` keeps its index. Captures are dropped, and
+// patterns with lookaheads or backreferences can't be translated.
+pub struct ByteFSM {
+    graph: Vec<ByteNode>,
+    start: usize,
+    restart: Option<usize>,
+}
+
+impl ByteFSM {
+    // Translates `nfa`, giving up if it can't be or if the result would take
+    // up more than `size_limit` bytes.
+    pub fn new(nfa: &NFA, size_limit: usize) -> Option<Self> {
+        let (start, restart) = nfa.entries();
+        let mut graph = Vec::with_capacity(nfa.graph().len());
+        let mut sets = Vec::new();
+        for (idx, node) in nfa.graph().iter().enumerate() {
+            graph.push(match node {
+                Node::Consume(set, next) => {
+                    sets.push((idx, set, *next));
+                    ByteNode::Epsilon(Vec::new())
+                }
+                Node::Epsilon(targets) => ByteNode::Epsilon(targets.clone()),
+                Node::Save(_, next) => ByteNode::Epsilon(vec![*next]),
+                Node::Assert(look, next) => ByteNode::Assert(*look, *next),
+                Node::Match => ByteNode::Match,
+                Node::Lookahead(..)
+                | Node::Backref(..)
+                | Node::Atomic(..)
+                | Node::Conditional(..) => return None,
+            });
+        }
+        let mut bytes = Self {
+            graph,
+            start,
+            restart,
+        };
+        // Byte ranges leading to the same node are shared, which merges the
+        // common tails of the sequences.
+        let mut ranges = Map::new();
+        for (idx, set, next) in sets {
+            let branches = bytes.compile_set(set, next, &mut ranges);
+            bytes.graph[idx] = ByteNode::Epsilon(branches);
+            if bytes.graph.len() * core::mem::size_of::<ByteNode>() > size_limit {
+                return None;
+            }
+        }
+        Some(bytes)
+    }
+    pub fn graph(&self) -> &[ByteNode] {
+        &self.graph
+    }
+    // The entry into all branches, and into those that may start after the
+    // beginning of the input.
+    pub fn entries(&self) -> (usize, Option<usize>) {
+        (self.start, self.restart)
+    }
+    // The automaton of the reversed pattern, which reads a haystack
+    // backwards from where a match ends to where it starts. Its start is
+    // the match node, and the start of this automaton is its match, so that
+    // it has no unanchored entry. Gives up if the result would take up more
+    // than `size_limit` bytes.
+    pub fn reversed(&self, size_limit: usize) -> Option<Self> {
+        let mut incoming: Vec<Vec<ByteNode>> = self.graph.iter().map(|_| Vec::new()).collect();
+        for (idx, node) in self.graph.iter().enumerate() {
+            match node {
+                ByteNode::Range(low, high, next) => {
+                    incoming[*next].push(ByteNode::Range(*low, *high, idx))
+                }
+                ByteNode::Epsilon(targets) => {
+                    for &next in targets {
+                        incoming[next].push(ByteNode::Epsilon(vec![idx]));
+                    }
+                }
+                ByteNode::Assert(look, next) => {
+                    incoming[*next].push(ByteNode::Assert(look.reversed(), idx))
+                }
+                ByteNode::Match => {}
+            }
+        }
+        // Every node turns into an epsilon move to its reversed incoming
+        // edges, which get nodes of their own unless they are epsilon moves.
+        let mut graph: Vec<ByteNode> = self
+            .graph
+            .iter()
+            .map(|_| ByteNode::Epsilon(Vec::new()))
+            .collect();
+        for (idx, edges) in incoming.into_iter().enumerate() {
+            let mut targets = Vec::with_capacity(edges.len());
+            for edge in edges {
+                match edge {
+                    ByteNode::Epsilon(sources) => targets.extend(sources),
+                    edge => {
+                        graph.push(edge);
+                        targets.push(graph.len() - 1);
+                    }
+                }
+            }
+            graph[idx] = ByteNode::Epsilon(targets);
+        }
+        graph.push(ByteNode::Match);
+        if graph.len() * core::mem::size_of::<ByteNode>() > size_limit {
+            return None;
+        }
+        let accept = graph.len() - 1;
+        if let ByteNode::Epsilon(targets) = &mut graph[self.start] {
+            targets.push(accept);
+        }
+        let start = self
+            .graph
+            .iter()
+            .position(|node| matches!(node, ByteNode::Match))
+            .expect("every pattern has a match node");
+        Some(Self {
+            graph,
+            start,
+            restart: None,
+        })
+    }
+    fn compile_set(
+        &mut self,
+        set: &CharSet,
+        next: usize,
+        ranges: &mut Map<(u8, u8, usize), usize>,
+    ) -> Vec<usize> {
+        let mut branches = Vec::new();
+        for &(from, to) in set.ranges() {
+            for sequence in utf8_sequences(from, to) {
+                let branch = sequence.iter().rev().fold(next, |next, &(low, high)| {
+                    *ranges.entry((low, high, next)).or_insert_with(|| {
+                        self.graph.push(ByteNode::Range(low, high, next));
+                        self.graph.len() - 1
+                    })
+                });
+                branches.push(branch);
+            }
+        }
+        branches
+    }
+    // The nodes of `nfa` that a simulation of this automaton is in at a
+    // character boundary when it is in `nodes`. Nodes of both automata share
+    // their indices, except for the byte ranges that consume a character,
+    // whose node counts as reached if the first bytes of all encodings are.
+    pub fn char_nodes(&self, nfa: &NFA, nodes: &[usize]) -> Vec<usize> {
+        let mut reached = vec![false; self.graph.len()];
+        for &idx in nodes {
+            reached[idx] = true;
+        }
+        let mut char_nodes: Vec<usize> = nodes
+            .iter()
+            .copied()
+            .filter(|&idx| idx < nfa.graph().len())
+            .collect();
+        for (idx, node) in nfa.graph().iter().enumerate() {
+            if let (Node::Consume(..), ByteNode::Epsilon(branches)) = (node, &self.graph[idx]) {
+                if !branches.is_empty() && branches.iter().all(|&branch| reached[branch]) {
+                    char_nodes.push(idx);
+                }
+            }
+        }
+        char_nodes
+    }
+    // Whether the pattern matches anywhere in `haystack`, found by
+    // simulating all states at once, so that it takes linear time.
+    pub fn is_match(&self, haystack: &[u8]) -> bool {
+        // The position at which each node was last added.
+        let mut seen = vec![usize::MAX; self.graph.len()];
+        let mut states = Vec::new();
+        if self.add(self.start, haystack, 0, &mut seen, &mut states) {
+            return true;
+        }
+        for (idx, &byte) in haystack.iter().enumerate() {
+            let position = idx + 1;
+            let mut next = Vec::with_capacity(states.len());
+            for &state in &states {
+                if let ByteNode::Range(low, high, target) = self.graph[state] {
+                    if (low..=high).contains(&byte)
+                        && self.add(target, haystack, position, &mut seen, &mut next)
+                    {
+                        return true;
+                    }
+                }
+            }
+            if let Some(restart) = self.restart {
+                if self.add(restart, haystack, position, &mut seen, &mut next) {
+                    return true;
+                }
+            } else if next.is_empty() {
+                return false;
+            }
+            states = next;
+        }
+        false
+    }
+    // Follows the epsilon moves from `idx`, adding the nodes that consume a
+    // byte to `states`. Returns true as soon as the pattern has matched.
+    fn add(
+        &self,
+        idx: usize,
+        haystack: &[u8],
+        position: usize,
+        seen: &mut [usize],
+        states: &mut Vec<usize>,
+    ) -> bool {
+        let mut stack = vec![idx];
+        while let Some(idx) = stack.pop() {
+            if seen[idx] == position {
+                continue;
+            }
+            seen[idx] = position;
+            match &self.graph[idx] {
+                ByteNode::Range(..) => states.push(idx),
+                ByteNode::Epsilon(targets) => stack.extend(targets.iter().rev()),
+                ByteNode::Assert(look, next) => {
+                    if look.holds(haystack, 0, position) {
+                        stack.push(*next);
+                    }
+                }
+                ByteNode::Match => return true,
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_ranges_by_encoding() {
+        assert_eq!(
+            utf8_sequences('\0', char::MAX),
+            [
+                vec![(0x00, 0x7F)],
+                vec![(0xC2, 0xDF), (0x80, 0xBF)],
+                vec![(0xE0, 0xE0), (0xA0, 0xBF), (0x80, 0xBF)],
+                vec![(0xE1, 0xEC), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xED, 0xED), (0x80, 0x9F), (0x80, 0xBF)],
+                vec![(0xEE, 0xEF), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xF0, 0xF0), (0x90, 0xBF), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xF1, 0xF3), (0x80, 0xBF), (0x80, 0xBF), (0x80, 0xBF)],
+                vec![(0xF4, 0xF4), (0x80, 0x8F), (0x80, 0xBF), (0x80, 0xBF)],
+            ]
+        );
+        assert_eq!(utf8_sequences('é', 'é'), [vec![(0xC3, 0xC3), (0xA9, 0xA9)]]);
+    }
+}
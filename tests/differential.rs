@@ -0,0 +1,191 @@
+//! Compares `is_match` and `find` with the `regex` crate on random patterns
+//! in the syntax both understand, run by `cargo test --test differential`.
+//!
+//! `REGEXRS_SEED` and `REGEXRS_ITERATIONS` change which and how many patterns
+//! are tried. `REGEXRS_CORPUS` names a file of cases to replay instead, one
+//! per line: the pattern and the haystack separated by a tab, with `\n`,
+//! `\t` and `\\` escaped. Discrepancies are printed in the same format, after
+//! removing as much of the pattern and the haystack as keeps them apart.
+
+use std::env;
+use std::fmt::Debug;
+use std::fs;
+use std::process::ExitCode;
+
+const SEED: u64 = 0x2545_f491_4f6c_dd1d;
+const ITERATIONS: usize = 200;
+const HAYSTACKS: usize = 10;
+
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+    fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len())]
+    }
+}
+
+// Leaves out `$`, which matches before a trailing newline in one syntax and
+// not in the other, and everything else the syntaxes disagree on. So are
+// repetitions of parts that may match nothing: after an iteration that did,
+// like `\w?` in the third one of `(?:\w?|\W){2,}` in `ab `, Python stops
+// repeating while `regex` goes on to the next alternative.
+fn pattern(rng: &mut Rng, depth: usize, repeated: bool) -> String {
+    let atoms = [
+        "a", "b", "é", "1", "[ab]", "[^a]", "[a-c1]", ".", r"\d", r"\w", r"\W", r"\s", r"\b", "^",
+        "(?m:^)", "(?m:$)",
+    ];
+    let (atoms, quantifiers) = match repeated {
+        true => (
+            &atoms[..atoms.len() - 4],
+            &["+", "{2}", "{1,3}", "{2,}"][..],
+        ),
+        false => (&atoms[..], &["*", "+", "?", "{2}", "{1,3}", "{2,}"][..]),
+    };
+    if depth == 0 {
+        return rng.pick(atoms).to_string();
+    }
+    let sub = |rng: &mut Rng| pattern(rng, depth - 1, repeated);
+    match rng.below(10) {
+        0..=2 => rng.pick(atoms).to_string(),
+        3..=4 => format!("{}{}", sub(rng), sub(rng)),
+        5 => format!("{}|{}", sub(rng), sub(rng)),
+        6 => format!("({})", sub(rng)),
+        _ => {
+            let quantifier = rng.pick(quantifiers);
+            let lazy = rng.pick(&["", "?"]);
+            format!("(?:{}){quantifier}{lazy}", pattern(rng, depth - 1, true))
+        }
+    }
+}
+
+fn haystack(rng: &mut Rng) -> String {
+    (0..rng.below(8))
+        .map(|_| rng.pick(&['a', 'b', 'c', 'é', '1', '٣', ' ', '_', '\n']))
+        .collect()
+}
+
+// What both crates make of a case, or `None` if either rejects the pattern.
+fn outcomes(pattern: &str, haystack: &str) -> Option<(Outcome, Outcome)> {
+    let ours = regexrs::Regex::new(pattern).ok()?;
+    let theirs = regex::Regex::new(pattern).ok()?;
+    let ours = Outcome {
+        is_match: ours.is_match(haystack),
+        find: ours.find(haystack).map(|m| m.range()),
+    };
+    let theirs = Outcome {
+        is_match: theirs.is_match(haystack),
+        find: theirs.find(haystack).map(|m| m.range()),
+    };
+    Some((ours, theirs))
+}
+
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    is_match: bool,
+    find: Option<std::ops::Range<usize>>,
+}
+
+fn disagree(pattern: &str, haystack: &str) -> bool {
+    outcomes(pattern, haystack).is_some_and(|(ours, theirs)| ours != theirs)
+}
+
+// Removes characters from the pattern and the haystack, one at a time, for
+// as long as the crates keep disagreeing.
+fn minimize(mut pattern: String, mut haystack: String) -> (String, String) {
+    loop {
+        let shorter = without_each(&pattern)
+            .map(|pattern| (pattern, haystack.clone()))
+            .chain(without_each(&haystack).map(|haystack| (pattern.clone(), haystack)))
+            .find(|(pattern, haystack)| disagree(pattern, haystack));
+        match shorter {
+            Some(case) => (pattern, haystack) = case,
+            None => return (pattern, haystack),
+        }
+    }
+}
+
+fn without_each(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.char_indices()
+        .map(|(idx, c)| format!("{}{}", &s[..idx], &s[idx + c.len_utf8()..]))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('\n', r"\n")
+        .replace('\t', r"\t")
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            },
+            (c, false) => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn setting<T: std::str::FromStr>(name: &str, default: T) -> T
+where
+    T::Err: Debug,
+{
+    env::var(name).map_or(default, |value| {
+        value.parse().unwrap_or_else(|e| panic!("{name}: {e:?}"))
+    })
+}
+
+fn main() -> ExitCode {
+    let cases: Vec<(String, String)> = match env::var("REGEXRS_CORPUS") {
+        Ok(path) => fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("{path}: {e}"))
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .map(|(pattern, haystack)| (unescape(pattern), unescape(haystack)))
+            .collect(),
+        Err(_) => {
+            let mut rng = Rng(setting("REGEXRS_SEED", SEED).max(1));
+            let iterations = setting("REGEXRS_ITERATIONS", ITERATIONS);
+            (0..iterations * HAYSTACKS)
+                .scan(String::new(), |pattern, idx| {
+                    if idx % HAYSTACKS == 0 {
+                        *pattern = self::pattern(&mut rng, 4, false);
+                    }
+                    Some((pattern.clone(), haystack(&mut rng)))
+                })
+                .collect()
+        }
+    };
+    let mut found = Vec::new();
+    for (pattern, haystack) in &cases {
+        if disagree(pattern, haystack) {
+            let case = minimize(pattern.clone(), haystack.clone());
+            if !found.contains(&case) {
+                found.push(case);
+            }
+        }
+    }
+    for (pattern, haystack) in &found {
+        let (ours, theirs) = outcomes(pattern, haystack).unwrap();
+        println!("{}\t{}", escape(pattern), escape(haystack));
+        eprintln!("    regexrs: {ours:?}\n    regex:   {theirs:?}");
+    }
+    println!("{} cases, {} discrepancies", cases.len(), found.len());
+    if found.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}